@@ -0,0 +1,40 @@
+//! This benchmark measures the time it takes to render a minute of busy PSG output at 44100 Hz.
+//! Run it using `cargo bench`, and compare with `cargo bench --features simd` to measure the
+//! speedup of the vectorized anti-alias filter.
+
+use psg::{ChannelIndex, PSG};
+use std::hint::black_box;
+use std::time::Instant;
+
+fn main() {
+    let mut psg = PSG::new(1789772.5, 44100).expect("Could not initialize PSG");
+
+    // Keep all generators busy
+    psg.set_tone_period(ChannelIndex::A, 100);
+    psg.set_tone_period(ChannelIndex::B, 151);
+    psg.set_tone_period(ChannelIndex::C, 202);
+    psg.set_noise_period(7);
+    psg.set_mixer(0b0011_0000);
+    psg.set_amplitude(ChannelIndex::A, 15);
+    psg.set_amplitude(ChannelIndex::B, 12);
+    psg.set_envelope_enabled(ChannelIndex::C, true);
+    psg.set_envelope_period(300);
+    psg.set_envelope_shape(10);
+
+    let frames = 44100 * 60;
+    let start = Instant::now();
+
+    for _ in 0..frames {
+        black_box(psg.render());
+    }
+
+    let elapsed = start.elapsed();
+
+    println!(
+        "Rendered {} frames in {:.3?} ({:.0} frames per second, {:.1}x real-time)",
+        frames,
+        elapsed,
+        frames as f64 / elapsed.as_secs_f64(),
+        60.0 / elapsed.as_secs_f64()
+    );
+}
@@ -0,0 +1,46 @@
+//! This example plays notes on the default output device while it runs. Every line that is read
+//! from the standard input is a MIDI note number to play on channel A, and an empty line silences
+//! the channel. The example ends at the end of the input.
+//!
+//! Run it with `cargo run --example realtime --features cpal`.
+
+use psg::{ChannelIndex, PsgBuilder, PsgController};
+use psg::math;
+use psg::realtime;
+use std::io::{self, BufRead};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Start playing a PSG with a clock rate of an MSX machine, and keep its controller.
+    let clock_rate = 1789772.5;
+    let (controller, receiver) = PsgController::new(256);
+    let stream = realtime::spawn(PsgBuilder::new(clock_rate, 44100), receiver)?;
+
+    println!("Playing at {} Hz, enter MIDI note numbers", stream.sample_rate());
+
+    // Enable the tone of channel A, and center it.
+    controller.write_register(7, 0x3e)?;
+    controller.set_panning(ChannelIndex::A, 0.5, true)?;
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            controller.write_register(8, 0)?;
+            continue;
+        }
+
+        let Ok(note) = line.trim().parse::<u8>() else {
+            println!("Not a note number: {line}");
+            continue;
+        };
+
+        let [low, high] = math::midi_pitch_to_tone_period(note as f64, clock_rate).to_le_bytes();
+
+        controller.write_register(0, low)?;
+        controller.write_register(1, high)?;
+        controller.write_register(8, 15)?;
+    }
+
+    stream.stop();
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! This example plays a 440 Hz tone through the default output device for two seconds, and lowers
+//! its volume halfway through from the main thread while it plays.
+//!
+//! Run it with `cargo run --example rodio_tone --features rodio-playback`.
+
+use psg::{ChannelIndex, PSG, PsgSource};
+use psg::math;
+use std::thread;
+use std::time::Duration;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Open the default output device, and initialize a PSG at its sampling rate.
+    let stream = rodio::OutputStreamBuilder::open_default_stream()?;
+    let clock_rate = 1789772.5;
+    let mut psg = PSG::new(clock_rate, stream.config().sample_rate())?;
+
+    // Set some registers.
+    let channel = psg.channel_mut(ChannelIndex::A);
+    channel.set_period(math::frequency_to_tone_period(440.0, clock_rate));
+    channel.set_amplitude(15);
+    channel.set_tone_disabled(false);
+
+    // Start playing, and keep a handle to the PSG for changing its registers.
+    let source = PsgSource::new(psg);
+    let psg = source.psg();
+    stream.mixer().add(source);
+
+    thread::sleep(Duration::from_secs(1));
+    psg.lock().unwrap().channel_mut(ChannelIndex::A).set_amplitude(10);
+    thread::sleep(Duration::from_secs(1));
+
+    Ok(())
+}
@@ -1,11 +1,12 @@
 //! This example renders a one second 440 Hz tone with a sampling rate of 44100 Hz. This snippet of
-//! audio is then written to a file called `output.raw` using a two channel little-endian f32
-//! sample format.
+//! audio is then written to a file called `tone.wav` in the 16-bit PCM format.
+//!
+//! Run it with `cargo run --example tone --features wav`.
 
-use psg::PSG;
+use psg::{ChannelIndex, PSG};
 use psg::math;
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use psg::wav::{self, WavFormat};
+use std::path::Path;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize a new PSG with a clock rate of an MSX machine and a sampling rate of 44100 Hz.
@@ -13,23 +14,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut psg = PSG::new(clock_rate, 44100).expect("Could not initialize PSG");
 
     // Set some registers.
-    let channel = psg.channel_mut(0);
+    let channel = psg.channel_mut(ChannelIndex::A);
     channel.set_period(math::frequency_to_tone_period(440.0, clock_rate));
     channel.set_amplitude(15);
     channel.set_tone_disabled(false);
 
-    // Open a file for writing
-    let mut file = BufWriter::new(File::create("tone.raw")?);
-
-    // Write a second of audio to the file
-    for _ in 0..44100 {
-        // Render the sample
-        let (left, right) = psg.render();
-
-        // Write samples as f32 in little-endian byte order
-        file.write(&(left as f32).to_le_bytes())?;
-        file.write(&(right as f32).to_le_bytes())?;
-    }
+    // Render a second of audio to the file
+    wav::render_to_file(&mut psg, 44100, Path::new("tone.wav"), WavFormat::Pcm16)?;
 
     Ok(())
 }
@@ -0,0 +1,62 @@
+/*
+ * C bindings for the psg crate, which are exported when it is built with the capi feature.
+ *
+ * A PSG is created using psg_new, and destroyed using psg_free. Every function accepts null
+ * pointers, and the functions that can fail return PSG_OK on success, or one of the negative error
+ * codes. This header is kept in sync with src/capi.rs by its tests.
+ */
+
+#ifndef PSG_H
+#define PSG_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The function succeeded. */
+#define PSG_OK 0
+
+/* A pointer argument was null. */
+#define PSG_ERROR_NULL_POINTER (-1)
+
+/* An argument was out of range, such as a register number above 15. */
+#define PSG_ERROR_INVALID_ARGUMENT (-2)
+
+/* The PSG panicked. It should be freed, because it may be in an inconsistent state. */
+#define PSG_ERROR_PANIC (-3)
+
+/* The chip types that can be passed to psg_set_chip_type. */
+#define PSG_CHIP_AY 0
+#define PSG_CHIP_YM 1
+#define PSG_CHIP_AY8930 2
+#define PSG_CHIP_SUNSOFT_5B 3
+
+/* A PSG, which is only used through pointers. */
+typedef struct Psg Psg;
+
+/* Create a new PSG, or return NULL when the clock rate is too high for the sample rate. */
+Psg *psg_new(double clock_rate, uint32_t sample_rate);
+
+/* Destroy a PSG. NULL is ignored. */
+void psg_free(Psg *psg);
+
+/* Write a value to a register from 0 to 15. */
+int32_t psg_set_register(Psg *psg, uint8_t reg, uint8_t value);
+
+/* Render frames into a buffer of interleaved stereo samples, which has room for 2 * frames floats. */
+int32_t psg_render(Psg *psg, float *interleaved, size_t frames);
+
+/* Set the chip type to one of the PSG_CHIP constants. */
+int32_t psg_set_chip_type(Psg *psg, uint32_t chip_type);
+
+/* Reset the registers to zero, like the reset line of the chip. */
+int32_t psg_reset(Psg *psg);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,243 @@
+use crate::{AddressDecoding, ChipType, ClockDivider, DCFilterMode, Error, InterpolationMode, MixingMode, OutputFilter, PSG, StereoMode};
+use crate::dc_filter::DEFAULT_DC_FILTER_LENGTH;
+
+/// A builder for configuring a [`PSG`] in a single expression, as an alternative to calling the
+/// setters after construction.
+///
+/// Every option defaults to the same value as a PSG constructed using [`PSG::new`]. The builder
+/// can be cloned, which allows a single template to be used for multiple chips:
+///
+/// ```
+/// use psg::{ChipType, PsgBuilder, StereoMode};
+///
+/// let template = PsgBuilder::new(1_773_400.0, 44100)
+///     .chip_type(ChipType::AY)
+///     .stereo_mode(StereoMode::Abc);
+///
+/// let first = template.clone().build().unwrap();
+/// let second = template.build().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct PsgBuilder {
+    clock_rate: f64,
+    sample_rate: u32,
+    chip_type: ChipType,
+    clock_divider: ClockDivider,
+    stereo_mode: StereoMode,
+    stereo_width: f64,
+    channels_swapped: bool,
+    mixing_mode: MixingMode,
+    interpolation_mode: InterpolationMode,
+    output_filter: OutputFilter,
+    dc_filter_enabled: bool,
+    dc_filter_mode: DCFilterMode,
+    dc_filter_length: usize,
+    address_decoding: AddressDecoding,
+    metering_enabled: bool,
+    ayumi_compatible: bool,
+    hardware_counters: bool,
+    shared_prescaler: bool,
+    envelope_smoothing: bool
+}
+
+impl PsgBuilder {
+    /// Initialize a new builder for a PSG with the specified clock and sample rates. See
+    /// [`PSG::new`] for the constraints on these rates.
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            sample_rate,
+            chip_type: ChipType::YM,
+            clock_divider: ClockDivider::Div1,
+            stereo_mode: StereoMode::Mono,
+            stereo_width: 1.0,
+            channels_swapped: false,
+            mixing_mode: MixingMode::Linear,
+            interpolation_mode: InterpolationMode::Parabolic,
+            output_filter: OutputFilter::None,
+            dc_filter_enabled: true,
+            dc_filter_mode: DCFilterMode::MovingAverage,
+            dc_filter_length: DEFAULT_DC_FILTER_LENGTH,
+            address_decoding: AddressDecoding::LowNibble,
+            metering_enabled: false,
+            ayumi_compatible: false,
+            hardware_counters: false,
+            shared_prescaler: false,
+            envelope_smoothing: false
+        }
+    }
+
+    /// Set the chip type. See [`PSG::set_chip_type`].
+    pub fn chip_type(mut self, chip_type: ChipType) -> Self {
+        self.chip_type = chip_type;
+        self
+    }
+
+    /// Set the divider that is applied to the chip's clock. See [`PSG::set_clock_divider`].
+    pub fn clock_divider(mut self, divider: ClockDivider) -> Self {
+        self.clock_divider = divider;
+        self
+    }
+
+    /// Set the panning of the channels. See [`PSG::set_stereo_mode`].
+    pub fn stereo_mode(mut self, mode: StereoMode) -> Self {
+        self.stereo_mode = mode;
+        self
+    }
+
+    /// Set the stereo width of the output signal. See [`PSG::set_stereo_width`].
+    pub fn stereo_width(mut self, width: f64) -> Self {
+        self.stereo_width = width;
+        self
+    }
+
+    /// Swap the left and right output channels. See [`PSG::set_channels_swapped`].
+    pub fn channels_swapped(mut self, swapped: bool) -> Self {
+        self.channels_swapped = swapped;
+        self
+    }
+
+    /// Set the way in which the outputs of the channels are combined. See
+    /// [`PSG::set_mixing_mode`].
+    pub fn mixing_mode(mut self, mode: MixingMode) -> Self {
+        self.mixing_mode = mode;
+        self
+    }
+
+    /// Set the interpolation algorithm, which trades rendering speed for less aliasing. See
+    /// [`PSG::set_interpolation_mode`].
+    pub fn interpolation_mode(mut self, mode: InterpolationMode) -> Self {
+        self.interpolation_mode = mode;
+        self
+    }
+
+    /// Set the analog output filter. See [`PSG::set_output_filter`].
+    pub fn output_filter(mut self, filter: OutputFilter) -> Self {
+        self.output_filter = filter;
+        self
+    }
+
+    /// Enable or disable the DC filter. See [`PSG::set_dc_filter_enabled`].
+    pub fn dc_filter_enabled(mut self, enabled: bool) -> Self {
+        self.dc_filter_enabled = enabled;
+        self
+    }
+
+    /// Set the type of DC filter. See [`PSG::set_dc_filter_mode`].
+    pub fn dc_filter_mode(mut self, mode: DCFilterMode) -> Self {
+        self.dc_filter_mode = mode;
+        self
+    }
+
+    /// Set the window length of the moving average DC filter, which must be a power of two. See
+    /// [`PSG::with_dc_filter_length`].
+    pub fn dc_filter_length(mut self, length: usize) -> Self {
+        self.dc_filter_length = length;
+        self
+    }
+
+    /// Set the way in which register addresses above 15 are handled. See
+    /// [`PSG::set_address_decoding`].
+    pub fn address_decoding(mut self, decoding: AddressDecoding) -> Self {
+        self.address_decoding = decoding;
+        self
+    }
+
+    /// Enable or disable output level metering. See [`PSG::enable_metering`].
+    pub fn metering(mut self, enabled: bool) -> Self {
+        self.metering_enabled = enabled;
+        self
+    }
+
+    /// Enable or disable Ayumi compatibility mode. See [`PSG::set_ayumi_compatible`].
+    pub fn ayumi_compatible(mut self, enabled: bool) -> Self {
+        self.ayumi_compatible = enabled;
+        self
+    }
+
+    /// Enable or disable hardware counter mode for the tone oscillators. See
+    /// [`PSG::set_hardware_counters`].
+    pub fn hardware_counters(mut self, enabled: bool) -> Self {
+        self.hardware_counters = enabled;
+        self
+    }
+
+    /// Enable or disable clocking the noise generator by the shared prescaler. See
+    /// [`PSG::set_shared_prescaler`].
+    pub fn shared_prescaler(mut self, enabled: bool) -> Self {
+        self.shared_prescaler = enabled;
+        self
+    }
+
+    /// Enable or disable envelope smoothing. See [`PSG::set_envelope_smoothing`].
+    pub fn envelope_smoothing(mut self, enabled: bool) -> Self {
+        self.envelope_smoothing = enabled;
+        self
+    }
+
+    /// Replace the sample rate, for building a PSG that matches an audio device.
+    #[cfg(feature = "cpal")]
+    pub(crate) fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Build the PSG, or return an error when the clock rate is too high for the sample rate, or
+    /// when the DC filter length is not a power of two.
+    pub fn build(self) -> Result<PSG, Error> {
+        let mut psg = PSG::with_dc_filter_length(self.clock_rate, self.sample_rate, self.dc_filter_length)?;
+
+        psg.set_chip_type(self.chip_type);
+        psg.set_clock_divider(self.clock_divider);
+        psg.set_stereo_mode(self.stereo_mode);
+        psg.set_stereo_width(self.stereo_width);
+        psg.set_channels_swapped(self.channels_swapped);
+        psg.set_mixing_mode(self.mixing_mode);
+        psg.set_interpolation_mode(self.interpolation_mode);
+        psg.set_output_filter(self.output_filter);
+        psg.set_dc_filter_enabled(self.dc_filter_enabled);
+        psg.set_dc_filter_mode(self.dc_filter_mode);
+        psg.set_address_decoding(self.address_decoding);
+        psg.enable_metering(self.metering_enabled);
+        psg.set_ayumi_compatible(self.ayumi_compatible);
+        psg.set_hardware_counters(self.hardware_counters);
+        psg.set_shared_prescaler(self.shared_prescaler);
+        psg.set_envelope_smoothing(self.envelope_smoothing);
+
+        Ok(psg)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::PsgBuilder;
+    use crate::{ChannelIndex, ChipType, DCFilterMode, Error, InterpolationMode, StereoMode};
+
+    #[test]
+    fn build() {
+        let template = PsgBuilder::new(1_000_000.0, 44100)
+            .chip_type(ChipType::AY)
+            .stereo_mode(StereoMode::Acb)
+            .interpolation_mode(InterpolationMode::BSpline)
+            .dc_filter_mode(DCFilterMode::OnePole { cutoff_hz: 20.0 })
+            .dc_filter_length(256);
+
+        let psg = template.clone().build().unwrap();
+
+        assert_eq!(psg.chip_type(), ChipType::AY);
+        assert_eq!(psg.interpolation_mode(), InterpolationMode::BSpline);
+        assert_eq!(psg.dc_filter_mode(), DCFilterMode::OnePole { cutoff_hz: 20.0 });
+        assert_eq!(psg.dc_filter_length(), 256);
+        assert_eq!(psg.channel(ChannelIndex::A).panning(), (1.0, 0.0));
+        assert_eq!(psg.channel(ChannelIndex::B).panning(), (0.0, 1.0));
+        assert_eq!(psg.channel(ChannelIndex::C).panning(), (0.5, 0.5));
+
+        assert!(template.clone().build().is_ok());
+        assert_eq!(template.dc_filter_length(100).build().err(), Some(Error::InvalidDCFilterLength(100)));
+
+        assert_eq!(
+            PsgBuilder::new(1e9, 44100).build().err(),
+            Some(Error::ClockRateTooHigh { clock_rate: 1e9, max: 44100.0 * 1024.0 })
+        );
+    }
+}
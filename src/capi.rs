@@ -0,0 +1,280 @@
+//! This module contains C bindings for the PSG, which are declared in `include/psg.h`. It is
+//! available when the `capi` feature is enabled.
+//!
+//! A PSG is created using `psg_new`, which returns an opaque pointer that is passed to the other
+//! functions, and destroyed using `psg_free`. Every function accepts null pointers and catches
+//! panics, so they never unwind into C. The functions that can fail return `PSG_OK` on success, or
+//! one of the negative error codes.
+//!
+//! This crate is only built as a Rust library, so the bindings are linked into a C program through
+//! a wrapper crate that is built as a shared or static library, and exports the bindings:
+//!
+//! ```toml
+//! [package]
+//! name = "psg-c"
+//! version = "0.1.0"
+//! edition = "2021"
+//!
+//! [lib]
+//! crate-type = ["cdylib", "staticlib"]
+//!
+//! [dependencies]
+//! psg = { version = "1", features = ["capi"] }
+//! ```
+//!
+//! The wrapper's `src/lib.rs` only contains `pub use psg::capi::*;`, and the C program is linked
+//! against the resulting `libpsg_c`:
+//!
+//! ```c
+//! #include "psg.h"
+//!
+//! float buffer[2 * 1024];
+//! Psg *psg = psg_new(2000000.0, 44100);
+//!
+//! psg_set_register(psg, 7, 0x3e);
+//! psg_set_register(psg, 8, 15);
+//! psg_render(psg, buffer, 1024);
+//! psg_free(psg);
+//! ```
+
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{ChipType, PSG};
+
+/// The function succeeded.
+pub const PSG_OK: i32 = 0;
+
+/// A pointer argument was null.
+pub const PSG_ERROR_NULL_POINTER: i32 = -1;
+
+/// An argument was out of range, such as a register number above 15.
+pub const PSG_ERROR_INVALID_ARGUMENT: i32 = -2;
+
+/// The PSG panicked. It should be freed, because it may be in an inconsistent state.
+pub const PSG_ERROR_PANIC: i32 = -3;
+
+/// The chip type of the General Instrument AY-3-8910. See [`ChipType::AY`].
+pub const PSG_CHIP_AY: u32 = 0;
+
+/// The chip type of the Yamaha YM2149. See [`ChipType::YM`].
+pub const PSG_CHIP_YM: u32 = 1;
+
+/// The chip type of the Microchip AY8930. See [`ChipType::AY8930`].
+pub const PSG_CHIP_AY8930: u32 = 2;
+
+/// The chip type of the Sunsoft 5B. See [`ChipType::Sunsoft5B`].
+pub const PSG_CHIP_SUNSOFT_5B: u32 = 3;
+
+/// A PSG that is owned by C code, which only sees it as an opaque type.
+#[derive(Debug)]
+pub struct Psg(PSG);
+
+/// Create a new PSG with the specified clock and sample rates, or return a null pointer when the
+/// clock rate is too high for the sample rate. See [`PSG::new`].
+#[no_mangle]
+pub extern "C" fn psg_new(clock_rate: f64, sample_rate: u32) -> *mut Psg {
+    match panic::catch_unwind(|| PSG::new(clock_rate, sample_rate)) {
+        Ok(Ok(psg)) => Box::into_raw(Box::new(Psg(psg))),
+        _ => std::ptr::null_mut()
+    }
+}
+
+/// Destroy a PSG. Null pointers are ignored.
+///
+/// # Safety
+///
+/// The pointer must be null, or returned by [`psg_new`] and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn psg_free(psg: *mut Psg) {
+    if !psg.is_null() {
+        // Safety: the pointer was created by Box::into_raw in psg_new
+        drop(unsafe { Box::from_raw(psg) });
+    }
+}
+
+/// Write a value to a register. See [`PSG::set_register`].
+///
+/// # Safety
+///
+/// The pointer must be null, or point to a PSG that was returned by [`psg_new`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn psg_set_register(psg: *mut Psg, register: u8, value: u8) -> i32 {
+    // Safety: guaranteed by the caller
+    unsafe { with_psg(psg, |psg| psg.try_set_register(register, value).map_or(PSG_ERROR_INVALID_ARGUMENT, |_| PSG_OK)) }
+}
+
+/// Render the specified number of frames into a buffer of interleaved stereo samples, which must
+/// have room for twice as many samples. See [`PSG::render_into_samples`].
+///
+/// # Safety
+///
+/// The PSG pointer must be null, or point to a PSG that was returned by [`psg_new`] and not freed.
+/// The buffer must be null, or valid for writing `2 * frames` floats.
+#[no_mangle]
+pub unsafe extern "C" fn psg_render(psg: *mut Psg, interleaved: *mut f32, frames: usize) -> i32 {
+    if interleaved.is_null() {
+        return PSG_ERROR_NULL_POINTER;
+    }
+
+    let Some(length) = frames.checked_mul(2) else {
+        return PSG_ERROR_INVALID_ARGUMENT;
+    };
+
+    // Safety: guaranteed by the caller
+    unsafe {
+        with_psg(psg, |psg| {
+            psg.render_into_samples(std::slice::from_raw_parts_mut(interleaved, length));
+            PSG_OK
+        })
+    }
+}
+
+/// Set the chip type to one of the `PSG_CHIP` constants. See [`PSG::set_chip_type`].
+///
+/// # Safety
+///
+/// The pointer must be null, or point to a PSG that was returned by [`psg_new`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn psg_set_chip_type(psg: *mut Psg, chip_type: u32) -> i32 {
+    let chip_type = match chip_type {
+        PSG_CHIP_AY => ChipType::AY,
+        PSG_CHIP_YM => ChipType::YM,
+        PSG_CHIP_AY8930 => ChipType::AY8930,
+        PSG_CHIP_SUNSOFT_5B => ChipType::Sunsoft5B,
+        _ => return PSG_ERROR_INVALID_ARGUMENT
+    };
+
+    // Safety: guaranteed by the caller
+    unsafe {
+        with_psg(psg, |psg| {
+            psg.set_chip_type(chip_type);
+            PSG_OK
+        })
+    }
+}
+
+/// Reset the registers to zero, like the reset line of the chip. See [`PSG::reset`].
+///
+/// # Safety
+///
+/// The pointer must be null, or point to a PSG that was returned by [`psg_new`] and not freed.
+#[no_mangle]
+pub unsafe extern "C" fn psg_reset(psg: *mut Psg) -> i32 {
+    // Safety: guaranteed by the caller
+    unsafe {
+        with_psg(psg, |psg| {
+            psg.reset();
+            PSG_OK
+        })
+    }
+}
+
+/// Call the function with the PSG, or return an error code when the pointer is null or the
+/// function panics.
+///
+/// # Safety
+///
+/// The pointer must be null, or point to a PSG that was returned by [`psg_new`] and not freed.
+unsafe fn with_psg(psg: *mut Psg, function: impl FnOnce(&mut PSG) -> i32) -> i32 {
+    // Safety: guaranteed by the caller
+    match unsafe { psg.as_mut() } {
+        Some(Psg(psg)) => panic::catch_unwind(AssertUnwindSafe(|| function(psg))).unwrap_or(PSG_ERROR_PANIC),
+        None => PSG_ERROR_NULL_POINTER
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{PSG_CHIP_AY8930, PSG_ERROR_INVALID_ARGUMENT, PSG_ERROR_NULL_POINTER, PSG_OK};
+    use super::{psg_free, psg_new, psg_render, psg_reset, psg_set_chip_type, psg_set_register};
+
+    const HEADER: &str = include_str!("../include/psg.h");
+    const SOURCE: &str = include_str!("capi.rs");
+
+    /// Convert a Rust type of the bindings to its C equivalent.
+    fn c_type(rust: &str) -> &'static str {
+        match rust.trim() {
+            "" => "void",
+            "*mut Psg" => "Psg *",
+            "*mut f32" => "float *",
+            "f64" => "double",
+            "i32" => "int32_t",
+            "u8" => "uint8_t",
+            "u32" => "uint32_t",
+            "usize" => "size_t",
+            other => panic!("unmapped type {other}")
+        }
+    }
+
+    #[test]
+    fn header_in_sync() {
+        // Every function of the bindings is declared with the same types, without parameter names
+        let functions: Vec<String> = SOURCE.lines()
+            .filter_map(|line| line.split_once("extern \"C\" fn ").map(|(_, signature)| signature))
+            .map(|signature| {
+                let (name, rest) = signature.split_once('(').unwrap();
+                let (parameters, rest) = rest.split_once(')').unwrap();
+                let result = rest.trim_start_matches(" -> ").trim_end_matches(" {");
+                let parameters: Vec<_> = parameters.split(", ").filter(|parameter| !parameter.is_empty())
+                    .map(|parameter| c_type(parameter.split_once(": ").unwrap().1))
+                    .collect();
+
+                let result = c_type(result);
+                let separator = if result.ends_with('*') { "" } else { " " };
+
+                format!("{result}{separator}{name}({});", parameters.join(", "))
+            })
+            .collect();
+
+        let declarations: Vec<String> = HEADER.lines()
+            .filter(|line| line.contains("psg_") && line.ends_with(");") && !line.starts_with([' ', '/', '#']))
+            .map(|line| {
+                let (head, parameters) = line.trim_end_matches(");").split_once('(').unwrap();
+                let parameters: Vec<_> = parameters.split(", ")
+                    .map(|parameter| parameter.trim_end_matches(|character: char| character.is_alphanumeric() || character == '_').trim_end())
+                    .collect();
+
+                format!("{head}({});", parameters.join(", "))
+            })
+            .collect();
+
+        assert_eq!(functions.len(), 6);
+        assert_eq!(declarations, functions);
+
+        // Every constant is defined with the same value
+        for line in SOURCE.lines().filter_map(|line| line.strip_prefix("pub const ")) {
+            let (name, value) = line.split_once(':').unwrap();
+            let value = value.split_once(" = ").unwrap().1.trim_end_matches(';');
+            let value = if value.starts_with('-') { format!("({value})") } else { value.to_owned() };
+
+            assert!(HEADER.contains(&format!("#define {name} {value}\n")), "{name} is not defined as {value}");
+        }
+    }
+
+    #[test]
+    fn null_pointers_and_errors() {
+        let mut buffer = [0.0f32; 8];
+
+        unsafe {
+            assert_eq!(psg_set_register(std::ptr::null_mut(), 8, 15), PSG_ERROR_NULL_POINTER);
+            assert_eq!(psg_render(std::ptr::null_mut(), buffer.as_mut_ptr(), 4), PSG_ERROR_NULL_POINTER);
+            assert_eq!(psg_reset(std::ptr::null_mut()), PSG_ERROR_NULL_POINTER);
+            psg_free(std::ptr::null_mut());
+
+            assert!(psg_new(1e12, 8000).is_null());
+
+            let psg = psg_new(2_000_000.0, 44100);
+            assert!(!psg.is_null());
+
+            assert_eq!(psg_set_register(psg, 16, 0), PSG_ERROR_INVALID_ARGUMENT);
+            assert_eq!(psg_set_chip_type(psg, 4), PSG_ERROR_INVALID_ARGUMENT);
+            assert_eq!(psg_set_chip_type(psg, PSG_CHIP_AY8930), PSG_OK);
+            assert_eq!(psg_render(psg, std::ptr::null_mut(), 4), PSG_ERROR_NULL_POINTER);
+            assert_eq!(psg_render(psg, buffer.as_mut_ptr(), 0), PSG_OK);
+            assert_eq!(psg_render(psg, buffer.as_mut_ptr(), usize::MAX), PSG_ERROR_INVALID_ARGUMENT);
+            assert_eq!((*psg).0.chip_type(), crate::ChipType::AY8930);
+
+            psg_free(psg);
+        }
+    }
+}
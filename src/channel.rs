@@ -1,3 +1,158 @@
+use alloc::boxed::Box;
+use core::fmt;
+
+use crate::{Error, Float, math, widen};
+use crate::state::{StateReader, StateWriter};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// One of the PSG's three channels, which are named A, B, and C on the real chip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelIndex {
+    /// The first channel, which is controlled by registers 0, 1, and 8.
+    A,
+
+    /// The second channel, which is controlled by registers 2, 3, and 9.
+    B,
+
+    /// The third channel, which is controlled by registers 4, 5, and 10.
+    C
+}
+
+impl ChannelIndex {
+    /// All three channels, in order.
+    pub const ALL: [ChannelIndex; 3] = [ChannelIndex::A, ChannelIndex::B, ChannelIndex::C];
+}
+
+impl From<ChannelIndex> for usize {
+    fn from(index: ChannelIndex) -> Self {
+        index as usize
+    }
+}
+
+impl From<ChannelIndex> for u8 {
+    fn from(index: ChannelIndex) -> Self {
+        index as u8
+    }
+}
+
+impl TryFrom<u8> for ChannelIndex {
+    type Error = Error;
+
+    /// Convert a channel number between 0 and 2 inclusive into a channel index, or return an
+    /// error for higher channel numbers.
+    fn try_from(index: u8) -> Result<Self, Error> {
+        match index {
+            0 => Ok(ChannelIndex::A),
+            1 => Ok(ChannelIndex::B),
+            2 => Ok(ChannelIndex::C),
+            _ => Err(Error::InvalidChannel(index))
+        }
+    }
+}
+
+/// Selects one of the channels, for the methods that take a channel: either a [`ChannelIndex`],
+/// or a channel number between 0 and 2 inclusive.
+///
+/// This trait is sealed, so it cannot be implemented outside of this crate.
+pub trait ChannelSelector: private::Sealed {
+    /// The selected channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3. Use [`ChannelIndex::try_from`] when the
+    /// channel number comes from untrusted data.
+    fn channel_index(self) -> ChannelIndex;
+}
+
+impl ChannelSelector for ChannelIndex {
+    fn channel_index(self) -> ChannelIndex {
+        self
+    }
+}
+
+impl ChannelSelector for u8 {
+    fn channel_index(self) -> ChannelIndex {
+        match ChannelIndex::try_from(self) {
+            Ok(index) => index,
+            Err(_) => panic!("channel number {} is not smaller than 3", self)
+        }
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for super::ChannelIndex {}
+    impl Sealed for u8 {}
+}
+
+/// The duty cycle of a channel's square wave, which is only supported by the AY8930 in expanded
+/// mode. The variants are named after the fraction of each period during which the output is
+/// high, and are listed in the order of their register values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DutyCycle {
+    /// A duty cycle of 3.125%, selected by register value 0.
+    ThirtySecond,
+
+    /// A duty cycle of 6.25%, selected by register value 1.
+    Sixteenth,
+
+    /// A duty cycle of 12.5%, selected by register value 2.
+    Eighth,
+
+    /// A duty cycle of 25%, selected by register value 3.
+    Quarter,
+
+    /// A duty cycle of 50%, selected by register value 4. This is the regular square wave.
+    Half,
+
+    /// A duty cycle of 75%, selected by register value 5.
+    ThreeQuarters,
+
+    /// A duty cycle of 87.5%, selected by register value 6.
+    SevenEighths,
+
+    /// A duty cycle of 93.75%, selected by register value 7.
+    FifteenSixteenths,
+
+    /// A duty cycle of 96.875%, selected by register value 8 and higher.
+    ThirtyOneThirtySeconds
+}
+
+impl DutyCycle {
+    /// All duty cycles, in the order of their register values.
+    pub const ALL: [DutyCycle; 9] = [
+        DutyCycle::ThirtySecond, DutyCycle::Sixteenth, DutyCycle::Eighth,
+        DutyCycle::Quarter, DutyCycle::Half, DutyCycle::ThreeQuarters,
+        DutyCycle::SevenEighths, DutyCycle::FifteenSixteenths, DutyCycle::ThirtyOneThirtySeconds
+    ];
+
+    /// Convert the value of a duty cycle register into a duty cycle. Only the lower four bits
+    /// are used, and values of 8 and higher all select a duty cycle of 96.875%.
+    pub fn from_register(value: u8) -> Self {
+        Self::ALL[((value & 0x0f) as usize).min(8)]
+    }
+
+    /// The register value that selects this duty cycle.
+    pub fn register(self) -> u8 {
+        self as u8
+    }
+
+    /// The fraction of each period during which the output is high, between 0 and 1.
+    pub fn ratio(self) -> f64 {
+        self.thirty_seconds() as f64 / 32.0
+    }
+
+    /// The duty cycle in units of 1/32 of a period.
+    fn thirty_seconds(self) -> u32 {
+        [1, 2, 4, 8, 16, 24, 28, 30, 31][self as usize]
+    }
+}
+
 /// One of the YM-3-8910/YM2149's tone generator channels.
 ///
 /// A channel represents a single square wave oscillator with configurable period, amplitude, and
@@ -6,12 +161,27 @@
 /// The channel's signal is generated as the sum of the square oscillator and the chip's noise
 /// generator, which can both be turned off independently. This signal is then multiplied with the
 /// channel amplitude, which can either be a fixed value, or the chip's envelope generator output.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Channel {
     // Oscillator
     period: u16,
     position: u16,
     value: u8,
 
+    // AY8930 duty cycle, which is only applied while the chip is in expanded mode
+    duty_cycle: DutyCycle,
+    duty_cycle_enabled: bool,
+
+    // Whether the amplitude register uses the AY8930's expanded mode layout, with a 5-bit amplitude
+    expanded_amplitude: bool,
+
+    // Whether the oscillator's counter only resets on an exact match with the period
+    hardware_counter: bool,
+
+    // Software detune that is applied by set_pitch
+    detune_cents: f64,
+
     // Flags
     pub(crate) tone_off: bool,
     pub(crate) noise_off: bool,
@@ -20,47 +190,209 @@ pub struct Channel {
     // Amplitude
     pub(crate) amplitude: u8,
 
+    // Mixer state derived from the flags and amplitude, see update_mixer
+    tone_mask: u8,
+    noise_mask: u8,
+    envelope_mask: u8,
+    fixed_level: u8,
+
     // Left/right panning
-    pub(crate) pan_left: f64,
-    pub(crate) pan_right: f64
+    pub(crate) pan_left: Float,
+    pub(crate) pan_right: Float,
+
+    // The digital-to-analog conversion table, and its levels multiplied by the left and right
+    // panning factors, boxed to keep the struct small
+    dac_table: Box<[Float; 32]>,
+    pub(crate) gains: Box<[(Float, Float); 32]>
 }
 
 impl Channel {
-    /// Initialize a new channel.
-    pub(crate) fn new() -> Self {
-        Self {
+    /// Initialize a new channel that uses the specified digital-to-analog conversion table.
+    pub(crate) fn new(dac_table: &[Float; 32]) -> Self {
+        let mut channel = Self {
             period: 1,
             position: 0,
             value: 0,
 
+            duty_cycle: DutyCycle::Half,
+            duty_cycle_enabled: false,
+
+            expanded_amplitude: false,
+
+            hardware_counter: false,
+
+            detune_cents: 0.0,
+
             tone_off: true,
             noise_off: true,
             envelope_on: false,
 
             amplitude: 0,
 
+            tone_mask: 0,
+            noise_mask: 0,
+            envelope_mask: 0,
+            fixed_level: 0,
+
             pan_left: 0.5,
-            pan_right: 0.5
+            pan_right: 0.5,
+
+            dac_table: Box::new(*dac_table),
+            gains: Box::new([(0.0, 0.0); 32])
+        };
+
+        channel.update_mixer();
+        channel.update_gains();
+        channel
+    }
+
+    /// Reset the channel to its power-on state, in which all of its registers are zero. This
+    /// enables the tone and noise (the mixer bits are active low), sets the amplitude to zero, and
+    /// restarts the oscillator. The duty cycle is reset to 50%, and the panning is left untouched.
+    pub fn reset(&mut self) {
+        self.period = 0;
+        self.position = 0;
+        self.value = 0;
+        self.duty_cycle = DutyCycle::Half;
+
+        self.tone_off = false;
+        self.noise_off = false;
+        self.envelope_on = false;
+        self.amplitude = 0;
+
+        self.update_mixer();
+    }
+
+    /// Set the digital-to-analog conversion table.
+    pub(crate) fn set_dac_table(&mut self, dac_table: &[Float; 32]) {
+        *self.dac_table = *dac_table;
+        self.update_gains();
+    }
+
+    /// Recompute the gains of all levels, which combine the digital-to-analog conversion table
+    /// with the panning factors. This avoids the multiplications when rendering.
+    fn update_gains(&mut self) {
+        for (gain, level) in self.gains.iter_mut().zip(*self.dac_table) {
+            *gain = (level * self.pan_left, level * self.pan_right);
         }
     }
 
+    /// Recompute the mixer state after a change to the flags or the amplitude.
+    ///
+    /// The tone and noise disabled flags are stored as masks that are either 0x00 or 0x1F, and the
+    /// channel's level is stored as either the fixed level derived from the amplitude, or as a mask
+    /// for the envelope level. This allows the level to be computed without any branches.
+    fn update_mixer(&mut self) {
+        self.tone_mask = if self.tone_off { 0x1f } else { 0x00 };
+        self.noise_mask = if self.noise_off { 0x1f } else { 0x00 };
+        self.envelope_mask = if self.envelope_on { 0x1f } else { 0x00 };
+        self.fixed_level = match (self.envelope_on, self.expanded_amplitude) {
+            (true, _) => 0,
+            (false, true) => self.amplitude,
+            (false, false) => self.amplitude * 2 + 1
+        };
+    }
+
+    /// Combine the output of the tone oscillator (0 or 1), the noise generator (0 or 1), and the
+    /// envelope generator into the channel's 5-bit output level.
+    fn mix(&self, tone: u8, noise: u8, envelope: u8) -> u8 {
+        // Negating the tone and noise bits expands them to masks of 0x00 or 0xff
+        let gate = (tone.wrapping_neg() | self.tone_mask) & (noise.wrapping_neg() | self.noise_mask);
+
+        gate & ((envelope & self.envelope_mask) | self.fixed_level)
+    }
+
+    /// Produce the channel's 5-bit output level for the next tick, given the output of the noise
+    /// generator (0 or 1) and the envelope generator.
+    pub(crate) fn render_level(&mut self, noise: u8, envelope: u8) -> u8 {
+        let tone = self.render();
+        self.mix(tone, noise, envelope)
+    }
+
     /// Produce a new sample for the channel's square wave oscillator.
-    pub(crate) fn render(&mut self) -> u8 {
-        self.position += 1;
+    fn render(&mut self) -> u8 {
+        if self.hardware_counter {
+            self.position = (self.position + 1) & 0x0fff;
+
+            if self.position == self.period() {
+                self.position = 0;
+                self.value ^= 1;
+            }
+        } else {
+            self.position += 1;
+
+            if self.position >= self.period {
+                self.position = 0;
+                self.value ^= 1;
+            }
+        }
 
-        if self.position >= self.period {
-            self.position = 0;
-            self.value ^= 1;
+        if self.duty_cycle_enabled {
+            return self.duty_cycle_output();
         }
 
         self.value
     }
 
+    /// The output of the square wave oscillator when a duty cycle is applied.
+    ///
+    /// The position and value of the oscillator are combined into a position within the full
+    /// period, and the output is high during the last part of the period. This makes a 50% duty
+    /// cycle identical to the regular square wave.
+    fn duty_cycle_output(&self) -> u8 {
+        let period = self.period() as u32;
+        let position = self.value as u32 * period + self.position as u32;
+
+        (position * 16 >= period * (32 - self.duty_cycle.thirty_seconds())) as u8
+    }
+
+    /// Write the channel's state to a saved state. The digital-to-analog conversion table is not
+    /// included, as it is determined by the PSG's chip type.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.u16(self.period);
+        writer.u16(self.position);
+        writer.u8(self.value);
+        writer.u8(self.duty_cycle.register());
+        writer.bool(self.tone_off);
+        writer.bool(self.noise_off);
+        writer.bool(self.envelope_on);
+        writer.u8(self.amplitude);
+        writer.float(self.pan_left);
+        writer.float(self.pan_right);
+        writer.f64(self.detune_cents);
+    }
+
+    /// Restore the channel's state from a saved state.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        self.period = reader.u16()?;
+        self.position = reader.u16()?;
+        self.value = reader.u8()?;
+        self.duty_cycle = match reader.u8()? {
+            value @ 0..=8 => DutyCycle::from_register(value),
+            _ => return Err(Error::InvalidState)
+        };
+        self.tone_off = reader.bool()?;
+        self.noise_off = reader.bool()?;
+        self.envelope_on = reader.bool()?;
+        self.amplitude = reader.u8()?;
+        self.pan_left = reader.float()?;
+        self.pan_right = reader.float()?;
+        self.detune_cents = reader.f64()?;
+
+        if self.period > 0x0fff || self.position > 0x0fff || self.value > 1 || self.amplitude > 0x1f {
+            return Err(Error::InvalidState);
+        }
+
+        self.update_mixer();
+        self.update_gains();
+        Ok(())
+    }
+
     /// The channel's tone period.
     ///
     /// This will return a value between 1 and 4095 inclusive.
     pub fn period(&self) -> u16 {
-        self.period
+        self.period.max(1)
     }
 
     /// Set the channel's tone period to a value between 1 and 4095 inclusive.
@@ -70,6 +402,34 @@ impl Channel {
         self.period = (period & 0x0fff).max(1);
     }
 
+    /// The channel's software detune in cents, see [`set_detune_cents`](Self::set_detune_cents).
+    pub fn detune_cents(&self) -> f64 {
+        self.detune_cents
+    }
+
+    /// Set the channel's software detune in cents, which is applied by
+    /// [`set_pitch`](Self::set_pitch). Running two channels a few cents apart produces chorus and
+    /// unison effects.
+    ///
+    /// The detune is not part of the chip's registers, so it does not affect the period that is
+    /// set with [`set_period`](Self::set_period) or by register writes. The default is zero.
+    pub fn set_detune_cents(&mut self, cents: f64) {
+        self.detune_cents = cents;
+    }
+
+    /// Set the channel's tone period to play the specified MIDI pitch number at the specified clock
+    /// rate, after applying the channel's detune. Pitches outside of the range of the tone
+    /// period are clamped to it.
+    ///
+    /// The clock rate should be the PSG's
+    /// [`effective_clock_rate`](crate::PSG::effective_clock_rate).
+    pub fn set_pitch(&mut self, midi_pitch: f64, clock_rate: f64) {
+        let pitch = midi_pitch + self.detune_cents / 100.0;
+        let period = math::midi_pitch_to_tone_period_checked(pitch, clock_rate).unwrap_or_else(|error| error.period());
+
+        self.set_period(period);
+    }
+
     /// The most significant byte for the channel's tone period.
     ///
     /// This will return a value between 0 and 15 inclusive.
@@ -80,13 +440,12 @@ impl Channel {
     /// Set the most significant byte of the channel's tone period to a value between 0 and 15
     /// inclusive.
     ///
-    /// Setting this byte to zero when the least significant byte is also set to zero will result
-    /// in the period being set to 1. It is therefore recommended to always set the most
-    /// significant byte first.
+    /// Like on the real chip, a period of zero (with both bytes set to zero) behaves like a period
+    /// of 1, but the bytes are stored as written.
     ///
     /// Values higher than 15 will be wrapped.
     pub fn set_period_msb(&mut self, period: u8) {
-        self.period = ((self.period & 0x00ff) | (((period as u16) & 0x0f) << 8)).max(1);
+        self.period = (self.period & 0x00ff) | (((period as u16) & 0x0f) << 8);
     }
 
     /// The least significant byte for the channel's tone period.
@@ -97,25 +456,99 @@ impl Channel {
     /// Set the least significant byte of the channel's tone period to a value between 0 and 255
     /// inclusive.
     ///
-    /// Setting this byte to zero when the most significant byte is also set to zero will result in
-    /// the period being set to 1. It is therefore recommended to always set the most significant
-    /// byte first.
+    /// Like on the real chip, a period of zero (with both bytes set to zero) behaves like a period
+    /// of 1, but the bytes are stored as written.
     pub fn set_period_lsb(&mut self, period: u8) {
-        self.period = ((self.period & 0x0f00) | (period as u16)).max(1);
+        self.period = (self.period & 0x0f00) | (period as u16);
+    }
+
+    /// The phase of the channel's square wave oscillator, as a tuple containing the position
+    /// within the current half-period (counted in ticks) as the first element, and whether the
+    /// oscillator's output is currently high as the second element.
+    pub fn phase(&self) -> (u16, bool) {
+        (self.position, self.value != 0)
+    }
+
+    /// Set the phase of the channel's square wave oscillator. The position is wrapped against the
+    /// current tone period.
+    ///
+    /// Only the oscillator is affected, the amplitude and mixer state are left untouched. This can
+    /// be used to restore a saved phase, or to align the phases of multiple channels.
+    pub fn set_phase(&mut self, position: u16, high: bool) {
+        self.position = position % self.period();
+        self.value = high as u8;
+    }
+
+    /// Restart the channel's square wave oscillator at the start of a low half-period.
+    ///
+    /// Resetting multiple channels with the same tone period in the same frame keeps them in
+    /// phase. Like [`set_phase`](Self::set_phase), this does not affect the amplitude.
+    pub fn reset_phase(&mut self) {
+        self.set_phase(0, false);
+    }
+
+    /// The duty cycle of the channel's square wave.
+    pub fn duty_cycle(&self) -> DutyCycle {
+        self.duty_cycle
+    }
+
+    /// Set the duty cycle of the channel's square wave. This corresponds to the AY8930's duty cycle
+    /// registers, and only takes effect while the PSG emulates an AY8930 in expanded mode. See
+    /// [`ChipType::AY8930`](crate::ChipType::AY8930).
+    pub fn set_duty_cycle(&mut self, duty_cycle: DutyCycle) {
+        self.duty_cycle = duty_cycle;
+    }
+
+    /// Enable or disable the duty cycle, which is controlled by the PSG's expanded mode.
+    pub(crate) fn set_duty_cycle_enabled(&mut self, enabled: bool) {
+        self.duty_cycle_enabled = enabled;
+    }
+
+    /// Enable or disable the hardware counter mode. See
+    /// [`PSG::set_hardware_counters`](crate::PSG::set_hardware_counters).
+    pub(crate) fn set_hardware_counter(&mut self, enabled: bool) {
+        self.hardware_counter = enabled;
+    }
+
+    /// Whether the amplitude register uses the 5-bit layout of the AY8930's expanded mode.
+    pub(crate) fn expanded_amplitude(&self) -> bool {
+        self.expanded_amplitude
+    }
+
+    /// Switch the amplitude register between the regular layout and the 5-bit layout of the
+    /// AY8930's expanded mode. The amplitude is wrapped to the new layout.
+    pub(crate) fn set_expanded_amplitude(&mut self, enabled: bool) {
+        self.expanded_amplitude = enabled;
+        self.amplitude &= self.amplitude_mask();
+        self.update_mixer();
+    }
+
+    /// The mask of the amplitude bits in the amplitude register.
+    fn amplitude_mask(&self) -> u8 {
+        if self.expanded_amplitude { 0x1f } else { 0x0f }
+    }
+
+    /// The mask of the used bits of the amplitude register.
+    pub(crate) fn amplitude_register_mask(&self) -> u8 {
+        (self.amplitude_mask() << 1) | 1
     }
 
     /// The channel's amplitude.
     ///
-    /// This will return a value between 0 and 15 inclusive.
+    /// This will return a value between 0 and 15 inclusive, or between 0 and 31 inclusive while
+    /// the PSG emulates an AY8930 in expanded mode.
     pub fn amplitude(&self) -> u8 {
         self.amplitude
     }
 
-    /// Set the channel's amplitude to a value between 0 and 15 inclusive.
+    /// Set the channel's amplitude to a value between 0 and 15 inclusive, or between 0 and 31
+    /// inclusive while the PSG emulates an AY8930 in expanded mode. See
+    /// [`ChipType::AY8930`](crate::ChipType::AY8930).
     ///
     /// Higher values are wrapped.
     pub fn set_amplitude(&mut self, amplitude: u8) {
-        self.amplitude = amplitude & 0x0f;
+        self.amplitude = amplitude & self.amplitude_mask();
+        self.update_mixer();
     }
 
     /// The channel's envelope enabled flag.
@@ -126,25 +559,33 @@ impl Channel {
     /// Set the channel's envelope enabled flag.
     pub fn set_envelope_enabled(&mut self, enabled: bool) {
         self.envelope_on = enabled;
+        self.update_mixer();
     }
 
     /// The channel's amplitude register.
     ///
     /// This is effectively a combination of the the amplitude and the envelope enabled flag using
-    /// a single byte. In this byte the bits 0 through 4 are the amplitude and bit 5 is the
-    /// envelope enabled flag.
+    /// a single byte. In this byte the bits 0 through 3 are the amplitude and bit 4 is the
+    /// envelope enabled flag. While the PSG emulates an AY8930 in expanded mode, bits 0 through 4
+    /// are the amplitude and bit 5 is the envelope enabled flag.
     pub fn amplitude_and_envelope_enabled(&self) -> u8 {
-        ((self.envelope_on as u8) << 4) | self.amplitude
+        let envelope_bit = self.amplitude_mask() + 1;
+
+        if self.envelope_on { envelope_bit | self.amplitude } else { self.amplitude }
     }
 
     /// Set the channel's amplitude to a value between 0 and 15 inclusive, taken from bits 0
     /// through 3 from the input value, and set the envelope enabled flag to the value of bit 4
-    /// from the input value.
+    /// from the input value. While the PSG emulates an AY8930 in expanded mode, the amplitude is
+    /// taken from bits 0 through 4, and the envelope enabled flag from bit 5.
     ///
     /// This is equivalent to writing to the channel's amplitude register on a real PSG.
     pub fn set_amplitude_and_envelope_enabled(&mut self, value: u8) {
-        self.amplitude = value & 0x0f;
-        self.envelope_on = value & 0x10 != 0;
+        let mask = self.amplitude_mask();
+
+        self.amplitude = value & mask;
+        self.envelope_on = value & (mask + 1) != 0;
+        self.update_mixer();
     }
 
     /// The channel's tone disabled flag.
@@ -155,6 +596,7 @@ impl Channel {
     /// Set the channel's tone disabled flag.
     pub fn set_tone_disabled(&mut self, disabled: bool) {
         self.tone_off = disabled;
+        self.update_mixer();
     }
 
     /// The channel's noise disabled flag.
@@ -165,12 +607,13 @@ impl Channel {
     /// Set the channel's noise disabled flag.
     pub fn set_noise_disabled(&mut self, disabled: bool) {
         self.noise_off = disabled;
+        self.update_mixer();
     }
 
     /// The channel's panning, represented as a scaling factor that is applied to the left channel
     /// (first value) and the right channel (second value).
     pub fn panning(&self) -> (f64, f64) {
-        (self.pan_left, self.pan_right)
+        (widen(self.pan_left), widen(self.pan_right))
     }
 
     /// Set the channel's panning to a value between 0.0 (full left) and 1.0 (full right)
@@ -181,12 +624,180 @@ impl Channel {
     /// root of the balance and the square root of one minus the balance and applies these values
     /// as the panning factors.
     pub fn set_panning(&mut self, balance: f64, equal_power: bool) {
-        self.pan_left = 1.0 - balance;
-        self.pan_right = balance;
+        let (mut pan_left, mut pan_right) = (1.0 - balance, balance);
 
         if equal_power {
-            self.pan_left = self.pan_left.sqrt();
-            self.pan_right = self.pan_right.sqrt();
+            pan_left = pan_left.sqrt();
+            pan_right = pan_right.sqrt();
         }
+
+        self.pan_left = pan_left as Float;
+        self.pan_right = pan_right as Float;
+
+        self.update_gains();
+    }
+}
+
+// The derived mixer state and gains are left out, as they follow from the other fields
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Channel")
+            .field("period", &self.period)
+            .field("duty_cycle", &self.duty_cycle)
+            .field("amplitude", &self.amplitude)
+            .field("tone_disabled", &self.tone_off)
+            .field("noise_disabled", &self.noise_off)
+            .field("envelope_enabled", &self.envelope_on)
+            .field("panning", &self.panning())
+            .field("detune_cents", &self.detune_cents)
+            .field("phase", &self.phase())
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{Channel, ChannelIndex, ChannelSelector, DutyCycle};
+    use crate::{DacTable, Error, PSG, math};
+
+    #[test]
+    fn channel_index() {
+        for (number, index) in ChannelIndex::ALL.into_iter().enumerate() {
+            assert_eq!(ChannelIndex::try_from(number as u8), Ok(index));
+            assert_eq!(usize::from(index), number);
+            assert_eq!(u8::from(index), number as u8);
+        }
+
+        assert_eq!(ChannelIndex::try_from(3), Err(Error::InvalidChannel(3)));
+    }
+
+    #[test]
+    fn channel_selector() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        // Channel numbers keep working alongside channel indices
+        psg.set_amplitude(1, 9);
+        psg.channel_mut(2u8).set_amplitude(5);
+
+        assert_eq!(psg.amplitude(ChannelIndex::B), 9);
+        assert_eq!(psg.channel(2).amplitude(), 5);
+        assert_eq!(1u8.channel_index(), ChannelIndex::B);
+        assert_eq!(ChannelIndex::C.channel_index(), ChannelIndex::C);
+    }
+
+    #[test]
+    #[should_panic(expected = "channel number 3 is not smaller than 3")]
+    fn channel_selector_out_of_range() {
+        let _ = PSG::new(1_000_000.0, 44100).unwrap().channel(3);
+    }
+
+    #[test]
+    fn duty_cycle() {
+        let mut channel = Channel::new(DacTable::AY.table());
+
+        channel.set_period(8);
+
+        let square: Vec<u8> = (0..64).map(|_| channel.render()).collect();
+
+        // The duty cycle is ignored until it is enabled, and 50% is identical to the square wave
+        channel.set_duty_cycle(DutyCycle::Quarter);
+        assert_eq!((0..64).map(|_| channel.render()).collect::<Vec<_>>(), square);
+
+        channel.set_duty_cycle(DutyCycle::Half);
+        channel.set_duty_cycle_enabled(true);
+        assert_eq!((0..64).map(|_| channel.render()).collect::<Vec<_>>(), square);
+
+        // A 25% duty cycle is high for 4 out of every 16 ticks
+        channel.set_duty_cycle(DutyCycle::Quarter);
+
+        let quarter: Vec<u8> = (0..64).map(|_| channel.render()).collect();
+
+        assert_eq!(quarter[..16], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 0]);
+        assert_eq!(quarter.iter().map(|&value| value as usize).sum::<usize>(), 16);
+
+        assert_eq!(DutyCycle::from_register(3), DutyCycle::Quarter);
+        assert_eq!(DutyCycle::from_register(0x0f), DutyCycle::ThirtyOneThirtySeconds);
+        assert_eq!(DutyCycle::ThirtySecond.ratio(), 0.03125);
+    }
+
+    #[test]
+    fn hardware_counter() {
+        let mut channel = Channel::new(DacTable::AY.table());
+        channel.set_period(8);
+
+        // Shortening the period below the position flips the output on the next tick
+        let _ = (0..5).map(|_| channel.render()).count();
+        channel.set_period(3);
+        assert_eq!(channel.render(), 1);
+
+        // In hardware counter mode the counter runs through the full 12-bit range first
+        channel.reset_phase();
+        channel.set_period(8);
+        channel.set_hardware_counter(true);
+
+        let _ = (0..5).map(|_| channel.render()).count();
+        channel.set_period(3);
+
+        let ticks = (0..4096).position(|_| channel.render() == 1).unwrap() + 1;
+        assert_eq!(ticks, 4096 - 5 + 3);
+
+        // Afterwards the new period is used
+        assert_eq!((0..6).map(|_| channel.render()).collect::<Vec<_>>(), [1, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn mix() {
+        let mut channel = Channel::new(DacTable::YM.table());
+        let mut seed: u32 = 1;
+
+        for _ in 0..100_000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let random = seed >> 8;
+
+            match random & 3 {
+                0 => channel.set_amplitude_and_envelope_enabled((random >> 2) as u8),
+                1 => channel.set_tone_disabled(random & 4 != 0),
+                2 => channel.set_noise_disabled(random & 4 != 0),
+                _ => channel.set_amplitude((random >> 2) as u8)
+            }
+
+            let tone = ((random >> 8) & 1) as u8;
+            let noise = ((random >> 9) & 1) as u8;
+            let envelope = ((random >> 10) & 31) as u8;
+
+            // The original mixer logic, which the branch-free version must match exactly
+            let mut expected = (tone | channel.tone_off as u8) & (noise | channel.noise_off as u8);
+
+            expected *= if channel.envelope_on {
+                envelope
+            } else {
+                channel.amplitude * 2 + 1
+            };
+
+            assert_eq!(channel.mix(tone, noise, envelope), expected);
+        }
+    }
+
+    #[test]
+    fn detune() {
+        let clock_rate = 1_000_000.0;
+        let mut lower = Channel::new(DacTable::AY.table());
+        let mut upper = Channel::new(DacTable::AY.table());
+
+        lower.set_detune_cents(-5.0);
+        upper.set_detune_cents(5.0);
+        assert_eq!(upper.detune_cents(), 5.0);
+
+        // At low pitches the periods are far enough apart to show a detune of a few cents
+        lower.set_pitch(36.0, clock_rate);
+        upper.set_pitch(36.0, clock_rate);
+
+        assert_eq!(lower.period(), math::midi_pitch_to_tone_period(35.95, clock_rate));
+        assert_eq!(upper.period(), math::midi_pitch_to_tone_period(36.05, clock_rate));
+        assert!(lower.period() > upper.period());
+
+        // The raw period is not detuned
+        upper.set_period(1000);
+        assert_eq!(upper.period(), 1000);
     }
 }
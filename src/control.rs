@@ -0,0 +1,534 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{ChannelIndex, ChipType, Error, PSG, Sample};
+
+/// The chip types in the order of their encoding in a queue slot.
+const CHIP_TYPES: [ChipType; 4] = [ChipType::AY, ChipType::YM, ChipType::AY8930, ChipType::Sunsoft5B];
+
+/// A change to a PSG that is sent from a [`PsgController`] to the thread that renders it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Write a value to a register. See [`PSG::set_register`].
+    WriteRegister {
+        /// The register number.
+        register: u8,
+
+        /// The value to write.
+        value: u8
+    },
+
+    /// Set the panning of a channel. See [`Channel::set_panning`](crate::Channel::set_panning).
+    SetPanning {
+        /// The channel to pan.
+        channel: ChannelIndex,
+
+        /// The balance, from 0 for the left to 1 for the right.
+        balance: f64,
+
+        /// Whether the balance is interpreted using an equal power law.
+        equal_power: bool
+    },
+
+    /// Set the gain that is applied to the output of a [`ControlledPsg`].
+    SetGain(f64),
+
+    /// Set the chip type. See [`PSG::set_chip_type`].
+    SetChipType(ChipType),
+
+    /// Write a value to a register right before the specified frame is rendered, counted like
+    /// [`PSG::frames_rendered`]. Writes for frames that have already been rendered are applied
+    /// right away.
+    ///
+    /// Writes for later frames are set aside until their frame comes, so they do not hold up the
+    /// commands that are sent after them. Writes for the same frame are applied in the order in
+    /// which they were sent. See [`ControlledPsg`] for how many writes can be set aside.
+    ScheduleWrite {
+        /// The frame before which the value is written.
+        frame: u64,
+
+        /// The register number.
+        register: u8,
+
+        /// The value to write.
+        value: u8
+    }
+}
+
+impl Command {
+    /// Encode the command into the two words of a queue slot.
+    fn encode(self) -> [u64; 2] {
+        match self {
+            Command::WriteRegister { register, value } => [(register as u64) << 8 | (value as u64) << 16, 0],
+            Command::SetPanning { channel, balance, equal_power } => {
+                [1 | (u8::from(channel) as u64) << 8 | (equal_power as u64) << 16, balance.to_bits()]
+            }
+            Command::SetGain(gain) => [2, gain.to_bits()],
+            Command::SetChipType(chip_type) => {
+                [3 | (CHIP_TYPES.iter().position(|&candidate| candidate == chip_type).unwrap_or(0) as u64) << 8, 0]
+            }
+            Command::ScheduleWrite { frame, register, value } => [4 | (register as u64) << 8 | (value as u64) << 16, frame]
+        }
+    }
+
+    /// Decode a command from the two words of a queue slot.
+    fn decode([first, second]: [u64; 2]) -> Self {
+        let field = |shift: u32| (first >> shift) as u8;
+
+        match field(0) {
+            0 => Command::WriteRegister { register: field(8), value: field(16) },
+            1 => Command::SetPanning {
+                channel: ChannelIndex::try_from(field(8)).unwrap_or(ChannelIndex::A),
+                balance: f64::from_bits(second),
+                equal_power: field(16) != 0
+            },
+            2 => Command::SetGain(f64::from_bits(second)),
+            3 => Command::SetChipType(CHIP_TYPES[field(8) as usize % CHIP_TYPES.len()]),
+            _ => Command::ScheduleWrite { frame: second, register: field(8), value: field(16) }
+        }
+    }
+}
+
+/// A bounded single-producer single-consumer queue of commands, which stores every command in a
+/// slot of two atomic words so neither side needs to lock or allocate.
+#[derive(Debug)]
+struct Queue {
+    slots: Box<[[AtomicU64; 2]]>,
+
+    // The number of commands that have been pushed and popped, which only ever grow
+    pushed: AtomicUsize,
+    popped: AtomicUsize
+}
+
+impl Queue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect(),
+            pushed: AtomicUsize::new(0),
+            popped: AtomicUsize::new(0)
+        }
+    }
+
+    /// Push a command, which must only be done by a single thread at a time.
+    fn push(&self, command: Command) -> bool {
+        let pushed = self.pushed.load(Ordering::Relaxed);
+
+        if pushed.wrapping_sub(self.popped.load(Ordering::Acquire)) == self.slots.len() {
+            return false;
+        }
+
+        let slot = &self.slots[pushed % self.slots.len()];
+
+        for (word, value) in slot.iter().zip(command.encode()) {
+            word.store(value, Ordering::Relaxed);
+        }
+
+        self.pushed.store(pushed.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Return the next command without popping it, which must only be done by the thread that
+    /// pops.
+    fn peek(&self) -> Option<Command> {
+        let popped = self.popped.load(Ordering::Relaxed);
+
+        if popped == self.pushed.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let slot = &self.slots[popped % self.slots.len()];
+        Some(Command::decode([slot[0].load(Ordering::Relaxed), slot[1].load(Ordering::Relaxed)]))
+    }
+
+    /// Pop a command, which must only be done by a single thread at a time.
+    fn pop(&self) -> Option<Command> {
+        let command = self.peek()?;
+
+        self.popped.store(self.popped.load(Ordering::Relaxed).wrapping_add(1), Ordering::Release);
+        Some(command)
+    }
+}
+
+/// A handle for changing a PSG that is rendered on another thread, such as an audio callback.
+///
+/// Commands are sent through a bounded lock-free queue to a [`ControlledPsg`], which applies them
+/// on the rendering thread. The controller can be cloned and shared between threads. Sending is
+/// serialized by a mutex on the controller's side, but the rendering side never locks or
+/// allocates, so it is safe to use in real-time code.
+///
+/// When the queue is full, a command is rejected with [`Error::QueueFull`], and the commands that
+/// are already queued are kept. The sender can then retry later, or drop the command.
+///
+/// ```
+/// use psg::PSG;
+///
+/// let psg = PSG::new(2_000_000.0, 44100).unwrap();
+/// let (mut psg, controller) = psg.split_control(64);
+///
+/// controller.write_register(8, 15).unwrap();
+///
+/// // On the rendering thread
+/// let frame = psg.render();
+/// assert_eq!(psg.psg().register(8), 15);
+/// ```
+#[derive(Clone, Debug)]
+pub struct PsgController {
+    queue: Arc<Queue>,
+    sending: Arc<Mutex<()>>
+}
+
+impl PsgController {
+    /// Initialize a new controller with a queue that holds up to the specified number of
+    /// commands, and the receiver that takes them from the queue. See also
+    /// [`PSG::split_control`].
+    pub fn new(capacity: usize) -> (Self, CommandReceiver) {
+        let queue = Arc::new(Queue::new(capacity));
+
+        let controller = Self {
+            queue: Arc::clone(&queue),
+            sending: Arc::new(Mutex::new(()))
+        };
+
+        (controller, CommandReceiver { queue })
+    }
+
+    /// Send a command, or return [`Error::QueueFull`] when the queue is full.
+    pub fn send(&self, command: Command) -> Result<(), Error> {
+        let _sending = self.sending.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if self.queue.push(command) { Ok(()) } else { Err(Error::QueueFull) }
+    }
+
+    /// Write a value to a register. See [`PSG::set_register`].
+    pub fn write_register(&self, register: u8, value: u8) -> Result<(), Error> {
+        self.send(Command::WriteRegister { register, value })
+    }
+
+    /// Set the panning of a channel. See [`Channel::set_panning`](crate::Channel::set_panning).
+    pub fn set_panning(&self, channel: ChannelIndex, balance: f64, equal_power: bool) -> Result<(), Error> {
+        self.send(Command::SetPanning { channel, balance, equal_power })
+    }
+
+    /// Set the gain that is applied to the output. See [`ControlledPsg::set_gain`].
+    pub fn set_gain(&self, gain: f64) -> Result<(), Error> {
+        self.send(Command::SetGain(gain))
+    }
+
+    /// Set the chip type. See [`PSG::set_chip_type`].
+    pub fn set_chip_type(&self, chip_type: ChipType) -> Result<(), Error> {
+        self.send(Command::SetChipType(chip_type))
+    }
+
+    /// Write a value to a register right before the specified frame is rendered. See
+    /// [`Command::ScheduleWrite`].
+    pub fn schedule_write(&self, frame: u64, register: u8, value: u8) -> Result<(), Error> {
+        self.send(Command::ScheduleWrite { frame, register, value })
+    }
+}
+
+/// The receiving side of a [`PsgController`], which is turned into a [`ControlledPsg`] on the
+/// rendering thread.
+#[derive(Debug)]
+pub struct CommandReceiver {
+    queue: Arc<Queue>
+}
+
+impl CommandReceiver {
+    /// Take the next command from the queue, if any.
+    pub fn receive(&mut self) -> Option<Command> {
+        self.queue.pop()
+    }
+}
+
+/// The rendering half of a PSG that is changed through a [`PsgController`], which is created by
+/// [`PSG::split_control`].
+///
+/// Every frame is rendered after applying the commands that have been sent so far, in the order
+/// in which they were sent. Scheduled writes wait for their frame, which makes them sample
+/// accurate. Applying the commands does not lock or allocate, apart from what the commands
+/// themselves do to the PSG, such as recording register writes.
+///
+/// Scheduled writes that are waiting for their frame are kept in a buffer with room for as many
+/// writes as the queue. While that buffer is full, another scheduled write for a later frame stays
+/// in the queue until the earliest waiting write is due, and holds up the commands behind it.
+#[derive(Debug)]
+pub struct ControlledPsg {
+    psg: PSG,
+    receiver: CommandReceiver,
+    gain: f64,
+
+    // The scheduled writes that were taken from the queue before their frame, as tuples of the
+    // frame, register, and value ordered by frame, which never grows beyond its capacity
+    scheduled: Vec<(u64, u8, u8)>,
+    scheduled_capacity: usize
+}
+
+impl ControlledPsg {
+    /// Initialize a new controlled PSG, which applies the commands of the receiver.
+    pub fn new(psg: PSG, receiver: CommandReceiver) -> Self {
+        let scheduled_capacity = receiver.queue.slots.len();
+
+        Self {
+            psg,
+            receiver,
+            gain: 1.0,
+            scheduled: Vec::with_capacity(scheduled_capacity),
+            scheduled_capacity
+        }
+    }
+
+    /// A reference to the PSG.
+    pub fn psg(&self) -> &PSG {
+        &self.psg
+    }
+
+    /// A mutable reference to the PSG, for changes that cannot be sent as commands.
+    pub fn psg_mut(&mut self) -> &mut PSG {
+        &mut self.psg
+    }
+
+    /// Consume the controlled PSG and return the PSG. Commands that have not been applied yet are
+    /// discarded.
+    pub fn into_inner(self) -> PSG {
+        self.psg
+    }
+
+    /// The gain that is applied to the output.
+    pub fn gain(&self) -> f64 {
+        self.gain
+    }
+
+    /// Set the gain that is applied to the output, which is 1 by default.
+    pub fn set_gain(&mut self, gain: f64) {
+        self.gain = gain;
+    }
+
+    /// Apply the commands that are due, and render a frame.
+    pub fn render(&mut self) -> (f64, f64) {
+        self.apply_commands();
+
+        let (left, right) = self.psg.render();
+        (left * self.gain, right * self.gain)
+    }
+
+    /// Render `interleaved.len() / 2` frames into the provided buffer of interleaved stereo samples.
+    /// See [`PSG::render_into_samples`].
+    pub fn render_into_samples<S: Sample>(&mut self, interleaved: &mut [S]) {
+        for frame in interleaved.chunks_exact_mut(2) {
+            let (left, right) = self.render();
+
+            frame[0] = S::from_f64(left);
+            frame[1] = S::from_f64(right);
+        }
+    }
+
+    /// Apply the scheduled writes that are due, followed by the queued commands. Scheduled writes
+    /// that are not due yet are set aside while there is room for them.
+    fn apply_commands(&mut self) {
+        let frame = self.psg.frames_rendered();
+
+        // The writes that were set aside were sent before the commands that are still queued
+        let due = self.scheduled.partition_point(|&(scheduled, ..)| scheduled <= frame);
+
+        for (_, register, value) in self.scheduled.drain(..due) {
+            self.psg.set_register(register, value);
+        }
+
+        while let Some(command) = self.receiver.queue.peek() {
+            let waiting = matches!(command, Command::ScheduleWrite { frame: scheduled, .. } if scheduled > frame);
+
+            if waiting && self.scheduled.len() == self.scheduled_capacity {
+                break;
+            }
+
+            self.receiver.queue.pop();
+
+            match command {
+                Command::WriteRegister { register, value } => self.psg.set_register(register, value),
+                Command::SetPanning { channel, balance, equal_power } => self.psg.channel_mut(channel).set_panning(balance, equal_power),
+                Command::SetGain(gain) => self.gain = gain,
+                Command::SetChipType(chip_type) => self.psg.set_chip_type(chip_type),
+                Command::ScheduleWrite { frame: scheduled, register, value } if scheduled > frame => {
+                    let index = self.scheduled.partition_point(|&(other, ..)| other <= scheduled);
+                    self.scheduled.insert(index, (scheduled, register, value));
+                }
+                Command::ScheduleWrite { register, value, .. } => self.psg.set_register(register, value)
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::{Command, ControlledPsg, PsgController};
+    use crate::{ChannelIndex, ChipType, Error, PSG};
+
+    #[test]
+    fn commands() {
+        let (controller, receiver) = PsgController::new(4);
+        let mut psg = ControlledPsg::new(PSG::new(2_000_000.0, 44100).unwrap(), receiver);
+
+        controller.write_register(0, 0x1c).unwrap();
+        controller.clone().set_panning(ChannelIndex::B, 0.25, true).unwrap();
+        controller.set_chip_type(ChipType::AY8930).unwrap();
+        controller.write_register(8, 15).unwrap();
+        assert_eq!(controller.write_register(9, 15), Err(Error::QueueFull));
+
+        let _ = psg.render();
+
+        let mut reference = PSG::new(2_000_000.0, 44100).unwrap();
+        reference.channel_mut(ChannelIndex::B).set_panning(0.25, true);
+
+        assert_eq!((psg.psg().register(0), psg.psg().register(8), psg.psg().register(9)), (0x1c, 15, 0));
+        assert_eq!(psg.psg().channel(ChannelIndex::B).panning(), reference.channel(ChannelIndex::B).panning());
+        assert_eq!(psg.psg().chip_type(), ChipType::AY8930);
+
+        // Every command survives the round trip through a queue slot, which wraps around
+        let (controller, mut receiver) = PsgController::new(3);
+
+        for command in [
+            Command::WriteRegister { register: 13, value: 0xff },
+            Command::SetPanning { channel: ChannelIndex::C, balance: 0.1, equal_power: false },
+            Command::SetGain(-0.5),
+            Command::SetChipType(ChipType::Sunsoft5B),
+            Command::ScheduleWrite { frame: u64::MAX, register: 7, value: 0x38 }
+        ] {
+            controller.send(command).unwrap();
+            assert_eq!(receiver.receive(), Some(command));
+        }
+
+        assert_eq!(receiver.receive(), None);
+    }
+
+    #[test]
+    fn scheduled_writes_and_gain() {
+        let psg = PSG::new(2_000_000.0, 44100).unwrap();
+        let mut reference = psg.clone();
+        let (mut psg, controller) = psg.split_control(16);
+
+        controller.write_register(7, 0x3e).unwrap();
+        controller.set_gain(0.5).unwrap();
+        controller.schedule_write(u64::MAX, 8, 1).unwrap();
+        controller.schedule_write(100, 8, 15).unwrap();
+        controller.schedule_write(50, 0, 0x20).unwrap();
+        controller.write_register(0, 0x40).unwrap();
+
+        // The commands after the scheduled writes are not held up by them, and the writes are
+        // applied in the order of their frames
+        reference.set_register(7, 0x3e);
+        reference.set_register(0, 0x40);
+
+        for frame in 0..200 {
+            match frame {
+                50 => reference.set_register(0, 0x20),
+                100 => reference.set_register(8, 15),
+                _ => ()
+            }
+
+            let (left, right) = reference.render();
+            assert_eq!(psg.render(), (0.5 * left, 0.5 * right));
+        }
+
+        // Writes for frames that have already been rendered are applied right away
+        controller.schedule_write(10, 8, 0).unwrap();
+        let _ = psg.render();
+        assert_eq!(psg.psg().register(8), 0);
+        assert_eq!(psg.gain(), 0.5);
+    }
+
+    #[test]
+    fn scheduled_write_buffer() {
+        let (mut psg, controller) = PSG::new(2_000_000.0, 44100).unwrap().split_control(2);
+
+        controller.schedule_write(20, 8, 20).unwrap();
+        controller.schedule_write(10, 8, 10).unwrap();
+        let _ = psg.render();
+
+        // Other commands are still applied while the buffer is full
+        controller.write_register(1, 0x01).unwrap();
+        let _ = psg.render();
+        assert_eq!(psg.psg().register(1), 0x01);
+
+        // Another write for a later frame holds up the queue until the write for frame 10 is due
+        controller.schedule_write(30, 8, 30).unwrap();
+        controller.write_register(0, 0x10).unwrap();
+        let _ = psg.render();
+
+        assert_eq!(controller.write_register(1, 0x01), Err(Error::QueueFull));
+        assert_eq!(psg.psg().register(0), 1);
+
+        let mut values = Vec::new();
+
+        while psg.psg().frames_rendered() < 40 {
+            let _ = psg.render();
+            values.push((psg.psg().register(8), psg.psg().register(0)));
+        }
+
+        // Once the write for frame 10 is applied, the queue drains, and the buffer never grows
+        assert_eq!(values[6], (0, 1));
+        assert_eq!(values[7], (10, 0x10));
+        assert_eq!((values[17], values[27]), ((20, 0x10), (30, 0x10)));
+        assert_eq!(psg.scheduled.capacity(), 2);
+    }
+
+    #[test]
+    fn concurrent_commands() {
+        const COUNT: usize = 20000;
+
+        let (mut psg, controller) = PSG::new(2_000_000.0, 44100).unwrap().split_control(64);
+        let written = Arc::new(Mutex::new(Vec::with_capacity(COUNT)));
+        let observed = Arc::clone(&written);
+
+        psg.psg_mut().set_write_observer(Some(Box::new(move |_, value, _| observed.lock().unwrap().push(value))));
+
+        // Send from two threads, one of which retries rejected commands until they are accepted
+        let senders = [(0, true), (1, false)].map(|(register, retry)| {
+            let controller = controller.clone();
+
+            thread::spawn(move || {
+                let mut accepted = 0;
+
+                for index in 0..COUNT / 2 {
+                    let command = Command::WriteRegister { register, value: (2 * index + register as usize) as u8 };
+
+                    loop {
+                        match controller.send(command) {
+                            Ok(()) => accepted += 1,
+                            Err(error) if retry => {
+                                assert_eq!(error, Error::QueueFull);
+                                thread::yield_now();
+                                continue;
+                            }
+                            Err(error) => assert_eq!(error, Error::QueueFull)
+                        }
+
+                        break;
+                    }
+                }
+
+                accepted
+            })
+        });
+
+        let mut buffer = [0.0f32; 2 * 32];
+
+        while !senders.iter().all(|sender| sender.is_finished()) {
+            psg.render_into_samples(&mut buffer);
+        }
+
+        psg.render_into_samples(&mut buffer);
+
+        let accepted = senders.map(|sender| sender.join().unwrap());
+        let written = written.lock().unwrap();
+
+        assert_eq!(accepted[0], COUNT / 2);
+        assert_eq!(written.len(), accepted[0] + accepted[1]);
+
+        // The writes of the retrying thread all arrived, in order
+        let first: Vec<u8> = written.iter().copied().filter(|value| value % 2 == 0).collect();
+        assert_eq!(first, (0..COUNT / 2).map(|index| (2 * index) as u8).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{Error, Float, widen};
+
+/// Digital-to-analog amplitude conversion table for the AY-3-8910. Internally, amplitudes are
+/// represented as 5-bit values. The AY only has 16 amplitude levels. This table therefore contains
+/// quantized values.
+#[cfg_attr(feature = "f32", allow(clippy::excessive_precision))]
+const AY_DAC_TABLE: [Float; 32] = [
+    0.0,             0.0,             0.00999465934234, 0.00999465934234,
+    0.0144502937362, 0.0144502937362, 0.0210574502174,  0.0210574502174,
+    0.0307011520562, 0.0307011520562, 0.0455481803616,  0.0455481803616,
+    0.0644998855573, 0.0644998855573, 0.107362478065,   0.107362478065,
+    0.126588845655,  0.126588845655,  0.20498970016,    0.20498970016,
+    0.292210269322,  0.292210269322,  0.372838941024,   0.372838941024,
+    0.492530708782,  0.492530708782,  0.635324635691,   0.635324635691,
+    0.805584802014,  0.805584802014,  1.0,              1.0
+];
+
+/// Digital-to-analog amplitude conversion table for the YM2149, utilizing the full 5-bit dynamic
+/// range. Note that the PSG registers only support setting the amplitude as a 4-bit value, and
+/// that a value of 0 always represents a mute channel. Only the envelope generator uses 5-bit
+/// amplitudes.
+#[cfg_attr(feature = "f32", allow(clippy::excessive_precision))]
+const YM_DAC_TABLE: [Float; 32] = [
+    0.0,             0.0,             0.00465400167849, 0.00772106507973,
+    0.0109559777218, 0.0139620050355, 0.0169985503929,  0.0200198367285,
+    0.024368657969,  0.029694056611,  0.0350652323186,  0.0403906309606,
+    0.0485389486534, 0.0583352407111, 0.0680552376593,  0.0777752346075,
+    0.0925154497597, 0.111085679408,  0.129747463188,   0.148485542077,
+    0.17666895552,   0.211551079576,  0.246387426566,   0.281101701381,
+    0.333730067903,  0.400427252613,  0.467383840696,   0.53443198291,
+    0.635172045472,  0.75800717174,   0.879926756695,   1.0
+];
+
+/// Digital-to-analog amplitude conversion table for the Sunsoft 5B. Its converter has 32 levels
+/// that are spaced 1.5 dB apart, and which follow the logarithmic curve more closely than those
+/// of the YM2149.
+#[cfg_attr(feature = "f32", allow(clippy::excessive_precision))]
+const SUNSOFT_5B_DAC_TABLE: [Float; 32] = [
+    0.0,              0.0056234132519, 0.00668343917569, 0.00794328234724,
+    0.00944060876286, 0.011220184543,  0.0133352143216,  0.0158489319246,
+    0.0188364908949,  0.0223872113857, 0.026607250598,   0.0316227766017,
+    0.0375837404288,  0.0446683592151, 0.0530884444231,  0.063095734448,
+    0.0749894209332,  0.0891250938134, 0.105925372518,   0.125892541179,
+    0.149623565609,   0.177827941004,  0.211348903984,   0.251188643151,
+    0.298538261892,   0.354813389234,  0.421696503429,   0.501187233627,
+    0.595662143529,   0.707945784384,  0.841395141645,   1.0
+];
+
+/// The built-in digital-to-analog conversion tables, which convert the 5-bit output level of a
+/// channel into its amplitude. See [`PSG::set_dac_table`](crate::PSG::set_dac_table).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DacTable {
+    /// The table of the AY-3-8910, which has 16 distinct levels.
+    AY,
+
+    /// The table of the YM2149, which has 32 distinct levels.
+    YM,
+
+    /// The table of the Sunsoft 5B, which has 32 distinct levels.
+    Sunsoft5B
+}
+
+impl DacTable {
+    /// A typical bias for [`biased_levels`](Self::biased_levels), which is roughly half of the
+    /// lowest non-zero amplitude of the YM2149.
+    pub const DEFAULT_BIAS: f64 = 0.0025;
+
+    /// The table's amplitudes for all 32 output levels.
+    pub fn levels(self) -> [f64; 32] {
+        self.table().map(widen)
+    }
+
+    /// The table's amplitudes with a hardware bias applied, for use with
+    /// [`PSG::set_dac_table`](crate::PSG::set_dac_table).
+    ///
+    /// On real hardware a channel at amplitude 0 does not output silence, but sits at a small
+    /// DC level above the level of a closed tone or noise gate. Some sample playback techniques
+    /// rely on the step between these levels. This raises every level except level 0 to at least
+    /// the specified bias, so a tone at amplitude 0 leaks into the output. The DC filter removes
+    /// the bias from the output in the long term.
+    ///
+    /// A bias that is negative or NaN leaves the table unchanged, and a bias above 1 results in a
+    /// table that is rejected by [`PSG::set_dac_table`](crate::PSG::set_dac_table).
+    pub fn biased_levels(self, bias: f64) -> [f64; 32] {
+        let mut levels = self.levels();
+
+        for level in &mut levels[1..] {
+            *level = level.max(bias);
+        }
+
+        levels
+    }
+
+    /// The table's amplitudes in the internal sample type.
+    pub(crate) fn table(self) -> &'static [Float; 32] {
+        match self {
+            DacTable::AY => &AY_DAC_TABLE,
+            DacTable::YM => &YM_DAC_TABLE,
+            DacTable::Sunsoft5B => &SUNSOFT_5B_DAC_TABLE
+        }
+    }
+}
+
+/// Validate a user-supplied table and convert it to the internal sample type. Every amplitude
+/// must be finite, lie between 0 and 1 inclusive, and not be lower than the amplitude of the
+/// previous level. Returns an error containing the index of the first invalid amplitude
+/// otherwise.
+pub(crate) fn validate(levels: &[f64; 32]) -> Result<[Float; 32], Error> {
+    let mut previous = 0.0;
+
+    for (index, &level) in levels.iter().enumerate() {
+        if !(previous..=1.0).contains(&level) {
+            return Err(Error::InvalidDacTable(index));
+        }
+
+        previous = level;
+    }
+
+    Ok(levels.map(|level| level as Float))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{DacTable, validate};
+    use crate::Error;
+
+    #[test]
+    fn validation() {
+        for table in [DacTable::AY, DacTable::YM, DacTable::Sunsoft5B] {
+            assert!(validate(&table.levels()).is_ok());
+        }
+
+        let mut levels = DacTable::YM.levels();
+
+        levels[5] = f64::NAN;
+        assert_eq!(validate(&levels), Err(Error::InvalidDacTable(5)));
+
+        levels[5] = 0.001;
+        assert_eq!(validate(&levels), Err(Error::InvalidDacTable(5)));
+
+        levels[5] = levels[4];
+        levels[31] = 1.5;
+        assert_eq!(validate(&levels), Err(Error::InvalidDacTable(31)));
+
+        levels[0] = -0.25;
+        assert_eq!(validate(&levels), Err(Error::InvalidDacTable(0)));
+    }
+
+    #[test]
+    fn biased_levels() {
+        for table in [DacTable::AY, DacTable::YM, DacTable::Sunsoft5B] {
+            let levels = table.biased_levels(DacTable::DEFAULT_BIAS);
+
+            assert!(validate(&levels).is_ok());
+            assert_eq!(levels[0], 0.0);
+            assert_eq!(levels[1], table.levels()[1].max(DacTable::DEFAULT_BIAS));
+            assert_eq!(levels[31], 1.0);
+
+            assert_eq!(table.biased_levels(f64::NAN), table.levels());
+            assert_eq!(table.biased_levels(-1.0), table.levels());
+            assert_eq!(validate(&table.biased_levels(2.0)), Err(Error::InvalidDacTable(1)));
+        }
+    }
+}
@@ -1,43 +1,201 @@
+use alloc::boxed::Box;
+use alloc::vec;
+
+use crate::{Error, Float};
+use crate::state::{StateReader, StateWriter};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// The default size of the DC filter's moving average window, in frames.
+pub const DEFAULT_DC_FILTER_LENGTH: usize = 1024;
+
+/// The type of filter that is used to remove the DC offset from the PSG's output signal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DCFilterMode {
+    /// Subtract the moving average of the most recent frames from the signal. This is the default
+    /// mode, and is identical to the filter used by Ayumi. See
+    /// [`PSG::with_dc_filter_length`](crate::PSG::with_dc_filter_length) for configuring the
+    /// window length.
+    MovingAverage,
+
+    /// A first-order high-pass filter with the specified cutoff frequency in Hz. Unlike the moving
+    /// average filter this has a smooth frequency response without ripple in the low bass, and it
+    /// requires very little state.
+    OnePole {
+        /// The cutoff frequency of the filter in Hz.
+        cutoff_hz: f64
+    }
+}
+
 /// A two channel DC-offset elimination filter.
 ///
 /// Essentialy this is just a moving average filter where the average is subtracted from the input
-/// signal.
-pub struct DCFilter<const S: usize = 1024> {
-    left_sum: f64,
-    right_sum: f64,
-    left_delay: [f64; S],
-    right_delay: [f64; S],
+/// signal. The size of the moving average window must be a power of two.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DCFilter {
+    left_sum: Float,
+    right_sum: Float,
+    left_delay: Box<[Float]>,
+    right_delay: Box<[Float]>,
     index: usize
 }
 
-impl<const S: usize> DCFilter<S> {
-    /// Initialize a new DC-offset elimination filter.
-    pub fn new() -> Self {
+impl DCFilter {
+    /// Initialize a new DC-offset elimination filter with the specified window size, which must be
+    /// a power of two.
+    pub fn new(size: usize) -> Self {
+        debug_assert!(size.is_power_of_two());
+
         Self {
             left_sum: 0.0,
             right_sum: 0.0,
-            left_delay: [0.0; S],
-            right_delay: [0.0; S],
+            left_delay: vec![0.0; size].into_boxed_slice(),
+            right_delay: vec![0.0; size].into_boxed_slice(),
             index: 0
         }
     }
 
+    /// The size of the filter's moving average window, in frames.
+    pub fn size(&self) -> usize {
+        self.left_delay.len()
+    }
+
+    /// Write the filter's sums and delay lines to a saved state.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.float(self.left_sum);
+        writer.float(self.right_sum);
+        writer.floats(&self.left_delay);
+        writer.floats(&self.right_delay);
+        writer.index(self.index);
+    }
+
+    /// Restore the filter's sums and delay lines from a saved state. The state must have been
+    /// saved by a filter of the same size.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        self.left_sum = reader.float()?;
+        self.right_sum = reader.float()?;
+        reader.floats(&mut self.left_delay)?;
+        reader.floats(&mut self.right_delay)?;
+        self.index = reader.index(self.left_delay.len())?;
+        Ok(())
+    }
+
     /// Render a new frame for the provided input samples.
     ///
     /// The result is a tuple containing the filtered left channel as the first element and the
     /// filtered right channel as the second element.
-    pub fn render(&mut self, left: f64, right: f64) -> (f64, f64) {
+    pub fn render(&mut self, left: Float, right: Float) -> (Float, Float) {
+          let size = self.left_delay.len();
+
           self.left_sum += -self.left_delay[self.index] + left;
           self.right_sum += -self.right_delay[self.index] + right;
 
           self.left_delay[self.index] = left;
           self.right_delay[self.index] = right;
 
-          self.index = (self.index + 1) & (S - 1);
+          self.index = (self.index + 1) & (size - 1);
 
           (
-              left - self.left_sum * (1.0 / S as f64),
-              right - self.right_sum * (1.0 / S as f64)
+              left - self.left_sum * (1.0 / size as Float),
+              right - self.right_sum * (1.0 / size as Float)
           )
     }
 }
+
+/// A two channel first-order high-pass filter, used as an alternative DC-offset elimination
+/// filter.
+///
+/// The filter is computed as `y[n] = x[n] - x[n - 1] + a * y[n - 1]`, where the coefficient `a`
+/// is derived from the cutoff frequency and the sample rate.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnePoleFilter {
+    coefficient: Float,
+    left_input: Float,
+    right_input: Float,
+    left_output: Float,
+    right_output: Float
+}
+
+impl OnePoleFilter {
+    /// Initialize a new one-pole high-pass filter for the specified cutoff frequency and sample
+    /// rate.
+    pub fn new(cutoff_hz: f64, sample_rate: u32) -> Self {
+        Self {
+            coefficient: (-2.0 * core::f64::consts::PI * cutoff_hz / sample_rate as f64).exp() as Float,
+            left_input: 0.0,
+            right_input: 0.0,
+            left_output: 0.0,
+            right_output: 0.0
+        }
+    }
+
+    /// Write the filter's input and output history to a saved state. The coefficient is not
+    /// included, as it is derived from the PSG's DC filter mode.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.float(self.left_input);
+        writer.float(self.right_input);
+        writer.float(self.left_output);
+        writer.float(self.right_output);
+    }
+
+    /// Restore the filter's input and output history from a saved state.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        self.left_input = reader.float()?;
+        self.right_input = reader.float()?;
+        self.left_output = reader.float()?;
+        self.right_output = reader.float()?;
+        Ok(())
+    }
+
+    /// Render a new frame for the provided input samples.
+    ///
+    /// The result is a tuple containing the filtered left channel as the first element and the
+    /// filtered right channel as the second element.
+    pub fn render(&mut self, left: Float, right: Float) -> (Float, Float) {
+        self.left_output = left - self.left_input + self.coefficient * self.left_output;
+        self.right_output = right - self.right_input + self.coefficient * self.right_output;
+
+        self.left_input = left;
+        self.right_input = right;
+
+        (self.left_output, self.right_output)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{Float, OnePoleFilter};
+
+    /// Compute the RMS level of a filtered signal after letting the filter settle for a second.
+    fn filtered_rms(signal: impl Fn(f64) -> f64) -> f64 {
+        let mut filter = OnePoleFilter::new(20.0, 44100);
+
+        for index in 0..44100 {
+            filter.render(signal(index as f64 / 44100.0) as Float, 0.0);
+        }
+
+        let sum = (44100..88200).map(|index| {
+            let (left, _) = filter.render(signal(index as f64 / 44100.0) as Float, 0.0);
+            crate::widen(left * left)
+        }).sum::<f64>();
+
+        (sum / 44100.0).sqrt()
+    }
+
+    #[test]
+    fn one_pole_filter() {
+        use std::f64::consts::PI;
+
+        // A 5 Hz square wave between 0 and 1 has an AC RMS level of 0.5
+        let square = filtered_rms(|time| if (time * 5.0).fract() < 0.5 { 1.0 } else { 0.0 });
+        assert!(square < 0.25, "square {}", square);
+
+        // A 100 Hz sine wave with a DC offset has an AC RMS level of 0.5 / sqrt(2)
+        let sine = filtered_rms(|time| 0.5 + 0.5 * (time * 100.0 * 2.0 * PI).sin());
+        assert!((sine / (0.5 / 2.0_f64.sqrt()) - 1.0).abs() < 0.03, "sine {}", sine);
+    }
+}
@@ -0,0 +1,449 @@
+//! This module contains helpers for exporting and importing recorded register writes as CSV or
+//! JSON, for comparing them with the logs of other emulators, and for replaying imported logs.
+//!
+//! Both formats are exported with one [`RegisterEvent`] per line. The CSV format has a
+//! `frame,register,value` header, and the JSON format is an array of objects with the same three
+//! fields:
+//!
+//! ```text
+//! frame,register,value
+//! 0,7,56
+//! 882,8,15
+//!
+//! [
+//!   {"frame": 0, "register": 7, "value": 56},
+//!   {"frame": 882, "register": 8, "value": 15}
+//! ]
+//! ```
+//!
+//! The parsers skip blank lines and comments, which start with `#` or `//` and run until the end of
+//! the line, and accept numbers in hexadecimal with a `0x` prefix as well. The JSON parser does
+//! not depend on the layout, so arrays on a single line and objects with the keys in any order are
+//! accepted too. Events must be ordered by frame.
+//!
+//! ```
+//! use psg::PSG;
+//! use psg::debugdump;
+//!
+//! let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+//! psg.start_recording();
+//! psg.set_register(7, 0x3e);
+//! psg.set_register(8, 15);
+//! let _ = psg.render_frames(100);
+//!
+//! let csv = debugdump::to_csv(&psg.stop_recording());
+//! let events = debugdump::from_csv(&csv).unwrap();
+//!
+//! let mut other = PSG::new(2_000_000.0, 44100).unwrap();
+//! let frames = debugdump::replay(&mut other, &events, 100);
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use crate::{PSG, RegisterEvent};
+
+/// The header line of the CSV format.
+const CSV_HEADER: &str = "frame,register,value";
+
+/// The error returned when a CSV or JSON log cannot be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The number of the offending line, starting at 1.
+    pub line: usize,
+
+    /// A description of the problem.
+    pub reason: &'static str
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+/// Export register events as CSV, with a header line followed by one line per event.
+pub fn to_csv(events: &[RegisterEvent]) -> String {
+    let mut csv = String::with_capacity(16 * (events.len() + 1));
+    csv.push_str(CSV_HEADER);
+    csv.push('\n');
+
+    for event in events {
+        let _ = writeln!(csv, "{},{},{}", event.frame, event.register, event.value);
+    }
+
+    csv
+}
+
+/// Import register events from CSV. A header line is skipped wherever it occurs.
+pub fn from_csv(text: &str) -> Result<Vec<RegisterEvent>, ParseError> {
+    parse_lines(text, |line| {
+        if line == CSV_HEADER {
+            return Ok(None);
+        }
+
+        match line.split(',').map(str::trim).collect::<Vec<_>>()[..] {
+            [frame, register, value] => event(frame, register, value).map(Some),
+            _ => Err("expected three comma-separated fields")
+        }
+    })
+}
+
+/// Export register events as a JSON array, with one event object per line.
+pub fn to_json(events: &[RegisterEvent]) -> String {
+    let mut json = String::with_capacity(48 * (events.len() + 1));
+    json.push_str("[\n");
+
+    for (index, event) in events.iter().enumerate() {
+        let separator = if index + 1 < events.len() { "," } else { "" };
+        let _ = writeln!(json, "  {{\"frame\": {}, \"register\": {}, \"value\": {}}}{separator}", event.frame, event.register, event.value);
+    }
+
+    json.push_str("]\n");
+    json
+}
+
+/// Import register events from a JSON array of event objects, like the ones written by
+/// [`to_json`]. The layout is free, and the keys of an object can be in any order. The line of an
+/// error is the line on which the offending token starts, or the line of the event object for
+/// invalid events.
+pub fn from_json(text: &str) -> Result<Vec<RegisterEvent>, ParseError> {
+    let mut scanner = JsonScanner::new(text);
+    let mut events = Vec::new();
+
+    scanner.expect(b'[', "expected an array")?;
+
+    loop {
+        match scanner.peek() {
+            Some(b']') => break,
+            Some(b'{') => (),
+            _ => return Err(scanner.error("expected an event object"))
+        }
+
+        let line = scanner.line;
+        let event = scanner.event_object()?;
+
+        push_ordered(&mut events, event).map_err(|reason| ParseError { line, reason })?;
+
+        match scanner.peek() {
+            Some(b',') => scanner.position += 1,
+            Some(b']') => (),
+            _ => return Err(scanner.error("expected a comma or the end of the array"))
+        }
+    }
+
+    scanner.position += 1;
+
+    if scanner.peek().is_some() {
+        return Err(scanner.error("unexpected text after the array"));
+    }
+
+    Ok(events)
+}
+
+/// Render the specified number of frames, applying the events when their frames come, and return
+/// the rendered frames. This reproduces the original output of a recording.
+///
+/// Events are applied using [`PSG::set_register`] right before rendering their frame, which is
+/// compared with [`PSG::frames_rendered`]. Events for frames that have already been rendered are
+/// applied right away, and events beyond the last rendered frame are ignored.
+#[must_use]
+pub fn replay(psg: &mut PSG, events: &[RegisterEvent], frames: usize) -> Vec<(f64, f64)> {
+    let mut events = events.iter().peekable();
+
+    (0..frames).map(|_| {
+        while let Some(event) = events.next_if(|event| event.frame <= psg.frames_rendered()) {
+            psg.set_register(event.register, event.value);
+        }
+
+        psg.render()
+    }).collect()
+}
+
+/// Convert register events that were recorded at the specified sample rate to frames for the
+/// [`FramePlayer`](crate::FramePlayer), which plays them at the specified frame rate.
+///
+/// Every frame contains the register values after the events up to its end, so writes within a
+/// frame are moved to its start, and only the last value of a register in a frame is kept. The
+/// envelope shape register is 0xFF in frames that do not write it. Writes to the GPIO port
+/// registers 14 and 15 are ignored.
+pub fn to_frames(events: &[RegisterEvent], sample_rate: u32, frame_rate: f64) -> Vec<[u8; 14]> {
+    let frame_of = |event: &RegisterEvent| (event.frame as f64 * frame_rate / sample_rate as f64) as usize;
+    let frame_count = events.last().map_or(0, |event| frame_of(event) + 1);
+
+    let mut frames = Vec::with_capacity(frame_count);
+    let mut registers = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff];
+    let mut events = events.iter().peekable();
+
+    for frame in 0..frame_count {
+        while let Some(event) = events.next_if(|event| frame_of(event) <= frame) {
+            if event.register < 14 {
+                registers[event.register as usize] = event.value;
+            }
+        }
+
+        frames.push(registers);
+        registers[13] = 0xff;
+    }
+
+    frames
+}
+
+/// Parse the lines of a log, skipping blank lines and comments. The line parser returns `None`
+/// for lines that do not contain an event.
+fn parse_lines(text: &str, mut parse_line: impl FnMut(&str) -> Result<Option<RegisterEvent>, &'static str>) -> Result<Vec<RegisterEvent>, ParseError> {
+    let mut events: Vec<RegisterEvent> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+
+        let error = |reason| ParseError { line: index + 1, reason };
+
+        if let Some(event) = parse_line(line).map_err(error)? {
+            push_ordered(&mut events, event).map_err(error)?;
+        }
+    }
+
+    Ok(events)
+}
+
+/// Append an event, or return an error when it comes before the previous event.
+fn push_ordered(events: &mut Vec<RegisterEvent>, event: RegisterEvent) -> Result<(), &'static str> {
+    if events.last().is_some_and(|last| last.frame > event.frame) {
+        return Err("the events are not ordered by frame");
+    }
+
+    events.push(event);
+    Ok(())
+}
+
+/// A scanner over the tokens of a JSON log, which skips whitespace and comments between them, and
+/// keeps track of the line number for errors.
+struct JsonScanner<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    line: usize
+}
+
+impl<'a> JsonScanner<'a> {
+    /// Initialize a new scanner at the start of the text.
+    fn new(text: &'a str) -> Self {
+        Self {
+            bytes: text.as_bytes(),
+            position: 0,
+            line: 1
+        }
+    }
+
+    /// An error at the current line.
+    fn error(&self, reason: &'static str) -> ParseError {
+        ParseError { line: self.line, reason }
+    }
+
+    /// Skip whitespace and comments, and return the next byte without consuming it.
+    fn peek(&mut self) -> Option<u8> {
+        loop {
+            let rest = &self.bytes[self.position..];
+
+            match rest.first()? {
+                b'\n' => self.line += 1,
+                byte if byte.is_ascii_whitespace() => (),
+                _ if rest.starts_with(b"#") || rest.starts_with(b"//") => {
+                    // Skip to the newline, which is counted on the next iteration
+                    self.position += rest.iter().position(|&byte| byte == b'\n').unwrap_or(rest.len());
+                    continue;
+                }
+                &byte => return Some(byte)
+            }
+
+            self.position += 1;
+        }
+    }
+
+    /// Consume the next byte, or return an error when it is not the expected one.
+    fn expect(&mut self, expected: u8, reason: &'static str) -> Result<(), ParseError> {
+        if self.peek() != Some(expected) {
+            return Err(self.error(reason));
+        }
+
+        self.position += 1;
+        Ok(())
+    }
+
+    /// Skip whitespace and comments, and consume a run of ASCII letters and digits, which is how
+    /// keys and numbers are spelled.
+    fn word(&mut self) -> &'a str {
+        self.peek();
+
+        let start = self.position;
+
+        while self.bytes.get(self.position).is_some_and(u8::is_ascii_alphanumeric) {
+            self.position += 1;
+        }
+
+        // The consumed bytes are ASCII, so they are valid UTF-8
+        core::str::from_utf8(&self.bytes[start..self.position]).unwrap_or_default()
+    }
+
+    /// Consume an event object. Invalid events are reported at the line of the opening brace.
+    fn event_object(&mut self) -> Result<RegisterEvent, ParseError> {
+        self.expect(b'{', "expected an event object")?;
+
+        let line = self.line;
+        let mut values = [None; 3];
+
+        while self.peek() != Some(b'}') {
+            self.expect(b'"', "expected a \"key\": value pair")?;
+
+            let index = match self.word() {
+                "frame" => 0,
+                "register" => 1,
+                "value" => 2,
+                _ => return Err(self.error("unknown key"))
+            };
+
+            self.expect(b'"', "unknown key")?;
+            self.expect(b':', "expected a \"key\": value pair")?;
+
+            values[index] = Some(self.word());
+
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => (),
+                _ => return Err(self.error("expected a comma or the end of the object"))
+            }
+        }
+
+        self.position += 1;
+
+        let event = match values {
+            [Some(frame), Some(register), Some(value)] => event(frame, register, value),
+            _ => Err("expected the frame, register and value keys")
+        };
+
+        event.map_err(|reason| ParseError { line, reason })
+    }
+}
+
+/// Parse the fields of an event.
+fn event(frame: &str, register: &str, value: &str) -> Result<RegisterEvent, &'static str> {
+    let register = number(register).ok_or("the register is not a number")?;
+    let value = number(value).ok_or("the value is not a number")?;
+
+    if register > 15 {
+        return Err("the register number is larger than 15");
+    }
+
+    Ok(RegisterEvent {
+        frame: number(frame).ok_or("the frame is not a number")?,
+        register: register as u8,
+        value: u8::try_from(value).map_err(|_| "the value is larger than 255")?
+    })
+}
+
+/// Parse a decimal number, or a hexadecimal number with a 0x prefix.
+fn number(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{ParseError, from_csv, from_json, replay, to_csv, to_frames, to_json};
+    use crate::{FramePlayer, PSG, RegisterEvent};
+
+    #[test]
+    fn round_trip() {
+        let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+        let mut original = Vec::new();
+
+        psg.start_recording();
+
+        for step in 0..20u8 {
+            psg.set_register(0, step * 11);
+            psg.set_register(7, 0x36 + step % 2);
+            psg.set_register(8, 15 - step / 2);
+            psg.set_register(13, 0x0a);
+
+            original.extend(psg.render_frames(300 + step as usize));
+        }
+
+        let events = psg.stop_recording();
+
+        for imported in [from_csv(&to_csv(&events)).unwrap(), from_json(&to_json(&events)).unwrap()] {
+            assert_eq!(imported, events);
+
+            let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+            assert_eq!(replay(&mut psg, &imported, original.len()), original);
+        }
+    }
+
+    #[test]
+    fn comments_and_errors() {
+        let csv = "# Exported from another emulator\n\nframe,register,value\n0,7,0x38\n  // Channel A\n10, 8, 15\n";
+
+        assert_eq!(from_csv(csv).unwrap(), [
+            RegisterEvent { frame: 0, register: 7, value: 0x38 },
+            RegisterEvent { frame: 10, register: 8, value: 15 }
+        ]);
+
+        assert_eq!(from_csv("frame,register,value\n0,16,0\n"), Err(ParseError { line: 2, reason: "the register number is larger than 15" }));
+        assert_eq!(from_csv("\n5,0,256"), Err(ParseError { line: 2, reason: "the value is larger than 255" }));
+        assert_eq!(from_csv("5,0,1\n4,0,1"), Err(ParseError { line: 2, reason: "the events are not ordered by frame" }));
+        assert_eq!(from_csv("5,0"), Err(ParseError { line: 1, reason: "expected three comma-separated fields" }));
+
+        let json = "[\n  # Comment\n  {\"value\": 1, \"frame\": 3, \"register\": 0},\n]";
+        assert_eq!(from_json(json).unwrap(), [RegisterEvent { frame: 3, register: 0, value: 1 }]);
+        assert_eq!(from_json("[\n{\"frame\": 3, \"register\": 0}\n]"), Err(ParseError { line: 2, reason: "expected the frame, register and value keys" }));
+        assert_eq!(from_json("[\n{\"frame\": 3, \"value\": 1, \"register\": 0}\n{}]"), Err(ParseError { line: 3, reason: "expected a comma or the end of the array" }));
+        assert_eq!(from_json("[{\"frame\": 3,\n \"reg\": 0}]"), Err(ParseError { line: 2, reason: "unknown key" }));
+        assert_eq!(from_json("[] []"), Err(ParseError { line: 1, reason: "unexpected text after the array" }));
+        assert!(from_json("[]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn json_layout() {
+        let events = [
+            RegisterEvent { frame: 0, register: 7, value: 0x38 },
+            RegisterEvent { frame: 882, register: 8, value: 15 },
+            RegisterEvent { frame: 882, register: 13, value: 0x0e }
+        ];
+
+        // A compact array on a single line, and objects with the keys in other orders
+        let compact = r#"[{"frame":0,"register":7,"value":56},{"value":15,"register":8,"frame":882},{"register":13,"frame":882,"value":14}]"#;
+        let spread = "[\n  {\n    \"register\": 7,\n    \"value\": 0x38, // Mixer\n    \"frame\": 0\n  }, {\"frame\": 882, \"register\": 8, \"value\": 15},\n  {\"frame\": 882, \"value\": 14, \"register\": 13}\n]\n";
+
+        assert_eq!(from_json(compact).unwrap(), events);
+        assert_eq!(from_json(spread).unwrap(), events);
+        assert_eq!(from_json(&to_json(&events).replace('\n', "")).unwrap(), events);
+    }
+
+    #[test]
+    fn frames() {
+        let events = [
+            RegisterEvent { frame: 0, register: 8, value: 15 },
+            RegisterEvent { frame: 100, register: 8, value: 14 },
+            RegisterEvent { frame: 900, register: 13, value: 0x08 },
+            RegisterEvent { frame: 2000, register: 0, value: 50 }
+        ];
+
+        let frames = to_frames(&events, 44100, 50.0);
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!((frames[0][8], frames[0][13]), (14, 0xff));
+        assert_eq!((frames[1][8], frames[1][13]), (14, 0x08));
+        assert_eq!((frames[2][0], frames[2][13]), (50, 0xff));
+
+        let player = FramePlayer::new(PSG::new(2_000_000.0, 44100).unwrap(), 50.0, frames);
+        assert_eq!(player.frame_count(), 3);
+    }
+}
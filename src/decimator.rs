@@ -1,32 +1,354 @@
+use crate::{Error, Float};
+use crate::state::{StateReader, StateWriter};
+
 /// The amount of times to oversample/decimate.
 pub const DECIMATE_FACTOR: usize = 8;
 
 /// The size of the windowed sinc FIR filter's impulse response.
 pub const FIR_SIZE: usize = 192;
 
+/// The coefficients of the windowed sinc FIR filter's impulse response. Since the impulse
+/// response is symmetrical, entry `i` is applied to both tap `i + 1` and tap `FIR_SIZE - 1 - i`,
+/// except for the last entry, which is only applied to the center tap. Tap 0 is always zero and is
+/// therefore omitted.
+#[allow(clippy::excessive_precision)]
+const FIR_COEFFS: [Float; 96] = [
+    -0.0000046183113992051936, -0.00001117761640887225,   -0.000018610264502005432,  -0.000025134586135631012,
+    -0.000028494281690666197,  -0.000026396828793275159,  -0.000017094212558802156,   0.0,
+     0.000023798193576966866,   0.000051281160242202183,   0.00007762197826243427,    0.000096759426664120416,
+     0.00010240229300393402,    0.000089344614218077106,   0.000054875700118949183,   0.0,
+    -0.000069839082210680165,  -0.0001447966132360757,    -0.00021158452917708308,   -0.00025535069106550544,
+    -0.00026228714374322104,   -0.00022258805927027799,   -0.00013323230495695704,    0.0,
+     0.00016182578767055206,    0.00032846175385096581,    0.00047045611576184863,    0.00055713851457530944,
+     0.00056212565121518726,    0.00046901918553962478,    0.00027624866838952986,    0.0,
+    -0.00032564179486838622,   -0.00065182310286710388,   -0.00092127787309319298,   -0.0010772534348943575,
+    -0.0010737727700273478,    -0.00088556645390392634,   -0.00051581896090765534,    0.0,
+     0.00059548767193795277,    0.0011803558710661009,     0.0016527320270369871,     0.0019152679330965555,
+     0.0018927324805381538,     0.0015481870327877937,     0.00089470695834941306,    0.0,
+    -0.0010178225878206125,    -0.0020037400552054292,    -0.0027874356824117317,    -0.003210329988021943,
+    -0.0031540624117984395,    -0.0025657163651900345,    -0.0014750752642111449,     0.0,
+     0.0016624165446378462,     0.0032591192839069179,     0.0045165685815867747,     0.0051838984346123896,
+     0.0050774264697459933,     0.0041192521414141585,     0.0023628575417966491,     0.0,
+    -0.0026543507866759182,    -0.0051990251084333425,    -0.0072020238234656924,    -0.0082672928192007358,
+    -0.0081033739572956287,    -0.006583111539570221,     -0.0037839040415292386,     0.0,
+     0.0042781252851152507,     0.0084176358598320178,     0.01172566057463055,       0.013550476647788672,
+     0.013388189369997496,      0.010979501242341259,      0.006381274941685413,      0.0,
+    -0.007421229604153888,     -0.01486456304340213,      -0.021143584622178104,     -0.02504275058758609,
+    -0.025473530942547201,     -0.021627310017882196,     -0.013104323383225543,      0.0,
+     0.017065133989980476,      0.036978919264451952,      0.05823318062093958,       0.079072012081405949,
+     0.097675998716952317,      0.11236045936950932,       0.12176343577287731,       0.125
+];
+
+/// The size of the decimator's ring buffer. This must be a power of two that is at least as
+/// large as FIR_SIZE.
+const RING_SIZE: usize = 256;
+
+/// The mask that is applied to wrap indices into the ring buffer.
+const RING_MASK: usize = RING_SIZE - 1;
+
 /// An 8x downsampler (by decimation) and anti-aliasing windowed sinc FIR filter.
+///
+/// Samples are stored in a ring buffer in reverse chronological order, so that the most recent
+/// sample is located at the current position and older samples follow at increasing indices. The
+/// first FIR_SIZE entries of the ring are mirrored past its end, which makes the filter window a
+/// contiguous slice at every position.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Decimator {
-    pub buffer: [f64; FIR_SIZE * 2]
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::array"))]
+    buffer: [Float; RING_SIZE + FIR_SIZE],
+    position: usize
 }
 
 impl Decimator {
     /// Initialize a new decimator.
     pub fn new() -> Self {
         Self {
-            buffer: [0.0; FIR_SIZE * 2]
+            buffer: [0.0; RING_SIZE + FIR_SIZE],
+            position: 0
         }
     }
 
-    /// Apply anti-alias filter and downsample.
-    #[allow(clippy::excessive_precision)]
-    pub fn render(&mut self, start: usize) -> f64 {
-        // Help the optimizer a little by eliminating the bounds check. This will make the copy
-        // operation at the bottom of the method more efficient.
-        assert!(start <= FIR_SIZE);
+    /// Push a new oversampled sample into the decimator.
+    #[inline]
+    pub fn push(&mut self, sample: Float) {
+        self.position = self.position.wrapping_sub(1) & RING_MASK;
+        self.buffer[self.position] = sample;
+
+        if self.position < FIR_SIZE {
+            self.buffer[self.position + RING_SIZE] = sample;
+        }
+    }
+
+    /// Write the decimator's ring buffer and position to a saved state. The mirrored entries are
+    /// not included.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.floats(&self.buffer[..RING_SIZE]);
+        writer.index(self.position);
+    }
+
+    /// Restore the decimator's ring buffer and position from a saved state.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        reader.floats(&mut self.buffer[..RING_SIZE])?;
+        self.buffer.copy_within(..FIR_SIZE, RING_SIZE);
+        self.position = reader.index(RING_SIZE)?;
+        Ok(())
+    }
+
+    /// Return the most recent FIR_SIZE samples, newest first.
+    fn window(&self) -> &[Float] {
+        &self.buffer[self.position..self.position + FIR_SIZE]
+    }
+
+    /// Apply anti-alias filter to the most recent FIR_SIZE samples and downsample.
+    ///
+    /// This should be called once after every DECIMATE_FACTOR calls to [`push`](Self::push).
+    pub fn render(&self) -> Float {
+        #[cfg(not(all(feature = "simd", not(feature = "f32"))))]
+        let result = convolve(self.window());
+
+        #[cfg(all(feature = "simd", not(feature = "f32")))]
+        let result = simd::convolve(self.window());
+
+        result
+    }
+}
+
+/// Apply the anti-alias filter to a buffer of FIR_SIZE samples.
+///
+/// The filter is computed as a dot product over the symmetric tap pairs. Since the filter's cutoff
+/// lies at an eighth of the sampling rate, the coefficient of every eighth pair is zero, so the
+/// pairs are processed in blocks of seven with the zero coefficient skipped. The pairs are summed
+/// in order of increasing tap index and the center tap is added last, which makes the result
+/// bit-identical to Ayumi.
+#[cfg_attr(all(feature = "simd", not(feature = "f32")), allow(dead_code))]
+fn convolve(buffer: &[Float]) -> Float {
+    assert!(buffer.len() >= FIR_SIZE);
+
+    // Starting at negative zero makes the first addition an exact no-op, even for negative zeros
+    let mut result = -0.0;
+
+    for block in (0..FIR_COEFFS.len()).step_by(DECIMATE_FACTOR) {
+        for index in block..block + DECIMATE_FACTOR - 1 {
+            result += FIR_COEFFS[index] * (buffer[index + 1] + buffer[FIR_SIZE - 1 - index]);
+        }
+    }
+
+    result + FIR_COEFFS[FIR_COEFFS.len() - 1] * buffer[FIR_SIZE / 2]
+}
+
+/// Explicitly vectorized implementations of the anti-alias filter.
+///
+/// These reorder the additions of the filter's dot product, so the results are not bit-identical
+/// to the scalar implementation. The difference is well below 1e-12 however. These are only
+/// available for double precision processing.
+#[cfg(all(feature = "simd", not(feature = "f32")))]
+mod simd {
+    use super::{FIR_COEFFS, FIR_SIZE};
+
+    /// The amount of symmetric tap pairs in the filter, excluding the center tap.
+    const PAIRS: usize = FIR_COEFFS.len() - 1;
+
+    /// The index of the center tap.
+    const CENTER: usize = FIR_SIZE / 2;
+
+    /// Compute the filter result for a range of tap pairs without vectorization.
+    fn convolve_pairs(buffer: &[f64], pairs: core::ops::Range<usize>) -> f64 {
+        pairs.fold(0.0, |result, index| {
+            result + FIR_COEFFS[index] * (buffer[index + 1] + buffer[FIR_SIZE - 1 - index])
+        })
+    }
+
+    /// Apply the anti-alias filter to a buffer of FIR_SIZE samples, using the best instruction set
+    /// that is available at runtime, or at compile time without the standard library.
+    #[cfg(target_arch = "x86_64")]
+    pub fn convolve(buffer: &[f64]) -> f64 {
+        #[cfg(feature = "std")]
+        let avx2 = is_x86_feature_detected!("avx2");
+
+        // Without the standard library, AVX2 can only be detected at compile time
+        #[cfg(not(feature = "std"))]
+        let avx2 = cfg!(target_feature = "avx2");
+
+        if avx2 {
+            // Safety: the availability of AVX2 has been checked
+            unsafe { convolve_avx2(buffer) }
+        } else {
+            convolve_sse2(buffer)
+        }
+    }
+
+    /// Apply the anti-alias filter to a buffer of FIR_SIZE samples using NEON instructions.
+    #[cfg(target_arch = "aarch64")]
+    pub fn convolve(buffer: &[f64]) -> f64 {
+        convolve_neon(buffer)
+    }
+
+    /// Apply the anti-alias filter to a buffer of FIR_SIZE samples without vectorization, for
+    /// architectures that have no vectorized implementation.
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub fn convolve(buffer: &[f64]) -> f64 {
+        convolve_pairs(buffer, 0..PAIRS) + FIR_COEFFS[PAIRS] * buffer[CENTER]
+    }
+
+    /// Apply the anti-alias filter to a buffer of FIR_SIZE samples using SSE2 instructions, which
+    /// are always available on x86_64.
+    #[cfg(target_arch = "x86_64")]
+    pub fn convolve_sse2(buffer: &[f64]) -> f64 {
+        use core::arch::x86_64::*;
+
+        assert!(buffer.len() >= FIR_SIZE);
+
+        let chunks = PAIRS / 2;
+
+        // Safety: the assertion above guarantees that all loads are within bounds
+        let accumulator = unsafe {
+            let mut accumulator = _mm_setzero_pd();
+
+            for chunk in 0..chunks {
+                let index = chunk * 2;
+
+                let head = _mm_loadu_pd(buffer.as_ptr().add(index + 1));
+                let tail = _mm_loadu_pd(buffer.as_ptr().add(FIR_SIZE - 2 - index));
+                let coefficients = _mm_loadu_pd(FIR_COEFFS.as_ptr().add(index));
+
+                // Reverse the tail so that the symmetric taps line up
+                let tail = _mm_shuffle_pd(tail, tail, 0b01);
+
+                accumulator = _mm_add_pd(accumulator, _mm_mul_pd(_mm_add_pd(head, tail), coefficients));
+            }
+
+            let mut lanes = [0.0; 2];
+            _mm_storeu_pd(lanes.as_mut_ptr(), accumulator);
+
+            lanes[0] + lanes[1]
+        };
+
+        accumulator + convolve_pairs(buffer, chunks * 2..PAIRS) + FIR_COEFFS[PAIRS] * buffer[CENTER]
+    }
 
-        let buffer = &mut self.buffer[start..start + FIR_SIZE];
+    /// Apply the anti-alias filter to a buffer of FIR_SIZE samples using AVX2 instructions.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the CPU supports AVX2.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn convolve_avx2(buffer: &[f64]) -> f64 {
+        use core::arch::x86_64::*;
 
-        let result = -0.0000046183113992051936 * (buffer[1]  + buffer[191]) +
+        assert!(buffer.len() >= FIR_SIZE);
+
+        let chunks = PAIRS / 4;
+        let mut accumulator = _mm256_setzero_pd();
+
+        for chunk in 0..chunks {
+            let index = chunk * 4;
+
+            // Safety: the assertion above guarantees that all loads are within bounds
+            let (head, tail, coefficients) = unsafe {
+                (
+                    _mm256_loadu_pd(buffer.as_ptr().add(index + 1)),
+                    _mm256_loadu_pd(buffer.as_ptr().add(FIR_SIZE - 4 - index)),
+                    _mm256_loadu_pd(FIR_COEFFS.as_ptr().add(index))
+                )
+            };
+
+            // Reverse the tail so that the symmetric taps line up
+            let tail = _mm256_permute4x64_pd(tail, 0b00_01_10_11);
+
+            accumulator = _mm256_add_pd(accumulator, _mm256_mul_pd(_mm256_add_pd(head, tail), coefficients));
+        }
+
+        let mut lanes = [0.0; 4];
+
+        // Safety: the lanes array has room for four values
+        unsafe { _mm256_storeu_pd(lanes.as_mut_ptr(), accumulator) };
+
+        (lanes[0] + lanes[1]) + (lanes[2] + lanes[3]) +
+            convolve_pairs(buffer, chunks * 4..PAIRS) +
+            FIR_COEFFS[PAIRS] * buffer[CENTER]
+    }
+
+    /// Apply the anti-alias filter to a buffer of FIR_SIZE samples using NEON instructions, which
+    /// are always available on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    pub fn convolve_neon(buffer: &[f64]) -> f64 {
+        use core::arch::aarch64::*;
+
+        assert!(buffer.len() >= FIR_SIZE);
+
+        let chunks = PAIRS / 2;
+
+        // Safety: the assertion above guarantees that all loads are within bounds
+        let accumulator = unsafe {
+            let mut accumulator = vdupq_n_f64(0.0);
+
+            for chunk in 0..chunks {
+                let index = chunk * 2;
+
+                let head = vld1q_f64(buffer.as_ptr().add(index + 1));
+                let tail = vld1q_f64(buffer.as_ptr().add(FIR_SIZE - 2 - index));
+                let coefficients = vld1q_f64(FIR_COEFFS.as_ptr().add(index));
+
+                // Reverse the tail so that the symmetric taps line up
+                let tail = vextq_f64(tail, tail, 1);
+
+                accumulator = vaddq_f64(accumulator, vmulq_f64(vaddq_f64(head, tail), coefficients));
+            }
+
+            vaddvq_f64(accumulator)
+        };
+
+        accumulator + convolve_pairs(buffer, chunks * 2..PAIRS) + FIR_COEFFS[PAIRS] * buffer[CENTER]
+    }
+
+    #[cfg(all(test, feature = "std"))]
+    mod tests {
+        use super::super::{convolve as convolve_scalar, FIR_SIZE};
+
+        /// Generate a buffer of pseudo-random samples between -1.0 and 1.0.
+        fn random_buffer(seed: &mut u32) -> [f64; FIR_SIZE] {
+            std::array::from_fn(|_| {
+                *seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (*seed >> 8) as f64 / (1 << 23) as f64 - 1.0
+            })
+        }
+
+        #[test]
+        fn convolve() {
+            let mut seed = 1;
+
+            for _ in 0..1000 {
+                let buffer = random_buffer(&mut seed);
+                let expected = convolve_scalar(&buffer);
+
+                assert!((super::convolve(&buffer) - expected).abs() < 1e-12);
+                assert!((super::convolve_pairs(&buffer, 0..super::PAIRS) + 0.125 * buffer[96] - expected).abs() < 1e-12);
+
+                #[cfg(target_arch = "x86_64")]
+                {
+                    assert!((super::convolve_sse2(&buffer) - expected).abs() < 1e-12);
+
+                    if is_x86_feature_detected!("avx2") {
+                        assert!((unsafe { super::convolve_avx2(&buffer) } - expected).abs() < 1e-12);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{DECIMATE_FACTOR, Decimator, FIR_COEFFS, FIR_SIZE, Float};
+
+    /// The original hand-written expression of the anti-alias filter, which is known to be
+    /// bit-identical to Ayumi.
+    #[cfg(not(feature = "f32"))]
+    #[allow(clippy::excessive_precision)]
+    fn reference_convolve(buffer: &[f64]) -> f64 {
+        -0.0000046183113992051936 * (buffer[1]  + buffer[191]) +
             -0.00001117761640887225  * (buffer[2]  + buffer[190]) +
             -0.000018610264502005432 * (buffer[3]  + buffer[189]) +
             -0.000025134586135631012 * (buffer[4]  + buffer[188]) +
@@ -110,13 +432,57 @@ impl Decimator {
              0.097675998716952317    * (buffer[93] + buffer[99])  +
              0.11236045936950932     * (buffer[94] + buffer[98])  +
              0.12176343577287731     * (buffer[95] + buffer[97])  +
-             0.125                   * buffer[96];
+             0.125                   * buffer[96]
+    }
 
-        // Copy first chunk to last chunk
-        let (mid, end) = buffer.split_at_mut(FIR_SIZE - DECIMATE_FACTOR);
-        let (start, _) = mid.split_at(DECIMATE_FACTOR);
-        end.copy_from_slice(start);
+    #[cfg(not(feature = "f32"))]
+    #[test]
+    fn convolve() {
+        let mut decimator = Decimator::new();
+        let mut seed: u32 = 1;
 
-        result
+        // Push an odd amount of samples at a time, so that every position in the ring is used
+        for _ in 0..1000 {
+            for _ in 0..7 {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                decimator.push((seed >> 8) as f64 / (1 << 23) as f64 - 1.0);
+            }
+
+            let window: [f64; FIR_SIZE] = std::array::from_fn(|index| {
+                decimator.buffer[(decimator.position + index) & super::RING_MASK]
+            });
+
+            assert_eq!(super::convolve(decimator.window()).to_bits(), reference_convolve(&window).to_bits());
+        }
+
+        for _ in 0..FIR_SIZE {
+            decimator.push(-0.0);
+        }
+
+        assert_eq!(super::convolve(decimator.window()).to_bits(), reference_convolve(&[-0.0; FIR_SIZE]).to_bits());
+    }
+
+    #[test]
+    fn window() {
+        let mut decimator = Decimator::new();
+
+        for sample in 0..1000 {
+            decimator.push(sample as Float);
+
+            let expected: Vec<Float> = (0..FIR_SIZE)
+                .map(|index| (sample as Float - index as Float).max(0.0))
+                .collect();
+
+            assert_eq!(decimator.window(), &expected[..]);
+        }
+    }
+
+    #[test]
+    fn zero_coefficients() {
+        let (_, pairs) = FIR_COEFFS.split_last().unwrap();
+
+        for (index, coefficient) in pairs.iter().enumerate() {
+            assert_eq!(*coefficient == 0.0, index % DECIMATE_FACTOR == DECIMATE_FACTOR - 1);
+        }
     }
 }
@@ -0,0 +1,237 @@
+use alloc::vec::Vec;
+
+use crate::{ChannelIndex, ChannelSelector, DacTable, PSG};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// Plays a sample on a channel by scheduling writes to its amplitude register, a technique that
+/// is known as "digidrums" in Atari ST music.
+///
+/// The sample consists of 4-bit amplitudes, which are written to the channel at the sample's
+/// playback rate using [`PSG::write_register_at`]. The writes are timed in chip clock cycles, so
+/// they are accurate to a single tick of the PSG regardless of the output sample rate. Playback
+/// starts at an output frame, counted like [`PSG::frames_rendered`], and frames are converted to
+/// cycles using the current clock and sample rates.
+///
+/// For the sample to be heard as intended, the channel's tone and noise should be disabled in the
+/// mixer, so the channel outputs its amplitude as a constant level. Writing an amplitude also
+/// disables the channel's envelope.
+///
+/// ```
+/// use psg::{ChannelIndex, Digidrum, PSG};
+///
+/// let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+/// psg.set_tone_disabled(ChannelIndex::A, true);
+/// psg.set_noise_disabled(ChannelIndex::A, true);
+///
+/// let mut drum = Digidrum::new(&[15, 12, 9, 6, 3, 0], 8000.0, ChannelIndex::A);
+/// let end_frame = drum.play(&mut psg, 0);
+///
+/// let _ = psg.render_frames(end_frame as usize);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Digidrum {
+    samples: Vec<u8>,
+    rate: f64,
+    channel: ChannelIndex,
+    restore_amplitude: bool,
+
+    // The channel's amplitude register value before playback started, when it is to be restored
+    previous_amplitude: Option<u8>
+}
+
+impl Digidrum {
+    /// Initialize a new digidrum from 4-bit samples, to be played at the specified rate in Hz on
+    /// the specified channel.
+    ///
+    /// The upper four bits of every sample are ignored. The rate must be positive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn new(samples: &[u8], rate: f64, channel: impl ChannelSelector) -> Self {
+        Self {
+            samples: samples.iter().map(|sample| sample & 0x0f).collect(),
+            rate,
+            channel: channel.channel_index(),
+            restore_amplitude: false,
+            previous_amplitude: None
+        }
+    }
+
+    /// Initialize a new digidrum from unsigned 8-bit samples, which are converted to the 4-bit
+    /// amplitudes whose levels in the DAC table are closest to the samples' linear levels.
+    ///
+    /// The DAC table should match the chip that plays the sample. The rate must be positive.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn from_8bit(samples: &[u8], rate: f64, channel: impl ChannelSelector, table: DacTable) -> Self {
+        let levels = table.levels();
+
+        // A 4-bit amplitude selects every second level of the 5-bit table
+        let amplitude = |sample: u8| {
+            let level = sample as f64 / 255.0;
+
+            (0..16).min_by(|&a: &u8, &b: &u8| {
+                let distance = |amplitude: u8| (levels[amplitude as usize * 2 + 1] - level).abs();
+                distance(a).total_cmp(&distance(b))
+            }).unwrap_or(0)
+        };
+
+        let samples: Vec<u8> = samples.iter().map(|&sample| amplitude(sample)).collect();
+        Self::new(&samples, rate, channel)
+    }
+
+    /// The 4-bit samples.
+    pub fn samples(&self) -> &[u8] {
+        &self.samples
+    }
+
+    /// The playback rate in Hz.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// The channel that plays the sample.
+    pub fn channel(&self) -> ChannelIndex {
+        self.channel
+    }
+
+    /// Whether the channel's amplitude register is restored after playback.
+    pub fn restore_amplitude(&self) -> bool {
+        self.restore_amplitude
+    }
+
+    /// Set whether the channel's amplitude register is restored to the value it had when
+    /// [`play`](Self::play) was called, once playback ends or is stopped. This is disabled by
+    /// default, in which case the channel keeps the amplitude of the last played sample.
+    pub fn set_restore_amplitude(&mut self, restore: bool) {
+        self.restore_amplitude = restore;
+    }
+
+    /// The amplitude writes for playback starting at the specified frame, as tuples of the chip
+    /// clock cycle and the amplitude. See [`PSG::write_register_at`] for how cycles are counted.
+    pub fn write_schedule(&self, psg: &PSG, start_frame: u64) -> Vec<(u64, u8)> {
+        let start_cycle = psg.frame_to_cycle(start_frame);
+        let clock_rate = psg.effective_clock_rate();
+
+        self.samples.iter().enumerate().map(|(index, &amplitude)| {
+            (start_cycle + (index as f64 * clock_rate / self.rate).round() as u64, amplitude)
+        }).collect()
+    }
+
+    /// The frame at which playback that starts at the specified frame ends, which is the first
+    /// frame after the last sample.
+    pub fn end_frame(&self, psg: &PSG, start_frame: u64) -> u64 {
+        start_frame + (self.samples.len() as f64 * psg.sample_rate() as f64 / self.rate).ceil() as u64
+    }
+
+    /// Schedule the playback of the sample starting at the specified frame, and return the frame
+    /// at which playback ends. See [`end_frame`](Self::end_frame).
+    ///
+    /// When the amplitude is to be restored, the current value of the channel's amplitude
+    /// register is saved, and written back once the last sample has been played.
+    pub fn play(&mut self, psg: &mut PSG, start_frame: u64) -> u64 {
+        let register = self.register();
+
+        for (cycle, amplitude) in self.write_schedule(psg, start_frame) {
+            psg.write_register_at(cycle, register, amplitude);
+        }
+
+        self.previous_amplitude = self.restore_amplitude.then(|| psg.raw_register(register));
+
+        let end_cycle = psg.frame_to_cycle(start_frame) + (self.samples.len() as f64 * psg.effective_clock_rate() / self.rate).round() as u64;
+
+        if let Some(amplitude) = self.previous_amplitude {
+            psg.write_register_at(end_cycle, register, amplitude);
+        }
+
+        self.end_frame(psg, start_frame)
+    }
+
+    /// Stop playback at the specified frame, by discarding the writes to the channel's amplitude
+    /// register that are scheduled from that frame onwards. The amplitude is restored at that
+    /// frame when this is enabled.
+    ///
+    /// Note that this also discards writes to the amplitude register that were scheduled by other
+    /// means.
+    pub fn stop(&mut self, psg: &mut PSG, frame: u64) {
+        let register = self.register();
+        let cycle = psg.frame_to_cycle(frame);
+
+        psg.cancel_scheduled_writes(register, cycle);
+
+        if let Some(amplitude) = self.previous_amplitude.take() {
+            psg.write_register_at(cycle, register, amplitude);
+        }
+    }
+
+    /// The channel's amplitude register.
+    fn register(&self) -> u8 {
+        u8::from(self.channel) + 8
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::Digidrum;
+    use crate::{ChannelIndex, DacTable, PSG};
+
+    #[test]
+    fn write_schedule() {
+        let psg = PSG::new(2_000_000.0, 44100).unwrap();
+        let drum = Digidrum::new(&[0x1f; 100], 8000.0, ChannelIndex::B);
+
+        let schedule = drum.write_schedule(&psg, 441);
+
+        // Frame 441 starts 10 ms into the output, and the samples are 250 cycles apart
+        assert_eq!(schedule.len(), 100);
+        assert_eq!(schedule[0], (20000, 15));
+        assert!(schedule.windows(2).all(|pair| pair[1].0 - pair[0].0 == 250));
+
+        // 100 samples at 8 kHz last 551.25 frames
+        assert_eq!(drum.end_frame(&psg, 441), 441 + 552);
+    }
+
+    #[test]
+    fn playback() {
+        let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+        psg.set_amplitude(ChannelIndex::C, 7);
+
+        let mut drum = Digidrum::new(&[15, 14, 13, 12], 8000.0, ChannelIndex::C);
+        drum.set_restore_amplitude(true);
+
+        let end_frame = drum.play(&mut psg, 10);
+        assert_eq!(end_frame, 33);
+
+        psg.start_recording();
+        let _ = psg.render_frames(end_frame as usize);
+
+        let events = psg.stop_recording();
+        let values: Vec<u8> = events.iter().map(|event| event.value).collect();
+
+        assert!(events.iter().all(|event| event.register == 10));
+        assert_eq!(values, [15, 14, 13, 12, 7]);
+        assert_eq!(events[1].frame - events[0].frame, 5);
+
+        // Stopping early restores the amplitude and discards the remaining samples
+        drum.play(&mut psg, 40);
+        drum.stop(&mut psg, 46);
+
+        psg.start_recording();
+        let _ = psg.render_frames(40);
+
+        let values: Vec<u8> = psg.stop_recording().iter().map(|event| event.value).collect();
+        assert_eq!(values, [15, 14, 7]);
+    }
+
+    #[test]
+    fn from_8bit() {
+        let drum = Digidrum::from_8bit(&[0, 128, 255], 8000.0, ChannelIndex::A, DacTable::AY);
+
+        assert_eq!(drum.samples(), [0, 12, 15]);
+    }
+}
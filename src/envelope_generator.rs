@@ -1,3 +1,8 @@
+use core::fmt;
+
+use crate::{Error, Float};
+use crate::state::{StateReader, StateWriter};
+
 /// The shape of an envelope segment.
 enum EnvelopeShape {
     /// Slide down from 31 to 0 and progress to the next shape afterwards.
@@ -37,7 +42,99 @@ const ENVELOPE_TABLE: [[EnvelopeShape; 2]; 16] = [
     [EnvelopeShape::SlideUp,   EnvelopeShape::HoldBottom]
 ];
 
+/// An envelope shape, as the four control bits of the envelope shape register (13).
+///
+/// The shape register is usually described using these bits, and the sixteen possible values
+/// result in ten distinct shapes. The constants of this type name the eight most commonly used
+/// shapes. Conversions to and from the raw register value are available using
+/// [`from_bits`](Self::from_bits) and [`bits`](Self::bits), or the equivalent `From`
+/// implementations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvelopeShapeId {
+    /// The CONT bit (bit 3). When cleared, the envelope drops to zero and holds after the first
+    /// slide, regardless of the other bits.
+    pub continuous: bool,
+
+    /// The ATT bit (bit 2). When set, the first slide goes up instead of down.
+    pub attack: bool,
+
+    /// The ALT bit (bit 1). When set, the direction reverses after every slide. Together with
+    /// HOLD, the envelope holds at the opposite level of the end of the first slide.
+    pub alternate: bool,
+
+    /// The HOLD bit (bit 0). When set, the envelope holds its level after the first slide.
+    pub hold: bool
+}
+
+impl EnvelopeShapeId {
+    /// A single slide down that holds at zero (`\___`), shape 0.
+    pub const DECAY: Self = Self::from_bits(0x00);
+
+    /// A single slide up that drops to zero and holds (`/___`), shape 4.
+    pub const ATTACK: Self = Self::from_bits(0x04);
+
+    /// A repeating sawtooth that slides down (`\\\\`), shape 8.
+    pub const SAW_DOWN: Self = Self::from_bits(0x08);
+
+    /// A repeating triangle that starts with a slide down (`\/\/`), shape 10.
+    pub const TRIANGLE_DOWN: Self = Self::from_bits(0x0a);
+
+    /// A single slide down that jumps to the top level and holds (`\¯¯¯`), shape 11.
+    pub const DECAY_HOLD_TOP: Self = Self::from_bits(0x0b);
+
+    /// A repeating sawtooth that slides up (`////`), shape 12.
+    pub const SAW_UP: Self = Self::from_bits(0x0c);
+
+    /// A single slide up that holds at the top level (`/¯¯¯`), shape 13.
+    pub const ATTACK_HOLD_TOP: Self = Self::from_bits(0x0d);
+
+    /// A repeating triangle that starts with a slide up (`/\/\`), shape 14.
+    pub const TRIANGLE_UP: Self = Self::from_bits(0x0e);
+
+    /// Convert a value of the envelope shape register into its control bits. Bits above bit 3
+    /// are ignored.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self {
+            continuous: bits & 0x08 != 0,
+            attack: bits & 0x04 != 0,
+            alternate: bits & 0x02 != 0,
+            hold: bits & 0x01 != 0
+        }
+    }
+
+    /// The value of the envelope shape register, between 0 and 15 inclusive.
+    pub const fn bits(self) -> u8 {
+        (self.continuous as u8) << 3 | (self.attack as u8) << 2 | (self.alternate as u8) << 1 | self.hold as u8
+    }
+
+    /// Whether the envelope ends after its first slide and holds a constant level afterwards.
+    pub const fn is_one_shot(self) -> bool {
+        !self.is_periodic()
+    }
+
+    /// Whether the envelope repeats indefinitely, which is the case when the CONT bit is set and
+    /// the HOLD bit is cleared.
+    pub const fn is_periodic(self) -> bool {
+        self.continuous && !self.hold
+    }
+}
+
+impl From<u8> for EnvelopeShapeId {
+    fn from(bits: u8) -> Self {
+        Self::from_bits(bits)
+    }
+}
+
+impl From<EnvelopeShapeId> for u8 {
+    fn from(shape: EnvelopeShapeId) -> Self {
+        shape.bits()
+    }
+}
+
 /// The PSG's envelope generator.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnvelopeGenerator {
     position: u16,
     period: u16,
@@ -58,6 +155,15 @@ impl EnvelopeGenerator {
         }
     }
 
+    /// Reset the envelope generator to its power-on state, with period and shape registers of
+    /// zero. The envelope is at rest at level zero until the shape is set.
+    pub fn reset(&mut self) {
+        *self = Self {
+            period: 0,
+            ..Self::new()
+        };
+    }
+
     /// Render the next clock tick for the envelope generator.
     ///
     /// Returns a byte containing the next envelope level.
@@ -93,6 +199,27 @@ impl EnvelopeGenerator {
         self.value
     }
 
+    /// The amplitude of the current envelope level, linearly interpolated towards the amplitude
+    /// of the next level based on the position within the current step.
+    ///
+    /// Only steps to an adjacent level are interpolated. When the envelope jumps to another level
+    /// or holds its level, the amplitude of the current level is returned as is.
+    pub(crate) fn smooth_amplitude(&self, table: &[Float; 32]) -> Float {
+        let current = table[self.value as usize];
+
+        // Render the next step on a copy to find out where the envelope is heading
+        let mut next = self.clone();
+        next.position = self.period() - 1;
+        let next_value = next.render();
+
+        if next_value.abs_diff(self.value) != 1 {
+            return current;
+        }
+
+        let fraction = self.position as Float / self.period() as Float;
+        current + (table[next_value as usize] - current) * fraction
+    }
+
     /// Reset the envelope generator's value based on the current envelope shape.
     ///
     /// The value is set to 31 when the shape starts at a high value, and 0 otherwise.
@@ -103,9 +230,39 @@ impl EnvelopeGenerator {
         };
     }
 
+    /// Whether the envelope generator has come to rest at the bottom of the current shape, after
+    /// which it will keep producing zero values until the shape is set again.
+    pub(crate) fn is_holding_bottom(&self) -> bool {
+        matches!(ENVELOPE_TABLE[self.shape as usize][self.segment as usize], EnvelopeShape::HoldBottom)
+    }
+
+    /// Write the envelope generator's state to a saved state.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.u16(self.position);
+        writer.u16(self.period);
+        writer.u8(self.shape);
+        writer.u8(self.segment);
+        writer.u8(self.value);
+    }
+
+    /// Restore the envelope generator's state from a saved state.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        self.position = reader.u16()?;
+        self.period = reader.u16()?;
+        self.shape = reader.u8()?;
+        self.segment = reader.u8()?;
+        self.value = reader.u8()?;
+
+        if self.position == u16::MAX || self.shape > 0x0f || self.segment > 1 || self.value > 31 {
+            return Err(Error::InvalidState);
+        }
+
+        Ok(())
+    }
+
     /// The envelope generator's period.
     pub fn period(&self) -> u16 {
-        self.period
+        self.period.max(1)
     }
 
     /// Set the envelope generator's period to a value between 1 and 65535 inclusive.
@@ -123,11 +280,10 @@ impl EnvelopeGenerator {
     /// Set the envelope generator's most significant byte of the period to a value between 0 and
     /// 255 inclusive.
     ///
-    /// Setting this byte to zero when the least significant byte is also set to zero will result
-    /// in the period being set to 1. It is therefore recommended to always set the most
-    /// significant byte first.
+    /// Like on the real chip, a period of zero (with both bytes set to zero) behaves like a period
+    /// of 1, but the bytes are stored as written.
     pub fn set_period_msb(&mut self, period: u8) {
-        self.period = ((self.period & 0x00ff) | (((period as u16) & 0xff) << 8)).max(1);
+        self.period = (self.period & 0x00ff) | ((period as u16) << 8);
     }
 
     /// The least significant byte for the envelope generator's period.
@@ -138,11 +294,10 @@ impl EnvelopeGenerator {
     /// Set the envelope generator's least significant byte of the period to a value between 0 and
     /// 255 inclusive.
     ///
-    /// Setting this byte to zero when the most significant byte is also set to zero will result in
-    /// the period being set to 1. It is therefore recommended to always set the most significant
-    /// byte first.
+    /// Like on the real chip, a period of zero (with both bytes set to zero) behaves like a period
+    /// of 1, but the bytes are stored as written.
     pub fn set_period_lsb(&mut self, period: u8) {
-        self.period = ((self.period & 0xff00) | (period as u16)).max(1);
+        self.period = (self.period & 0xff00) | (period as u16);
     }
 
     /// The envelope generator's shape.
@@ -158,8 +313,203 @@ impl EnvelopeGenerator {
     /// datasheets.
     pub fn set_shape(&mut self, shape: u8) {
         self.shape = shape & 0x0f;
+        self.retrigger();
+    }
+
+    /// Set the envelope generator's shape only when it differs from the current shape, and return
+    /// whether the envelope was restarted.
+    ///
+    /// This is useful when replaying register dumps, which usually repeat the shape in every
+    /// frame even though the envelope should only restart when it changes.
+    pub fn set_shape_if_changed(&mut self, shape: u8) -> bool {
+        let changed = shape & 0x0f != self.shape;
+
+        if changed {
+            self.set_shape(shape);
+        }
+
+        changed
+    }
+
+    /// Restart the envelope from the beginning of its current shape, exactly like writing the
+    /// same shape again using [`set_shape`](Self::set_shape) does.
+    pub fn retrigger(&mut self) {
         self.position = 0;
         self.segment = 0;
         self.reset_segment();
     }
+
+    /// The envelope generator's shape as its four control bits.
+    pub fn shape_typed(&self) -> EnvelopeShapeId {
+        EnvelopeShapeId::from_bits(self.shape)
+    }
+
+    /// Set the envelope generator's shape using its four control bits. This is equivalent to
+    /// calling [`set_shape`](Self::set_shape) with the shape's register value, and restarts the
+    /// envelope in the same way.
+    pub fn set_shape_typed(&mut self, shape: EnvelopeShapeId) {
+        self.set_shape(shape.bits());
+    }
+
+    /// The envelope generator's current level.
+    ///
+    /// This returns a value between 0 and 31 inclusive. On the AY-3-8910 only the upper four bits
+    /// are significant.
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Set the envelope generator's current level to a value between 0 and 31 inclusive.
+    ///
+    /// Higher values are wrapped.
+    pub fn set_value(&mut self, value: u8) {
+        self.value = value & 0x1f;
+    }
+
+    /// The current segment of the envelope shape, which is either 0 (the initial slide) or 1 (the
+    /// part that follows it).
+    pub fn segment(&self) -> u8 {
+        self.segment
+    }
+
+    /// Set the current segment of the envelope shape to either 0 or 1. The level is left
+    /// untouched.
+    ///
+    /// Higher values are wrapped.
+    pub fn set_segment(&mut self, segment: u8) {
+        self.segment = segment & 1;
+    }
+
+    /// The position within the current step of the envelope, counted in ticks. The envelope level
+    /// changes when the position reaches the period.
+    pub fn position(&self) -> u16 {
+        self.position
+    }
+
+    /// Set the position within the current step of the envelope. The position is wrapped against
+    /// the current period.
+    pub fn set_position(&mut self, position: u16) {
+        self.position = position % self.period();
+    }
+
+    /// Whether the envelope generator is holding a constant level, which happens when a shape that
+    /// does not repeat (shapes 0-7, 9, 11, 13, and 15) has finished its slide. This can be used to
+    /// detect the end of a note that uses such a shape.
+    pub fn is_holding(&self) -> bool {
+        matches!(ENVELOPE_TABLE[self.shape as usize][self.segment as usize], EnvelopeShape::HoldTop | EnvelopeShape::HoldBottom)
+    }
+}
+
+impl fmt::Debug for EnvelopeGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnvelopeGenerator")
+            .field("period", &self.period)
+            .field("shape", &self.shape)
+            .field("segment", &self.segment)
+            .field("value", &self.value)
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{EnvelopeGenerator, EnvelopeShapeId};
+
+    /// Render the next amount of envelope levels.
+    fn render(generator: &mut EnvelopeGenerator, count: usize) -> Vec<u8> {
+        (0..count).map(|_| generator.render()).collect()
+    }
+
+    #[test]
+    fn restore_mid_slide() {
+        let mut generator = EnvelopeGenerator::new();
+
+        // A triangle starting with an upwards slide, restored just before it reaches the top
+        generator.set_shape(14);
+        generator.set_period(3);
+        generator.set_value(29);
+        generator.set_position(1);
+
+        assert_eq!(render(&mut generator, 8), [29, 30, 30, 30, 31, 31, 31, 31]);
+        assert_eq!(generator.segment(), 1);
+        assert_eq!(render(&mut generator, 4), [31, 31, 30, 30]);
+
+        // A one-shot slide down, restored near the bottom
+        generator.set_shape(9);
+        generator.set_period(1);
+        generator.set_value(2);
+
+        assert!(!generator.is_holding());
+        assert_eq!(render(&mut generator, 4), [1, 0, 0, 0]);
+        assert!(generator.is_holding());
+        assert_eq!(generator.segment(), 1);
+    }
+
+    #[test]
+    fn setters() {
+        let mut generator = EnvelopeGenerator::new();
+        generator.set_period(100);
+
+        generator.set_value(40);
+        assert_eq!(generator.value(), 8);
+
+        generator.set_segment(3);
+        assert_eq!(generator.segment(), 1);
+
+        generator.set_position(250);
+        assert_eq!(generator.position(), 50);
+    }
+
+    #[test]
+    fn retrigger() {
+        let mut generator = EnvelopeGenerator::new();
+        generator.set_period(2);
+        generator.set_shape(10);
+
+        let reference = render(&mut generator, 200);
+
+        // Retriggering restarts the envelope like writing the shape again
+        generator.retrigger();
+        assert_eq!((generator.position(), generator.segment(), generator.value()), (0, 0, 31));
+        assert_eq!(render(&mut generator, 200), reference);
+
+        // Writing an unchanged shape leaves the envelope running
+        render(&mut generator, 5);
+        let mut running = generator.clone();
+
+        assert!(!generator.set_shape_if_changed(0x1a));
+        assert_eq!(render(&mut generator, 100), render(&mut running, 100));
+
+        // Writing a different shape restarts it
+        assert!(generator.set_shape_if_changed(14));
+        assert_eq!((generator.shape(), generator.position(), generator.segment(), generator.value()), (14, 0, 0, 0));
+    }
+
+    #[test]
+    fn typed_shapes() {
+        let mut generator = EnvelopeGenerator::new();
+        generator.set_period(1);
+
+        for bits in 0..16 {
+            let shape = EnvelopeShapeId::from_bits(bits);
+            assert_eq!(shape.bits(), bits);
+            assert_eq!(u8::from(shape), bits);
+
+            generator.set_shape(bits);
+            assert_eq!(generator.shape_typed(), shape);
+
+            // Only the one-shot shapes come to rest after the first slide
+            render(&mut generator, 64);
+            assert_eq!(generator.is_holding(), shape.is_one_shot());
+            assert_eq!(generator.is_holding(), !shape.is_periodic());
+        }
+
+        generator.set_shape_typed(EnvelopeShapeId::TRIANGLE_UP);
+        assert_eq!(generator.shape(), 14);
+        assert_eq!(generator.value(), 0);
+
+        assert_eq!(EnvelopeShapeId::from(0x1c), EnvelopeShapeId::SAW_UP);
+        assert_eq!(EnvelopeShapeId::SAW_DOWN, EnvelopeShapeId { continuous: true, attack: false, alternate: false, hold: false });
+    }
 }
@@ -1,18 +1,135 @@
-use std::fmt::{Display, Formatter, Result};
+use core::fmt::{Display, Formatter, Result};
 
 /// An enum representing all possible errors that the PSG may encounter during operation.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     /// The clock rate is too high for the requested sample rate.
-    ClockRateTooHigh
+    ClockRateTooHigh {
+        /// The requested clock rate in Hz.
+        clock_rate: f64,
+
+        /// The upper limit of the clock rate for the requested sample rate in Hz. The clock rate
+        /// must be lower than this value.
+        max: f64
+    },
+
+    /// The clock rate is not a finite number above zero. Contains the requested clock rate in Hz.
+    InvalidClockRate(f64),
+
+    /// The provided output buffers do not have the same length.
+    BufferLengthMismatch {
+        /// The length of the left channel's buffer.
+        left: usize,
+
+        /// The length of the right channel's buffer.
+        right: usize
+    },
+
+    /// The DC filter length is not a power of two.
+    InvalidDCFilterLength(usize),
+
+    /// The channel number is not smaller than 3.
+    InvalidChannel(u8),
+
+    /// The register number is not smaller than 16.
+    InvalidRegister(u8),
+
+    /// The digital-to-analog conversion table contains an amplitude that is not finite, lies
+    /// outside of the range 0 to 1, or is lower than the amplitude of the previous level. Contains
+    /// the index of the first invalid amplitude.
+    InvalidDacTable(usize),
+
+    /// The saved state is malformed or truncated.
+    InvalidState,
+
+    /// The saved state uses a format version that is not supported by this version of the crate.
+    UnsupportedStateVersion(u16),
+
+    /// The saved state was produced by a PSG with a different clock rate.
+    StateClockRateMismatch {
+        /// The clock rate of the PSG that saved the state in Hz.
+        state: f64,
+
+        /// The clock rate of the PSG that the state is loaded into in Hz.
+        psg: f64
+    },
+
+    /// The saved state was produced by a PSG with a different sample rate.
+    StateSampleRateMismatch {
+        /// The sample rate of the PSG that saved the state in Hz.
+        state: u32,
+
+        /// The sample rate of the PSG that the state is loaded into in Hz.
+        psg: u32
+    },
+
+    /// The saved state was produced by a PSG with a different DC filter length.
+    StateDCFilterLengthMismatch {
+        /// The DC filter length of the PSG that saved the state.
+        state: usize,
+
+        /// The DC filter length of the PSG that the state is loaded into.
+        psg: usize
+    },
+
+    /// The command queue of a [`PsgController`](crate::PsgController) is full, so the command
+    /// was rejected.
+    QueueFull
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         match self {
-            Error::ClockRateTooHigh => f.write_str("the clock rate is too high for the requested sample rate")
+            Error::ClockRateTooHigh { clock_rate, max } => {
+                write!(f, "the clock rate of {} Hz is too high for the requested sample rate, it must be lower than {} Hz", clock_rate, max)
+            }
+            Error::InvalidClockRate(clock_rate) => write!(f, "the clock rate of {} Hz is invalid, it must be a finite number above zero", clock_rate),
+            Error::BufferLengthMismatch { left, right } => {
+                write!(f, "the output buffers do not have the same length (left is {}, right is {})", left, right)
+            }
+            Error::InvalidDCFilterLength(length) => write!(f, "the DC filter length {} is not a power of two", length),
+            Error::InvalidChannel(channel) => write!(f, "channel {} does not exist, it must be smaller than 3", channel),
+            Error::InvalidRegister(register) => write!(f, "register {} does not exist, it must be smaller than 16", register),
+            Error::InvalidDacTable(index) => {
+                write!(f, "the DAC table amplitude at index {} is not finite, outside of [0, 1], or lower than the previous amplitude", index)
+            }
+            Error::InvalidState => write!(f, "the saved state is malformed or truncated"),
+            Error::UnsupportedStateVersion(version) => write!(f, "the saved state has unsupported format version {}", version),
+            Error::StateClockRateMismatch { state, psg } => {
+                write!(f, "the state was saved with a clock rate of {} Hz, but the PSG uses {} Hz", state, psg)
+            }
+            Error::StateSampleRateMismatch { state, psg } => {
+                write!(f, "the state was saved with a sample rate of {} Hz, but the PSG uses {} Hz", state, psg)
+            }
+            Error::StateDCFilterLengthMismatch { state, psg } => {
+                write!(f, "the state was saved with a DC filter length of {}, but the PSG uses {}", state, psg)
+            }
+            Error::QueueFull => write!(f, "the command queue is full"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::Error;
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            Error::ClockRateTooHigh { clock_rate: 1e9, max: 2e8 }.to_string(),
+            "the clock rate of 1000000000 Hz is too high for the requested sample rate, it must be lower than 200000000 Hz"
+        );
+
+        assert_eq!(Error::InvalidClockRate(-1.0).to_string(), "the clock rate of -1 Hz is invalid, it must be a finite number above zero");
+
+        assert_eq!(Error::InvalidChannel(7).to_string(), "channel 7 does not exist, it must be smaller than 3");
+
+        assert_eq!(
+            Error::StateSampleRateMismatch { state: 8000, psg: 44100 }.to_string(),
+            "the state was saved with a sample rate of 8000 Hz, but the PSG uses 44100 Hz"
+        );
+    }
+}
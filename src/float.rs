@@ -0,0 +1,142 @@
+/// The floating point functions that are provided by the standard library, implemented using libm
+/// for builds without it.
+///
+/// When the standard library is linked anyway, for example by the test harness or by a dependency,
+/// its methods take precedence over the ones of this trait, so the imports of the trait allow
+/// being unused.
+#[allow(dead_code)]
+pub trait FloatExt {
+    fn ceil(self) -> Self;
+    fn cos(self) -> Self;
+    fn exp(self) -> Self;
+    fn exp2(self) -> Self;
+    fn fract(self) -> Self;
+    fn log10(self) -> Self;
+    fn log2(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+    fn round(self) -> Self;
+    fn sin(self) -> Self;
+    fn sqrt(self) -> Self;
+}
+
+impl FloatExt for f64 {
+    fn ceil(self) -> Self {
+        libm::ceil(self)
+    }
+
+    fn cos(self) -> Self {
+        libm::cos(self)
+    }
+
+    fn exp(self) -> Self {
+        libm::exp(self)
+    }
+
+    fn exp2(self) -> Self {
+        libm::exp2(self)
+    }
+
+    fn fract(self) -> Self {
+        self - libm::trunc(self)
+    }
+
+    fn log10(self) -> Self {
+        libm::log10(self)
+    }
+
+    fn log2(self) -> Self {
+        libm::log2(self)
+    }
+
+    fn powf(self, exponent: Self) -> Self {
+        libm::pow(self, exponent)
+    }
+
+    fn round(self) -> Self {
+        libm::round(self)
+    }
+
+    fn sin(self) -> Self {
+        libm::sin(self)
+    }
+
+    fn sqrt(self) -> Self {
+        libm::sqrt(self)
+    }
+}
+
+impl FloatExt for f32 {
+    fn ceil(self) -> Self {
+        libm::ceilf(self)
+    }
+
+    fn cos(self) -> Self {
+        libm::cosf(self)
+    }
+
+    fn exp(self) -> Self {
+        libm::expf(self)
+    }
+
+    fn exp2(self) -> Self {
+        libm::exp2f(self)
+    }
+
+    fn fract(self) -> Self {
+        self - libm::truncf(self)
+    }
+
+    fn log10(self) -> Self {
+        libm::log10f(self)
+    }
+
+    fn log2(self) -> Self {
+        libm::log2f(self)
+    }
+
+    fn powf(self, exponent: Self) -> Self {
+        libm::powf(self, exponent)
+    }
+
+    fn round(self) -> Self {
+        libm::roundf(self)
+    }
+
+    fn sin(self) -> Self {
+        libm::sinf(self)
+    }
+
+    fn sqrt(self) -> Self {
+        libm::sqrtf(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FloatExt;
+
+    /// The test harness links the standard library, whose methods take precedence over the trait,
+    /// so the trait is called explicitly and compared with them.
+    #[test]
+    fn matches_std() {
+        for value in [0.3f64, 1.0, 2.5, 7.75, 440.0] {
+            let close = |libm: f64, std: f64| assert!((libm - std).abs() <= 1e-12 * std.abs().max(1.0), "{value}: {libm} {std}");
+
+            close(FloatExt::ceil(-value), (-value).ceil());
+            close(FloatExt::cos(value), value.cos());
+            close(FloatExt::exp(value / 100.0), (value / 100.0).exp());
+            close(FloatExt::exp2(value / 12.0), (value / 12.0).exp2());
+            close(FloatExt::fract(-value), (-value).fract());
+            close(FloatExt::log10(value), value.log10());
+            close(FloatExt::log2(value), value.log2());
+            close(FloatExt::powf(value, 1.5), value.powf(1.5));
+            close(FloatExt::round(value), value.round());
+            close(FloatExt::sin(value), value.sin());
+            close(FloatExt::sqrt(value), value.sqrt());
+
+            let value = value as f32;
+            assert!((FloatExt::sqrt(value) - value.sqrt()).abs() <= 1e-6 * value.sqrt());
+            assert_eq!(FloatExt::round(value), value.round());
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{Error, Float};
+use crate::state::{StateReader, StateWriter};
+
+/// The maximum number of half-band decimation stages that can be placed after the main decimator.
+/// Every stage halves the output rate, so this allows for a total extra decimation factor of 16.
+pub const MAX_HALF_BAND_STAGES: usize = 4;
+
+/// The amount of taps in the half-band filter, including the taps with zero coefficients.
+pub const HALF_BAND_TAPS: usize = 27;
+
+/// The size of the half-band filter's delay line. This must be a power of two that is larger than
+/// the amount of filter taps.
+const BUFFER_SIZE: usize = 32;
+
+/// The offset of the center tap in the delay line, relative to the most recent input sample.
+const CENTER: usize = 13;
+
+/// The non-zero coefficients of the half-band filter, excluding the center tap (which is 0.5).
+/// These are applied symmetrically at odd offsets (1, 3, 5, ...) from the center tap.
+#[cfg_attr(feature = "f32", allow(clippy::excessive_precision))]
+const COEFFICIENTS: [Float; 7] = [
+     0.3126362213157209,
+    -0.0901077578274098,
+     0.04010778965027655,
+    -0.017917196603567664,
+     0.007100922992971307,
+    -0.0022303062045729784,
+     0.00041032667658172427
+];
+
+/// A 2x downsampler using a 27-tap Blackman windowed sinc half-band FIR filter.
+///
+/// This is used as an additional decimation stage when the PSG's clock rate is too high for the
+/// main decimator to handle at the requested sample rate. Half of the filter's coefficients are
+/// zero, so only the odd taps and the center tap need to be computed.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HalfBandFilter {
+    buffer: [Float; BUFFER_SIZE],
+    index: usize
+}
+
+impl HalfBandFilter {
+    /// Initialize a new half-band filter.
+    pub fn new() -> Self {
+        Self {
+            buffer: [0.0; BUFFER_SIZE],
+            index: 0
+        }
+    }
+
+    /// Write the filter's delay line to a saved state.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.floats(&self.buffer);
+        writer.index(self.index);
+    }
+
+    /// Restore the filter's delay line from a saved state.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        reader.floats(&mut self.buffer)?;
+        self.index = reader.index(BUFFER_SIZE)?;
+        Ok(())
+    }
+
+    /// Feed two consecutive input samples into the filter and produce a single output sample.
+    pub fn render(&mut self, first: Float, second: Float) -> Float {
+        self.buffer[self.index] = first;
+        self.buffer[(self.index + 1) & (BUFFER_SIZE - 1)] = second;
+        self.index = (self.index + 2) & (BUFFER_SIZE - 1);
+
+        // Offset 0 is the most recent sample
+        let at = |offset: usize| self.buffer[self.index.wrapping_sub(offset + 1) & (BUFFER_SIZE - 1)];
+
+        COEFFICIENTS.iter().enumerate().fold(0.5 * at(CENTER), |result, (index, coefficient)| {
+            let offset = index * 2 + 1;
+            result + coefficient * (at(CENTER - offset) + at(CENTER + offset))
+        })
+    }
+}
@@ -1,38 +1,146 @@
-/// A 2nd order 4-point parabolic interpolator with cached coefficient, allowing the same input
-/// value (and its history) to be used for multiple intermediate points.
+use crate::{Error, Float};
+use crate::state::{StateReader, StateWriter};
+
+/// The algorithm that is used to interpolate the PSG's output from the tick rate to the internal
+/// oversampled rate.
+///
+/// Both algorithms are described in detail here:
+/// http://yehar.com/blog/wp-content/uploads/2009/08/deip.pdf
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InterpolationMode {
+    /// A 4-point, 2nd-order parabolic interpolator. This is the default mode, and is identical to
+    /// the interpolator used by Ayumi.
+    Parabolic,
+
+    /// A 6-point, 5th-order B-spline interpolator. This suppresses the images of high tones much
+    /// more effectively, which results in less aliasing, at the cost of a slightly duller top end
+    /// and some additional processing time.
+    BSpline
+}
+
+/// An interpolator with cached coefficients, allowing the same input value (and its history) to be
+/// used for multiple intermediate points.
 ///
-/// More concrete details about the interpolation algorithm can be found here:
+/// The interpolator supports both a 4-point parabolic and a 6-point B-spline algorithm. The
+/// parabolic algorithm interpolates between the second and third most recent input values, while
+/// the B-spline algorithm needs an extra input value of history and interpolates between the third
+/// and fourth most recent input values.
+///
+/// More concrete details about the interpolation algorithms can be found here:
 /// http://yehar.com/blog/wp-content/uploads/2009/08/deip.pdf
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Interpolator {
-    y: [f64; 4],
-    coefficients: [f64; 3]
+    mode: InterpolationMode,
+    y: [Float; 6],
+    coefficients: [Float; 6]
 }
 
 impl Interpolator {
     /// Initialize a new interpolator.
     pub fn new() -> Self {
         Self {
-            y: [0.0; 4],
-            coefficients: [0.0; 3],
+            mode: InterpolationMode::Parabolic,
+            y: [0.0; 6],
+            coefficients: [0.0; 6]
         }
     }
 
+    /// The interpolation algorithm that is currently used.
+    pub fn mode(&self) -> InterpolationMode {
+        self.mode
+    }
+
+    /// Set the interpolation algorithm. The input history is retained, so the new algorithm takes
+    /// effect immediately.
+    pub fn set_mode(&mut self, mode: InterpolationMode) {
+        self.mode = mode;
+        self.update_coefficients();
+    }
+
+    /// Write the interpolator's input history to a saved state. The mode is not included, as it
+    /// is shared by both of the PSG's interpolators.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.floats(&self.y);
+    }
+
+    /// Restore the interpolator's input history from a saved state.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        reader.floats(&mut self.y)?;
+        self.update_coefficients();
+        Ok(())
+    }
+
     /// Feed a new value into the interpolator.
-    pub fn feed(&mut self, input: f64) {
-        self.y[0] = self.y[1];
-        self.y[1] = self.y[2];
-        self.y[2] = self.y[3];
-        self.y[3] = input;
+    #[inline]
+    pub fn feed(&mut self, input: Float) {
+        self.y = [self.y[1], self.y[2], self.y[3], self.y[4], self.y[5], input];
 
-        let y1 = self.y[2] - self.y[0];
+        self.update_coefficients();
+    }
+
+    /// Recompute the cached coefficients from the input history.
+    #[inline]
+    fn update_coefficients(&mut self) {
+        let y = &self.y;
+
+        match self.mode {
+            InterpolationMode::Parabolic => {
+                let y1 = y[4] - y[2];
+
+                self.coefficients[0] = 0.5 * y[3] + 0.25 * (y[2] + y[4]);
+                self.coefficients[1] = 0.5 * y1;
+                self.coefficients[2] = 0.25 * (y[5] - y[3] - y1);
+            }
 
-        self.coefficients[0] = 0.5 * self.y[1] + 0.25 * (self.y[0] + self.y[2]);
-        self.coefficients[1] = 0.5 * y1;
-        self.coefficients[2] = 0.25 * (self.y[3] - self.y[1] - y1);
+            InterpolationMode::BSpline => {
+                let ym2py2 = y[0] + y[4];
+                let ym1py1 = y[1] + y[3];
+                let y2mym2 = y[4] - y[0];
+                let y1mym1 = y[3] - y[1];
+                let sixth_ym1py1 = 1.0 / 6.0 * ym1py1;
+
+                self.coefficients[0] = 1.0 / 120.0 * ym2py2 + 13.0 / 60.0 * ym1py1 + 11.0 / 20.0 * y[2];
+                self.coefficients[1] = 1.0 / 24.0 * y2mym2 + 5.0 / 12.0 * y1mym1;
+                self.coefficients[2] = 1.0 / 12.0 * ym2py2 + sixth_ym1py1 - 0.5 * y[2];
+                self.coefficients[3] = 1.0 / 12.0 * y2mym2 - 1.0 / 6.0 * y1mym1;
+                self.coefficients[4] = 1.0 / 24.0 * ym2py2 - sixth_ym1py1 + 0.25 * y[2];
+                self.coefficients[5] = 1.0 / 120.0 * (y[5] - y[0]) + 1.0 / 24.0 * (y[1] - y[4]) +
+                    1.0 / 12.0 * (y[3] - y[2]);
+            }
+        }
     }
 
     /// Perform a new interpolation for the intermediate value x (0..=1).
-    pub fn interpolate(&self, x: f64) -> f64 {
-        (self.coefficients[2] * x + self.coefficients[1]) * x + self.coefficients[0]
+    #[inline]
+    pub fn interpolate(&self, x: Float) -> Float {
+        let c = &self.coefficients;
+
+        match self.mode {
+            InterpolationMode::Parabolic => (c[2] * x + c[1]) * x + c[0],
+            InterpolationMode::BSpline => ((((c[5] * x + c[4]) * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0]
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{Float, InterpolationMode, Interpolator};
+
+    #[test]
+    fn constant_input() {
+        for mode in [InterpolationMode::Parabolic, InterpolationMode::BSpline] {
+            let mut interpolator = Interpolator::new();
+            interpolator.set_mode(mode);
+
+            for _ in 0..6 {
+                interpolator.feed(0.75);
+            }
+
+            for step in 0..=8 {
+                assert!((interpolator.interpolate(step as Float / 8.0) - 0.75).abs() < 1e-6);
+            }
+        }
     }
 }
@@ -0,0 +1,418 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::ym::YmError;
+
+/// The number of symbols in the code of the code lengths of the literal and length code.
+const LENGTH_SYMBOLS: usize = 19;
+
+/// The number of symbols in the literal and length code: 256 literals, and match lengths from 3
+/// to 256.
+const LITERAL_SYMBOLS: usize = 510;
+
+/// The shortest match length.
+const MIN_MATCH_LENGTH: usize = 3;
+
+/// The longest code in any Huffman table.
+const MAX_CODE_LENGTH: usize = 16;
+
+/// Whether the data is an LHA archive, which starts with a header size and checksum followed by
+/// a compression method such as "-lh5-".
+pub(crate) fn is_lha(data: &[u8]) -> bool {
+    matches!(data.get(2..7), Some([b'-', b'l', b'h', _, b'-']))
+}
+
+/// Decompress the first file in an LHA archive. Stored files and the -lh4- through -lh7- methods
+/// are supported, which differ only in the size of their sliding window.
+pub(crate) fn decompress(data: &[u8]) -> Result<Vec<u8>, YmError> {
+    let header = Header::parse(data)?;
+
+    let compressed = header.data_offset.checked_add(header.compressed_size).and_then(|end| data.get(header.data_offset..end))
+        .ok_or(YmError::CorruptArchive("the compressed data is truncated"))?;
+
+    let window_bits = match &header.method {
+        b"-lh0-" => None,
+        b"-lh4-" => Some(12),
+        b"-lh5-" => Some(13),
+        b"-lh6-" => Some(15),
+        b"-lh7-" => Some(16),
+        method => return Err(YmError::UnsupportedCompression(String::from_utf8_lossy(method).into_owned()))
+    };
+
+    let output = match window_bits {
+        Some(window_bits) => Decoder::new(compressed, window_bits).decode(header.original_size)?,
+        None => compressed.to_vec()
+    };
+
+    if output.len() != header.original_size {
+        return Err(YmError::CorruptArchive("the decompressed size does not match the header"));
+    }
+
+    if crc16(&output) != header.crc {
+        return Err(YmError::CorruptArchive("the checksum of the decompressed data does not match"));
+    }
+
+    Ok(output)
+}
+
+/// The fields of an LHA file header that are needed for decompressing the file.
+struct Header {
+    method: [u8; 5],
+    compressed_size: usize,
+    original_size: usize,
+    crc: u16,
+    data_offset: usize
+}
+
+impl Header {
+    /// Parse a level 0, 1 or 2 header.
+    fn parse(data: &[u8]) -> Result<Self, YmError> {
+        const TRUNCATED: YmError = YmError::CorruptArchive("the header is truncated");
+
+        let u16_at = |offset: usize| data.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).ok_or(TRUNCATED);
+        let u32_at = |offset: usize| data.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).ok_or(TRUNCATED);
+
+        let method = data.get(2..7).and_then(|method| method.try_into().ok()).ok_or(TRUNCATED)?;
+        let mut compressed_size = u32_at(7)? as usize;
+        let original_size = u32_at(11)? as usize;
+        let level = *data.get(20).ok_or(TRUNCATED)?;
+
+        let (crc, data_offset) = match level {
+            0 | 1 => {
+                let header_size = data[0] as usize + 2;
+                let name_length = *data.get(21).ok_or(TRUNCATED)? as usize;
+
+                let checksum = data.get(2..header_size).ok_or(TRUNCATED)?.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+
+                if checksum != data[1] {
+                    return Err(YmError::CorruptArchive("the header checksum does not match"));
+                }
+
+                if 24 + name_length > header_size {
+                    return Err(TRUNCATED);
+                }
+
+                let crc = u16_at(22 + name_length)?;
+                let mut data_offset = header_size;
+
+                // Level 1 headers are followed by extended headers, which are included in the
+                // compressed size, and each of which ends with the size of the next one
+                if level == 1 {
+                    let mut next_size = u16_at(header_size - 2)? as usize;
+
+                    while next_size != 0 {
+                        if next_size < 3 {
+                            return Err(YmError::CorruptArchive("an extended header is invalid"));
+                        }
+
+                        data_offset += next_size;
+                        compressed_size = compressed_size.checked_sub(next_size).ok_or(YmError::CorruptArchive("an extended header is invalid"))?;
+                        next_size = u16_at(data_offset - 2)? as usize;
+                    }
+                }
+
+                (crc, data_offset)
+            }
+            2 => (u16_at(21)?, u16_at(0)? as usize),
+            _ => return Err(YmError::CorruptArchive("the header level is unknown"))
+        };
+
+        Ok(Self {
+            method,
+            compressed_size,
+            original_size,
+            crc,
+            data_offset
+        })
+    }
+}
+
+/// Decodes the -lh4- through -lh7- methods, which compress the data with LZ77 followed by Huffman
+/// coding in blocks, each with its own code tables.
+struct Decoder<'a> {
+    reader: BitReader<'a>,
+    offset_symbols: usize,
+    offset_bits: u32
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8], window_bits: u32) -> Self {
+        Self {
+            reader: BitReader::new(data),
+            offset_symbols: (window_bits as usize + 1).max(14),
+            offset_bits: if window_bits > 13 { 5 } else { 4 }
+        }
+    }
+
+    fn decode(mut self, size: usize) -> Result<Vec<u8>, YmError> {
+        let mut output = Vec::with_capacity(size);
+
+        while output.len() < size {
+            let block_size = self.reader.bits(16)?;
+
+            if block_size == 0 {
+                return Err(YmError::CorruptArchive("a block is empty"));
+            }
+
+            let length_code = self.read_code(LENGTH_SYMBOLS, 5, Some(3))?;
+            let literal_code = self.read_literal_code(&length_code)?;
+            let offset_code = self.read_code(self.offset_symbols, self.offset_bits, None)?;
+
+            for _ in 0..block_size {
+                if output.len() == size {
+                    break;
+                }
+
+                let symbol = literal_code.decode(&mut self.reader)? as usize;
+
+                if symbol < 256 {
+                    output.push(symbol as u8);
+                    continue;
+                }
+
+                let length = symbol - 256 + MIN_MATCH_LENGTH;
+                let distance = self.read_offset(&offset_code)? + 1;
+
+                if distance > output.len() {
+                    return Err(YmError::CorruptArchive("a match refers to data before the start of the file"));
+                }
+
+                for _ in 0..length.min(size - output.len()) {
+                    output.push(output[output.len() - distance]);
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Read the code lengths of the code of the code lengths, or of the offset code. Lengths up to
+    /// 6 are stored in 3 bits, and longer ones as a run of ones. The code of the code lengths has
+    /// a 2-bit run of zero lengths after its third length.
+    fn read_code(&mut self, symbols: usize, count_bits: u32, skip_after: Option<usize>) -> Result<Huffman, YmError> {
+        let count = self.reader.bits(count_bits)? as usize;
+
+        if count == 0 {
+            return Huffman::single(self.reader.bits(count_bits)? as usize, symbols);
+        }
+
+        if count > symbols {
+            return Err(YmError::CorruptArchive("a code table is too large"));
+        }
+
+        let mut lengths = vec![0; symbols];
+        let mut index = 0;
+
+        while index < count {
+            let mut length = self.reader.bits(3)? as usize;
+
+            if length == 7 {
+                while self.reader.bit()? {
+                    length += 1;
+
+                    if length > MAX_CODE_LENGTH {
+                        return Err(YmError::CorruptArchive("a code length is too long"));
+                    }
+                }
+            }
+
+            lengths[index] = length as u8;
+            index += 1;
+
+            if Some(index) == skip_after {
+                index += self.reader.bits(2)? as usize;
+            }
+        }
+
+        Huffman::new(&lengths)
+    }
+
+    /// Read the code lengths of the literal and length code, which are coded with the code of the
+    /// code lengths. Symbols 0 through 2 encode runs of zero lengths.
+    fn read_literal_code(&mut self, length_code: &Huffman) -> Result<Huffman, YmError> {
+        let count = self.reader.bits(9)? as usize;
+
+        if count == 0 {
+            return Huffman::single(self.reader.bits(9)? as usize, LITERAL_SYMBOLS);
+        }
+
+        if count > LITERAL_SYMBOLS {
+            return Err(YmError::CorruptArchive("a code table is too large"));
+        }
+
+        let mut lengths = vec![0; LITERAL_SYMBOLS];
+        let mut index = 0;
+
+        while index < count {
+            match length_code.decode(&mut self.reader)? {
+                0 => index += 1,
+                1 => index += self.reader.bits(4)? as usize + 3,
+                2 => index += self.reader.bits(9)? as usize + 20,
+                symbol => {
+                    lengths[index] = symbol as u8 - 2;
+                    index += 1;
+                }
+            }
+        }
+
+        Huffman::new(&lengths)
+    }
+
+    /// Read a match offset, which is coded as its bit length followed by its remaining bits.
+    fn read_offset(&mut self, offset_code: &Huffman) -> Result<usize, YmError> {
+        match offset_code.decode(&mut self.reader)? as u32 {
+            0 => Ok(0),
+            bits => Ok(((1 << (bits - 1)) | self.reader.bits(bits - 1)?) as usize)
+        }
+    }
+}
+
+/// A canonical Huffman code, in which shorter codes come first, and codes of equal length are
+/// ordered by their symbols.
+struct Huffman {
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, YmError> {
+        let mut counts = [0; MAX_CODE_LENGTH + 1];
+
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+
+        // Every code must fit in the space left by the shorter codes
+        let mut available = 1i32;
+
+        for &count in &counts[1..] {
+            available = 2 * available - count as i32;
+
+            if available < 0 {
+                return Err(YmError::CorruptArchive("a code table is invalid"));
+            }
+        }
+
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16).filter(|&symbol| lengths[symbol as usize] != 0).collect();
+        symbols.sort_by_key(|&symbol| lengths[symbol as usize]);
+
+        counts[0] = 0;
+        Ok(Self { counts, symbols })
+    }
+
+    /// A code that consists of a single symbol, which is decoded without reading any bits.
+    fn single(symbol: usize, symbols: usize) -> Result<Self, YmError> {
+        if symbol >= symbols {
+            return Err(YmError::CorruptArchive("a code table is invalid"));
+        }
+
+        let mut counts = [0; MAX_CODE_LENGTH + 1];
+        counts[0] = 1;
+
+        Ok(Self { counts, symbols: vec![symbol as u16] })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, YmError> {
+        if self.counts[0] == 1 {
+            return Ok(self.symbols[0]);
+        }
+
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+
+        for &count in &self.counts[1..] {
+            code |= reader.bit()? as usize;
+
+            if code - first < count as usize {
+                return Ok(self.symbols[index + code - first]);
+            }
+
+            index += count as usize;
+            first = (first + count as usize) << 1;
+            code <<= 1;
+        }
+
+        Err(YmError::CorruptArchive("the compressed data contains an invalid code"))
+    }
+}
+
+/// Reads bits from the most significant bit of each byte onwards.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn bit(&mut self) -> Result<bool, YmError> {
+        let byte = self.data.get(self.position / 8).ok_or(YmError::CorruptArchive("the compressed data ends unexpectedly"))?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+
+        self.position += 1;
+        Ok(bit)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, YmError> {
+        (0..count).try_fold(0, |value, _| Ok((value << 1) | self.bit()? as u32))
+    }
+}
+
+/// The CRC-16 of the decompressed data, which uses the reflected 0x8005 polynomial.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u16, |crc, _| if crc & 1 != 0 { (crc >> 1) ^ 0xa001 } else { crc >> 1 })
+    })
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{crc16, decompress};
+    use crate::ym::YmError;
+
+    /// An LHA archive with a level 0 header, which contains a YM6! file that is compressed with
+    /// the -lh5- method.
+    const COMPRESSED_YM: &[u8] = include_bytes!("../testdata/compressed.ym");
+
+    #[test]
+    fn decompress_lh5() {
+        let data = decompress(COMPRESSED_YM).unwrap();
+
+        assert_eq!(data.len(), 1673);
+        assert_eq!(&data[..12], b"YM6!LeOnArD!");
+        assert_eq!(&data[data.len() - 4..], b"End!");
+    }
+
+    #[test]
+    fn errors() {
+        let mut data = COMPRESSED_YM.to_vec();
+
+        // The method is part of the header checksum
+        data[5] = b'1';
+        data[1] = data[1].wrapping_sub(b'5' - b'1');
+        assert_eq!(decompress(&data), Err(YmError::UnsupportedCompression("-lh1-".into())));
+
+        data[1] = data[1].wrapping_add(1);
+        assert_eq!(decompress(&data), Err(YmError::CorruptArchive("the header checksum does not match")));
+
+        assert_eq!(decompress(&COMPRESSED_YM[..60]), Err(YmError::CorruptArchive("the compressed data is truncated")));
+        assert_eq!(decompress(&COMPRESSED_YM[..10]), Err(YmError::CorruptArchive("the header is truncated")));
+
+        // Damaging the compressed data anywhere must not panic
+        for index in 40..COMPRESSED_YM.len() - 1 {
+            let mut data = COMPRESSED_YM.to_vec();
+            data[index] ^= 0x55;
+
+            let _ = decompress(&data);
+        }
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc16(b"123456789"), 0xbb3d);
+    }
+}
@@ -18,12 +18,12 @@
 //! a loop:
 //!
 //! ```
-//! # use psg::PSG;
+//! # use psg::{ChannelIndex, PSG};
 //! // Initialize a new PSG with a clock rate of an MSX machine and a sampling rate of 44100 Hz.
 //! let mut psg = PSG::new(1789772.5, 44100)?;
 //!
 //! // Set some registers.
-//! let channel = psg.channel_mut(0);
+//! let channel = psg.channel_mut(ChannelIndex::A);
 //! channel.set_period(100);
 //! channel.set_amplitude(8);
 //! channel.set_tone_disabled(false);
@@ -40,56 +40,184 @@
 //!
 //! For more detailed information on how to use the crate, please have a look at the [`PSG`]
 //! struct, which is the workhorse of the crate.
+//!
+//! # Features
+//!
+//! The crate uses the standard library by default. Disabling the `std` feature makes it `no_std`,
+//! for embedded targets, in which case it needs an allocator, and the `libm` feature must be
+//! enabled for the floating point math. Rendering does not allocate, unless register writes are
+//! recorded or scheduled. The modules for playing audio and writing WAV files need the standard
+//! library.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("the libm feature must be enabled when the std feature is disabled");
 
+extern crate alloc;
+
+// The test harness needs the standard library
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+mod builder;
 mod channel;
+mod dac_table;
 mod dc_filter;
 mod decimator;
+mod digidrum;
 mod envelope_generator;
 mod error;
+mod half_band;
 mod interpolator;
+mod meter;
 mod noise_generator;
+mod output_filter;
+mod player;
+mod ports;
+mod recording;
+mod sample;
+mod state;
+mod sync_buzzer;
+
+#[cfg(feature = "std")]
+mod control;
+
+#[cfg(not(feature = "std"))]
+mod float;
 
+#[cfg(feature = "formats-ym")]
+mod lha;
+
+#[cfg(feature = "serde")]
+mod serde_support;
+
+#[cfg(feature = "dasp")]
+mod signal;
+
+#[cfg(feature = "rodio")]
+mod source;
+
+pub mod debugdump;
 pub mod math;
+pub mod voices;
+
+#[cfg(feature = "formats-mym")]
+pub mod mym;
+
+#[cfg(feature = "formats-psg")]
+pub mod psg_file;
+
+#[cfg(feature = "formats-vgm")]
+pub mod vgm;
+
+#[cfg(feature = "formats-ym")]
+pub mod ym;
 
-pub use channel::Channel;
-pub use envelope_generator::EnvelopeGenerator;
+#[cfg(feature = "wav")]
+pub mod wav;
+
+#[cfg(feature = "cpal")]
+pub mod realtime;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use builder::PsgBuilder;
+pub use channel::{Channel, ChannelIndex, ChannelSelector, DutyCycle};
+#[cfg(feature = "std")]
+pub use control::{Command, CommandReceiver, ControlledPsg, PsgController};
+pub use dac_table::DacTable;
+pub use dc_filter::DCFilterMode;
+pub use digidrum::Digidrum;
+pub use envelope_generator::{EnvelopeGenerator, EnvelopeShapeId};
 pub use error::Error;
+pub use interpolator::InterpolationMode;
+pub use meter::Meter;
 pub use noise_generator::NoiseGenerator;
+pub use output_filter::{MachinePreset, OutputFilter};
+pub use player::{FramePlayer, PlayerStatus};
+pub use ports::PsgPorts;
+pub use recording::RegisterEvent;
+pub use sample::Sample;
+#[cfg(feature = "dasp")]
+pub use signal::PsgSignal;
+
+#[cfg(feature = "rodio")]
+pub use source::PsgSource;
+pub use state::{PsgState, STATE_VERSION};
+pub use sync_buzzer::SyncBuzzer;
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
 
 use decimator::{DECIMATE_FACTOR, Decimator, FIR_SIZE};
-use dc_filter::DCFilter;
+use dc_filter::{DEFAULT_DC_FILTER_LENGTH, DCFilter, OnePoleFilter};
+use half_band::{HALF_BAND_TAPS, HalfBandFilter, MAX_HALF_BAND_STAGES};
 use interpolator::Interpolator;
+use meter::MeterAccumulator;
+use output_filter::OutputFilterChain;
+use state::{StateReader, StateWriter};
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use float::FloatExt;
+
+/// The floating point type that is used for internal processing. This is `f64` by default, or `f32`
+/// when the `f32` feature is enabled.
+#[cfg(not(feature = "f32"))]
+type Float = f64;
 
-/// Digital-to-analog amplitude conversion table for the AY-3-8910. Internally, amplitudes are
-/// represented as 5-bit values. The AY only has 16 amplitude levels. This table therefore contains
-/// quantized values.
-const AY_DAC_TABLE: [f64; 32] = [
-    0.0,             0.0,             0.00999465934234, 0.00999465934234,
-    0.0144502937362, 0.0144502937362, 0.0210574502174,  0.0210574502174,
-    0.0307011520562, 0.0307011520562, 0.0455481803616,  0.0455481803616,
-    0.0644998855573, 0.0644998855573, 0.107362478065,   0.107362478065,
-    0.126588845655,  0.126588845655,  0.20498970016,    0.20498970016,
-    0.292210269322,  0.292210269322,  0.372838941024,   0.372838941024,
-    0.492530708782,  0.492530708782,  0.635324635691,   0.635324635691,
-    0.805584802014,  0.805584802014,  1.0,              1.0
+/// The floating point type that is used for internal processing. This is `f64` by default, or `f32`
+/// when the `f32` feature is enabled.
+#[cfg(feature = "f32")]
+type Float = f32;
+
+/// Convert an internally processed value to double precision.
+#[allow(clippy::unnecessary_cast)]
+#[inline(always)]
+fn widen(value: Float) -> f64 {
+    value as f64
+}
+
+/// The scale of the fixed-point clock phase, which stores the fractional part of the phase in all
+/// 64 bits of an integer.
+const PHASE_SCALE: f64 = 18446744073709551616.0;
+
+/// The maximum difference between consecutive output levels for a frame to be considered silent.
+/// This corresponds to a level of roughly -80 dB.
+const SILENCE_THRESHOLD: f64 = 0.0001;
+
+/// The bits of each register that are used by the PSG.
+const REGISTER_MASKS: [u8; 16] = [
+    0xff, 0x0f, 0xff, 0x0f, 0xff, 0x0f, 0x1f, 0xff,
+    0x1f, 0x1f, 0x1f, 0xff, 0xff, 0x0f, 0xff, 0xff
 ];
 
-/// Digital-to-analog amplitude conversion table for the YM2149, utilizing the full 5-bit dynamic
-/// range. Note that the PSG registers only support setting the amplitude as a 4-bit value, and
-/// that a value of 0 always represents a mute channel. Only the envelope generator uses 5-bit
-/// amplitudes.
-const YM_DAC_TABLE: [f64; 32] = [
-    0.0,             0.0,             0.00465400167849, 0.00772106507973,
-    0.0109559777218, 0.0139620050355, 0.0169985503929,  0.0200198367285,
-    0.024368657969,  0.029694056611,  0.0350652323186,  0.0403906309606,
-    0.0485389486534, 0.0583352407111, 0.0680552376593,  0.0777752346075,
-    0.0925154497597, 0.111085679408,  0.129747463188,   0.148485542077,
-    0.17666895552,   0.211551079576,  0.246387426566,   0.281101701381,
-    0.333730067903,  0.400427252613,  0.467383840696,   0.53443198291,
-    0.635172045472,  0.75800717174,   0.879926756695,   1.0
+/// The bits of each register in the AY8930's second expanded mode register bank that are used by
+/// the PSG. Registers 13 through 15 are shared by both banks.
+const BANK_B_REGISTER_MASKS: [u8; 13] = [
+    0xff, 0xff, 0xff, 0xff, 0x0f, 0x0f, 0x0f, 0x0f,
+    0x0f, 0x00, 0x00, 0x00, 0x00
 ];
 
+/// A callback that receives rendered stereo frames, with the left channel as the first argument
+/// and the right channel as the second. See [`PSG::set_frame_tap`].
+pub type FrameTap = Box<dyn FnMut(f64, f64) + Send>;
+
+/// A callback that receives register writes, with the register number as the first argument, the
+/// written value as the second, and the number of rendered frames as the third. See
+/// [`PSG::set_write_observer`].
+pub type WriteObserver = Box<dyn FnMut(u8, u8, u64) + Send>;
+
 /// An enumeration of the various chip variants supported by the PSG struct.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChipType {
     /// The original General Instrument AY-3-8910.
     AY,
@@ -97,18 +225,225 @@ pub enum ChipType {
     /// The Yamaha YM2149. In all respects identical to the AY-3-8910, except for the envelope
     /// generator, which has double the resolution in its digital-to-analog converter, resulting in
     /// smoother envelopes.
-    YM
+    YM,
+
+    /// The Microchip AY8930, an enhanced AY-3-8910. It starts out in compatibility mode, in which
+    /// it behaves exactly like the AY-3-8910.
+    ///
+    /// Writing a value with the upper bits set to 101 (0xA0 through 0xBF) to the envelope shape
+    /// register (13) switches the chip to expanded mode, while any other value switches it back
+    /// to compatibility mode. In expanded mode, bit 4 of register 13 selects one of two register
+    /// banks for registers 0 through 12. Bank A contains the usual registers, while bank B
+    /// contains the following:
+    ///
+    /// Registers 0 and 1: Channel B envelope period (least and most significant byte) \
+    /// Registers 2 and 3: Channel C envelope period (least and most significant byte) \
+    /// Register 4: Channel B envelope shape \
+    /// Register 5: Channel C envelope shape \
+    /// Registers 6 through 8: Channel A, B, and C duty cycle (see [`DutyCycle`])
+    ///
+    /// The remaining registers of bank B are stored, but have no effect.
+    ///
+    /// In expanded mode the amplitude registers (8 through 10) hold a 5-bit amplitude in bits 0
+    /// through 4, which reaches all 32 output levels, and the envelope enabled flag in bit 5. The
+    /// bits are kept when switching modes, and reinterpreted using the layout of the new mode.
+    ///
+    /// In expanded mode every channel has its own envelope generator. Channel A uses the regular
+    /// envelope generator, which is controlled by registers 11 through 13 of bank A, while
+    /// channels B and C use the generators controlled by bank B. See
+    /// [`PSG::channel_envelope`].
+    AY8930,
+
+    /// The Sunsoft 5B, a YM2149 derivative that is used as an expansion sound chip in NES
+    /// cartridges. Only the tone, noise, and envelope generators are wired out, and its
+    /// digital-to-analog converter has its own logarithmic curve (see [`DacTable::Sunsoft5B`]).
+    ///
+    /// The 5B is clocked at the NES CPU rate, and divides its clock by two internally. To emulate
+    /// it, use the CPU clock rate as the PSG's clock rate, together with
+    /// [`ClockDivider::Div2`]:
+    ///
+    /// ```
+    /// use psg::{ChipType, ClockDivider, PSG};
+    ///
+    /// let mut psg = PSG::with_chip_type(1_789_773.0, 44100, ChipType::Sunsoft5B).unwrap();
+    /// psg.set_clock_divider(ClockDivider::Div2);
+    /// ```
+    ///
+    /// NSF rips access the 5B's registers by writing the register number to the address latch at
+    /// $C000, and the value to the data port at $E000. These writes map directly to
+    /// [`PSG::write_address`] and [`PSG::write_data`]. The registers of the 5B cannot be read
+    /// back, so reading them returns the written values with the unused bits cleared, like on the
+    /// YM2149.
+    Sunsoft5B
 }
 
 impl ChipType {
-    /// Return a reference to the digital-to-analog amplitude conversion table for the current chip
-    /// type.
-    fn log2lin_table(&self) -> &'static [f64; 32] {
+    /// The built-in digital-to-analog amplitude conversion table for the chip type.
+    pub fn dac_table(&self) -> DacTable {
+        match self {
+            ChipType::AY | ChipType::AY8930 => DacTable::AY,
+            ChipType::YM => DacTable::YM,
+            ChipType::Sunsoft5B => DacTable::Sunsoft5B
+        }
+    }
+
+    /// Convert a raw register value into the value that is read back from the chip. The YM2149
+    /// and Sunsoft 5B read back zero for the unused bits, while the AY-3-8910 and AY8930 return
+    /// them as written.
+    fn readback(&self, register: usize, raw: u8) -> u8 {
+        match self {
+            ChipType::AY | ChipType::AY8930 => raw,
+            ChipType::YM | ChipType::Sunsoft5B => raw & REGISTER_MASKS[register]
+        }
+    }
+}
+
+/// The way in which the outputs of the three channels are combined.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MixingMode {
+    /// The channel outputs are simply added together. This is the default, and matches the
+    /// behavior of Ayumi.
+    Linear,
+
+    /// Emulate the non-linear mixing of real hardware, where the three channel outputs share a
+    /// single load resistor. This causes loud chords to be slightly compressed compared to linear
+    /// mixing.
+    ///
+    /// The sum of the channel levels `s` is compressed as `s * (1 + c) / (1 + c * s)`, where `c`
+    /// is the compression factor. This leaves a single channel at full volume unchanged. A
+    /// compression factor of 0.25 is a reasonable approximation of typical hardware, which
+    /// results in three channels at full volume producing about 2.1 times the level of a single
+    /// channel.
+    Analog {
+        /// The compression factor. A value of 0.0 is equivalent to linear mixing.
+        compression: f64
+    }
+}
+
+impl MixingMode {
+    /// Return the factor by which each channel's level should be scaled, given the sum of all
+    /// channel levels.
+    fn scale(&self, sum: Float) -> Float {
+        match *self {
+            MixingMode::Linear => 1.0,
+            MixingMode::Analog { compression } => {
+                let compression = compression as Float;
+                (1.0 + compression) / (1.0 + compression * sum)
+            }
+        }
+    }
+}
+
+/// The divider that is applied to the chip's clock before it is used, which is selected by the
+/// YM2149's SEL pin. See [`PSG::set_clock_divider`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClockDivider {
+    /// The clock is used as is. This is the default, and the only option on the AY-3-8910.
+    Div1,
+
+    /// The clock is divided by two, which is selected by pulling the YM2149's SEL pin low.
+    Div2
+}
+
+impl ClockDivider {
+    /// The amount by which the clock is divided.
+    pub fn divisor(self) -> u32 {
         match self {
-            ChipType::AY => &AY_DAC_TABLE,
-            ChipType::YM => &YM_DAC_TABLE
+            ClockDivider::Div1 => 1,
+            ClockDivider::Div2 => 2
         }
     }
+
+    /// Divide a clock rate by this divider, which yields the rate that should be passed to the
+    /// helpers in the [`math`] module.
+    pub fn divide(self, clock_rate: f64) -> f64 {
+        clock_rate / self.divisor() as f64
+    }
+}
+
+/// The way in which register addresses above 15 are handled by
+/// [`PSG::write_address`](PSG::write_address).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AddressDecoding {
+    /// Only the low nibble of the address is latched, so addresses above 15 select one of the
+    /// sixteen registers. This is the default, and matches chips that do not decode the upper
+    /// address bits.
+    LowNibble,
+
+    /// Addresses above 15 are latched as is, which deselects all registers. Data writes are then
+    /// ignored and data reads return zero until a valid address is latched.
+    Full
+}
+
+/// Common ways of panning the three channels, for use with
+/// [`PSG::set_stereo_mode`](PSG::set_stereo_mode).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StereoMode {
+    /// All channels are centered. This is the default, and matches machines with a mono output
+    /// such as the MSX and the ZX Spectrum 128K.
+    Mono,
+
+    /// Channel A is panned left, channel B is centered, and channel C is panned right. This is
+    /// the layout of the Amstrad CPC's stereo output, and of many ZX Spectrum stereo interfaces.
+    Abc,
+
+    /// Channel A is panned left, channel C is centered, and channel B is panned right. This is an
+    /// alternative layout that is supported by many ZX Spectrum stereo interfaces and emulators.
+    Acb
+}
+
+impl StereoMode {
+    /// Return the balance of each channel, between 0.0 (full left) and 1.0 (full right).
+    fn balances(&self) -> [f64; 3] {
+        match self {
+            StereoMode::Mono => [0.5, 0.5, 0.5],
+            StereoMode::Abc => [0.0, 0.5, 1.0],
+            StereoMode::Acb => [0.0, 1.0, 0.5]
+        }
+    }
+}
+
+/// The shape of the gain curve of a fade out, see [`PSG::begin_fade_out`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FadeCurve {
+    /// The gain falls linearly from 1.0 to 0.0. This is the default.
+    Linear,
+
+    /// The gain follows the square of the linear curve. It falls quickly at first and slowly
+    /// towards the end, which is perceived as a more even fade.
+    Quadratic
+}
+
+impl FadeCurve {
+    /// Return the gain at the specified position in a fade, which runs from 1.0 at the start to
+    /// 0.0 at the end.
+    fn gain(self, position: f64) -> f64 {
+        match self {
+            FadeCurve::Linear => position,
+            FadeCurve::Quadratic => position * position
+        }
+    }
+}
+
+/// The progress of a fade out, as returned by [`PSG::fade_state`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FadeState {
+    /// No fade out is in progress, so the output is at full volume.
+    Inactive,
+
+    /// A fade out is in progress.
+    FadingOut {
+        /// The number of frames until the output is silent.
+        remaining_frames: u64
+    },
+
+    /// The fade out has finished, and the output stays silent until the fade is cancelled.
+    Finished
 }
 
 /// The programmable sound generator (PSG). This struct is the workhorse of the crate and
@@ -139,279 +474,4965 @@ impl ChipType {
 /// to create timbres that vastly differ from the usual square wave and noise sounds. The effect
 /// works best when using the YM2149 chip type, as it has double the dynamic range in the envelope
 /// generator.
+///
+/// When the `serde` feature is enabled, the PSG's complete state can be serialized for save states.
+/// Rendering after deserializing produces output that is identical to the original instance. The
+/// installed callbacks (frame taps, the write observer, and the GPIO port devices) are not part of
+/// the state, and need to be installed again after deserializing.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PSG {
     channels: [Channel; 3],
     noise_generator: NoiseGenerator,
     envelope_generator: EnvelopeGenerator,
 
-    log2lin_table: &'static [f64; 32],
+    // The AY8930's expanded mode envelope generators of channels B and C
+    channel_envelopes: [EnvelopeGenerator; 2],
+
+    // GPIO direction bits of the mixer register, GPIO port values, and the connected devices
+    io_direction: u8,
+    io_ports: [u8; 2],
+    #[cfg_attr(feature = "serde", serde(skip))]
+    ports: Option<Box<dyn PsgPorts + Send>>,
+
+    // The on-chip register file, see raw_register for how it is kept in sync, and the AY8930's
+    // second expanded mode register bank
+    register_file: [u8; 16],
+    bank_b_register_file: [u8; 13],
+
+    // Bus interface
+    address: u8,
+    address_decoding: AddressDecoding,
+
+    chip_type: ChipType,
+    log2lin_table: Box<[Float; 32]>,
+    mixing_mode: MixingMode,
+    hardware_counters: bool,
+    envelope_smoothing: bool,
+
+    // The master prescaler that clocks the noise generator when enabled, see set_shared_prescaler
+    shared_prescaler: bool,
+    prescaler: u8,
 
-    // Clock signal
-    x: f64,
-    step: f64,
+    // Clock signal, as fixed-point fractions scaled by PHASE_SCALE
+    clock_rate: f64,
+    clock_divider: ClockDivider,
+    phase: u64,
+    step: u64,
+    sample_rate: u32,
+
+    // Ayumi compatibility mode, which accumulates the clock phase in floating point instead,
+    // using the step exactly as Ayumi computes it
+    ayumi_compatible: bool,
+    ayumi_phase: f64,
+    ayumi_step: f64,
+
+    // Counters
+    frames_rendered: u64,
+    ticks_elapsed: u64,
+
+    // Register writes that are scheduled for a specific tick, and the tick of the first one
+    scheduled_writes: VecDeque<(u64, u8, u8)>,
+    next_write_tick: u64,
+
+    // Register write recording and observation
+    recording: Option<Vec<RegisterEvent>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    write_observer: Option<WriteObserver>,
+
+    // Silence detection
+    silence_reference: (f64, f64),
+    silence_frames: u64,
+
+    // Output filter
+    output_filter: OutputFilter,
+    output_filter_chain: OutputFilterChain,
+
+    // Stereo image
+    stereo_width: f64,
+    channels_swapped: bool,
+
+    // Fade out, as the total and remaining number of frames
+    fade_curve: FadeCurve,
+    fade: Option<(u64, u64)>,
+
+    // Channel monitoring, which only affects the final mix, and the resulting audible channels
+    channels_muted: [bool; 3],
+    channels_soloed: [bool; 3],
+    channels_audible: [bool; 3],
+
+    // Metering
+    metering_enabled: bool,
+    meter: MeterAccumulator,
+
+    // Frame taps
+    #[cfg_attr(feature = "serde", serde(skip))]
+    frame_tap: Option<FrameTap>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    raw_frame_tap: Option<FrameTap>,
 
     // Interpolators
     left_interpolator: Interpolator,
     right_interpolator: Interpolator,
 
-    // Decimators (anti-alias filters)
-    left_decimator: Decimator,
-    right_decimator: Decimator,
-    decimator_index: usize,
+    // Decimators (anti-alias filters), boxed to keep the struct small
+    left_decimator: Box<Decimator>,
+    right_decimator: Box<Decimator>,
+
+    // Additional half-band decimation stages for low sample rates
+    left_half_bands: Box<[HalfBandFilter; MAX_HALF_BAND_STAGES]>,
+    right_half_bands: Box<[HalfBandFilter; MAX_HALF_BAND_STAGES]>,
+    half_band_stages: usize,
 
     // DC filter
-    dc_filter: DCFilter
+    dc_filter: DCFilter,
+    one_pole_filter: OnePoleFilter,
+    dc_filter_mode: DCFilterMode,
+    dc_filter_enabled: bool
 }
 
 impl PSG {
     /// Initialize a new PSG struct using the specified clock and sample rates.
     ///
-    /// There is an upper bound to the clock rate that can be used for a given sample rate. This
-    /// upper limit can be computed by multiplying the sample rate by 128. Providing a clock rate
-    /// higher than this will return an error. For a 44100 Hz sample rate the highest supported
-    /// clock rate is 5.6448 MHz, well above the most popular PSG clock rates.
+    /// Internally the PSG is rendered at 8 times the sample rate, and the chip's clock is divided
+    /// by 8 to produce ticks, which limits the clock rate that can be used for a given sample rate
+    /// to 64 times the sample rate. When the clock rate exceeds this limit (e.g. when using low
+    /// sample rates) up to four additional half-band decimation stages are inserted, each doubling
+    /// the internal rendering rate. The absolute upper limit of the clock rate can therefore be
+    /// computed by multiplying the sample rate by 1024. Providing a clock rate at or above this
+    /// limit will return an error. For an 8000 Hz sample rate the highest supported clock rate is
+    /// just below 8.192 MHz, well above the most popular PSG clock rates. A clock rate that is not
+    /// a finite number above zero returns [`Error::InvalidClockRate`].
     ///
     /// By default the PSG is configured to emulate a Yamaha YM2149, but this can be changed
-    /// afterwards by calling [`set_chip_type`](Self::set_chip_type).
+    /// afterwards by calling [`set_chip_type`](Self::set_chip_type). Use [`PsgBuilder`] to
+    /// configure all options at construction time.
     pub fn new(clock_rate: f64, sample_rate: u32) -> Result<Self, Error> {
-        // First compute the step value to determine if it is within bounds
-        let step = clock_rate / (sample_rate as f64 * 8.0 * DECIMATE_FACTOR as f64);
+        Self::with_dc_filter_length(clock_rate, sample_rate, DEFAULT_DC_FILTER_LENGTH)
+    }
 
-        if step >= 1.0 {
-            return Err(Error::ClockRateTooHigh);
+    /// Initialize a new PSG struct using the specified clock and sample rates, emulating the
+    /// specified chip type.
+    ///
+    /// See [`new`](Self::new) for the constraints on the clock and sample rates.
+    pub fn with_chip_type(clock_rate: f64, sample_rate: u32, chip_type: ChipType) -> Result<Self, Error> {
+        let mut psg = Self::new(clock_rate, sample_rate)?;
+        psg.set_chip_type(chip_type);
+        Ok(psg)
+    }
+
+    /// Initialize a new PSG struct using the specified clock and sample rates, and the specified
+    /// DC filter length.
+    ///
+    /// The DC filter removes the DC offset from the output signal by subtracting the moving
+    /// average of the last `dc_filter_length` frames. Shorter lengths raise the filter's cutoff
+    /// frequency, which may be useful for low sample rates, while longer lengths keep the cutoff
+    /// frequency low at high sample rates such as 192 kHz. The length must be a power of two, or
+    /// an error is returned. The default length used by [`new`](Self::new) is 1024 frames.
+    ///
+    /// See [`new`](Self::new) for the constraints on the clock and sample rates.
+    pub fn with_dc_filter_length(clock_rate: f64, sample_rate: u32, dc_filter_length: usize) -> Result<Self, Error> {
+        if !dc_filter_length.is_power_of_two() {
+            return Err(Error::InvalidDCFilterLength(dc_filter_length));
         }
 
+        let (step, ayumi_step, half_band_stages) = Self::clock_step(clock_rate, sample_rate, ClockDivider::Div1)?;
+
         Ok(Self {
-            channels: [Channel::new(), Channel::new(), Channel::new()],
+            channels: core::array::from_fn(|_| Channel::new(DacTable::YM.table())),
             noise_generator: NoiseGenerator::new(),
             envelope_generator: EnvelopeGenerator::new(),
 
-            log2lin_table: ChipType::YM.log2lin_table(),
+            channel_envelopes: core::array::from_fn(|_| EnvelopeGenerator::new()),
+
+            io_direction: 0,
+            io_ports: [0; 2],
+            ports: None,
+
+            register_file: [0; 16],
+            bank_b_register_file: [0; 13],
+
+            address: 0,
+            address_decoding: AddressDecoding::LowNibble,
 
-            x: 0.0,
+            chip_type: ChipType::YM,
+            log2lin_table: Box::new(*DacTable::YM.table()),
+            mixing_mode: MixingMode::Linear,
+            hardware_counters: false,
+            envelope_smoothing: false,
+
+            shared_prescaler: false,
+            prescaler: 0,
+
+            clock_rate,
+            clock_divider: ClockDivider::Div1,
+            phase: 0,
             step,
+            sample_rate,
+
+            ayumi_compatible: false,
+            ayumi_phase: 0.0,
+            ayumi_step,
+
+            frames_rendered: 0,
+            ticks_elapsed: 0,
+
+            scheduled_writes: VecDeque::new(),
+            next_write_tick: u64::MAX,
+
+            recording: None,
+            write_observer: None,
+
+            silence_reference: (0.0, 0.0),
+            silence_frames: 0,
+
+            output_filter: OutputFilter::None,
+            output_filter_chain: OutputFilterChain::new(OutputFilter::None, sample_rate),
+
+            stereo_width: 1.0,
+            channels_swapped: false,
+
+            fade_curve: FadeCurve::Linear,
+            fade: None,
+
+            channels_muted: [false; 3],
+            channels_soloed: [false; 3],
+            channels_audible: [true; 3],
+
+            metering_enabled: false,
+            meter: MeterAccumulator::new(),
+
+            frame_tap: None,
+            raw_frame_tap: None,
 
             left_interpolator: Interpolator::new(),
             right_interpolator: Interpolator::new(),
 
-            left_decimator: Decimator::new(),
-            right_decimator: Decimator::new(),
-            decimator_index: 0,
+            left_decimator: Box::new(Decimator::new()),
+            right_decimator: Box::new(Decimator::new()),
+
+            left_half_bands: Box::new(core::array::from_fn(|_| HalfBandFilter::new())),
+            right_half_bands: Box::new(core::array::from_fn(|_| HalfBandFilter::new())),
+            half_band_stages,
 
-            dc_filter: DCFilter::new()
+            dc_filter: DCFilter::new(dc_filter_length),
+            one_pole_filter: OnePoleFilter::new(0.0, sample_rate),
+            dc_filter_mode: DCFilterMode::MovingAverage,
+            dc_filter_enabled: true
         })
     }
 
-    /// Set the PSG chip type to the specified type.
+    /// Compute the fixed-point clock step, the floating point step that is used in Ayumi
+    /// compatibility mode, and the number of half-band decimation stages for the specified clock
+    /// and sample rates, or return an error when the clock rate is invalid or too high.
     ///
-    /// This only affects the envelope generator resolution, which is higher for the Yamaha YM2149.
-    pub fn set_chip_type(&mut self, chip_type: ChipType) {
-        self.log2lin_table = chip_type.log2lin_table();
-    }
+    /// The number of stages only depends on the undivided clock rate, so that changing the clock
+    /// divider never changes the filters.
+    fn clock_step(clock_rate: f64, sample_rate: u32, divider: ClockDivider) -> Result<(u64, f64, usize), Error> {
+        // A step of zero would never advance the PSG, so rendering until a cycle would not end
+        if !clock_rate.is_finite() || clock_rate <= 0.0 {
+            return Err(Error::InvalidClockRate(clock_rate));
+        }
 
-    /// Render the next PSG clock tick.
-    ///
-    /// Returns a tuple containing the left channel as the first element and the right channel as
-    /// the second.
-    fn render_tick(&mut self) -> (f64, f64) {
-        let noise = self.noise_generator.render();
-        let envelope = self.envelope_generator.render();
+        // First compute the step value to determine if it is within bounds
+        let mut step = clock_rate / (sample_rate as f64 * 8.0 * DECIMATE_FACTOR as f64);
 
-        self.channels.iter_mut().fold((0.0, 0.0), |(left, right), channel| {
-            let mut level = (channel.render() | channel.tone_off as u8) & (noise | channel.noise_off as u8);
+        // Double the internal rate until the step fits, adding a half-band stage each time
+        let mut half_band_stages = 0;
 
-            level *= if channel.envelope_on {
-                envelope
-            } else {
-                channel.amplitude * 2 + 1
-            };
+        while step >= 1.0 && half_band_stages < MAX_HALF_BAND_STAGES {
+            step /= 2.0;
+            half_band_stages += 1;
+        }
 
-            let amplitude = self.log2lin_table[level as usize];
+        if step >= 1.0 {
+            let max = sample_rate as f64 * 8.0 * DECIMATE_FACTOR as f64 * (1 << MAX_HALF_BAND_STAGES) as f64;
+            return Err(Error::ClockRateTooHigh { clock_rate, max });
+        }
 
-            (left + amplitude * channel.pan_left, right + amplitude * channel.pan_right)
-        })
-    }
+        let step = divider.divide(step);
 
-    /// Render the next frame.
-    ///
-    /// Returns a tuple containing the left channel as the first element and the right channel as
-    /// the second.
-    pub fn render(&mut self) -> (f64, f64) {
-        let decimator_start = FIR_SIZE - self.decimator_index * DECIMATE_FACTOR;
+        Ok(((step * PHASE_SCALE) as u64, step, half_band_stages))
+    }
 
-        // modulo 23
-        self.decimator_index = (self.decimator_index + 1) % (FIR_SIZE / DECIMATE_FACTOR - 1);
+    /// Whether Ayumi compatibility mode is enabled.
+    pub fn ayumi_compatible(&self) -> bool {
+        self.ayumi_compatible
+    }
 
-        // Fill decimator buffers in reverse
-        // TODO: Since the filter is symmetrical, does this matter?
-        for offset in (0..DECIMATE_FACTOR).rev() {
-            self.x += self.step;
+    /// Enable or disable Ayumi compatibility mode, which reproduces the exact output of the
+    /// original Ayumi implementation, quirks included. This is useful for verifying the output
+    /// against reference recordings made with Ayumi. Compatibility mode is disabled by default.
+    ///
+    /// The following behaviors differ between the modes:
+    ///
+    ///  - A noise period register of zero behaves like a period of 1, like on the real chip.
+    ///    Ayumi instead updates the noise generator on every tick, which doubles the noise
+    ///    frequency.
+    ///  - The clock phase is accumulated in fixed-point, so that no rounding errors build up.
+    ///    Ayumi accumulates it in double precision floating point, which causes the output to
+    ///    differ by around 1e-12.
+    ///
+    /// Note that Ayumi starts out with a noise period of zero, while the PSG starts with a noise
+    /// period of 1, so write all registers before rendering to reproduce Ayumi's output from the
+    /// start. The output is only bit-identical when neither the `simd` nor the `f32` feature is
+    /// enabled, and when the clock rate is low enough for the sample rate that no additional
+    /// half-band decimation stages are needed (see [`new`](Self::new)), as Ayumi does not support
+    /// these.
+    pub fn set_ayumi_compatible(&mut self, enabled: bool) {
+        if enabled && !self.ayumi_compatible {
+            self.ayumi_phase = self.phase as f64 / PHASE_SCALE;
+        } else if !enabled && self.ayumi_compatible {
+            self.phase = (self.ayumi_phase * PHASE_SCALE) as u64;
+        }
 
-            if self.x >= 1.0 {
-                self.x -= 1.0;
+        self.ayumi_compatible = enabled;
+        self.noise_generator.set_ayumi_compatible(enabled);
+    }
 
-                let (left, right) = self.render_tick();
+    /// Whether the tone oscillators use hardware counter mode.
+    pub fn hardware_counters(&self) -> bool {
+        self.hardware_counters
+    }
 
-                self.left_interpolator.feed(left);
-                self.right_interpolator.feed(right);
-            }
+    /// Enable or disable hardware counter mode for the tone oscillators. The mode is disabled by
+    /// default.
+    ///
+    /// By default, a tone oscillator flips its output as soon as its counter reaches or exceeds
+    /// the tone period. Lowering the period below the current counter value therefore flips the
+    /// output on the very next tick. In hardware counter mode, the counter is only reset when it
+    /// exactly matches the period, like on the real chip. When the period is lowered below the
+    /// current counter value, the counter first runs through the full 12-bit range and wraps
+    /// around before the new period takes effect, which lengthens the current half-period by up
+    /// to 4096 ticks. Some music engines exploit this for detune effects.
+    pub fn set_hardware_counters(&mut self, enabled: bool) {
+        self.hardware_counters = enabled;
 
-            self.left_decimator.buffer[decimator_start + offset] = self.left_interpolator.interpolate(self.x);
-            self.right_decimator.buffer[decimator_start + offset] = self.right_interpolator.interpolate(self.x);
+        for channel in &mut self.channels {
+            channel.set_hardware_counter(enabled);
         }
+    }
 
-        self.dc_filter.render(
-            self.left_decimator.render(decimator_start),
-            self.right_decimator.render(decimator_start)
-        )
+    /// Whether envelope smoothing is enabled.
+    pub fn envelope_smoothing(&self) -> bool {
+        self.envelope_smoothing
     }
 
-    /// Return a reference to the specified channel number's [`Channel`] struct.
+    /// Enable or disable envelope smoothing, which is disabled by default.
     ///
-    /// The channel number must be smaller than 3.
-    pub fn channel(&self, index: u8) -> &Channel {
-        &self.channels[index as usize]
+    /// Even with the 32 levels of the YM2149, slow envelopes produce audible steps ("zipper
+    /// noise"), especially at high levels where the steps are large. With smoothing enabled, the
+    /// amplitude of a channel that uses the envelope is linearly interpolated between the current
+    /// and the next envelope level based on the position within the envelope period, which
+    /// results in continuous fades. The envelope generator itself and its registers keep their
+    /// 5-bit behavior.
+    ///
+    /// Note that this is not how the real chip behaves. Since consecutive levels of the AY-3-8910's
+    /// table are equal in pairs, smoothing is most effective with the YM2149's table.
+    pub fn set_envelope_smoothing(&mut self, enabled: bool) {
+        self.envelope_smoothing = enabled;
     }
 
-    /// Return a mutable reference to the specified channel number's [`Channel`] struct.
-    ///
-    /// The channel number must be smaller than 3.
-    pub fn channel_mut(&mut self, index: u8) -> &mut Channel {
-        &mut self.channels[index as usize]
+    /// Whether the noise generator is clocked by the shared prescaler.
+    pub fn shared_prescaler(&self) -> bool {
+        self.shared_prescaler
     }
 
-    /// Return a reference to the PSG's noise generator.
-    pub fn noise_generator(&self) -> &NoiseGenerator {
-        &self.noise_generator
+    /// Enable or disable clocking the noise generator by a master prescaler that is shared with
+    /// the tone and envelope generators. The shared prescaler is disabled by default, which
+    /// matches Ayumi.
+    ///
+    /// On the real chip, all generators are clocked by the same prescaler, which divides the clock
+    /// by 8 for the tone and envelope generators, and by another 2 for the noise generator. By
+    /// default the noise generator instead counts ticks using its own counter, so the phase
+    /// between tone edges and noise updates is arbitrary. With the shared prescaler, the noise
+    /// generator only advances on the prescaler's rising edges, which occur on every other tick
+    /// and keep the same phase relationship to the tone and envelope generators as on the real
+    /// chip.
+    ///
+    /// After a reset, the first rising edge occurs on the first tick, so the noise generator's
+    /// shift register is first updated on tick `2 * period - 1`, one tick earlier than without
+    /// the shared prescaler. The prescaler's phase is part of the saved state.
+    pub fn set_shared_prescaler(&mut self, enabled: bool) {
+        if enabled != self.shared_prescaler {
+            // The noise counter counts prescaler edges instead of ticks when the prescaler is used
+            let counter = self.noise_generator.counter();
+
+            self.noise_generator.set_counter(if enabled { counter >> 1 } else { counter << 1 });
+        }
+
+        self.shared_prescaler = enabled;
     }
 
-    /// Return a mutable reference to the PSG's noise generator.
-    pub fn noise_generator_mut(&mut self) -> &mut NoiseGenerator {
-        &mut self.noise_generator
+    /// The chip's clock rate in Hz, before the clock divider is applied.
+    pub fn clock_rate(&self) -> f64 {
+        self.clock_rate
     }
 
-    /// Return a reference to the PSG's envelope generator.
-    pub fn envelope_generator(&self) -> &EnvelopeGenerator {
-        &self.envelope_generator
+    /// The clock rate in Hz after the clock divider is applied, which determines the pitch of the
+    /// generated sound. Pass this to the helpers in the [`math`] module when converting between
+    /// periods and frequencies.
+    pub fn effective_clock_rate(&self) -> f64 {
+        self.clock_divider.divide(self.clock_rate)
     }
 
-    /// Return a mutable reference to the PSG's envelope generator.
-    pub fn envelope_generator_mut(&mut self) -> &mut EnvelopeGenerator {
-        &mut self.envelope_generator
+    /// The divider that is applied to the chip's clock.
+    pub fn clock_divider(&self) -> ClockDivider {
+        self.clock_divider
     }
 
-    /// Set a channel's tone period to a value between 1 and 4095 inclusive.
+    /// Set the divider that is applied to the chip's clock, which emulates the YM2149's SEL pin.
     ///
-    /// Smaller values are set to 1, larger values are wrapped. The channel number must be smaller
-    /// than 3.
-    pub fn set_tone_period(&mut self, channel: u8, period: u16) {
-        self.channels[channel as usize].set_period(period);
+    /// Dividing the clock by two lowers the frequencies of all tones, noise, and envelopes by an
+    /// octave, while [`clock_rate`](Self::clock_rate) keeps reporting the undivided rate (see
+    /// [`effective_clock_rate`](Self::effective_clock_rate)). The change takes effect with the
+    /// next tick, and the oscillators and filters keep their state, so switching the divider while
+    /// rendering does not cause any glitches. Note that the cycles that are used for scheduling
+    /// register writes are cycles of the divided clock. The default is [`ClockDivider::Div1`].
+    pub fn set_clock_divider(&mut self, divider: ClockDivider) {
+        // The number of half-band stages only depends on the undivided clock rate, which is known
+        // to be supported
+        let (step, ayumi_step, _) = Self::clock_step(self.clock_rate, self.sample_rate, divider).unwrap();
+
+        self.clock_divider = divider;
+        self.step = step;
+        self.ayumi_step = ayumi_step;
     }
 
-    /// Set a channel's amplitude to a value between 0 and 15 inclusive.
+    /// Change the chip's clock rate, for example to emulate a machine with a switchable CPU
+    /// speed.
     ///
-    /// Larger values are wrapped. The channel number must be smaller than 3.
-    pub fn set_amplitude(&mut self, channel: u8, amplitude: u8) {
-        self.channels[channel as usize].set_amplitude(amplitude);
+    /// The oscillators and filters keep their state, so the pitch of the sound changes without
+    /// interruption. Returns an error when the clock rate is invalid or too high for the sample
+    /// rate (see [`new`](Self::new)), in which case the clock rate is left unchanged.
+    pub fn set_clock_rate(&mut self, clock_rate: f64) -> Result<(), Error> {
+        let (step, ayumi_step, half_band_stages) = Self::clock_step(clock_rate, self.sample_rate, self.clock_divider)?;
+
+        self.clock_rate = clock_rate;
+        self.step = step;
+        self.ayumi_step = ayumi_step;
+        self.half_band_stages = half_band_stages;
+
+        Ok(())
     }
 
-    /// Set a channel's tone disable flag.
-    ///
-    /// The channel number must be smaller than 3.
-    pub fn set_tone_disabled(&mut self, channel: u8, disabled: bool) {
-        self.channels[channel as usize].set_tone_disabled(disabled);
+    /// The output sample rate in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
     }
 
-    /// Set a channel's noise disable flag.
+    /// Change the output sample rate, for example when an audio backend renegotiates the device's
+    /// sample rate.
     ///
-    /// The channel number must be smaller than 3.
-    pub fn set_noise_disabled(&mut self, channel: u8, disabled: bool) {
-        self.channels[channel as usize].set_noise_disabled(disabled);
+    /// The registers and the state of the tone, noise, and envelope generators are kept, but the
+    /// resampling and output filters are rebuilt for the new rate and start out empty. Call
+    /// [`prime`](Self::prime) afterwards to avoid a click. Returns an error when the clock rate is
+    /// too high for the new sample rate (see [`new`](Self::new)), in which case the PSG is left
+    /// unchanged.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) -> Result<(), Error> {
+        let (step, ayumi_step, half_band_stages) = Self::clock_step(self.clock_rate, sample_rate, self.clock_divider)?;
+
+        self.sample_rate = sample_rate;
+        self.step = step;
+        self.ayumi_step = ayumi_step;
+        self.half_band_stages = half_band_stages;
+
+        let mode = self.interpolation_mode();
+        self.left_interpolator = Interpolator::new();
+        self.right_interpolator = Interpolator::new();
+        self.set_interpolation_mode(mode);
+
+        *self.left_decimator = Decimator::new();
+        *self.right_decimator = Decimator::new();
+        *self.left_half_bands = core::array::from_fn(|_| HalfBandFilter::new());
+        *self.right_half_bands = core::array::from_fn(|_| HalfBandFilter::new());
+
+        self.dc_filter = DCFilter::new(self.dc_filter.size());
+        self.one_pole_filter = OnePoleFilter::new(0.0, sample_rate);
+        self.set_dc_filter_mode(self.dc_filter_mode);
+        self.set_output_filter(self.output_filter);
+
+        Ok(())
     }
 
-    /// Set a channel's envelope enable flag.
+    /// Set the PSG chip type to the specified type.
+    ///
+    /// This affects the envelope generator resolution, which is higher for the Yamaha YM2149, and
+    /// the values of unused register bits when reading back registers using
+    /// [`register`](Self::register). Switching to the AY8930 enables expanded mode when the value
+    /// of register 13 selects it.
     ///
-    /// The channel number must be smaller than 3.
-    pub fn set_envelope_enabled(&mut self, channel: u8, enabled: bool) {
-        self.channels[channel as usize].set_envelope_enabled(enabled);
+    /// The digital-to-analog conversion table is replaced by the built-in table for the chip type,
+    /// so a custom table needs to be set again afterwards. See
+    /// [`set_dac_table`](Self::set_dac_table).
+    pub fn set_chip_type(&mut self, chip_type: ChipType) {
+        self.chip_type = chip_type;
+        self.apply_dac_table(*chip_type.dac_table().table());
+        self.update_expanded_mode();
     }
 
-    /// Set the noise generator's period to a value between 1 and 31 inclusive.
-    ///
-    /// Smaller values are set to 1, larger values are wrapped.
-    pub fn set_noise_period(&mut self, period: u8) {
-        self.noise_generator.set_period(period);
+    /// The amplitudes of the digital-to-analog conversion table, which converts the 5-bit output
+    /// level of each channel into its amplitude.
+    pub fn dac_table(&self) -> [f64; 32] {
+        self.log2lin_table.map(widen)
     }
 
-    /// Set the PSG's mixer register value.
-    ///
-    /// The mixer value is an 8-bit number consisting of the following bits:
+    /// Replace the digital-to-analog conversion table, for example with the amplitudes measured
+    /// on a real chip.
     ///
-    /// Bit 0: Channel A tone enable (0 to enable, 1 to disable) \
-    /// Bit 1: Channel B tone enable (0 to enable, 1 to disable) \
-    /// Bit 2: Channel C tone enable (0 to enable, 1 to disable) \
-    /// Bit 3: Channel A noise enable (0 to enable, 1 to disable) \
-    /// Bit 4: Channel B noise enable (0 to enable, 1 to disable) \
-    /// Bit 5: Channel C noise enable (0 to enable, 1 to disable) \
-    /// Bit 6: GPIO In/out A toggle (ignored in this implementation) \
-    /// Bit 7: GPIO In/out B toggle (ignored in this implementation)
-    pub fn set_mixer(&mut self, mixer: u8) {
-        self.channels[0].set_tone_disabled(mixer & 0x01 != 0);
-        self.channels[1].set_tone_disabled(mixer & 0x02 != 0);
-        self.channels[2].set_tone_disabled(mixer & 0x04 != 0);
-        self.channels[0].set_noise_disabled(mixer & 0x08 != 0);
-        self.channels[1].set_noise_disabled(mixer & 0x10 != 0);
-        self.channels[2].set_noise_disabled(mixer & 0x20 != 0);
+    /// The table contains the amplitudes of all 32 output levels, where the fixed amplitudes 0
+    /// through 15 of the amplitude registers map to the odd levels, and the envelope generator uses
+    /// all levels, like the 5-bit amplitudes of the AY8930's expanded mode. Every amplitude must be
+    /// finite, lie between 0 and 1 inclusive, and not be lower than the amplitude of the previous
+    /// level, otherwise an error is returned containing the index of the first invalid amplitude.
+    /// The built-in tables are available through [`DacTable`], including variants that emulate the
+    /// DC level of a channel at amplitude 0 using [`DacTable::biased_levels`]. Setting the chip
+    /// type selects its built-in table again.
+    pub fn set_dac_table(&mut self, levels: &[f64; 32]) -> Result<(), Error> {
+        self.apply_dac_table(dac_table::validate(levels)?);
+        Ok(())
+    }
+
+    /// Use the provided digital-to-analog conversion table for the PSG and its channels.
+    fn apply_dac_table(&mut self, table: [Float; 32]) {
+        *self.log2lin_table = table;
 
-        // Note: the GPIO bits are ignored
+        for channel in &mut self.channels {
+            channel.set_dac_table(&self.log2lin_table);
+        }
     }
 
-    /// Set the envelope generator period to a value between 1 and 65535 inclusive.
+    /// Whether the PSG emulates an AY8930 in expanded mode. See [`ChipType::AY8930`].
+    pub fn expanded_mode(&self) -> bool {
+        self.chip_type == ChipType::AY8930 && self.register_file[13] & 0xe0 == 0xa0
+    }
+
+    /// Apply the duty cycles and 5-bit amplitudes of the channels only while the chip is in
+    /// expanded mode.
     ///
-    /// Lower values are set to 1.
-    pub fn set_envelope_period(&mut self, period: u16) {
-        self.envelope_generator.set_period(period);
+    /// Like on the real chip, the amplitude registers keep their bits when the mode changes, and
+    /// the bits are reinterpreted using the register layout of the new mode.
+    fn update_expanded_mode(&mut self) {
+        let enabled = self.expanded_mode();
+
+        for index in 0..3 {
+            if self.channels[index].expanded_amplitude() != enabled {
+                let value = self.bank_a_raw_register(index as u8 + 8);
+
+                self.channels[index].set_expanded_amplitude(enabled);
+                self.channels[index].set_amplitude_and_envelope_enabled(value);
+            }
+        }
+
+        for channel in &mut self.channels {
+            channel.set_duty_cycle_enabled(enabled);
+        }
     }
 
-    /// Set shape to a value between 0 and 15 inclusive.
+    /// Whether the specified register number refers to the AY8930's second expanded mode register
+    /// bank, which is the case for registers 0 through 12 when bank B is selected.
+    fn is_bank_b_register(&self, register: u8) -> bool {
+        register < 13 && self.expanded_mode() && self.register_file[13] & 0x10 != 0
+    }
+
+    /// The PSG chip type.
+    pub fn chip_type(&self) -> ChipType {
+        self.chip_type
+    }
+
+    /// The way in which the outputs of the three channels are combined.
+    pub fn mixing_mode(&self) -> MixingMode {
+        self.mixing_mode
+    }
+
+    /// Set the way in which the outputs of the three channels are combined.
     ///
-    /// Higher values are wrapped.
-    pub fn set_envelope_shape(&mut self, shape: u8) {
-        self.envelope_generator.set_shape(shape);
+    /// The default is [`MixingMode::Linear`], which keeps the output sample-accurate compared to
+    /// Ayumi. See [`MixingMode::Analog`] for emulating the non-linear mixing of real hardware.
+    pub fn set_mixing_mode(&mut self, mode: MixingMode) {
+        self.mixing_mode = mode;
     }
 
-    /// Set a PSG register to the provided value.
+    /// The algorithm that is used to interpolate the output of the chip to the internal sample rate.
+    pub fn interpolation_mode(&self) -> InterpolationMode {
+        self.left_interpolator.mode()
+    }
+
+    /// Set the algorithm that is used to interpolate the output of the chip to the internal sample
+    /// rate.
     ///
-    /// This function is particularly useful when writing emulators, as it provides a convenient
-    /// API to the PSG's address/data ports that is easy to map from machine code.
+    /// The default is [`InterpolationMode::Parabolic`], which keeps the output sample-accurate
+    /// compared to Ayumi. [`InterpolationMode::BSpline`] reduces aliasing of high tones, and
+    /// delays the output by a single tick.
+    pub fn set_interpolation_mode(&mut self, mode: InterpolationMode) {
+        self.left_interpolator.set_mode(mode);
+        self.right_interpolator.set_mode(mode);
+    }
+
+    /// Render the next PSG clock tick.
     ///
-    /// For an exact specification of the register numbers and their accepted values, please refer
-    /// to the AY-3-8910 or YM2149 datasheets. Note that the AY-3-8910 datasheet uses octal numbers
-    /// when referring to register numbers.
+    /// Returns a tuple containing the left channel as the first element and the right channel as
+    /// the second.
+    fn render_tick(&mut self) -> (Float, Float) {
+        if self.ticks_elapsed >= self.next_write_tick {
+            self.apply_scheduled_writes();
+        }
+
+        self.ticks_elapsed += 1;
+
+        let noise = if self.shared_prescaler {
+            self.prescaler ^= 1;
+            self.noise_generator.render_prescaled(self.prescaler == 1)
+        } else {
+            self.noise_generator.render()
+        };
+
+        let envelope = self.envelope_generator.render();
+
+        // In expanded mode channels B and C use their own envelope generators
+        let envelopes = if self.expanded_mode() {
+            [envelope, self.channel_envelopes[0].render(), self.channel_envelopes[1].render()]
+        } else {
+            [envelope; 3]
+        };
+
+        if self.envelope_smoothing {
+            return self.render_smooth_tick(noise, envelopes);
+        }
+
+        let mut levels = [0; 3];
+
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            let level = channel.render_level(noise, envelopes[index]);
+
+            levels[index] = level as usize;
+
+            if self.metering_enabled {
+                let amplitude = if self.channels_audible[index] { self.log2lin_table[level as usize] } else { 0.0 };
+
+                self.meter.feed_channel(index, widen(amplitude));
+            }
+        }
+
+        if self.mixing_mode == MixingMode::Linear {
+            // The channels' gains already include the panning, so linear mixing only needs lookups
+            return self.channels.iter().zip(levels).zip(self.channels_audible).fold((0.0, 0.0), |(left, right), ((channel, level), audible)| {
+                if !audible {
+                    return (left, right);
+                }
+
+                let (gain_left, gain_right) = channel.gains[level];
+
+                (left + gain_left, right + gain_right)
+            });
+        }
+
+        let amplitudes: [Float; 3] = core::array::from_fn(|index| {
+            if self.channels_audible[index] { self.log2lin_table[levels[index]] } else { 0.0 }
+        });
+        let scale = self.mixing_mode.scale(amplitudes.iter().sum());
+
+        self.channels.iter().zip(amplitudes).fold((0.0, 0.0), |(left, right), (channel, amplitude)| {
+            let amplitude = amplitude * scale;
+
+            (left + amplitude * channel.pan_left, right + amplitude * channel.pan_right)
+        })
+    }
+
+    /// Combine the channel outputs for the current tick when envelope smoothing is enabled, in
+    /// which case the channels that use the envelope output interpolated amplitudes instead of
+    /// table lookups.
+    fn render_smooth_tick(&mut self, noise: u8, envelopes: [u8; 3]) -> (Float, Float) {
+        let expanded_mode = self.expanded_mode();
+        let mut amplitudes: [Float; 3] = [0.0; 3];
+
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            amplitudes[index] = if channel.envelope_enabled() {
+                // Mixing with the maximum envelope level reveals whether the tone and noise gates
+                // are open
+                match channel.render_level(noise, 31) {
+                    0 => self.log2lin_table[0],
+                    _ => {
+                        let envelope = match index {
+                            1 | 2 if expanded_mode => &self.channel_envelopes[index - 1],
+                            _ => &self.envelope_generator
+                        };
+
+                        envelope.smooth_amplitude(&self.log2lin_table)
+                    }
+                }
+            } else {
+                self.log2lin_table[channel.render_level(noise, envelopes[index]) as usize]
+            };
+
+            if !self.channels_audible[index] {
+                amplitudes[index] = 0.0;
+            }
+
+            if self.metering_enabled {
+                self.meter.feed_channel(index, widen(amplitudes[index]));
+            }
+        }
+
+        let scale = self.mixing_mode.scale(amplitudes.iter().sum());
+
+        self.channels.iter().zip(amplitudes).fold((0.0, 0.0), |(left, right), (channel, amplitude)| {
+            let amplitude = amplitude * scale;
+
+            (left + amplitude * channel.pan_left, right + amplitude * channel.pan_right)
+        })
+    }
+
+    /// Apply all scheduled register writes that are due before the next tick is rendered.
+    fn apply_scheduled_writes(&mut self) {
+        while let Some(&(tick, register, value)) = self.scheduled_writes.front() {
+            if tick > self.ticks_elapsed {
+                break;
+            }
+
+            self.scheduled_writes.pop_front();
+            self.set_register(register, value);
+        }
+
+        self.next_write_tick = self.scheduled_writes.front().map_or(u64::MAX, |&(tick, ..)| tick);
+    }
+
+    /// Advance the clock phase by a single oversampled step.
     ///
-    /// Please note that the GPIO registers (14 and 15) are ignored in this implementation, and
-    /// that writing to any register number higher than 15 will have no effect.
-    pub fn set_register(&mut self, register: u8, value: u8) {
-        // Note: the AY-3-8910 datasheet uses octal register numbers. The YM2149 datasheet uses
-        // decimal numbers.
-        match register {
-            0 => self.channels[0].set_period_lsb(value),
-            1 => self.channels[0].set_period_msb(value),
-            2 => self.channels[1].set_period_lsb(value),
-            3 => self.channels[1].set_period_msb(value),
-            4 => self.channels[2].set_period_lsb(value),
-            5 => self.channels[2].set_period_msb(value),
-            6 => self.noise_generator.set_period(value),
-            7 => self.set_mixer(value),
-            8 => self.channels[0].set_amplitude_and_envelope_enabled(value),
-            9 => self.channels[1].set_amplitude_and_envelope_enabled(value),
-            10 => self.channels[2].set_amplitude_and_envelope_enabled(value),
-            11 => self.envelope_generator.set_period_lsb(value),
-            12 => self.envelope_generator.set_period_msb(value),
-            13 => self.envelope_generator.set_shape(value),
-            14 => (), // GPIO port A data store is ignored here
-            15 => (), // GPIO port B data store is ignored here
-            _ => ()
+    /// The phase is accumulated in fixed-point, so that no rounding errors build up over long
+    /// runs. Returns true when the phase wraps around, which means a new tick should be rendered.
+    fn advance_phase(&mut self) -> bool {
+        if self.ayumi_compatible {
+            self.ayumi_phase += self.ayumi_step;
+
+            if self.ayumi_phase >= 1.0 {
+                self.ayumi_phase -= 1.0;
+                return true;
+            }
+
+            return false;
+        }
+
+        let (phase, wrapped) = self.phase.overflowing_add(self.step);
+
+        self.phase = phase;
+
+        wrapped
+    }
+
+    /// The clock phase as a fraction between 0 and 1, which is the position between the two most
+    /// recent ticks.
+    fn phase_fraction(&self) -> Float {
+        if self.ayumi_compatible {
+            self.ayumi_phase as Float
+        } else {
+            self.phase as Float / PHASE_SCALE as Float
+        }
+    }
+
+    /// Render the next frame at the internal sample rate, before any half-band decimation and DC
+    /// filtering is applied.
+    ///
+    /// Returns a tuple containing the left channel as the first element and the right channel as
+    /// the second.
+    fn render_oversampled(&mut self) -> (Float, Float) {
+        for _ in 0..DECIMATE_FACTOR {
+            if self.advance_phase() {
+                let (left, right) = self.render_tick();
+
+                self.left_interpolator.feed(left);
+                self.right_interpolator.feed(right);
+            }
+
+            let x = self.phase_fraction();
+
+            self.left_decimator.push(self.left_interpolator.interpolate(x));
+            self.right_decimator.push(self.right_interpolator.interpolate(x));
+        }
+
+        (self.left_decimator.render(), self.right_decimator.render())
+    }
+
+    /// Render the next frame for the specified half-band decimation stage. Stage 0 is the output
+    /// of the main decimator, every subsequent stage halves the sample rate of the previous one.
+    ///
+    /// Returns a tuple containing the left channel as the first element and the right channel as
+    /// the second.
+    fn render_decimated(&mut self, stage: usize) -> (Float, Float) {
+        if stage == 0 {
+            return self.render_oversampled();
+        }
+
+        let (first_left, first_right) = self.render_decimated(stage - 1);
+        let (second_left, second_right) = self.render_decimated(stage - 1);
+
+        (
+            self.left_half_bands[stage - 1].render(first_left, second_left),
+            self.right_half_bands[stage - 1].render(first_right, second_right)
+        )
+    }
+
+    /// Apply the DC filter for the current mode to the provided frame.
+    ///
+    /// Returns a tuple containing the left channel as the first element and the right channel as
+    /// the second.
+    fn render_dc_filter(&mut self, left: Float, right: Float) -> (Float, Float) {
+        match self.dc_filter_mode {
+            DCFilterMode::MovingAverage => self.dc_filter.render(left, right),
+            DCFilterMode::OnePole { .. } => self.one_pole_filter.render(left, right)
+        }
+    }
+
+    /// Render the next frame.
+    ///
+    /// Returns a tuple containing the left channel as the first element and the right channel as
+    /// the second.
+    pub fn render(&mut self) -> (f64, f64) {
+        self.frames_rendered += 1;
+
+        let (raw_left, raw_right) = self.render_decimated(self.half_band_stages);
+        let (left, right) = (widen(raw_left), widen(raw_right));
+
+        if let Some(tap) = &mut self.raw_frame_tap {
+            tap(left, right);
+        }
+
+        // Silence is detected before the DC filter, so that the filter's slow decay after the last
+        // note does not count as audible output.
+        let (reference_left, reference_right) = self.silence_reference;
+
+        if (left - reference_left).abs() < SILENCE_THRESHOLD && (right - reference_right).abs() < SILENCE_THRESHOLD {
+            self.silence_frames += 1;
+        } else {
+            self.silence_reference = (left, right);
+            self.silence_frames = 0;
+        }
+
+        // The DC filter keeps running while disabled, so that enabling it later on does not
+        // introduce a transient
+        let (mut left, mut right) = match self.render_dc_filter(raw_left, raw_right) {
+            (left, right) if self.dc_filter_enabled => (widen(left), widen(right)),
+            _ => (left, right)
+        };
+
+        if !self.output_filter_chain.is_empty() {
+            (left, right) = self.output_filter_chain.render(left, right);
+        }
+
+        if self.stereo_width != 1.0 {
+            let mid = (left + right) * 0.5;
+            let side = (left - right) * 0.5 * self.stereo_width;
+
+            left = mid + side;
+            right = mid - side;
+        }
+
+        if self.channels_swapped {
+            (left, right) = (right, left);
+        }
+
+        // The fade is applied after the DC filter, so that scaling the signal cannot introduce a
+        // DC step
+        if let Some((frames, remaining)) = &mut self.fade {
+            let gain = match *remaining {
+                0 => 0.0,
+                _ => {
+                    *remaining -= 1;
+                    self.fade_curve.gain(*remaining as f64 / *frames as f64)
+                }
+            };
+
+            left *= gain;
+            right *= gain;
+        }
+
+        if self.metering_enabled {
+            self.meter.feed_frame(left, right);
+        }
+
+        if let Some(tap) = &mut self.frame_tap {
+            tap(left, right);
         }
+
+        (left, right)
+    }
+
+    /// Whether the DC filter is enabled.
+    pub fn dc_filter_enabled(&self) -> bool {
+        self.dc_filter_enabled
+    }
+
+    /// Enable or disable the DC filter.
+    ///
+    /// The DC filter is enabled by default, and removes the DC offset that is inherent to the
+    /// PSG's unipolar output signal. Disabling it is useful when the output is already processed
+    /// by a high-pass filter further down the signal chain. Note that the filter does not
+    /// introduce any latency, so disabling it does not affect the timing of the output.
+    pub fn set_dc_filter_enabled(&mut self, enabled: bool) {
+        self.dc_filter_enabled = enabled;
+    }
+
+    /// The length of the moving average DC filter's window in frames, as configured with
+    /// [`with_dc_filter_length`](Self::with_dc_filter_length).
+    pub fn dc_filter_length(&self) -> usize {
+        self.dc_filter.size()
+    }
+
+    /// The type of filter that is used to remove the DC offset from the output signal.
+    pub fn dc_filter_mode(&self) -> DCFilterMode {
+        self.dc_filter_mode
+    }
+
+    /// Set the type of filter that is used to remove the DC offset from the output signal.
+    ///
+    /// The default is [`DCFilterMode::MovingAverage`]. Selecting a one-pole filter resets its
+    /// state.
+    pub fn set_dc_filter_mode(&mut self, mode: DCFilterMode) {
+        if let DCFilterMode::OnePole { cutoff_hz } = mode {
+            self.one_pole_filter = OnePoleFilter::new(cutoff_hz, self.sample_rate);
+        }
+
+        self.dc_filter_mode = mode;
+    }
+
+    /// Prime the PSG's internal filters with the current steady-state signal.
+    ///
+    /// A newly constructed PSG has filters that start out with zeroed buffers, which causes an
+    /// audible fade-in or click during the first frames when playback starts at full volume.
+    /// Priming renders (and discards) enough frames to fully populate the interpolators,
+    /// decimators, DC filter and output filters, so that the next frame returned by
+    /// [`render`](Self::render) is already clean. For best results, set up the registers for the
+    /// first note before priming.
+    ///
+    /// Note that the tone, noise and envelope generators keep running while priming. The frame
+    /// and tick counters are not affected, and frame taps, metering, and silence detection are
+    /// bypassed, so the primed frames do not show up in the next reading of [`meter`](Self::meter).
+    pub fn prime(&mut self) {
+        let frames = self.dc_filter.size() + FIR_SIZE / DECIMATE_FACTOR + HALF_BAND_TAPS;
+        let ticks_elapsed = self.ticks_elapsed;
+
+        // Scheduled writes are held back, as they refer to ticks after the priming
+        let next_write_tick = core::mem::replace(&mut self.next_write_tick, u64::MAX);
+        let metering_enabled = core::mem::replace(&mut self.metering_enabled, false);
+
+        for _ in 0..frames {
+            let (raw_left, raw_right) = self.render_decimated(self.half_band_stages);
+
+            let (left, right) = match self.render_dc_filter(raw_left, raw_right) {
+                (left, right) if self.dc_filter_enabled => (widen(left), widen(right)),
+                _ => (widen(raw_left), widen(raw_right))
+            };
+
+            if !self.output_filter_chain.is_empty() {
+                self.output_filter_chain.render(left, right);
+            }
+        }
+
+        self.ticks_elapsed = ticks_elapsed;
+        self.next_write_tick = next_write_tick;
+        self.metering_enabled = metering_enabled;
+    }
+
+    /// Reset the chip to its power-on state, like pulling the chip's /RESET pin low.
+    ///
+    /// Like on the real chip, all registers are cleared to zero. This silences all channels, and
+    /// enables their tone and noise (the mixer bits are active low) at the minimum period. The
+    /// oscillators and the noise and envelope generators are restarted, the GPIO ports are
+    /// configured as inputs, and the address latch is cleared. Afterwards the filters are
+    /// [primed](Self::prime) with the silent output, so that rendering continues without clicks.
+    ///
+    /// The configuration is kept, including the chip type, the panning of the channels, and the
+    /// filter settings. The frame and tick counters, and any scheduled register writes, are not
+    /// affected either. An AY8930 is switched back to compatibility mode, and the duty cycles of
+    /// its channels are reset to 50%. The reset is not recorded as register writes, and is not
+    /// passed on to the write observer.
+    pub fn reset(&mut self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(frame = self.frames_rendered, "reset");
+
+        self.reset_chip();
+        self.prime();
+
+        // Priming keeps the generators running, so restart them at their power-on state again
+        self.reset_chip();
+    }
+
+    /// Silence all channels immediately, for example to stop hung notes in live use.
+    ///
+    /// This sets the amplitudes of all channels to zero, disables their envelopes, and disables
+    /// their tone and noise in the mixer. Register writes that were scheduled with
+    /// [`write_register_at`](Self::write_register_at) are discarded, so they cannot turn a
+    /// channel back on. The tone, noise, and envelope periods, the GPIO port directions, and the
+    /// channel panning are left untouched, so playback can resume by writing the amplitudes and
+    /// the mixer again.
+    pub fn panic(&mut self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(frame = self.frames_rendered, "panic");
+
+        self.scheduled_writes.clear();
+        self.next_write_tick = u64::MAX;
+
+        for channel in ChannelIndex::ALL {
+            let state = self.channel_mut(channel);
+
+            state.set_amplitude(0);
+            state.set_envelope_enabled(false);
+            self.sync_register(u8::from(channel) + 8);
+        }
+
+        self.set_mixer(self.io_direction | 0x3f);
+    }
+
+    /// Reset the registers and the tone, noise, and envelope generators to their power-on state.
+    fn reset_chip(&mut self) {
+        for channel in &mut self.channels {
+            channel.reset();
+        }
+
+        self.noise_generator.reset();
+        self.envelope_generator.reset();
+        self.prescaler = 0;
+
+        for envelope in &mut self.channel_envelopes {
+            envelope.reset();
+        }
+
+        self.io_direction = 0;
+        self.io_ports = [0; 2];
+        self.register_file = [0; 16];
+        self.bank_b_register_file = [0; 13];
+        self.address = 0;
+
+        self.update_expanded_mode();
+    }
+
+    /// The number of frames that have been rendered since construction, or since the last call to
+    /// [`reset_counters`](Self::reset_counters).
+    pub fn frames_rendered(&self) -> u64 {
+        self.frames_rendered
+    }
+
+    /// The number of internal PSG clock ticks that have elapsed since construction, or since the
+    /// last call to [`reset_counters`](Self::reset_counters).
+    ///
+    /// A tick occurs once every 8 cycles of the chip's clock. This is the rate at which the tone,
+    /// noise, and envelope generators are updated.
+    pub fn ticks_elapsed(&self) -> u64 {
+        self.ticks_elapsed
+    }
+
+    /// The amount of rendered audio in seconds, derived from the amount of rendered frames and the
+    /// sample rate.
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.frames_rendered as f64 / self.sample_rate as f64
+    }
+
+    /// The number of chip clock cycles that have elapsed since construction, or since the last
+    /// call to [`reset_counters`](Self::reset_counters).
+    ///
+    /// This is always a multiple of 8, as the chip's clock is divided by 8 to produce ticks. See
+    /// [`write_register_at`](Self::write_register_at).
+    pub fn cycles_elapsed(&self) -> u64 {
+        self.ticks_elapsed * 8
+    }
+
+    /// Convert an output frame number into the chip clock cycle at which it approximately starts,
+    /// using the current clock and sample rates.
+    pub(crate) fn frame_to_cycle(&self, frame: u64) -> u64 {
+        (frame as f64 * self.effective_clock_rate() / self.sample_rate as f64).round() as u64
+    }
+
+    /// Reset the rendered frames and elapsed ticks counters to zero.
+    ///
+    /// Register writes that are scheduled using [`write_register_at`](Self::write_register_at)
+    /// are moved along, so they still occur at the same moment.
+    pub fn reset_counters(&mut self) {
+        for (tick, ..) in &mut self.scheduled_writes {
+            *tick = tick.saturating_sub(self.ticks_elapsed);
+        }
+
+        self.next_write_tick = self.next_write_tick.saturating_sub(self.ticks_elapsed);
+        self.frames_rendered = 0;
+        self.ticks_elapsed = 0;
+    }
+
+    /// Schedule a register write at the specified chip clock cycle.
+    ///
+    /// This allows emulators to apply register writes at the exact moment the emulated CPU
+    /// performs them, rather than at the start of the next rendered frame. Cycles are counted
+    /// since construction, or since the last call to [`reset_counters`](Self::reset_counters),
+    /// and can be related to the rendered audio using [`cycles_elapsed`](Self::cycles_elapsed).
+    ///
+    /// The PSG divides its clock by 8 to produce ticks, at which the tone, noise, and envelope
+    /// generators are updated. A write at cycle `c` therefore takes effect at the start of tick
+    /// `c / 8`, before that tick is rendered. Writes at cycles that have already passed take
+    /// effect at the start of the next tick, and writes at the same cycle are applied in the order
+    /// in which they were scheduled.
+    ///
+    /// The scheduled writes are applied while rendering, so they should be followed by a call to
+    /// [`render_until_cycle`](Self::render_until_cycle) or any of the other rendering methods.
+    pub fn write_register_at(&mut self, cycle: u64, register: u8, value: u8) {
+        let tick = cycle / 8;
+        let index = self.scheduled_writes.partition_point(|&(scheduled, ..)| scheduled <= tick);
+
+        self.scheduled_writes.insert(index, (tick, register, value));
+        self.next_write_tick = self.next_write_tick.min(tick);
+    }
+
+    /// Discard the writes to a register that are scheduled at or after the specified chip clock
+    /// cycle. See [`write_register_at`](Self::write_register_at).
+    pub fn cancel_scheduled_writes(&mut self, register: u8, cycle: u64) {
+        let tick = cycle / 8;
+
+        self.scheduled_writes.retain(|&(scheduled, scheduled_register, _)| scheduled_register != register || scheduled < tick);
+        self.next_write_tick = self.scheduled_writes.front().map_or(u64::MAX, |&(tick, ..)| tick);
+    }
+
+    /// Render frames until the specified chip clock cycle has been reached, passing each frame to
+    /// the provided callback.
+    ///
+    /// Frames are rendered in full, so the PSG may run up to one frame past the specified cycle.
+    /// The overshoot is carried over to the next call, so no audio is lost or duplicated. See
+    /// [`write_register_at`](Self::write_register_at) for how cycles are counted.
+    pub fn render_until_cycle(&mut self, cycle: u64, mut sink: impl FnMut(f64, f64)) {
+        while self.ticks_elapsed < cycle.div_ceil(8) {
+            let (left, right) = self.render();
+            sink(left, right);
+        }
+    }
+
+    /// Whether the current register state can produce no audible output.
+    ///
+    /// This is the case when every channel either has an effective amplitude of zero, or has both
+    /// its tone and noise disabled with the envelope disabled. The effective amplitude is zero when
+    /// the channel amplitude is set to zero with the envelope disabled, or when the envelope is
+    /// enabled and has come to rest at the bottom of a non-repeating shape.
+    ///
+    /// Note that a channel with both tone and noise disabled will still output a constant level,
+    /// which is inaudible but can be modulated by writing the amplitude register (this is how
+    /// sample playback is usually performed). When the envelope is running, such a channel plays
+    /// the envelope itself as a buzzer voice, so it is not silent.
+    pub fn is_silent(&self) -> bool {
+        let expanded_mode = self.expanded_mode();
+
+        self.channels().all(|(index, channel)| {
+            let envelope = if expanded_mode {
+                self.channel_envelope(index)
+            } else {
+                &self.envelope_generator
+            };
+
+            let amplitude_silent = if channel.envelope_on {
+                envelope.is_holding_bottom()
+            } else {
+                channel.amplitude == 0
+            };
+
+            amplitude_silent || (channel.tone_off && channel.noise_off && !channel.envelope_on)
+        })
+    }
+
+    /// The number of consecutive frames for which the rendered output has been silent.
+    ///
+    /// Output is considered silent when its level stays within a small threshold (roughly -80 dB)
+    /// of a constant value. The measurement takes place before the DC filter, so constant levels
+    /// and the slow decay of the DC filter after the last note are both treated as silence. This
+    /// makes it suitable for detecting the end of a tune, e.g. by stopping playback after a
+    /// couple of seconds of silence.
+    ///
+    /// Note that very low-pitched tones may produce runs of silent frames up to half of their
+    /// period.
+    pub fn silence_duration_frames(&self) -> u64 {
+        self.silence_frames
+    }
+
+    /// The analog output filter that is applied to the output signal.
+    pub fn output_filter(&self) -> OutputFilter {
+        self.output_filter
+    }
+
+    /// Set the analog output filter that is applied to the output signal.
+    ///
+    /// Real machines pass the PSG's output through RC networks and amplifiers, which makes them
+    /// sound duller than the raw chip output. The output filter can be used to emulate this, either
+    /// by using a simple low-pass filter or by selecting one of the [`MachinePreset`]s. The
+    /// filter is applied after the DC filter, and its state is reset when changing the filter. By
+    /// default no output filter is applied.
+    pub fn set_output_filter(&mut self, filter: OutputFilter) {
+        self.output_filter = filter;
+        self.output_filter_chain = OutputFilterChain::new(filter, self.sample_rate);
+    }
+
+    /// The stereo width of the output signal.
+    pub fn stereo_width(&self) -> f64 {
+        self.stereo_width
+    }
+
+    /// Set the stereo width of the output signal to a value between 0.0 (mono) and 1.0 (full
+    /// stereo) inclusive.
+    ///
+    /// Values in between mix part of each output channel into the other one, narrowing the stereo
+    /// image produced by the channel panning. Values outside of this range are clamped. The
+    /// default is 1.0.
+    pub fn set_stereo_width(&mut self, width: f64) {
+        self.stereo_width = width.clamp(0.0, 1.0);
+    }
+
+    /// Pan the three channels according to one of the common stereo layouts.
+    ///
+    /// This is a shortcut for setting the panning of every channel using
+    /// [`Channel::set_panning`], and overrides any panning that was set before.
+    pub fn set_stereo_mode(&mut self, mode: StereoMode) {
+        for (channel, balance) in self.channels.iter_mut().zip(mode.balances()) {
+            channel.set_panning(balance, false);
+        }
+    }
+
+    /// Whether the left and right output channels are swapped.
+    pub fn channels_swapped(&self) -> bool {
+        self.channels_swapped
+    }
+
+    /// Swap the left and right output channels.
+    ///
+    /// This is applied at the very end of the output stage, after channel panning and stereo
+    /// width have been applied. This is useful for emulating machines that wire the channels to
+    /// the opposite sides.
+    pub fn set_channels_swapped(&mut self, swapped: bool) {
+        self.channels_swapped = swapped;
+    }
+
+    /// Start fading out the output over the specified number of frames, for example to end a
+    /// looping tune.
+    ///
+    /// The output gain follows the [fade curve](Self::set_fade_curve) from full volume down to
+    /// zero, which is reached at the last frame of the fade. The output then stays silent until
+    /// [`cancel_fade`](Self::cancel_fade) is called, and [`fade_state`](Self::fade_state) reports
+    /// [`FadeState::Finished`]. The chip keeps running as usual during the fade, so register
+    /// writes are still applied. Starting a new fade while another one is in progress restarts it
+    /// at full volume.
+    ///
+    /// The progress of the fade is part of a [saved state](Self::save_state), so loading a state
+    /// that was saved before the fade started cancels it.
+    pub fn begin_fade_out(&mut self, duration_frames: u64) {
+        self.fade = Some((duration_frames, duration_frames));
+    }
+
+    /// The progress of the current fade out.
+    pub fn fade_state(&self) -> FadeState {
+        match self.fade {
+            None => FadeState::Inactive,
+            Some((_, 0)) => FadeState::Finished,
+            Some((_, remaining_frames)) => FadeState::FadingOut { remaining_frames }
+        }
+    }
+
+    /// Stop the current fade out, if any, and restore the output to full volume.
+    pub fn cancel_fade(&mut self) {
+        self.fade = None;
+    }
+
+    /// The shape of the gain curve of fade outs.
+    pub fn fade_curve(&self) -> FadeCurve {
+        self.fade_curve
+    }
+
+    /// Set the shape of the gain curve of fade outs. This also applies to a fade out that is in
+    /// progress. The default is [`FadeCurve::Linear`].
+    pub fn set_fade_curve(&mut self, curve: FadeCurve) {
+        self.fade_curve = curve;
+    }
+
+    /// Install a callback that is invoked with every rendered frame, or remove it by passing
+    /// `None`.
+    ///
+    /// The callback receives the final output frame, exactly as it is returned by
+    /// [`render`](Self::render) and any of the buffer rendering methods. This is useful for
+    /// visualizations such as oscilloscopes, without having to restructure the render loop.
+    pub fn set_frame_tap(&mut self, tap: Option<FrameTap>) {
+        self.frame_tap = tap;
+    }
+
+    /// Install a callback that is invoked with every raw frame, or remove it by passing `None`.
+    ///
+    /// Raw frames are taken directly from the decimator, before the DC filter and the stereo
+    /// width and channel swap controls are applied.
+    pub fn set_raw_frame_tap(&mut self, tap: Option<FrameTap>) {
+        self.raw_frame_tap = tap;
+    }
+
+    /// Render the specified amount of frames and return them as a vector of tuples, containing the
+    /// left channel as the first element and the right channel as the second.
+    ///
+    /// This is mostly useful for quick experiments and tests. For real-time use, prefer rendering
+    /// into an existing buffer to avoid allocations.
+    #[must_use]
+    pub fn render_frames(&mut self, frames: usize) -> Vec<(f64, f64)> {
+        let mut output = Vec::with_capacity(frames);
+        output.extend((0..frames).map(|_| self.render()));
+        output
+    }
+
+    /// Render the specified duration of audio and return it as a vector of frames.
+    ///
+    /// The amount of rendered frames is the duration multiplied by the sample rate, rounded to the
+    /// nearest integer. See [`render_frames`](Self::render_frames) for the format of the result.
+    ///
+    /// ```
+    /// # use psg::PSG;
+    /// let mut psg = PSG::new(1789772.5, 44100)?;
+    /// let frames = psg.render_seconds(1.5);
+    ///
+    /// assert_eq!(frames.len(), 66150);
+    /// # Ok::<(), psg::Error>(())
+    /// ```
+    #[must_use]
+    pub fn render_seconds(&mut self, seconds: f64) -> Vec<(f64, f64)> {
+        self.render_frames((seconds * self.sample_rate as f64).round() as usize)
+    }
+
+    /// Render `left.len()` frames and add them to the provided buffers, after scaling them by the
+    /// specified gain.
+    ///
+    /// This is useful for mixing the output of multiple sound chips into a shared buffer, as is
+    /// the case when emulating machines with two PSGs (e.g. TurboSound), without having to render
+    /// into an intermediate buffer first.
+    ///
+    /// Returns an error when the left and right buffers do not have the same length.
+    pub fn render_add_into(&mut self, left: &mut [f64], right: &mut [f64], gain: f64) -> Result<(), Error> {
+        if left.len() != right.len() {
+            return Err(Error::BufferLengthMismatch { left: left.len(), right: right.len() });
+        }
+
+        for (left, right) in left.iter_mut().zip(right.iter_mut()) {
+            let (rendered_left, rendered_right) = self.render();
+
+            *left += rendered_left * gain;
+            *right += rendered_right * gain;
+        }
+
+        Ok(())
+    }
+
+    /// Render `interleaved.len() / 2` frames into the provided buffer of interleaved stereo samples,
+    /// converting them to the buffer's sample format.
+    ///
+    /// The left channel is written to the even indices and the right channel to the odd indices.
+    /// When the buffer has an odd length, the last element is left untouched. See [`Sample`] for
+    /// details on how the samples are converted.
+    pub fn render_into_samples<S: Sample>(&mut self, interleaved: &mut [S]) {
+        for frame in interleaved.chunks_exact_mut(2) {
+            let (left, right) = self.render();
+
+            frame[0] = S::from_f64(left);
+            frame[1] = S::from_f64(right);
+        }
+    }
+
+    /// Whether a channel is muted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn channel_muted(&self, channel: impl ChannelSelector) -> bool {
+        self.channels_muted[usize::from(channel.channel_index())]
+    }
+
+    /// Mute or unmute a channel, for auditioning the channels of a tune.
+    ///
+    /// A muted channel does not contribute to the output, but is otherwise emulated as usual. Its
+    /// registers and the recorded register writes are not affected, so muting is invisible to the
+    /// music data. See [`is_channel_audible`](Self::is_channel_audible) for the interaction with
+    /// [`set_channel_solo`](Self::set_channel_solo).
+    ///
+    /// The mute and solo flags are part of a [saved state](Self::save_state), and are restored
+    /// when it is loaded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn set_channel_muted(&mut self, channel: impl ChannelSelector, muted: bool) {
+        self.channels_muted[usize::from(channel.channel_index())] = muted;
+        self.update_audible_channels();
+    }
+
+    /// Whether a channel is soloed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn channel_solo(&self, channel: impl ChannelSelector) -> bool {
+        self.channels_soloed[usize::from(channel.channel_index())]
+    }
+
+    /// Solo or unsolo a channel, for auditioning the channels of a tune.
+    ///
+    /// While any channel is soloed, the channels that are not soloed do not contribute to the
+    /// output. Like muting, soloing does not affect the registers or the recorded register writes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn set_channel_solo(&mut self, channel: impl ChannelSelector, soloed: bool) {
+        self.channels_soloed[usize::from(channel.channel_index())] = soloed;
+        self.update_audible_channels();
+    }
+
+    /// Whether a channel contributes to the output, given the mute and solo settings of all
+    /// channels.
+    ///
+    /// A channel is audible when it is not muted, and either it is soloed or no channel is soloed.
+    /// Muting takes precedence, so a channel that is both soloed and muted is silent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn is_channel_audible(&self, channel: impl ChannelSelector) -> bool {
+        self.channels_audible[usize::from(channel.channel_index())]
+    }
+
+    /// Recompute the audible channels after a change of the mute or solo settings.
+    fn update_audible_channels(&mut self) {
+        let any_soloed = self.channels_soloed.contains(&true);
+
+        for ((audible, muted), soloed) in self.channels_audible.iter_mut().zip(self.channels_muted).zip(self.channels_soloed) {
+            *audible = !muted && (soloed || !any_soloed);
+        }
+    }
+
+    /// Enable or disable output level metering.
+    ///
+    /// Metering is disabled by default. When enabled, the levels of each channel and the master
+    /// output are tracked while rendering, and can be read using [`meter`](Self::meter). Changing
+    /// this setting resets the accumulated levels.
+    pub fn enable_metering(&mut self, enabled: bool) {
+        self.metering_enabled = enabled;
+        self.meter = MeterAccumulator::new();
+    }
+
+    /// Return the output levels that were measured since the previous call to this method, and
+    /// reset the measurement.
+    ///
+    /// When metering is disabled all levels are zero. See [`Meter`] for a description of the
+    /// measured values and their scale.
+    pub fn meter(&mut self) -> Meter {
+        self.meter.take()
+    }
+
+    /// Return a reference to the specified channel's [`Channel`] struct.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3. Use [`try_channel`](Self::try_channel)
+    /// when the channel number comes from untrusted data.
+    pub fn channel(&self, index: impl ChannelSelector) -> &Channel {
+        &self.channels[usize::from(index.channel_index())]
+    }
+
+    /// Return a reference to the specified channel number's [`Channel`] struct, or `None` if the
+    /// channel number is not smaller than 3. This is useful when the channel number comes from
+    /// untrusted data.
+    pub fn try_channel(&self, index: u8) -> Option<&Channel> {
+        self.channels.get(index as usize)
+    }
+
+    /// Return a mutable reference to the specified channel's [`Channel`] struct.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3. Use
+    /// [`try_channel_mut`](Self::try_channel_mut) when the channel number comes from untrusted
+    /// data.
+    pub fn channel_mut(&mut self, index: impl ChannelSelector) -> &mut Channel {
+        &mut self.channels[usize::from(index.channel_index())]
+    }
+
+    /// Return a mutable reference to the specified channel number's [`Channel`] struct, or `None`
+    /// if the channel number is not smaller than 3. This is useful when the channel number comes
+    /// from untrusted data.
+    pub fn try_channel_mut(&mut self, index: u8) -> Option<&mut Channel> {
+        self.channels.get_mut(index as usize)
+    }
+
+    /// Return an iterator over all three channels and their indices.
+    pub fn channels(&self) -> impl Iterator<Item = (ChannelIndex, &Channel)> {
+        ChannelIndex::ALL.into_iter().zip(self.channels.iter())
+    }
+
+    /// Return an iterator over all three channels and their indices, allowing the channels to be
+    /// modified.
+    pub fn channels_mut(&mut self) -> impl Iterator<Item = (ChannelIndex, &mut Channel)> {
+        ChannelIndex::ALL.into_iter().zip(self.channels.iter_mut())
+    }
+
+    /// Return a reference to the PSG's noise generator.
+    pub fn noise_generator(&self) -> &NoiseGenerator {
+        &self.noise_generator
+    }
+
+    /// Return a mutable reference to the PSG's noise generator.
+    pub fn noise_generator_mut(&mut self) -> &mut NoiseGenerator {
+        &mut self.noise_generator
+    }
+
+    /// Return a reference to the PSG's envelope generator.
+    pub fn envelope_generator(&self) -> &EnvelopeGenerator {
+        &self.envelope_generator
+    }
+
+    /// Return a mutable reference to the PSG's envelope generator.
+    pub fn envelope_generator_mut(&mut self) -> &mut EnvelopeGenerator {
+        &mut self.envelope_generator
+    }
+
+    /// Return a reference to the envelope generator that a channel uses when the PSG emulates an
+    /// AY8930 in expanded mode. For channel A this is the regular envelope generator. In all
+    /// other cases every channel uses the regular envelope generator. See
+    /// [`ChipType::AY8930`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn channel_envelope(&self, channel: impl ChannelSelector) -> &EnvelopeGenerator {
+        match channel.channel_index() {
+            ChannelIndex::A => &self.envelope_generator,
+            ChannelIndex::B => &self.channel_envelopes[0],
+            ChannelIndex::C => &self.channel_envelopes[1]
+        }
+    }
+
+    /// Return a mutable reference to the envelope generator that a channel uses when the PSG
+    /// emulates an AY8930 in expanded mode. See [`channel_envelope`](Self::channel_envelope).
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn channel_envelope_mut(&mut self, channel: impl ChannelSelector) -> &mut EnvelopeGenerator {
+        match channel.channel_index() {
+            ChannelIndex::A => &mut self.envelope_generator,
+            ChannelIndex::B => &mut self.channel_envelopes[0],
+            ChannelIndex::C => &mut self.channel_envelopes[1]
+        }
+    }
+
+    /// A channel's tone period.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn tone_period(&self, channel: impl ChannelSelector) -> u16 {
+        self.channel(channel).period()
+    }
+
+    /// Set a channel's tone period to a value between 1 and 4095 inclusive.
+    ///
+    /// Smaller values are set to 1, larger values are wrapped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3. Use
+    /// [`try_set_tone_period`](Self::try_set_tone_period) when the channel number comes from
+    /// untrusted data.
+    pub fn set_tone_period(&mut self, channel: impl ChannelSelector, period: u16) {
+        let channel = channel.channel_index();
+
+        self.channel_mut(channel).set_period(period);
+        self.sync_register(u8::from(channel) * 2);
+        self.sync_register(u8::from(channel) * 2 + 1);
+    }
+
+    /// Set a channel's tone period like [`set_tone_period`](Self::set_tone_period), but using a
+    /// channel number that may come from untrusted data. Returns an error when the channel number
+    /// is not smaller than 3.
+    pub fn try_set_tone_period(&mut self, channel: u8, period: u16) -> Result<(), Error> {
+        self.set_tone_period(ChannelIndex::try_from(channel)?, period);
+        Ok(())
+    }
+
+    /// The frequency in Hz of a channel's tone period at the effective clock rate.
+    ///
+    /// This reports the programmed period, regardless of whether the channel's tone is enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn channel_frequency(&self, channel: impl ChannelSelector) -> f64 {
+        math::tone_period_to_frequency(self.tone_period(channel), self.effective_clock_rate())
+    }
+
+    /// Set a channel's tone period to the period that is closest to the specified frequency in Hz
+    /// at the effective clock rate. Frequencies outside of the range of the tone period are
+    /// clamped to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn set_channel_frequency(&mut self, channel: impl ChannelSelector, frequency: f64) {
+        let period = math::frequency_to_tone_period_checked(frequency, self.effective_clock_rate()).unwrap_or_else(|error| error.period());
+        self.set_tone_period(channel, period);
+    }
+
+    /// The MIDI pitch number of a channel's tone period at the effective clock rate, which is not
+    /// necessarily an integer.
+    ///
+    /// This reports the programmed period, regardless of whether the channel's tone is enabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn channel_midi_pitch(&self, channel: impl ChannelSelector) -> f64 {
+        math::tone_period_to_midi_pitch(self.tone_period(channel), self.effective_clock_rate())
+    }
+
+    /// Set a channel's tone period to the period that is closest to the specified MIDI pitch
+    /// number at the effective clock rate. Pitches outside of the range of the tone period are
+    /// clamped to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn set_channel_midi_pitch(&mut self, channel: impl ChannelSelector, pitch: f64) {
+        let period = math::midi_pitch_to_tone_period_checked(pitch, self.effective_clock_rate()).unwrap_or_else(|error| error.period());
+        self.set_tone_period(channel, period);
+    }
+
+    /// A channel's amplitude.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn amplitude(&self, channel: impl ChannelSelector) -> u8 {
+        self.channel(channel).amplitude()
+    }
+
+    /// Set a channel's amplitude to a value between 0 and 15 inclusive, or between 0 and 31
+    /// inclusive while emulating an AY8930 in expanded mode. See [`ChipType::AY8930`].
+    ///
+    /// Larger values are wrapped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3. Use
+    /// [`try_set_amplitude`](Self::try_set_amplitude) when the channel number comes from untrusted
+    /// data.
+    pub fn set_amplitude(&mut self, channel: impl ChannelSelector, amplitude: u8) {
+        let channel = channel.channel_index();
+
+        self.channel_mut(channel).set_amplitude(amplitude);
+        self.sync_register(u8::from(channel) + 8);
+    }
+
+    /// Set a channel's amplitude like [`set_amplitude`](Self::set_amplitude), but using a channel
+    /// number that may come from untrusted data. Returns an error when the channel number is not
+    /// smaller than 3.
+    pub fn try_set_amplitude(&mut self, channel: u8, amplitude: u8) -> Result<(), Error> {
+        self.set_amplitude(ChannelIndex::try_from(channel)?, amplitude);
+        Ok(())
+    }
+
+    /// A channel's tone disable flag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn tone_disabled(&self, channel: impl ChannelSelector) -> bool {
+        self.channel(channel).tone_disabled()
+    }
+
+    /// Set a channel's tone disable flag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn set_tone_disabled(&mut self, channel: impl ChannelSelector, disabled: bool) {
+        self.channel_mut(channel).set_tone_disabled(disabled);
+        self.sync_register(7);
+    }
+
+    /// A channel's noise disable flag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn noise_disabled(&self, channel: impl ChannelSelector) -> bool {
+        self.channel(channel).noise_disabled()
+    }
+
+    /// Set a channel's noise disable flag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn set_noise_disabled(&mut self, channel: impl ChannelSelector, disabled: bool) {
+        self.channel_mut(channel).set_noise_disabled(disabled);
+        self.sync_register(7);
+    }
+
+    /// A channel's envelope enable flag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn envelope_enabled(&self, channel: impl ChannelSelector) -> bool {
+        self.channel(channel).envelope_enabled()
+    }
+
+    /// Set a channel's envelope enable flag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn set_envelope_enabled(&mut self, channel: impl ChannelSelector, enabled: bool) {
+        let channel = channel.channel_index();
+
+        self.channel_mut(channel).set_envelope_enabled(enabled);
+        self.sync_register(u8::from(channel) + 8);
+    }
+
+    /// Set the noise generator's period to a value between 1 and 31 inclusive.
+    ///
+    /// Smaller values are set to 1, larger values are wrapped.
+    pub fn set_noise_period(&mut self, period: u8) {
+        self.noise_generator.set_period(period);
+        self.sync_register(6);
+    }
+
+    /// Set the PSG's mixer register value.
+    ///
+    /// The mixer value is an 8-bit number consisting of the following bits:
+    ///
+    /// Bit 0: Channel A tone enable (0 to enable, 1 to disable) \
+    /// Bit 1: Channel B tone enable (0 to enable, 1 to disable) \
+    /// Bit 2: Channel C tone enable (0 to enable, 1 to disable) \
+    /// Bit 3: Channel A noise enable (0 to enable, 1 to disable) \
+    /// Bit 4: Channel B noise enable (0 to enable, 1 to disable) \
+    /// Bit 5: Channel C noise enable (0 to enable, 1 to disable) \
+    /// Bit 6: GPIO port A direction (0 for input, 1 for output) \
+    /// Bit 7: GPIO port B direction (0 for input, 1 for output)
+    ///
+    /// When a GPIO port is switched to output, its stored value is written to the connected
+    /// device. See [`set_ports`](Self::set_ports).
+    pub fn set_mixer(&mut self, mixer: u8) {
+        self.apply_mixer(mixer);
+        self.sync_register(7);
+    }
+
+    /// Apply a mixer register value, without recording it.
+    fn apply_mixer(&mut self, mixer: u8) {
+        self.channels[0].set_tone_disabled(mixer & 0x01 != 0);
+        self.channels[1].set_tone_disabled(mixer & 0x02 != 0);
+        self.channels[2].set_tone_disabled(mixer & 0x04 != 0);
+        self.channels[0].set_noise_disabled(mixer & 0x08 != 0);
+        self.channels[1].set_noise_disabled(mixer & 0x10 != 0);
+        self.channels[2].set_noise_disabled(mixer & 0x20 != 0);
+
+        let switched_to_output = mixer & !self.io_direction & 0xc0;
+        self.io_direction = mixer & 0xc0;
+
+        if switched_to_output & 0x40 != 0 {
+            self.write_port(0);
+        }
+
+        if switched_to_output & 0x80 != 0 {
+            self.write_port(1);
+        }
+    }
+
+    /// Whether the specified GPIO port (0 for port A, 1 for port B) is configured as an output.
+    fn is_port_output(&self, port: usize) -> bool {
+        self.io_direction & (0x40 << port) != 0
+    }
+
+    /// Pass the stored value of a GPIO port on to the connected device if the port is configured
+    /// as an output.
+    fn write_port(&mut self, port: usize) {
+        if !self.is_port_output(port) {
+            return;
+        }
+
+        if let Some(ports) = &mut self.ports {
+            match port {
+                0 => ports.write_a(self.io_ports[0]),
+                _ => ports.write_b(self.io_ports[1])
+            }
+        }
+    }
+
+    /// Connect devices to the GPIO ports, or disconnect them by passing `None`.
+    ///
+    /// Once connected, writes to registers 14 and 15 are passed on to the devices when the
+    /// respective port is configured as an output, and reads using
+    /// [`read_register`](Self::read_register), [`read_data`](Self::read_data) or
+    /// [`bus_transaction`](Self::bus_transaction) are passed on when the port is configured as an
+    /// input. The port directions are set using bits 6 and 7 of the mixer register.
+    pub fn set_ports(&mut self, ports: Option<Box<dyn PsgPorts + Send>>) {
+        self.ports = ports;
+    }
+
+    /// The PSG's mixer register value, reconstructed from the channels' tone and noise disabled
+    /// flags. See [`set_mixer`](Self::set_mixer) for the meaning of the individual bits.
+    pub fn mixer(&self) -> u8 {
+        self.channels.iter().enumerate().fold(self.io_direction, |mixer, (index, channel)| {
+            mixer | (channel.tone_disabled() as u8) << index | (channel.noise_disabled() as u8) << (index + 3)
+        })
+    }
+
+    /// Set the envelope generator period to a value between 1 and 65535 inclusive.
+    ///
+    /// Lower values are set to 1.
+    pub fn set_envelope_period(&mut self, period: u16) {
+        self.envelope_generator.set_period(period);
+        self.sync_register(11);
+        self.sync_register(12);
+    }
+
+    /// Set shape to a value between 0 and 15 inclusive.
+    ///
+    /// Higher values are wrapped.
+    pub fn set_envelope_shape(&mut self, shape: u8) {
+        self.write_envelope_shape(shape);
+        self.sync_register(13);
+    }
+
+    /// Restart the envelope from the beginning of its current shape, which is equivalent to
+    /// writing the same shape to the envelope shape register again.
+    pub fn retrigger_envelope(&mut self) {
+        self.write_envelope_shape(self.envelope_generator.shape());
+    }
+
+    /// Set up a channel to play a "buzzer" sound at the specified MIDI pitch number, in which the
+    /// envelope runs at `ratio` times the frequency of the channel's tone.
+    ///
+    /// This sets the channel's tone period, the envelope period and shape, and enables the
+    /// envelope for the channel. The mixer is left untouched, so the tone can be enabled or
+    /// disabled independently. The periods are computed using [`math::buzzer_periods`] for the
+    /// effective clock rate. Returns the detune of the envelope relative to the exact ratio in
+    /// cents, or an error when either period lies outside of its range, in which case nothing is
+    /// changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn set_buzzer(&mut self, channel: impl ChannelSelector, pitch: f64, shape: u8, ratio: f64) -> Result<f64, math::RangeError> {
+        let channel = channel.channel_index();
+        let (tone_period, envelope_period, detune) = math::buzzer_periods(pitch, ratio, self.effective_clock_rate())?;
+
+        self.set_tone_period(channel, tone_period);
+        self.set_envelope_period(envelope_period);
+        self.set_envelope_shape(shape);
+        self.set_envelope_enabled(channel, true);
+
+        Ok(detune)
+    }
+
+    /// Advance the software envelope of each channel by one frame, and write the resulting
+    /// amplitudes to the channels. This is meant to be called once per frame, for example before
+    /// each call to [`render_frames`](Self::render_frames).
+    pub fn apply_soft_envelopes(&mut self, envelopes: &mut [voices::SoftEnvelope; 3]) {
+        for (channel, envelope) in ChannelIndex::ALL.into_iter().zip(envelopes) {
+            self.set_amplitude(channel, envelope.tick());
+        }
+    }
+
+    /// Set the envelope generator's shape, which restarts the envelope.
+    fn write_envelope_shape(&mut self, shape: u8) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(shape = shape & 0x0f, frame = self.frames_rendered, "envelope retriggered");
+
+        self.envelope_generator.set_shape(shape);
+    }
+
+    /// Set a PSG register to the provided value.
+    ///
+    /// This function is particularly useful when writing emulators, as it provides a convenient
+    /// API to the PSG's address/data ports that is easy to map from machine code.
+    ///
+    /// For an exact specification of the register numbers and their accepted values, please refer
+    /// to the AY-3-8910 or YM2149 datasheets. Note that the AY-3-8910 datasheet uses octal numbers
+    /// when referring to register numbers.
+    ///
+    /// Writes to the GPIO registers (14 and 15) are stored, and passed on to the connected devices
+    /// when the port is configured as an output. See [`set_ports`](Self::set_ports). Writing to
+    /// any register number higher than 15 will have no effect. When emulating an AY8930 in
+    /// expanded mode, writes to registers 0 through 12 go to the selected register bank. See
+    /// [`ChipType::AY8930`].
+    pub fn set_register(&mut self, register: u8, value: u8) {
+        if self.is_bank_b_register(register) {
+            self.bank_b_register_file[register as usize] = value;
+            self.write_bank_b_register(register, value);
+        } else {
+            // Note: the AY-3-8910 datasheet uses octal register numbers. The YM2149 datasheet
+            // uses decimal numbers.
+            if let Some(raw) = self.register_file.get_mut(register as usize) {
+                *raw = value;
+            }
+
+            match register {
+                0 => self.channels[0].set_period_lsb(value),
+                1 => self.channels[0].set_period_msb(value),
+                2 => self.channels[1].set_period_lsb(value),
+                3 => self.channels[1].set_period_msb(value),
+                4 => self.channels[2].set_period_lsb(value),
+                5 => self.channels[2].set_period_msb(value),
+                6 => self.noise_generator.set_period(value),
+                7 => self.apply_mixer(value),
+                8 => self.channels[0].set_amplitude_and_envelope_enabled(value),
+                9 => self.channels[1].set_amplitude_and_envelope_enabled(value),
+                10 => self.channels[2].set_amplitude_and_envelope_enabled(value),
+                11 => self.envelope_generator.set_period_lsb(value),
+                12 => self.envelope_generator.set_period_msb(value),
+                13 => {
+                    self.write_envelope_shape(value);
+                    self.update_expanded_mode();
+                }
+                14 | 15 => {
+                    let port = register as usize - 14;
+
+                    self.io_ports[port] = value;
+                    self.write_port(port);
+                }
+                _ => return
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(register, value, frame = self.frames_rendered, "register write");
+
+        if let Some(observer) = &mut self.write_observer {
+            observer(register, value, self.frames_rendered);
+        }
+
+        self.record(register, value);
+    }
+
+    /// Apply a write to one of the registers in the AY8930's second expanded mode register bank.
+    fn write_bank_b_register(&mut self, register: u8, value: u8) {
+        match register {
+            0 => self.channel_envelopes[0].set_period_lsb(value),
+            1 => self.channel_envelopes[0].set_period_msb(value),
+            2 => self.channel_envelopes[1].set_period_lsb(value),
+            3 => self.channel_envelopes[1].set_period_msb(value),
+            4 => self.channel_envelopes[0].set_shape(value),
+            5 => self.channel_envelopes[1].set_shape(value),
+            6..=8 => self.channels[register as usize - 6].set_duty_cycle(DutyCycle::from_register(value)),
+            _ => ()
+        }
+    }
+
+    /// Install a callback that is invoked with every register write, or remove it by passing
+    /// `None`.
+    ///
+    /// The callback is invoked for every write through [`set_register`](Self::set_register) and
+    /// the methods built on top of it, such as [`write_data`](Self::write_data), with the
+    /// register number, the written value, and the number of frames rendered so far. Writes to
+    /// register numbers above 15 are ignored by the PSG, and are not passed on. This is useful
+    /// for comparing the register writes of a player against a reference.
+    ///
+    /// When the `tracing` feature is enabled, the same information is also emitted as a
+    /// trace-level event, along with a debug-level event whenever the envelope is retriggered.
+    pub fn set_write_observer(&mut self, observer: Option<WriteObserver>) {
+        self.write_observer = observer;
+    }
+
+    /// Split the PSG into a rendering half, which stays on the audio thread, and a
+    /// [`PsgController`] with a queue for up to the specified number of commands, which changes
+    /// the PSG from other threads without locking the rendering half.
+    ///
+    /// ```
+    /// use std::thread;
+    ///
+    /// use psg::PSG;
+    ///
+    /// let (mut psg, controller) = PSG::new(2_000_000.0, 44100).unwrap().split_control(256);
+    ///
+    /// thread::spawn(move || {
+    ///     controller.write_register(7, 0x3e).unwrap();
+    ///     controller.write_register(8, 15).unwrap();
+    /// }).join().unwrap();
+    ///
+    /// let mut buffer = vec![0.0f32; 2 * 512];
+    /// psg.render_into_samples(&mut buffer);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn split_control(self, capacity: usize) -> (ControlledPsg, PsgController) {
+        let (controller, receiver) = PsgController::new(capacity);
+
+        (ControlledPsg::new(self, receiver), controller)
+    }
+
+    /// Set a PSG register like [`set_register`](Self::set_register), but return an error instead of
+    /// ignoring the write when the register number is not smaller than 16.
+    pub fn try_set_register(&mut self, register: u8, value: u8) -> Result<(), Error> {
+        if register >= 16 {
+            return Err(Error::InvalidRegister(register));
+        }
+
+        self.set_register(register, value);
+        Ok(())
+    }
+
+    /// Start recording register writes, discarding any previous recording.
+    ///
+    /// While recording, every write through [`set_register`](Self::set_register) (and the methods
+    /// built on top of it) is logged, together with the number of frames rendered at that moment.
+    /// The typed setters of this struct, such as [`set_tone_period`](Self::set_tone_period), are
+    /// logged as writes to the registers they affect. Changes made directly to a channel or
+    /// generator through [`channel_mut`](Self::channel_mut) and friends are not recorded.
+    ///
+    /// Replaying the recorded events through [`set_register`](Self::set_register) before
+    /// rendering their frames reproduces the original output, except for writes scheduled with
+    /// [`write_register_at`](Self::write_register_at), which are moved to the start of the
+    /// following frame.
+    ///
+    /// Register writes to the AY8930's second register bank are recorded like any other write,
+    /// together with the writes to register 13 that select the bank, so they are replayed into
+    /// the same bank. The typed setters are the exception: they always change the registers of
+    /// the first bank, which a register write cannot reach while the second bank is selected.
+    /// Their events are recorded with the register numbers of the first bank, so replaying them
+    /// writes to the second bank instead. While the second bank is selected, use
+    /// [`set_register`](Self::set_register) to keep the recording reproducible. See
+    /// [`ChipType::AY8930`].
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Stop recording register writes, and return the recorded events. Returns an empty vector
+    /// when no recording was in progress.
+    pub fn stop_recording(&mut self) -> Vec<RegisterEvent> {
+        self.recording.take().unwrap_or_default()
+    }
+
+    /// Whether register writes are currently being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Log a register write if a recording is in progress.
+    fn record(&mut self, register: u8, value: u8) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(RegisterEvent {
+                frame: self.frames_rendered,
+                register,
+                value
+            });
+        }
+    }
+
+    /// Update the register file after a register has been changed through one of the typed
+    /// setters, and log its new value if a recording is in progress.
+    fn sync_register(&mut self, register: u8) {
+        let raw = self.bank_a_raw_register(register);
+
+        self.register_file[register as usize] = raw;
+        self.record(register, raw);
+    }
+
+    /// Read the value of a register.
+    ///
+    /// The value is reconstructed from the current state of the channels and the noise and
+    /// envelope generators, so changes made through the other setters are reflected as well.
+    /// Registers 14 and 15 return the values that were last written to the GPIO ports, without
+    /// reading from the connected devices (use [`read_register`](Self::read_register) for that),
+    /// and register numbers above 15 read back as zero.
+    ///
+    /// The unused high bits of registers 1, 3, 5, 6, 8 through 10, and 13 depend on the chip type.
+    /// The YM2149 reads them back as zero, while the AY-3-8910 returns them as they were last
+    /// written using [`set_register`](Self::set_register). Software commonly relies on this to
+    /// detect the chip model, for example by writing 0xFF to register 1 and reading it back.
+    ///
+    /// See [`set_register`](Self::set_register) for the meaning of the individual registers.
+    pub fn register(&self, register: u8) -> u8 {
+        if register >= 16 {
+            return 0;
+        }
+
+        self.chip_type.readback(register as usize, self.raw_register(register))
+    }
+
+    /// Read the raw value of a register from the on-chip register file, including the unused
+    /// bits, regardless of the chip type. Register numbers above 15 read back as zero.
+    ///
+    /// The register file is kept in sync with the rest of the PSG using the following rules:
+    ///
+    /// - Writes through [`set_register`](Self::set_register) (and the methods built on top of it,
+    ///   such as [`write_data`](Self::write_data)) store the byte as written, including the unused
+    ///   bits.
+    /// - The used bits always reflect the current state of the channels and the noise and envelope
+    ///   generators, no matter how that state was changed. This includes the typed setters of this
+    ///   struct, and direct changes through [`channel_mut`](Self::channel_mut),
+    ///   [`noise_generator_mut`](Self::noise_generator_mut) and
+    ///   [`envelope_generator_mut`](Self::envelope_generator_mut).
+    /// - The unused bits keep the value that was last written through
+    ///   [`set_register`](Self::set_register), and are never changed by any other method.
+    ///
+    /// Writing the raw values back using [`set_register`](Self::set_register) therefore restores
+    /// the register state exactly, which makes them suitable for save states.
+    ///
+    /// When emulating an AY8930 in expanded mode, registers 0 through 12 are read from the
+    /// selected register bank. See [`ChipType::AY8930`].
+    pub fn raw_register(&self, register: u8) -> u8 {
+        if self.is_bank_b_register(register) {
+            let index = register as usize;
+
+            return (self.bank_b_register_file[index] & !BANK_B_REGISTER_MASKS[index]) |
+                self.bank_b_register_state(register);
+        }
+
+        self.bank_a_raw_register(register)
+    }
+
+    /// Read the raw value of a register from the regular register bank. See
+    /// [`raw_register`](Self::raw_register).
+    fn bank_a_raw_register(&self, register: u8) -> u8 {
+        let Some(&raw) = self.register_file.get(register as usize) else {
+            return 0;
+        };
+
+        let mask = match register {
+            8..=10 => self.channels[register as usize - 8].amplitude_register_mask(),
+            _ => REGISTER_MASKS[register as usize]
+        };
+
+        (raw & !mask) | self.register_state(register)
+    }
+
+    /// Read the raw values of all sixteen registers at once. See
+    /// [`raw_register`](Self::raw_register).
+    pub fn raw_registers(&self) -> [u8; 16] {
+        core::array::from_fn(|register| self.raw_register(register as u8))
+    }
+
+    /// Reconstruct the used bits of a register from the current state of the channels and the
+    /// noise and envelope generators. The unused bits are set to zero.
+    fn register_state(&self, register: u8) -> u8 {
+        match register {
+            0 => self.channels[0].period_lsb(),
+            1 => self.channels[0].period_msb(),
+            2 => self.channels[1].period_lsb(),
+            3 => self.channels[1].period_msb(),
+            4 => self.channels[2].period_lsb(),
+            5 => self.channels[2].period_msb(),
+            6 => self.noise_generator.period_register(),
+            7 => self.mixer(),
+            8 => self.channels[0].amplitude_and_envelope_enabled(),
+            9 => self.channels[1].amplitude_and_envelope_enabled(),
+            10 => self.channels[2].amplitude_and_envelope_enabled(),
+            11 => self.envelope_generator.period_lsb(),
+            12 => self.envelope_generator.period_msb(),
+            13 => self.envelope_generator.shape(),
+            14 => self.io_ports[0],
+            15 => self.io_ports[1],
+            _ => 0
+        }
+    }
+
+    /// Reconstruct the used bits of a register in the AY8930's second expanded mode register bank.
+    /// The unused bits are set to zero.
+    fn bank_b_register_state(&self, register: u8) -> u8 {
+        match register {
+            0 => self.channel_envelopes[0].period_lsb(),
+            1 => self.channel_envelopes[0].period_msb(),
+            2 => self.channel_envelopes[1].period_lsb(),
+            3 => self.channel_envelopes[1].period_msb(),
+            4 => self.channel_envelopes[0].shape(),
+            5 => self.channel_envelopes[1].shape(),
+            6..=8 => self.channels[register as usize - 6].duty_cycle().register(),
+            _ => 0
+        }
+    }
+
+    /// Read the value of a register like the CPU of an emulated machine would.
+    ///
+    /// This is identical to [`register`](Self::register), except that reads of a GPIO port that
+    /// is configured as an input return the value read from the connected device. Without a
+    /// connected device the stored value is returned. See [`set_ports`](Self::set_ports).
+    pub fn read_register(&mut self, register: u8) -> u8 {
+        let port = match register {
+            14 => 0,
+            15 => 1,
+            _ => return self.register(register)
+        };
+
+        if !self.is_port_output(port) {
+            if let Some(ports) = &mut self.ports {
+                return match port {
+                    0 => ports.read_a(),
+                    _ => ports.read_b()
+                };
+            }
+        }
+
+        self.register(register)
+    }
+
+    /// Read the values of all sixteen registers at once.
+    ///
+    /// See [`register`](Self::register) for details on how the values are read back.
+    pub fn registers(&self) -> [u8; 16] {
+        core::array::from_fn(|register| self.register(register as u8))
+    }
+
+    /// Read the values of the fourteen sound registers at once, skipping the GPIO ports.
+    pub fn sound_registers(&self) -> [u8; 14] {
+        core::array::from_fn(|register| self.register(register as u8))
+    }
+
+    /// Write all sixteen registers at once, for example when replaying a frame from a register
+    /// dump.
+    ///
+    /// The envelope shape register (13) is written last, and only when its value differs from the
+    /// current raw value of the register, including the high bits that select the expanded mode and
+    /// register bank of the AY8930. This is because writing the shape restarts the envelope, while
+    /// register dumps usually repeat the shape in every frame. The value 0xFF means that the shape
+    /// register is not written at all, following the convention of YM register dumps. To restart
+    /// the envelope with an unchanged shape, use [`set_register`](Self::set_register) instead.
+    pub fn set_registers(&mut self, registers: &[u8; 16]) {
+        self.set_register(14, registers[14]);
+        self.set_register(15, registers[15]);
+        self.set_sound_registers(registers.first_chunk().unwrap());
+    }
+
+    /// The way in which register addresses above 15 are handled by
+    /// [`write_address`](Self::write_address).
+    pub fn address_decoding(&self) -> AddressDecoding {
+        self.address_decoding
+    }
+
+    /// Set the way in which register addresses above 15 are handled by
+    /// [`write_address`](Self::write_address). The default is [`AddressDecoding::LowNibble`].
+    pub fn set_address_decoding(&mut self, decoding: AddressDecoding) {
+        self.address_decoding = decoding;
+    }
+
+    /// Latch the register address for subsequent data port accesses.
+    ///
+    /// Together with [`write_data`](Self::write_data) and [`read_data`](Self::read_data) this
+    /// emulates the address/data port interface of the real chip, which makes it easy to connect
+    /// the PSG to an emulated CPU bus. The address remains latched until it is written again, so
+    /// the same register can be accessed multiple times. See
+    /// [`set_address_decoding`](Self::set_address_decoding) for how addresses above 15 are
+    /// handled.
+    pub fn write_address(&mut self, address: u8) {
+        self.address = match self.address_decoding {
+            AddressDecoding::LowNibble => address & 0x0f,
+            AddressDecoding::Full => address
+        };
+    }
+
+    /// The currently latched register address.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Write a value to the register that is selected by the latched address.
+    pub fn write_data(&mut self, value: u8) {
+        self.set_register(self.address, value);
+    }
+
+    /// Read the value of the register that is selected by the latched address.
+    ///
+    /// Like [`read_register`](Self::read_register), this reads from the connected device when the
+    /// selected register is a GPIO port that is configured as an input.
+    pub fn read_data(&mut self) -> u8 {
+        self.read_register(self.address)
+    }
+
+    /// Perform a single bus cycle using the levels of the chip's BDIR and BC1 bus control signals
+    /// and the value on the data bus, as a cycle-accurate machine emulator would present them.
+    ///
+    /// The signals are decoded like on the real chip, with BC2 tied high as it is in most
+    /// machines:
+    ///
+    /// BDIR low, BC1 low: inactive, the data bus is ignored \
+    /// BDIR low, BC1 high: read from the latched register, the value is returned \
+    /// BDIR high, BC1 low: write the data bus value to the latched register \
+    /// BDIR high, BC1 high: latch the data bus value as the register address
+    ///
+    /// Only read cycles return a value. The address latch is shared with
+    /// [`write_address`](Self::write_address), so both interfaces can be mixed freely.
+    pub fn bus_transaction(&mut self, bdir: bool, bc1: bool, data: u8) -> Option<u8> {
+        match (bdir, bc1) {
+            (false, false) => None,
+            (false, true) => Some(self.read_data()),
+            (true, false) => {
+                self.write_data(data);
+                None
+            }
+            (true, true) => {
+                self.write_address(data);
+                None
+            }
+        }
+    }
+
+    /// Write the fourteen sound registers at once, leaving the GPIO ports untouched.
+    ///
+    /// See [`set_registers`](Self::set_registers) for how the envelope shape register is handled.
+    pub fn set_sound_registers(&mut self, registers: &[u8; 14]) {
+        for (register, &value) in registers[..13].iter().enumerate() {
+            self.set_register(register as u8, value);
+        }
+
+        let shape = registers[13];
+
+        if shape != 0xff && shape != self.bank_a_raw_register(13) {
+            self.set_register(13, shape);
+        }
+    }
+
+    /// Save the complete dynamic state of the PSG, including the contents of all filters, the
+    /// clock phase, and any scheduled register writes, so that it can be restored using
+    /// [`load_state`](Self::load_state). See [`PsgState`] for a description of the format.
+    ///
+    /// Like with the `serde` feature, the installed callbacks and an ongoing register write
+    /// recording are not part of the state.
+    pub fn save_state(&self) -> PsgState {
+        let mut writer = StateWriter::new();
+
+        writer.f64(self.clock_rate);
+        writer.u32(self.sample_rate);
+        writer.index(self.dc_filter.size());
+
+        writer.u8(match self.chip_type {
+            ChipType::AY => 0,
+            ChipType::YM => 1,
+            ChipType::AY8930 => 2,
+            ChipType::Sunsoft5B => 3
+        });
+
+        writer.floats(&*self.log2lin_table);
+
+        writer.u8(match self.address_decoding {
+            AddressDecoding::LowNibble => 0,
+            AddressDecoding::Full => 1
+        });
+
+        writer.u8(match self.clock_divider {
+            ClockDivider::Div1 => 0,
+            ClockDivider::Div2 => 1
+        });
+
+        match self.mixing_mode {
+            MixingMode::Linear => writer.u8(0),
+            MixingMode::Analog { compression } => {
+                writer.u8(1);
+                writer.f64(compression);
+            }
+        }
+
+        writer.u8(match self.interpolation_mode() {
+            InterpolationMode::Parabolic => 0,
+            InterpolationMode::BSpline => 1
+        });
+
+        match self.output_filter {
+            OutputFilter::None => writer.u8(0),
+            OutputFilter::LowPass { cutoff_hz } => {
+                writer.u8(1);
+                writer.f64(cutoff_hz);
+            }
+            OutputFilter::Machine(preset) => {
+                writer.u8(2);
+                writer.u8(match preset {
+                    MachinePreset::ZxSpectrum => 0,
+                    MachinePreset::Msx => 1,
+                    MachinePreset::AtariSt => 2,
+                    MachinePreset::AmstradCpc => 3
+                });
+            }
+        }
+
+        match self.dc_filter_mode {
+            DCFilterMode::MovingAverage => writer.u8(0),
+            DCFilterMode::OnePole { cutoff_hz } => {
+                writer.u8(1);
+                writer.f64(cutoff_hz);
+            }
+        }
+
+        writer.bool(self.dc_filter_enabled);
+        writer.bool(self.ayumi_compatible);
+        writer.bool(self.hardware_counters);
+        writer.bool(self.shared_prescaler);
+        writer.bool(self.envelope_smoothing);
+        writer.f64(self.stereo_width);
+        writer.bool(self.channels_swapped);
+
+        writer.u8(match self.fade_curve {
+            FadeCurve::Linear => 0,
+            FadeCurve::Quadratic => 1
+        });
+
+        writer.bool(self.fade.is_some());
+
+        if let Some((frames, remaining)) = self.fade {
+            writer.u64(frames);
+            writer.u64(remaining);
+        }
+
+        for (&muted, &soloed) in self.channels_muted.iter().zip(&self.channels_soloed) {
+            writer.bool(muted);
+            writer.bool(soloed);
+        }
+
+        writer.bool(self.metering_enabled);
+
+        // Chip state
+        for channel in &self.channels {
+            channel.save_state(&mut writer);
+        }
+
+        self.noise_generator.save_state(&mut writer);
+        self.envelope_generator.save_state(&mut writer);
+
+        for envelope in &self.channel_envelopes {
+            envelope.save_state(&mut writer);
+        }
+
+        writer.u8(self.io_direction);
+
+        for &value in self.io_ports.iter().chain(&self.register_file).chain(&self.bank_b_register_file) {
+            writer.u8(value);
+        }
+
+        writer.u8(self.address);
+
+        // Clock and counters
+        writer.u64(self.phase);
+        writer.f64(self.ayumi_phase);
+        writer.u8(self.prescaler);
+        writer.u64(self.frames_rendered);
+        writer.u64(self.ticks_elapsed);
+
+        writer.index(self.scheduled_writes.len());
+
+        for &(tick, register, value) in &self.scheduled_writes {
+            writer.u64(tick);
+            writer.u8(register);
+            writer.u8(value);
+        }
+
+        writer.f64(self.silence_reference.0);
+        writer.f64(self.silence_reference.1);
+        writer.u64(self.silence_frames);
+
+        self.meter.save_state(&mut writer);
+
+        // Filters
+        self.left_interpolator.save_state(&mut writer);
+        self.right_interpolator.save_state(&mut writer);
+        self.left_decimator.save_state(&mut writer);
+        self.right_decimator.save_state(&mut writer);
+
+        for filter in self.left_half_bands.iter().chain(self.right_half_bands.iter()) {
+            filter.save_state(&mut writer);
+        }
+
+        self.dc_filter.save_state(&mut writer);
+        self.one_pole_filter.save_state(&mut writer);
+        self.output_filter_chain.save_state(&mut writer);
+
+        writer.finish()
+    }
+
+    /// Restore the complete dynamic state of the PSG from a state that was saved using
+    /// [`save_state`](Self::save_state). Rendering after loading produces output that is identical
+    /// to the PSG that saved the state.
+    ///
+    /// The state must have been saved by a PSG with the same clock rate, sample rate, and DC
+    /// filter length, otherwise an error describing the mismatch is returned. An error is also
+    /// returned for malformed states and unsupported format versions. The PSG is left untouched
+    /// when an error is returned. The installed callbacks and an ongoing register write recording
+    /// are kept.
+    pub fn load_state(&mut self, state: &PsgState) -> Result<(), Error> {
+        let mut reader = StateReader::new(state)?;
+
+        let clock_rate = reader.f64()?;
+
+        if clock_rate != self.clock_rate {
+            return Err(Error::StateClockRateMismatch { state: clock_rate, psg: self.clock_rate });
+        }
+
+        let sample_rate = reader.u32()?;
+
+        if sample_rate != self.sample_rate {
+            return Err(Error::StateSampleRateMismatch { state: sample_rate, psg: self.sample_rate });
+        }
+
+        let dc_filter_length = reader.u32()? as usize;
+
+        if dc_filter_length != self.dc_filter.size() {
+            return Err(Error::StateDCFilterLengthMismatch { state: dc_filter_length, psg: self.dc_filter.size() });
+        }
+
+        // Restore into a new instance, so that a malformed state leaves this one untouched
+        let mut psg = Self::with_dc_filter_length(self.clock_rate, self.sample_rate, dc_filter_length)?;
+        psg.read_state(&mut reader)?;
+        reader.finish()?;
+
+        psg.ports = self.ports.take();
+        psg.recording = self.recording.take();
+        psg.write_observer = self.write_observer.take();
+        psg.frame_tap = self.frame_tap.take();
+        psg.raw_frame_tap = self.raw_frame_tap.take();
+
+        *self = psg;
+        Ok(())
+    }
+
+    /// Compute a fingerprint of the complete dynamic state of the PSG, as a hash of the state
+    /// returned by [`save_state`](Self::save_state).
+    ///
+    /// The fingerprint is deterministic across platforms, as floating point values are hashed
+    /// using their exact bit patterns. Two PSGs that have the same fingerprint render identical
+    /// output, which makes this useful for asserting that two code paths leave the PSG in the same
+    /// state. See also the [`PartialEq`] implementation, which performs the same comparison.
+    pub fn state_fingerprint(&self) -> u64 {
+        self.save_state().fingerprint()
+    }
+
+    /// Read everything that follows the header of a saved state into this PSG, which must have
+    /// been newly constructed.
+    fn read_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        self.set_chip_type(match reader.u8()? {
+            0 => ChipType::AY,
+            1 => ChipType::YM,
+            2 => ChipType::AY8930,
+            3 => ChipType::Sunsoft5B,
+            _ => return Err(Error::InvalidState)
+        });
+
+        let mut levels = [0.0; 32];
+
+        for level in &mut levels {
+            *level = reader.f64()?;
+        }
+
+        self.set_dac_table(&levels).map_err(|_| Error::InvalidState)?;
+
+        self.address_decoding = match reader.u8()? {
+            0 => AddressDecoding::LowNibble,
+            1 => AddressDecoding::Full,
+            _ => return Err(Error::InvalidState)
+        };
+
+        self.set_clock_divider(match reader.u8()? {
+            0 => ClockDivider::Div1,
+            1 => ClockDivider::Div2,
+            _ => return Err(Error::InvalidState)
+        });
+
+        self.mixing_mode = match reader.u8()? {
+            0 => MixingMode::Linear,
+            1 => MixingMode::Analog { compression: reader.f64()? },
+            _ => return Err(Error::InvalidState)
+        };
+
+        self.set_interpolation_mode(match reader.u8()? {
+            0 => InterpolationMode::Parabolic,
+            1 => InterpolationMode::BSpline,
+            _ => return Err(Error::InvalidState)
+        });
+
+        self.set_output_filter(match reader.u8()? {
+            0 => OutputFilter::None,
+            1 => OutputFilter::LowPass { cutoff_hz: reader.f64()? },
+            2 => OutputFilter::Machine(match reader.u8()? {
+                0 => MachinePreset::ZxSpectrum,
+                1 => MachinePreset::Msx,
+                2 => MachinePreset::AtariSt,
+                3 => MachinePreset::AmstradCpc,
+                _ => return Err(Error::InvalidState)
+            }),
+            _ => return Err(Error::InvalidState)
+        });
+
+        self.set_dc_filter_mode(match reader.u8()? {
+            0 => DCFilterMode::MovingAverage,
+            1 => DCFilterMode::OnePole { cutoff_hz: reader.f64()? },
+            _ => return Err(Error::InvalidState)
+        });
+
+        self.dc_filter_enabled = reader.bool()?;
+        self.set_ayumi_compatible(reader.bool()?);
+        self.set_hardware_counters(reader.bool()?);
+        self.shared_prescaler = reader.bool()?;
+        self.envelope_smoothing = reader.bool()?;
+        self.stereo_width = reader.f64()?;
+        self.channels_swapped = reader.bool()?;
+
+        self.fade_curve = match reader.u8()? {
+            0 => FadeCurve::Linear,
+            1 => FadeCurve::Quadratic,
+            _ => return Err(Error::InvalidState)
+        };
+
+        self.fade = match reader.bool()? {
+            true => Some((reader.u64()?, reader.u64()?)),
+            false => None
+        };
+
+        if matches!(self.fade, Some((frames, remaining)) if remaining > frames) {
+            return Err(Error::InvalidState);
+        }
+
+        for channel in ChannelIndex::ALL {
+            self.set_channel_muted(channel, reader.bool()?);
+            self.set_channel_solo(channel, reader.bool()?);
+        }
+
+        self.metering_enabled = reader.bool()?;
+
+        // Chip state
+        for channel in &mut self.channels {
+            channel.load_state(reader)?;
+        }
+
+        self.noise_generator.load_state(reader)?;
+        self.envelope_generator.load_state(reader)?;
+
+        for envelope in &mut self.channel_envelopes {
+            envelope.load_state(reader)?;
+        }
+
+        self.io_direction = reader.u8()?;
+
+        if self.io_direction & !0xc0 != 0 {
+            return Err(Error::InvalidState);
+        }
+
+        for value in self.io_ports.iter_mut().chain(&mut self.register_file).chain(&mut self.bank_b_register_file) {
+            *value = reader.u8()?;
+        }
+
+        // The saved amplitudes already use the register layout of the saved mode
+        let expanded_mode = self.expanded_mode();
+
+        for channel in &mut self.channels {
+            channel.set_expanded_amplitude(expanded_mode);
+        }
+
+        self.update_expanded_mode();
+
+        self.address = reader.u8()?;
+
+        // Clock and counters
+        self.phase = reader.u64()?;
+        self.ayumi_phase = reader.f64()?;
+        self.prescaler = reader.u8()?;
+
+        if self.prescaler > 1 {
+            return Err(Error::InvalidState);
+        }
+        self.frames_rendered = reader.u64()?;
+        self.ticks_elapsed = reader.u64()?;
+
+        for _ in 0..reader.u32()? {
+            self.scheduled_writes.push_back((reader.u64()?, reader.u8()?, reader.u8()?));
+        }
+
+        self.next_write_tick = self.scheduled_writes.front().map_or(u64::MAX, |&(tick, _, _)| tick);
+
+        self.silence_reference = (reader.f64()?, reader.f64()?);
+        self.silence_frames = reader.u64()?;
+
+        self.meter.load_state(reader)?;
+
+        // Filters
+        self.left_interpolator.load_state(reader)?;
+        self.right_interpolator.load_state(reader)?;
+        self.left_decimator.load_state(reader)?;
+        self.right_decimator.load_state(reader)?;
+
+        for filter in self.left_half_bands.iter_mut().chain(self.right_half_bands.iter_mut()) {
+            filter.load_state(reader)?;
+        }
+
+        self.dc_filter.load_state(reader)?;
+        self.one_pole_filter.load_state(reader)?;
+        self.output_filter_chain.load_state(reader)
+    }
+}
+
+/// Cloning a PSG copies its complete state, including the contents of all filters, so the clone
+/// renders output that is identical to the original. This can be used to preview the effect of a
+/// register write without disturbing the original instance.
+///
+/// The installed callbacks (frame taps, the write observer, and the GPIO port devices) are not
+/// cloned, and need to be installed on the clone separately.
+impl Clone for PSG {
+    fn clone(&self) -> Self {
+        Self {
+            channels: self.channels.clone(),
+            noise_generator: self.noise_generator.clone(),
+            envelope_generator: self.envelope_generator.clone(),
+
+            channel_envelopes: self.channel_envelopes.clone(),
+
+            io_direction: self.io_direction,
+            io_ports: self.io_ports,
+            ports: None,
+
+            register_file: self.register_file,
+            bank_b_register_file: self.bank_b_register_file,
+
+            address: self.address,
+            address_decoding: self.address_decoding,
+
+            chip_type: self.chip_type,
+            log2lin_table: self.log2lin_table.clone(),
+            mixing_mode: self.mixing_mode,
+            hardware_counters: self.hardware_counters,
+            envelope_smoothing: self.envelope_smoothing,
+
+            shared_prescaler: self.shared_prescaler,
+            prescaler: self.prescaler,
+
+            clock_rate: self.clock_rate,
+            clock_divider: self.clock_divider,
+            phase: self.phase,
+            step: self.step,
+            sample_rate: self.sample_rate,
+
+            ayumi_compatible: self.ayumi_compatible,
+            ayumi_phase: self.ayumi_phase,
+            ayumi_step: self.ayumi_step,
+
+            frames_rendered: self.frames_rendered,
+            ticks_elapsed: self.ticks_elapsed,
+
+            scheduled_writes: self.scheduled_writes.clone(),
+            next_write_tick: self.next_write_tick,
+
+            recording: self.recording.clone(),
+            write_observer: None,
+
+            silence_reference: self.silence_reference,
+            silence_frames: self.silence_frames,
+
+            output_filter: self.output_filter,
+            output_filter_chain: self.output_filter_chain.clone(),
+
+            stereo_width: self.stereo_width,
+            channels_swapped: self.channels_swapped,
+
+            fade_curve: self.fade_curve,
+            fade: self.fade,
+
+            channels_muted: self.channels_muted,
+            channels_soloed: self.channels_soloed,
+            channels_audible: self.channels_audible,
+
+            metering_enabled: self.metering_enabled,
+            meter: self.meter.clone(),
+
+            frame_tap: None,
+            raw_frame_tap: None,
+
+            left_interpolator: self.left_interpolator.clone(),
+            right_interpolator: self.right_interpolator.clone(),
+
+            left_decimator: self.left_decimator.clone(),
+            right_decimator: self.right_decimator.clone(),
+
+            left_half_bands: self.left_half_bands.clone(),
+            right_half_bands: self.right_half_bands.clone(),
+            half_band_stages: self.half_band_stages,
+
+            dc_filter: self.dc_filter.clone(),
+            one_pole_filter: self.one_pole_filter.clone(),
+            dc_filter_mode: self.dc_filter_mode,
+            dc_filter_enabled: self.dc_filter_enabled
+        }
+    }
+}
+
+/// Two PSGs are equal when their complete dynamic state is identical, as saved by
+/// [`PSG::save_state`]. This includes the registers, the internal state of the tone, noise, and
+/// envelope generators, the clock phase, the counters, and the contents of all filters.
+/// Floating point values are compared using their exact bit patterns.
+///
+/// The installed callbacks and an ongoing register write recording are not compared.
+impl PartialEq for PSG {
+    fn eq(&self, other: &Self) -> bool {
+        self.save_state() == other.save_state()
+    }
+}
+
+// The filter buffers are left out, as they would drown out the register state
+impl fmt::Debug for PSG {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PSG")
+            .field("chip_type", &self.chip_type)
+            .field("clock_rate", &self.clock_rate)
+            .field("clock_divider", &self.clock_divider)
+            .field("sample_rate", &self.sample_rate)
+            .field("ayumi_compatible", &self.ayumi_compatible)
+            .field("hardware_counters", &self.hardware_counters)
+            .field("shared_prescaler", &self.shared_prescaler)
+            .field("envelope_smoothing", &self.envelope_smoothing)
+            .field("registers", &format_args!("{:02x?}", self.raw_registers()))
+            .field("expanded_mode", &self.expanded_mode())
+            .field("address", &self.address)
+            .field("channels", &self.channels)
+            .field("noise_generator", &self.noise_generator)
+            .field("envelope_generator", &self.envelope_generator)
+            .field("channel_envelopes", &self.channel_envelopes)
+            .field("frames_rendered", &self.frames_rendered)
+            .field("ticks_elapsed", &self.ticks_elapsed)
+            .field("scheduled_writes", &self.scheduled_writes.len())
+            .field("mixing_mode", &self.mixing_mode)
+            .field("interpolation_mode", &self.interpolation_mode())
+            .field("output_filter", &self.output_filter)
+            .field("dc_filter_mode", &self.dc_filter_mode)
+            .field("dc_filter_enabled", &self.dc_filter_enabled)
+            .field("stereo_width", &self.stereo_width)
+            .field("channels_muted", &self.channels_muted)
+            .field("channels_soloed", &self.channels_soloed)
+            .field("channels_swapped", &self.channels_swapped)
+            .field("fade_curve", &self.fade_curve)
+            .field("fade_state", &self.fade_state())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    /// Advance the linear congruential generator that drives the pseudo-random tests, and return
+    /// its new state.
+    fn next_random(seed: &mut u32) -> u32 {
+        *seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        *seed
+    }
+
+    /// Create a PSG that plays the chord with noise and a running envelope that is shared by the
+    /// tests that compare a PSG with a copy, a restored state, or a reset.
+    fn test_tune(clock_rate: f64, sample_rate: u32) -> PSG {
+        let mut psg = PSG::new(clock_rate, sample_rate).unwrap();
+        psg.set_registers(&[0x1c, 0x01, 0xfd, 0x00, 0x33, 0x02, 0x0b, 0x30, 0x0e, 0x10, 0x0a, 0x00, 0x08, 0x0e, 0, 0]);
+        psg
+    }
+
+    /// Count the number of upward zero crossings in the left channel of the next amount of frames.
+    fn count_rising_edges(psg: &mut PSG, frames: usize) -> usize {
+        let mut previous = psg.render().0;
+
+        (0..frames).filter(|_| {
+            let (left, _) = psg.render();
+            let rising = previous < 0.0 && left >= 0.0;
+            previous = left;
+            rising
+        }).count()
+    }
+
+    /// Render the specified amount of frames while writing pseudo-random values to the registers,
+    /// and return an FNV-1a hash of the rendered samples.
+    #[cfg(not(any(feature = "simd", feature = "f32")))]
+    fn render_hash(clock_rate: f64, sample_rate: u32, frames: usize) -> u64 {
+        let mut psg = PSG::new(clock_rate, sample_rate).unwrap();
+        let mut seed: u32 = 12345;
+        let mut hash: u64 = 0xcbf29ce484222325;
+
+        for frame in 0..frames {
+            if frame % 64 == 0 {
+                for _ in 0..4 {
+                    let register = ((next_random(&mut seed) >> 16) % 14) as u8;
+                    psg.set_register(register, (next_random(&mut seed) >> 16) as u8);
+                }
+            }
+
+            let (left, right) = psg.render();
+
+            hash = (hash ^ left.to_bits()).wrapping_mul(0x100000001b3);
+            hash = (hash ^ right.to_bits()).wrapping_mul(0x100000001b3);
+        }
+
+        hash
+    }
+
+    // The vectorized filter sums in a different order and single precision processing loses some
+    // accuracy, so neither is bit-identical
+    #[cfg(not(any(feature = "simd", feature = "f32")))]
+    #[test]
+    fn golden_output() {
+        // These hashes pin the exact output of this implementation as it was recorded, they are not
+        // a comparison with Ayumi. Changes that deliberately alter the output update them, any
+        // other change to the output is considered a regression.
+        assert_eq!(render_hash(1789772.5, 44100, 4096), 0x86162546a2b857c5);
+        assert_eq!(render_hash(2_000_000.0, 11025, 4096), 0xaa96973d117bfe23);
+    }
+
+    /// The reference tune rendered with double precision processing, stored as interleaved little
+    /// endian f32 samples. Regenerate with `cargo test generate_reference_tune -- --ignored`.
+    const REFERENCE_TUNE: &[u8] = include_bytes!("../testdata/reference_tune.bin");
+
+    /// Render a short reference tune, consisting of a melody, an enveloped bass line, and noise
+    /// percussion.
+    fn render_reference_tune() -> Vec<(f64, f64)> {
+        const MELODY: [u16; 8] = [424, 378, 337, 318, 283, 318, 337, 378];
+
+        let mut psg = PSG::new(1_773_400.0, 44100).unwrap();
+
+        psg.set_amplitude(ChannelIndex::A, 13);
+        psg.set_tone_disabled(ChannelIndex::A, false);
+        psg.channel_mut(ChannelIndex::A).set_panning(0.25, true);
+
+        psg.set_tone_period(ChannelIndex::B, 1696);
+        psg.set_tone_disabled(ChannelIndex::B, false);
+        psg.set_envelope_enabled(ChannelIndex::B, true);
+        psg.set_envelope_period(212);
+
+        psg.set_noise_period(6);
+        psg.set_noise_disabled(ChannelIndex::C, false);
+        psg.channel_mut(ChannelIndex::C).set_panning(0.75, true);
+
+        let mut frames = Vec::new();
+
+        for (step, &period) in MELODY.iter().enumerate() {
+            psg.set_tone_period(ChannelIndex::A, period);
+            psg.set_envelope_shape(if step % 2 == 0 { 8 } else { 12 });
+            psg.set_amplitude(ChannelIndex::C, if step % 2 == 0 { 12 } else { 0 });
+
+            frames.extend(psg.render_frames(512));
+        }
+
+        frames
+    }
+
+    #[cfg(not(feature = "f32"))]
+    #[test]
+    #[ignore]
+    fn generate_reference_tune() {
+        let bytes: Vec<u8> = render_reference_tune().into_iter()
+            .flat_map(|(left, right)| [left as f32, right as f32])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+
+        std::fs::write(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/reference_tune.bin"), bytes).unwrap();
+    }
+
+    /// The output of Ayumi for a short register script, stored as interleaved little endian f64
+    /// samples. This was rendered by `testdata/ayumi_reference.c`, which contains the script.
+    #[cfg(not(any(feature = "f32", feature = "simd")))]
+    const AYUMI_REFERENCE: &[u8] = include_bytes!("../testdata/ayumi_reference.bin");
+
+    #[cfg(not(any(feature = "f32", feature = "simd")))]
+    #[test]
+    fn ayumi_compatible() {
+        const REGISTERS: [u8; 14] = [0xa8, 0x01, 0xa0, 0x06, 0x00, 0x00, 0x00, 0x1c, 0x0d, 0x10, 0x0c, 0xd4, 0x00, 0x0e];
+
+        let mut psg = PsgBuilder::new(1_773_400.0, 44100).ayumi_compatible(true).build().unwrap();
+
+        for ((_, channel), balance) in psg.channels_mut().zip([0.25, 0.5, 0.75]) {
+            channel.set_panning(balance, false);
+        }
+
+        for (register, &value) in REGISTERS.iter().enumerate() {
+            psg.set_register(register as u8, value);
+        }
+
+        let mut frames = psg.render_frames(1024);
+
+        psg.set_register(0, 0x51);
+        psg.set_register(6, 0x05);
+        psg.set_register(13, 0x0a);
+
+        frames.extend(psg.render_frames(1024));
+
+        let reference: Vec<(f64, f64)> = AYUMI_REFERENCE.chunks_exact(16)
+            .map(|bytes| {
+                let (left, right) = bytes.split_at(8);
+                (f64::from_le_bytes(left.try_into().unwrap()), f64::from_le_bytes(right.try_into().unwrap()))
+            })
+            .collect();
+
+        assert_eq!(frames, reference);
+
+        // The same script differs slightly without compatibility mode, mostly due to the doubled
+        // noise frequency
+        psg = PsgBuilder::new(1_773_400.0, 44100).build().unwrap();
+
+        for (register, &value) in REGISTERS.iter().enumerate() {
+            psg.set_register(register as u8, value);
+        }
+
+        assert_ne!(psg.render_frames(1024), reference[..1024]);
+    }
+
+    #[test]
+    fn ayumi_step() {
+        // Ayumi accumulates the step exactly as computed from the rates. At low clock rates the
+        // step is small enough that the fixed-point step would round it.
+        let step = 100.0 / (44100.0 * 8.0 * DECIMATE_FACTOR as f64);
+        let mut phase = 0.0;
+
+        let mut psg = PsgBuilder::new(100.0, 44100).ayumi_compatible(true).build().unwrap();
+
+        for _ in 0..100_000 {
+            phase += step;
+
+            let wrapped = phase >= 1.0;
+
+            if wrapped {
+                phase -= 1.0;
+            }
+
+            assert_eq!(psg.advance_phase(), wrapped);
+        }
+
+        assert_eq!(psg.ayumi_phase.to_bits(), phase.to_bits());
+    }
+
+    #[test]
+    fn reference_tune() {
+        let frames = render_reference_tune();
+
+        let reference: Vec<f64> = REFERENCE_TUNE.chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()) as f64)
+            .collect();
+
+        assert_eq!(frames.len() * 2, reference.len());
+
+        let sum = frames.iter()
+            .flat_map(|&(left, right)| [left, right])
+            .zip(reference)
+            .map(|(sample, expected)| (sample - expected) * (sample - expected))
+            .sum::<f64>();
+
+        let difference = (sum / (frames.len() * 2) as f64).sqrt();
+
+        // Double precision processing only differs by the rounding of the stored reference
+        #[cfg(not(feature = "f32"))]
+        assert!(difference < 1e-7, "difference {}", difference);
+
+        // Single precision processing loses some accuracy, which results in an RMS difference of
+        // about 1.6e-7 (-136 dB)
+        #[cfg(feature = "f32")]
+        assert!(difference < 1e-6, "difference {}", difference);
+    }
+
+    #[test]
+    fn struct_size() {
+        assert!(std::mem::size_of::<PSG>() <= 1024, "{}", std::mem::size_of::<PSG>());
+
+        // A PSG can be moved into a collection of chips
+        let chips: Vec<PSG> = (0..4).map(|_| PSG::new(1789772.5, 44100).unwrap()).collect();
+        assert_eq!(chips.len(), 4);
+    }
+
+    #[test]
+    fn phase_lock() {
+        // Doubling the sample rate exactly halves the step, so the instances must stay tick-locked
+        let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+        let mut double_rate_psg = PSG::new(2_000_000.0, 88200).unwrap();
+
+        let mut ticks = 0_u64;
+        let mut double_rate_ticks = 0_u64;
+
+        for _ in 0..100_000_000 {
+            ticks += psg.advance_phase() as u64;
+            double_rate_ticks += double_rate_psg.advance_phase() as u64;
+            double_rate_ticks += double_rate_psg.advance_phase() as u64;
+
+            assert_eq!(ticks, double_rate_ticks);
+        }
+
+        assert_eq!(ticks, (100_000_000.0 * 2_000_000.0 / (44100.0 * 64.0)) as u64);
+    }
+
+    #[test]
+    fn low_sample_rate() {
+        assert!(PSG::new(2_000_000.0, 11025).is_ok());
+        assert!(PSG::new(2_000_000.0, 100).is_err());
+
+        let clock_rate = 1_000_000.0;
+        let mut psg = PSG::new(clock_rate, 8000).unwrap();
+
+        let channel = psg.channel_mut(ChannelIndex::A);
+        channel.set_period(math::frequency_to_tone_period(440.0, clock_rate));
+        channel.set_amplitude(15);
+        channel.set_tone_disabled(false);
+
+        // Let the filters settle before measuring
+        for _ in 0..4000 {
+            psg.render();
+        }
+
+        let expected = math::tone_period_to_frequency(psg.channel(ChannelIndex::A).period(), clock_rate);
+        let measured = count_rising_edges(&mut psg, 8000) as f64;
+
+        assert!((measured - expected).abs() <= 2.0, "measured {} Hz, expected {} Hz", measured, expected);
+    }
+
+    #[test]
+    fn invalid_clock_rate() {
+        for clock_rate in [0.0, -0.0, -1_000_000.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(matches!(PSG::new(clock_rate, 44100), Err(Error::InvalidClockRate(rate)) if rate.to_bits() == clock_rate.to_bits()));
+        }
+
+        // The clock rate is left unchanged, so rendering until a cycle still ends
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        assert_eq!(psg.set_clock_rate(0.0), Err(Error::InvalidClockRate(0.0)));
+        assert_eq!(psg.clock_rate(), 1_000_000.0);
+
+        psg.render_until_cycle(10_000, |_, _| {});
+        assert!(psg.cycles_elapsed() >= 10_000);
+    }
+
+    #[test]
+    fn change_rates() {
+        let mut psg = PSG::new(1_000_000.0, 8000).unwrap();
+        assert_eq!((psg.clock_rate(), psg.sample_rate()), (1_000_000.0, 8000));
+
+        // A 250 Hz tone
+        psg.set_tone_period(ChannelIndex::A, 250);
+        psg.set_amplitude(ChannelIndex::A, 15);
+        psg.set_tone_disabled(ChannelIndex::A, false);
+        psg.prime();
+
+        assert!(count_rising_edges(&mut psg, 8000).abs_diff(250) <= 2);
+
+        // Doubling the clock rate doubles the pitch
+        psg.set_clock_rate(2_000_000.0).unwrap();
+        assert_eq!(psg.clock_rate(), 2_000_000.0);
+        assert!(count_rising_edges(&mut psg, 8000).abs_diff(500) <= 2);
+
+        assert!(psg.set_clock_rate(1e9).is_err());
+        assert_eq!(psg.clock_rate(), 2_000_000.0);
+
+        // Doubling the sample rate keeps the pitch, which now spans twice the amount of frames
+        psg.set_sample_rate(16000).unwrap();
+        psg.prime();
+        assert_eq!(psg.sample_rate(), 16000);
+        assert_eq!(psg.tone_period(ChannelIndex::A), 250);
+        assert!(count_rising_edges(&mut psg, 16000).abs_diff(500) <= 2);
+
+        assert!(psg.set_sample_rate(100).is_err());
+        assert_eq!(psg.sample_rate(), 16000);
+    }
+
+    #[test]
+    fn custom_dac_table() {
+        let mut psg = PSG::with_chip_type(1_000_000.0, 44100, ChipType::AY).unwrap();
+        assert_eq!(psg.dac_table(), DacTable::AY.levels());
+
+        let levels = std::array::from_fn(|level| level as f64 / 32.0);
+        psg.set_dac_table(&levels).unwrap();
+        assert_eq!(psg.dac_table(), levels);
+
+        // Invalid tables are rejected, and leave the current table in place
+        assert_eq!(psg.set_dac_table(&[2.0; 32]), Err(Error::InvalidDacTable(0)));
+        assert_eq!(psg.dac_table(), levels);
+
+        // Channel A at level 15, and channels B and C at level 1, all panned to the center
+        psg.set_mixer(0x3f);
+        psg.set_amplitude(ChannelIndex::A, 7);
+        psg.set_dc_filter_enabled(false);
+        psg.prime();
+
+        // The anti-alias filter's gain at DC is slightly below 1
+        let (left, right) = psg.render();
+        assert!((left - 17.0 / 64.0).abs() < 1e-4 && (right - 17.0 / 64.0).abs() < 1e-4);
+
+        // The table is part of the saved state
+        let mut restored = PSG::new(1_000_000.0, 44100).unwrap();
+        restored.load_state(&psg.save_state()).unwrap();
+        assert_eq!(restored.dac_table(), levels);
+
+        // Setting the chip type selects its built-in table
+        psg.set_chip_type(ChipType::YM);
+        assert_eq!(psg.dac_table(), DacTable::YM.levels());
+    }
+
+    #[test]
+    fn sunsoft_5b() {
+        let mut psg = PSG::with_chip_type(1_789_773.0, 44100, ChipType::Sunsoft5B).unwrap();
+        psg.set_clock_divider(ClockDivider::Div2);
+        assert_eq!(psg.dac_table(), DacTable::Sunsoft5B.levels());
+
+        // The writes to $C000 and $E000 of an NSF driver initializing the 5B and starting an A4
+        // on channel A with a period of 127, which results in a 440.4 Hz tone
+        let script = [(0x07, 0x3e), (0x08, 0x0c), (0x09, 0x00), (0x0a, 0x00), (0x00, 0x7f), (0x01, 0x00)];
+
+        for (address, value) in script {
+            psg.write_address(address);
+            psg.write_data(value);
+        }
+
+        assert_eq!(psg.tone_period(ChannelIndex::A), 127);
+
+        // The pitch is stable across consecutive seconds, once the DC filter has settled
+        let _ = psg.render_frames(4410);
+
+        for _ in 0..2 {
+            assert!(count_rising_edges(&mut psg, 44100).abs_diff(440) <= 1);
+        }
+
+        // The state round trips the chip type
+        let mut restored = PSG::new(1_789_773.0, 44100).unwrap();
+        restored.load_state(&psg.save_state()).unwrap();
+        assert_eq!(restored.chip_type(), ChipType::Sunsoft5B);
+    }
+
+    #[test]
+    fn shared_prescaler() {
+        // The tick on which the noise generator's shift register is first updated after a reset
+        let first_update = |shared: bool| {
+            let mut psg = PsgBuilder::new(1_000_000.0, 44100).shared_prescaler(shared).build().unwrap();
+            psg.reset();
+            psg.set_noise_period(3);
+
+            let initial = psg.noise_generator().state();
+
+            (1..=16).find(|_| {
+                psg.render_tick();
+                psg.noise_generator().state() != initial
+            })
+        };
+
+        assert_eq!(first_update(false), Some(6));
+        assert_eq!(first_update(true), Some(5));
+
+        // The prescaler's phase is part of the state, so a restored PSG continues identically
+        let mut psg = PsgBuilder::new(1_000_000.0, 44100).shared_prescaler(true).build().unwrap();
+        psg.set_noise_period(3);
+        psg.render_tick();
+
+        let mut restored = PSG::new(1_000_000.0, 44100).unwrap();
+        restored.load_state(&psg.save_state()).unwrap();
+        assert!(restored.shared_prescaler());
+
+        for _ in 0..1000 {
+            assert_eq!(psg.render_tick(), restored.render_tick());
+        }
+    }
+
+    #[test]
+    fn envelope_smoothing() {
+        let mut psg = PsgBuilder::new(1_000_000.0, 44100).envelope_smoothing(true).build().unwrap();
+        let table = DacTable::YM.levels();
+
+        // Channel A outputs a slow decay envelope, with its tone and noise disabled
+        psg.set_mixer(0x3f);
+        psg.set_envelope_enabled(ChannelIndex::A, true);
+        psg.set_envelope_period(8);
+        psg.set_envelope_shape(0);
+
+        let mut previous = f64::MAX;
+        let mut decreasing = 0;
+
+        for _ in 0..8 * 31 {
+            let position = psg.envelope_generator().position();
+            let (left, _) = psg.render_tick();
+            let amplitude = widen(left) * 2.0;
+
+            // The amplitude keeps decreasing, and matches the table at the start of each step
+            assert!(amplitude <= previous);
+            decreasing += (amplitude < previous) as usize;
+
+            if position == 7 {
+                let level = psg.envelope_generator().value() as usize;
+                assert!((amplitude - table[level]).abs() < 1e-6);
+            }
+
+            previous = amplitude;
+        }
+
+        // The amplitude changes on every tick instead of once per step, except during the last
+        // step as levels 0 and 1 are both silent, while the register-visible envelope level keeps
+        // its 5-bit steps
+        assert_eq!(decreasing, 8 * 30);
+        assert_eq!(psg.envelope_generator().value(), 0);
+    }
+
+    #[test]
+    fn channel_frequency() {
+        let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+
+        psg.set_clock_divider(ClockDivider::Div2);
+        psg.set_tone_disabled(ChannelIndex::B, true);
+
+        psg.set_channel_frequency(ChannelIndex::B, 440.0);
+        assert_eq!(psg.tone_period(ChannelIndex::B), math::frequency_to_tone_period(440.0, 1_000_000.0));
+        assert_eq!(psg.channel_frequency(ChannelIndex::B), math::tone_period_to_frequency(142, 1_000_000.0));
+
+        psg.set_channel_midi_pitch(ChannelIndex::B, 60.0);
+        assert_eq!(psg.tone_period(ChannelIndex::B), math::midi_pitch_to_tone_period(60.0, 1_000_000.0));
+        assert_eq!(psg.channel_midi_pitch(ChannelIndex::B), math::tone_period_to_midi_pitch(psg.tone_period(ChannelIndex::B), 1_000_000.0));
+
+        // Setting the reported values keeps the period
+        for period in [1, 142, 239, 4095] {
+            psg.set_tone_period(ChannelIndex::C, period);
+
+            psg.set_channel_frequency(ChannelIndex::C, psg.channel_frequency(ChannelIndex::C));
+            assert_eq!(psg.tone_period(ChannelIndex::C), period);
+
+            psg.set_channel_midi_pitch(ChannelIndex::C, psg.channel_midi_pitch(ChannelIndex::C));
+            assert_eq!(psg.tone_period(ChannelIndex::C), period);
+        }
+
+        // Out of range frequencies are clamped
+        psg.set_channel_frequency(ChannelIndex::A, 1.0);
+        assert_eq!(psg.tone_period(ChannelIndex::A), 4095);
+    }
+
+    #[test]
+    fn buzzer() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let pitch = math::tone_period_to_midi_pitch(160, 1_000_000.0);
+
+        assert_eq!(psg.set_buzzer(ChannelIndex::B, pitch, 8, 2.0), Ok(0.0));
+        assert_eq!(psg.tone_period(ChannelIndex::B), 160);
+        assert_eq!(psg.envelope_generator().period(), 5);
+        assert_eq!(psg.envelope_generator().shape(), 8);
+        assert!(psg.envelope_enabled(ChannelIndex::B));
+
+        // Invalid ratios leave the PSG untouched
+        assert!(psg.set_buzzer(ChannelIndex::A, 69.0, 12, 1000.0).is_err());
+        assert_eq!(psg.envelope_generator().shape(), 8);
+        assert!(!psg.envelope_enabled(ChannelIndex::A));
+    }
+
+    #[test]
+    fn volume_leakage() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        // A 625 Hz tone at amplitude 0, which toggles the channel between levels 0 and 1
+        psg.set_tone_period(ChannelIndex::A, 50);
+        psg.set_mixer(0x3e);
+
+        let peak = |psg: &mut PSG| {
+            psg.render_frames(4410).iter().skip(2205).fold(0.0f64, |peak, &(left, _)| peak.max(left.abs()))
+        };
+
+        assert_eq!(peak(&mut psg), 0.0);
+
+        psg.set_dac_table(&DacTable::YM.biased_levels(DacTable::DEFAULT_BIAS)).unwrap();
+        assert!(peak(&mut psg) > DacTable::DEFAULT_BIAS / 8.0);
+    }
+
+    #[test]
+    fn clock_divider() {
+        let mut psg = PSG::new(2_000_000.0, 8000).unwrap();
+
+        // A 500 Hz tone, which drops to 250 Hz when the clock is divided by two
+        psg.set_tone_period(ChannelIndex::A, 250);
+        psg.set_amplitude(ChannelIndex::A, 15);
+        psg.set_tone_disabled(ChannelIndex::A, false);
+        psg.prime();
+
+        assert!(count_rising_edges(&mut psg, 8000).abs_diff(500) <= 2);
+
+        psg.set_clock_divider(ClockDivider::Div2);
+        assert_eq!((psg.clock_rate(), psg.effective_clock_rate()), (2_000_000.0, 1_000_000.0));
+        assert!(count_rising_edges(&mut psg, 8000).abs_diff(250) <= 2);
+
+        let frequency = math::tone_period_to_frequency(psg.tone_period(ChannelIndex::A), psg.effective_clock_rate());
+        assert_eq!(frequency, 250.0);
+
+        // The divider is part of the saved state
+        let mut restored = PSG::new(2_000_000.0, 8000).unwrap();
+        restored.load_state(&psg.save_state()).unwrap();
+        assert_eq!(restored.clock_divider(), ClockDivider::Div2);
+        assert_eq!(restored.render_frames(256), psg.render_frames(256));
+
+        psg.set_clock_divider(ClockDivider::Div1);
+        assert!(count_rising_edges(&mut psg, 8000).abs_diff(500) <= 2);
+    }
+
+    #[test]
+    fn counters() {
+        let mut psg = PSG::new(1_000_000.0, 50000).unwrap();
+
+        for _ in 0..50000 {
+            psg.render();
+        }
+
+        psg.set_chip_type(ChipType::AY);
+
+        assert_eq!(psg.frames_rendered(), 50000);
+        assert_eq!(psg.elapsed_seconds(), 1.0);
+
+        // One second at a clock rate of 1 MHz should produce 125000 ticks
+        assert!(psg.ticks_elapsed().abs_diff(125000) <= 1);
+
+        psg.reset_counters();
+
+        assert_eq!(psg.frames_rendered(), 0);
+        assert_eq!(psg.ticks_elapsed(), 0);
+        assert_eq!(psg.elapsed_seconds(), 0.0);
+    }
+
+    #[test]
+    fn silence_detection() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        assert!(psg.is_silent());
+
+        psg.set_amplitude(ChannelIndex::B, 15);
+        psg.set_tone_period(ChannelIndex::B, 100);
+        psg.set_tone_disabled(ChannelIndex::B, false);
+
+        assert!(!psg.is_silent());
+
+        for _ in 0..4410 {
+            psg.render();
+        }
+
+        assert!(psg.silence_duration_frames() < 100);
+
+        // Disabling the tone leaves a constant level, which should also be considered silent
+        psg.set_tone_disabled(ChannelIndex::B, true);
+
+        assert!(psg.is_silent());
+
+        for _ in 0..2048 {
+            psg.render();
+        }
+
+        assert!(psg.silence_duration_frames() >= 2000);
+
+        // A decayed envelope is silent as well
+        psg.set_tone_disabled(ChannelIndex::B, false);
+        psg.set_envelope_enabled(ChannelIndex::B, true);
+        psg.set_envelope_period(10);
+        psg.set_envelope_shape(0);
+
+        assert!(!psg.is_silent());
+
+        for _ in 0..4410 {
+            psg.render();
+        }
+
+        assert!(psg.is_silent());
+
+        // A repeating envelope without tone and noise is an audible buzzer voice
+        psg.set_tone_disabled(ChannelIndex::B, true);
+        psg.set_noise_disabled(ChannelIndex::B, true);
+        psg.set_envelope_shape(8);
+
+        assert!(!psg.is_silent());
+
+        for _ in 0..4410 {
+            psg.render();
+        }
+
+        assert!(!psg.is_silent());
+    }
+
+    #[test]
+    fn metering() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        psg.set_amplitude(ChannelIndex::A, 15);
+        psg.set_tone_period(ChannelIndex::A, 100);
+        psg.set_tone_disabled(ChannelIndex::A, false);
+        psg.channel_mut(ChannelIndex::A).set_panning(0.0, false);
+
+        for _ in 0..4410 {
+            psg.render();
+        }
+
+        // Nothing is measured while metering is disabled
+        assert_eq!(psg.meter(), Meter::default());
+
+        psg.enable_metering(true);
+
+        for _ in 0..4410 {
+            psg.render();
+        }
+
+        let meter = psg.meter();
+
+        assert_eq!(meter.channel_peaks, [1.0, 0.0, 0.0]);
+        assert!(meter.left_peak > 0.4);
+        assert!(meter.left_rms > 0.4 && meter.left_rms <= meter.left_peak);
+        assert!(meter.right_peak < 0.0001);
+
+        // Reading the meter resets it
+        assert_eq!(psg.meter(), Meter::default());
+    }
+
+    #[test]
+    fn channel_mute_and_solo() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let audible = |psg: &PSG| ChannelIndex::ALL.map(|channel| psg.is_channel_audible(channel));
+
+        assert_eq!(audible(&psg), [true, true, true]);
+
+        psg.set_channel_muted(ChannelIndex::A, true);
+        assert_eq!(audible(&psg), [false, true, true]);
+
+        // Soloing a channel silences the other channels
+        psg.set_channel_solo(ChannelIndex::B, true);
+        assert_eq!(audible(&psg), [false, true, false]);
+
+        psg.set_channel_solo(ChannelIndex::C, true);
+        assert_eq!(audible(&psg), [false, true, true]);
+
+        // Muting takes precedence over soloing
+        psg.set_channel_solo(ChannelIndex::A, true);
+        assert_eq!(audible(&psg), [false, true, true]);
+
+        psg.set_channel_muted(ChannelIndex::A, false);
+        assert_eq!(audible(&psg), [true, true, true]);
+
+        psg.set_channel_solo(ChannelIndex::B, false);
+        psg.set_channel_solo(ChannelIndex::C, false);
+        assert_eq!(audible(&psg), [true, false, false]);
+
+        psg.set_channel_solo(ChannelIndex::A, false);
+        assert_eq!(audible(&psg), [true, true, true]);
+        assert!(!psg.channel_muted(ChannelIndex::A) && !psg.channel_solo(ChannelIndex::A));
+
+        // A muted channel renders like a silent channel, without touching the registers
+        let mut reference = PSG::new(1_000_000.0, 44100).unwrap();
+
+        for psg in [&mut psg, &mut reference] {
+            psg.set_registers(&[100, 0, 150, 0, 200, 0, 0, 0x38, 15, 15, 15, 0, 0, 0, 0, 0]);
+            psg.enable_metering(true);
+            psg.start_recording();
+        }
+
+        psg.set_channel_muted(ChannelIndex::B, true);
+        reference.set_amplitude(ChannelIndex::B, 0);
+
+        for _ in 0..4410 {
+            assert_eq!(psg.render(), reference.render());
+        }
+
+        assert_eq!(psg.register(9), 15);
+        assert_eq!(psg.meter().channel_peaks, [1.0, 0.0, 1.0]);
+        assert!(psg.stop_recording().is_empty());
+    }
+
+    #[test]
+    fn fade_out() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let mut reference = PSG::new(1_000_000.0, 44100).unwrap();
+
+        for psg in [&mut psg, &mut reference] {
+            psg.set_registers(&[100, 0, 0, 0, 0, 0, 0, 0x3e, 15, 0, 0, 0, 0, 0, 0, 0]);
+            let _ = psg.render_frames(100);
+
+            // The write falls inside the fade, and must still be applied
+            psg.write_register_at(psg.cycles_elapsed() + 48, 0, 50);
+        }
+
+        psg.begin_fade_out(4);
+        assert_eq!(psg.fade_state(), FadeState::FadingOut { remaining_frames: 4 });
+
+        for gain in [0.75, 0.5, 0.25, 0.0] {
+            let (left, right) = reference.render();
+            assert_eq!(psg.render(), (left * gain, right * gain));
+        }
+
+        // The output stays silent after the last frame of the fade
+        assert_eq!(psg.fade_state(), FadeState::Finished);
+        assert_eq!(psg.tone_period(ChannelIndex::A), 50);
+
+        for _ in 0..10 {
+            let _ = reference.render();
+            assert_eq!(psg.render(), (0.0, 0.0));
+        }
+
+
+        psg.cancel_fade();
+        assert_eq!(psg.fade_state(), FadeState::Inactive);
+
+        for _ in 0..100 {
+            assert_eq!(psg.render(), reference.render());
+        }
+
+        psg.set_fade_curve(FadeCurve::Quadratic);
+        psg.begin_fade_out(2);
+
+        let (left, right) = reference.render();
+        assert_eq!(psg.render(), (left * 0.25, right * 0.25));
+    }
+
+    #[test]
+    fn panic() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        psg.set_registers(&[100, 0, 150, 0, 200, 0, 5, 0x00, 0x10, 15, 0x10, 0x00, 0x02, 0x0e, 0, 0]);
+        let _ = psg.render_frames(4410);
+
+        psg.write_register_at(psg.cycles_elapsed() + 8000, 9, 15);
+        psg.panic();
+
+        assert!(psg.is_silent());
+        assert_eq!(psg.raw_registers(), [100, 0, 150, 0, 200, 0, 5, 0x3f, 0, 0, 0, 0x00, 0x02, 0x0e, 0, 0]);
+
+        // Allow the DC filter to decay after the step to silence
+        let _ = psg.render_frames(psg.dc_filter_length());
+
+        let frames = psg.render_frames(2048);
+        let rms = (frames.iter().map(|(left, right)| left * left + right * right).sum::<f64>() / 4096.0).sqrt();
+
+        assert!(rms < 0.001);
+        assert_eq!(psg.amplitude(ChannelIndex::B), 0);
+    }
+
+    #[test]
+    fn render_add_into() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let mut reference = PSG::new(1_000_000.0, 44100).unwrap();
+
+        for psg in [&mut psg, &mut reference] {
+            psg.set_amplitude(ChannelIndex::A, 15);
+            psg.set_tone_period(ChannelIndex::A, 100);
+            psg.set_tone_disabled(ChannelIndex::A, false);
+        }
+
+        let mut left = [1.0; 256];
+        let mut right = [2.0; 256];
+
+        psg.render_add_into(&mut left, &mut right, 0.5).unwrap();
+
+        for (left, right) in left.iter().zip(right.iter()) {
+            let (expected_left, expected_right) = reference.render();
+
+            assert_eq!(*left, 1.0 + expected_left * 0.5);
+            assert_eq!(*right, 2.0 + expected_right * 0.5);
+        }
+
+        assert!(matches!(
+            psg.render_add_into(&mut [0.0; 4], &mut [0.0; 5], 1.0),
+            Err(Error::BufferLengthMismatch { left: 4, right: 5 })
+        ));
+    }
+
+    #[test]
+    fn render_into_samples() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let mut reference = PSG::new(1_000_000.0, 44100).unwrap();
+
+        for psg in [&mut psg, &mut reference] {
+            psg.set_amplitude(ChannelIndex::A, 15);
+            psg.set_tone_period(ChannelIndex::A, 100);
+            psg.set_tone_disabled(ChannelIndex::A, false);
+        }
+
+        let mut buffer = [0i16; 513];
+        psg.render_into_samples(&mut buffer);
+
+        for frame in buffer.chunks_exact(2) {
+            let (left, right) = reference.render();
+
+            assert_eq!(frame[0], i16::from_f64(left));
+            assert_eq!(frame[1], i16::from_f64(right));
+        }
+
+        assert_eq!(buffer[512], 0);
+        assert_eq!(psg.frames_rendered(), 256);
+    }
+
+    #[test]
+    fn render_frames() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let mut reference = PSG::new(1_000_000.0, 44100).unwrap();
+
+        for psg in [&mut psg, &mut reference] {
+            psg.set_amplitude(ChannelIndex::A, 15);
+            psg.set_tone_period(ChannelIndex::A, 100);
+            psg.set_tone_disabled(ChannelIndex::A, false);
+        }
+
+        let frames = psg.render_frames(1000);
+
+        assert_eq!(frames.len(), 1000);
+        assert!(frames.iter().all(|&frame| frame == reference.render()));
+
+        assert_eq!(psg.render_seconds(0.01).len(), 441);
+        assert_eq!(psg.render_seconds(0.00001).len(), 0);
+    }
+
+    #[test]
+    fn frame_taps() {
+        use std::sync::{Arc, Mutex};
+
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        psg.set_amplitude(ChannelIndex::A, 15);
+        psg.set_tone_period(ChannelIndex::A, 100);
+        psg.set_tone_disabled(ChannelIndex::A, false);
+
+        let tapped = Arc::new(Mutex::new(Vec::new()));
+        let raw_tapped = Arc::new(Mutex::new(Vec::new()));
+
+        let frames = Arc::clone(&tapped);
+        psg.set_frame_tap(Some(Box::new(move |left, right| frames.lock().unwrap().push((left, right)))));
+
+        let frames = Arc::clone(&raw_tapped);
+        psg.set_raw_frame_tap(Some(Box::new(move |left, right| frames.lock().unwrap().push((left, right)))));
+
+        let rendered = psg.render_frames(100);
+
+        psg.set_frame_tap(None);
+        psg.set_raw_frame_tap(None);
+        psg.render();
+
+        assert_eq!(*tapped.lock().unwrap(), rendered);
+
+        // The raw frames have not been DC filtered, so they differ from the rendered frames
+        let raw_tapped = raw_tapped.lock().unwrap();
+
+        assert_eq!(raw_tapped.len(), 100);
+        assert!(raw_tapped.iter().zip(rendered.iter()).any(|(raw, frame)| raw != frame));
+    }
+
+    #[test]
+    fn prime() {
+        // With these rates a single period of the tone spans exactly 64 frames, which fits the DC
+        // filter's window an integer amount of times
+        let mut psg = PSG::new(1_102_500.0, 44100).unwrap();
+
+        psg.set_amplitude(ChannelIndex::A, 15);
+        psg.set_tone_period(ChannelIndex::A, 100);
+        psg.set_tone_disabled(ChannelIndex::A, false);
+        psg.prime();
+
+        assert_eq!(psg.frames_rendered(), 0);
+        assert_eq!(psg.ticks_elapsed(), 0);
+
+        let peak = |frames: &[(f64, f64)]| frames.iter().fold(0.0_f64, |peak, (left, _)| peak.max(left.abs()));
+
+        let frames = psg.render_frames(300);
+        let first = peak(&frames[0..64]);
+        let steady = peak(&frames[200..264]);
+
+        assert!((first - steady).abs() < 0.01 * steady, "first {}, steady {}", first, steady);
+
+        // The output filters are primed as well, so the first period matches a later one frame by
+        // frame, and the primed frames are not metered
+        let mut psg = PSG::new(1_102_500.0, 44100).unwrap();
+
+        psg.set_amplitude(ChannelIndex::A, 15);
+        psg.set_tone_period(ChannelIndex::A, 100);
+        psg.set_tone_disabled(ChannelIndex::A, false);
+        psg.set_output_filter(OutputFilter::Machine(MachinePreset::ZxSpectrum));
+        psg.enable_metering(true);
+        psg.prime();
+
+        assert_eq!(psg.meter(), Meter::default());
+
+        let frames = psg.render_frames(256);
+        let steady = peak(&frames[192..]);
+
+        for (frame, (first, later)) in frames[..64].iter().zip(&frames[192..]).enumerate() {
+            assert!((first.0 - later.0).abs() < 0.01 * steady, "frame {}: first {}, later {}", frame, first.0, later.0);
+        }
+
+        assert!(psg.meter().left_peak > 0.0);
+    }
+
+    #[test]
+    fn dc_filter() {
+        assert!(matches!(PSG::with_dc_filter_length(1_000_000.0, 44100, 1000), Err(Error::InvalidDCFilterLength(1000))));
+
+        let mut psg = PSG::with_dc_filter_length(1_000_000.0, 44100, 512).unwrap();
+
+        // A channel with both tone and noise disabled outputs a constant level
+        psg.set_amplitude(ChannelIndex::A, 15);
+
+        let mean = |frames: Vec<(f64, f64)>| frames.iter().map(|(left, _)| left).sum::<f64>() / frames.len() as f64;
+
+        psg.prime();
+        assert!(mean(psg.render_frames(4096)).abs() < 1e-6);
+
+        psg.set_dc_filter_enabled(false);
+        assert!((mean(psg.render_frames(4096)) - 0.5).abs() < 0.001);
+
+        psg.set_dc_filter_enabled(true);
+        assert!(mean(psg.render_frames(4096)).abs() < 1e-6);
+
+        psg.set_dc_filter_mode(DCFilterMode::OnePole { cutoff_hz: 20.0 });
+        let _ = psg.render_frames(44100);
+        assert!(mean(psg.render_frames(4096)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn register_readback() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let mut expected: [u8; 16] = std::array::from_fn(|register| psg.register(register as u8));
+        let mut seed: u32 = 1;
+
+        // Every register keeps its value when other registers are written
+        for _ in 0..10_000 {
+            next_random(&mut seed);
+
+            let register = ((seed >> 16) & 15) as usize;
+            let value = (seed >> 8) as u8;
+
+            psg.set_register(register as u8, value);
+            expected[register] = value & REGISTER_MASKS[register];
+
+            for (register, expected) in expected.iter().enumerate() {
+                assert_eq!(psg.register(register as u8), *expected, "register {}", register);
+            }
+        }
+
+        assert_eq!(psg.register(16), 0);
+
+        // Changes made through other setters are reflected as well
+        psg.set_tone_period(ChannelIndex::B, 0x0234);
+        psg.set_amplitude(ChannelIndex::C, 9);
+        psg.set_envelope_enabled(ChannelIndex::C, true);
+
+        assert_eq!((psg.register(2), psg.register(3), psg.register(10)), (0x34, 0x02, 0x19));
+    }
+
+    #[test]
+    fn chip_type_readback() {
+        let mut psg = PSG::with_chip_type(1_000_000.0, 44100, ChipType::AY).unwrap();
+        assert_eq!(psg.chip_type(), ChipType::AY);
+
+        for (register, value) in [(1, 0xff), (6, 0xe5), (8, 0xf3), (13, 0xfa)] {
+            psg.set_register(register, value);
+        }
+
+        psg.set_chip_type(ChipType::YM);
+        assert_eq!([1, 6, 8, 13].map(|register| psg.register(register)), [0x0f, 0x05, 0x13, 0x0a]);
+
+        psg.set_chip_type(ChipType::AY);
+        assert_eq!([1, 6, 8, 13].map(|register| psg.register(register)), [0xff, 0xe5, 0xf3, 0xfa]);
+
+        // The used bits still reflect changes made through other setters
+        psg.set_tone_period(ChannelIndex::A, 0x0123);
+        psg.set_amplitude(ChannelIndex::A, 2);
+        assert_eq!((psg.register(1), psg.register(8)), (0xf1, 0xf2));
+    }
+
+    #[test]
+    fn ay8930_duty_cycle() {
+        const REGISTERS: [u8; 16] = [0x00, 0x01, 0, 0, 0, 0, 0x05, 0x3e, 0x0f, 0, 0, 0, 0, 0, 0, 0];
+
+        // In compatibility mode the duty cycle is ignored, and the output matches the AY-3-8910
+        let mut ay = PSG::with_chip_type(1_000_000.0, 44100, ChipType::AY).unwrap();
+        let mut psg = PSG::with_chip_type(1_000_000.0, 44100, ChipType::AY8930).unwrap();
+
+        ay.set_registers(&REGISTERS);
+        psg.set_registers(&REGISTERS);
+        psg.channel_mut(ChannelIndex::A).set_duty_cycle(DutyCycle::Quarter);
+
+        assert!(!psg.expanded_mode());
+        assert_eq!(psg.render_frames(1024), ay.render_frames(1024));
+
+        // Select expanded mode with register bank B, and set a 25% duty cycle for channel A
+        psg.set_register(13, 0xb0);
+        psg.set_register(6, 0x03);
+
+        assert!(psg.expanded_mode());
+        assert_eq!(psg.register(6), 0x03);
+        assert_eq!(psg.noise_generator().period(), 5);
+
+        // Switching back to bank A keeps the duty cycle, and the amplitude of channel A is raised
+        // to the 5-bit equivalent of 15
+        psg.set_register(13, 0xa0);
+        psg.set_register(8, 0x1f);
+
+        assert_eq!(psg.register(6), 0x05);
+        assert_eq!(psg.channel(ChannelIndex::A).duty_cycle(), DutyCycle::Quarter);
+
+        let mean = |frames: &[(f64, f64)]| frames.iter().map(|&(left, _)| left).sum::<f64>() / frames.len() as f64;
+
+        ay.set_dc_filter_enabled(false);
+        psg.set_dc_filter_enabled(false);
+
+        let square = ay.render_frames(44100);
+        let pulse = psg.render_frames(44100);
+        let peak = square.iter().map(|&(left, _)| left).fold(0.0, f64::max);
+
+        // The pulse is high for a quarter of the time, at the same level as the square wave
+        let high = pulse.iter().filter(|&&(left, _)| left > peak * 0.5).count() as f64 / pulse.len() as f64;
+
+        assert!((high - 0.25).abs() < 0.01, "high {}", high);
+        assert!((mean(&pulse) / mean(&square) - 0.5).abs() < 0.01);
+
+        // Any other value switches back to compatibility mode
+        psg.set_register(13, 0x00);
+        assert!(!psg.expanded_mode());
+    }
+
+    #[test]
+    fn ay8930_amplitudes() {
+        let mut psg = PSG::with_chip_type(1_000_000.0, 44100, ChipType::AY8930).unwrap();
+        let levels: [f64; 32] = core::array::from_fn(|level| level as f64 / 31.0);
+
+        psg.set_dac_table(&levels).unwrap();
+
+        let levels = psg.dac_table();
+
+        psg.set_channel_muted(ChannelIndex::B, true);
+        psg.set_channel_muted(ChannelIndex::C, true);
+        psg.channel_mut(ChannelIndex::A).set_panning(0.0, false);
+        psg.set_register(7, 0x3f);
+
+        // In compatibility mode the fixed amplitudes only reach the odd levels
+        psg.set_register(8, 0x1f);
+        assert_eq!((psg.amplitude(ChannelIndex::A), psg.channel(ChannelIndex::A).envelope_enabled()), (15, true));
+
+        psg.set_register(8, 0x0f);
+        assert_eq!(widen(psg.render_tick().0), levels[31]);
+
+        // In expanded mode all 32 levels are reachable, and bit 5 enables the envelope
+        psg.set_register(13, 0xa0);
+
+        for amplitude in 0..32 {
+            psg.set_register(8, amplitude);
+
+            assert_eq!(psg.amplitude(ChannelIndex::A), amplitude);
+            assert_eq!(psg.register(8), amplitude);
+            assert_eq!(widen(psg.render_tick().0), levels[amplitude as usize]);
+        }
+
+        psg.set_amplitude(ChannelIndex::A, 0x3e);
+        assert_eq!((psg.amplitude(ChannelIndex::A), psg.raw_register(8)), (0x1e, 0x1e));
+
+        psg.set_register(8, 0x3f);
+        assert!(psg.channel(ChannelIndex::A).envelope_enabled());
+        assert_eq!(psg.amplitude(ChannelIndex::A), 31);
+
+        // The bits are reinterpreted when switching back to compatibility mode
+        psg.set_register(8, 0x1b);
+        psg.set_register(13, 0x00);
+
+        assert_eq!((psg.amplitude(ChannelIndex::A), psg.channel(ChannelIndex::A).envelope_enabled()), (11, true));
+        assert_eq!(psg.register(8), 0x1b);
+    }
+
+    #[test]
+    fn ay8930_channel_envelopes() {
+        let mut psg = PSG::with_chip_type(1_000_000.0, 44100, ChipType::AY8930).unwrap();
+
+        // Channel A is panned fully left, channel B fully right, and channel C is muted
+        psg.channel_mut(ChannelIndex::A).set_panning(0.0, false);
+        psg.channel_mut(ChannelIndex::B).set_panning(1.0, false);
+        psg.set_registers(&[0, 0, 0, 0, 0, 0, 0, 0x3f, 0x00, 0x00, 0x00, 5, 0, 0xff, 0, 0]);
+
+        // Channel A uses the regular envelope registers, channel B is set up through bank B
+        psg.set_register(13, 0xbe);
+        psg.set_register(0, 3);
+        psg.set_register(1, 0);
+        psg.set_register(4, 0x08);
+
+        assert_eq!(psg.register(0), 3);
+        assert_eq!(psg.register(4), 0x08);
+        assert_eq!(psg.channel_envelope(ChannelIndex::B).period(), 3);
+        assert_eq!(psg.envelope_generator().period(), 5);
+
+        psg.set_register(13, 0xae);
+        assert_eq!(psg.register(13), 0xae);
+        assert_eq!(psg.register(0), 0);
+
+        // The envelope enabled flag is bit 5 of the amplitude registers in expanded mode
+        psg.set_register(8, 0x20);
+        psg.set_register(9, 0x20);
+
+        let mut expected_a = EnvelopeGenerator::new();
+        expected_a.set_period(5);
+        expected_a.set_shape(0x0e);
+
+        let mut expected_b = EnvelopeGenerator::new();
+        expected_b.set_period(3);
+        expected_b.set_shape(0x08);
+
+        let table = DacTable::AY.table();
+
+        for _ in 0..500 {
+            let (left, right) = psg.render_tick();
+
+            assert_eq!(left, table[expected_a.render() as usize]);
+            assert_eq!(right, table[expected_b.render() as usize]);
+        }
+
+        // The generators of channels B and C are not used in compatibility mode
+        psg.set_register(13, 0x0e);
+        psg.set_register(8, 0x10);
+        psg.set_register(9, 0x10);
+        expected_a.set_shape(0x0e);
+
+        let value_b = psg.channel_envelope(ChannelIndex::B).value();
+
+        for _ in 0..100 {
+            let level = table[expected_a.render() as usize];
+
+            assert_eq!(psg.render_tick(), (level, level));
+        }
+
+        assert_eq!(psg.channel_envelope_mut(ChannelIndex::B).value(), value_b);
+    }
+
+    #[test]
+    fn set_registers() {
+        let frame = [
+            0x00, 0x01, 0x80, 0x00, 0x40, 0x00, 0x10, 0x38,
+            0x10, 0x10, 0x0f, 0x40, 0x00, 0x0c, 0x12, 0x34
+        ];
+
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        psg.set_registers(&frame);
+        assert_eq!(psg.registers(), frame);
+        assert_eq!(psg.sound_registers(), *frame.first_chunk().unwrap());
+
+        for _ in 0..100 {
+            psg.render();
+        }
+
+        // Replaying the frame with an unchanged shape, or without a shape write, must not restart
+        // the envelope
+        let mut replayed = PSG::new(1_000_000.0, 44100).unwrap();
+        replayed.set_registers(&frame);
+        for _ in 0..100 {
+            replayed.render();
+        }
+        replayed.set_registers(&frame);
+
+        let mut skipped = PSG::new(1_000_000.0, 44100).unwrap();
+        skipped.set_registers(&frame);
+        for _ in 0..100 {
+            skipped.render();
+        }
+
+        let mut unwritten = *frame.first_chunk::<14>().unwrap();
+        unwritten[13] = 0xff;
+        skipped.set_sound_registers(&unwritten);
+
+        let expected = psg.render_frames(1000);
+        assert_eq!(replayed.render_frames(1000), expected);
+        assert_eq!(skipped.render_frames(1000), expected);
+        assert_eq!(skipped.registers(), frame);
+
+        // Writing the shape register directly restarts the envelope
+        let mut restarted = PSG::new(1_000_000.0, 44100).unwrap();
+        restarted.set_registers(&frame);
+        for _ in 0..100 {
+            restarted.render();
+        }
+        restarted.set_register(13, 0x0c);
+
+        assert_ne!(restarted.render_frames(1000), psg.render_frames(1000));
+
+        // Changing only the high bits of the shape register switches the AY8930's mode and bank
+        let mut psg = PSG::with_chip_type(1_000_000.0, 44100, ChipType::AY8930).unwrap();
+        psg.set_registers(&frame);
+
+        let mut expanded = frame;
+        expanded[13] = 0xbc;
+        psg.set_registers(&expanded);
+        assert!(psg.expanded_mode());
+        assert_eq!(psg.raw_register(13), 0xbc);
+
+        expanded[13] = 0x0c;
+        psg.set_registers(&expanded);
+        assert!(!psg.expanded_mode());
+    }
+
+    #[test]
+    fn address_latch() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        psg.write_address(8);
+        psg.write_data(0x03);
+        assert_eq!(psg.channel(ChannelIndex::A).amplitude(), 3);
+
+        // The address stays latched for subsequent writes and reads
+        psg.write_data(0x07);
+        psg.write_data(0x1c);
+        assert_eq!(psg.read_data(), 0x1c);
+        assert_eq!((psg.channel(ChannelIndex::A).amplitude(), psg.channel(ChannelIndex::A).envelope_enabled()), (12, true));
+
+        psg.write_address(0);
+        psg.write_data(0x34);
+        psg.write_address(1);
+        psg.write_data(0x02);
+        assert_eq!(psg.channel(ChannelIndex::A).period(), 0x0234);
+
+        psg.write_address(0);
+        assert_eq!(psg.read_data(), 0x34);
+
+        // Only the low nibble of high addresses is latched by default
+        psg.write_address(0x1d);
+        assert_eq!(psg.address(), 13);
+        psg.write_data(0x0e);
+        assert_eq!(psg.envelope_generator().shape(), 0x0e);
+        assert_eq!(psg.read_data(), 0x0e);
+
+        // With full decoding high addresses deselect all registers
+        psg.set_address_decoding(AddressDecoding::Full);
+        psg.write_address(0x18);
+        psg.write_data(0x05);
+        assert_eq!(psg.read_data(), 0);
+        assert_eq!(psg.channel(ChannelIndex::A).amplitude(), 12);
+    }
+
+    #[test]
+    fn bus_transaction() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        assert_eq!(psg.bus_transaction(true, true, 2), None);
+        assert_eq!(psg.bus_transaction(false, false, 0xff), None);
+        assert_eq!(psg.bus_transaction(true, false, 0x56), None);
+        assert_eq!(psg.bus_transaction(false, false, 0xff), None);
+        assert_eq!(psg.bus_transaction(false, true, 0xff), Some(0x56));
+        assert_eq!(psg.channel(ChannelIndex::B).period_lsb(), 0x56);
+
+        // The address latch is shared with the address/data port interface
+        psg.write_address(7);
+        assert_eq!(psg.bus_transaction(true, false, 0x2a), None);
+        assert_eq!(psg.mixer(), 0x2a);
+
+        psg.bus_transaction(true, true, 2);
+        assert_eq!(psg.read_data(), 0x56);
+    }
+
+    #[test]
+    fn ports() {
+        use std::sync::{Arc, Mutex};
+
+        // Records the writes to both ports, and returns fixed values for reads
+        struct Devices(Arc<Mutex<Vec<(char, u8)>>>);
+
+        impl PsgPorts for Devices {
+            fn read_a(&mut self) -> u8 {
+                0xa5
+            }
+
+            fn write_a(&mut self, value: u8) {
+                self.0.lock().unwrap().push(('a', value));
+            }
+
+            fn read_b(&mut self) -> u8 {
+                0x5b
+            }
+
+            fn write_b(&mut self, value: u8) {
+                self.0.lock().unwrap().push(('b', value));
+            }
+        }
+
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        // Without devices the stored values are read back
+        psg.set_register(14, 0x12);
+        assert_eq!(psg.read_register(14), 0x12);
+
+        psg.set_ports(Some(Box::new(Devices(writes.clone()))));
+
+        // Both ports are inputs by default, so reads go to the devices and writes are only stored
+        psg.set_register(14, 0x34);
+        psg.set_register(15, 0x56);
+        assert_eq!((psg.read_register(14), psg.read_register(15)), (0xa5, 0x5b));
+        assert_eq!((psg.register(14), psg.register(15)), (0x34, 0x56));
+        assert!(writes.lock().unwrap().is_empty());
+
+        // Switching a port to output writes its stored value, after which reads return the stored
+        // value
+        psg.set_mixer(0x40);
+        psg.set_register(14, 0x78);
+        psg.set_register(15, 0x9a);
+        assert_eq!((psg.read_register(14), psg.read_register(15)), (0x78, 0x5b));
+        assert_eq!(*writes.lock().unwrap(), [('a', 0x34), ('a', 0x78)]);
+
+        psg.set_mixer(0xbf);
+        assert_eq!((psg.read_register(14), psg.read_register(15)), (0xa5, 0x9a));
+        assert_eq!(*writes.lock().unwrap(), [('a', 0x34), ('a', 0x78), ('b', 0x9a)]);
+
+        // Reads through the data port go to the devices as well
+        psg.write_address(15);
+        psg.set_mixer(0x00);
+        assert_eq!(psg.read_data(), 0x5b);
+        assert_eq!(psg.bus_transaction(false, true, 0), Some(0x5b));
+    }
+
+    #[test]
+    fn mixer() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        for mixer in 0..=255 {
+            psg.set_mixer(mixer);
+            assert_eq!(psg.mixer(), mixer);
+
+            for channel in ChannelIndex::ALL {
+                assert_eq!(psg.tone_disabled(channel), mixer & (1 << u8::from(channel)) != 0);
+                assert_eq!(psg.noise_disabled(channel), mixer & (8 << u8::from(channel)) != 0);
+            }
+        }
+
+        psg.set_tone_disabled(ChannelIndex::B, false);
+        psg.set_noise_disabled(ChannelIndex::C, false);
+        assert_eq!(psg.mixer(), 0xdd);
+    }
+
+    #[test]
+    fn channel_getters() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        psg.set_tone_period(ChannelIndex::A, 0x0123);
+        psg.set_amplitude(ChannelIndex::B, 0x1a);
+        psg.set_envelope_enabled(ChannelIndex::C, true);
+
+        assert_eq!((psg.tone_period(ChannelIndex::A), psg.tone_period(ChannelIndex::B)), (0x0123, 1));
+        assert_eq!((psg.amplitude(ChannelIndex::A), psg.amplitude(ChannelIndex::B)), (0, 0x0a));
+        assert_eq!((psg.envelope_enabled(ChannelIndex::B), psg.envelope_enabled(ChannelIndex::C)), (false, true));
+    }
+
+    #[test]
+    fn write_register_at() {
+        fn render(writes: &[(u64, u8, u8)]) -> Vec<(f64, f64)> {
+            let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+            for &(cycle, register, value) in writes {
+                psg.write_register_at(cycle, register, value);
+            }
+
+            let mut frames = Vec::new();
+            psg.render_until_cycle(20_000, |left, right| frames.push((left, right)));
+
+            assert!(psg.cycles_elapsed() >= 20_000);
+            assert_eq!(psg.register(8), 0);
+            frames
+        }
+
+        // Writes that are 100 cycles apart produce a short pulse, while simultaneous writes cancel
+        let apart = render(&[(10_000, 8, 15), (10_100, 8, 0)]);
+        let simultaneous = render(&[(10_000, 8, 15), (10_000, 8, 0)]);
+        let scheduled_in_reverse = render(&[(10_100, 8, 0), (10_000, 8, 15)]);
+
+        assert_ne!(apart, simultaneous);
+        assert_eq!(apart, scheduled_in_reverse);
+        assert!(simultaneous.iter().all(|&frame| frame == simultaneous[0]));
+
+        // A write takes effect at the start of the tick containing the cycle
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        psg.write_register_at(8007, 8, 15);
+
+        while psg.ticks_elapsed() < 1000 {
+            psg.render_tick();
+        }
+
+        assert_eq!(psg.register(8), 0);
+        psg.render_tick();
+        assert_eq!(psg.register(8), 15);
+    }
+
+    #[test]
+    fn recording_bank_b() {
+        fn setup() -> PSG {
+            let mut psg = PSG::with_chip_type(1_000_000.0, 44100, ChipType::AY8930).unwrap();
+            psg.set_registers(&[0x00, 0x01, 0x80, 0x01, 0, 0, 0, 0x3c, 0x0f, 0x0f, 0, 0, 0, 0, 0, 0]);
+            psg
+        }
+
+        let mut psg = setup();
+        let mut expected = Vec::new();
+
+        psg.start_recording();
+
+        for frame in 0..1000 {
+            match frame {
+                100 => psg.set_register(13, 0xb0), // Expanded mode with register bank B
+                200 => psg.set_register(6, 0x03),
+                300 => {
+                    psg.set_register(0, 0x40);
+                    psg.set_register(4, 0x0e);
+                }
+                400 => psg.set_register(13, 0xa0), // Register bank A
+                500 => {
+                    psg.set_amplitude(ChannelIndex::A, 0x1f);
+                    psg.set_envelope_enabled(ChannelIndex::B, true);
+                    psg.set_tone_period(ChannelIndex::B, 0x0200);
+                }
+                600 => psg.set_register(13, 0xb0),
+                700 => psg.set_register(7, 0x06),
+                _ => ()
+            }
+
+            expected.push(psg.render());
+        }
+
+        assert_eq!(psg.channel(ChannelIndex::A).duty_cycle(), DutyCycle::from_register(0x03));
+        assert_eq!(psg.channel(ChannelIndex::B).duty_cycle(), DutyCycle::from_register(0x06));
+
+        // The bank switches are recorded, so the writes are replayed into the same bank
+        let mut replayed = setup();
+        let mut events = psg.stop_recording().into_iter().peekable();
+
+        for expected in expected {
+            while let Some(event) = events.next_if(|event| event.frame == replayed.frames_rendered()) {
+                replayed.set_register(event.register, event.value);
+            }
+
+            assert_eq!(replayed.render(), expected);
+        }
+
+        assert_eq!(replayed.raw_registers(), psg.raw_registers());
+        assert_eq!(replayed.channel_envelope(ChannelIndex::B).period(), 0x40);
+    }
+
+    #[test]
+    fn recording() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let mut expected = Vec::new();
+
+        psg.set_register(7, 0x3f);
+        psg.start_recording();
+        assert!(psg.is_recording());
+
+        for frame in 0..4000 {
+            match frame {
+                100 => psg.set_register(7, 0x36),
+                200 => psg.set_tone_period(ChannelIndex::A, 0x01c3),
+                300 => psg.set_amplitude(ChannelIndex::A, 12),
+                400 => psg.set_noise_period(7),
+                500 => psg.set_tone_disabled(ChannelIndex::B, false),
+                600 => psg.set_amplitude(ChannelIndex::B, 9),
+                700 => psg.set_envelope_period(80),
+                800 => psg.set_envelope_shape(0x0a),
+                900 => psg.set_envelope_enabled(ChannelIndex::C, true),
+                1000 => psg.set_tone_disabled(ChannelIndex::C, false),
+                1500 => psg.set_envelope_shape(0x0a), // Restarts the envelope
+                2000 => psg.set_mixer(0x00),
+                2500 => psg.set_registers(&[0x20, 0, 0x40, 0, 0x60, 0, 0x03, 0x38, 15, 15, 16, 40, 0, 0x0c, 0, 0]),
+                3000 => psg.set_noise_disabled(ChannelIndex::A, true),
+                _ => ()
+            }
+
+            expected.push(psg.render());
+        }
+
+        let events = psg.stop_recording();
+        assert!(!psg.is_recording());
+        assert!(psg.stop_recording().is_empty());
+        assert_eq!(events.first(), Some(&RegisterEvent { frame: 100, register: 7, value: 0x36 }));
+
+        // Replaying the events into a new PSG must produce identical output
+        let mut replayed = PSG::new(1_000_000.0, 44100).unwrap();
+        replayed.set_register(7, 0x3f);
+
+        let mut events = events.iter().peekable();
+
+        for expected in expected {
+            while let Some(event) = events.next_if(|event| event.frame == replayed.frames_rendered()) {
+                replayed.set_register(event.register, event.value);
+            }
+
+            assert_eq!(replayed.render(), expected);
+        }
+    }
+
+    #[test]
+    fn fallible_access() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        assert!(psg.try_channel(2).is_some());
+        assert!(psg.try_channel(3).is_none());
+        assert!(psg.try_channel_mut(7).is_none());
+
+        assert_eq!(psg.try_set_tone_period(1, 0x123), Ok(()));
+        assert_eq!(psg.try_set_tone_period(3, 0x123), Err(Error::InvalidChannel(3)));
+        assert_eq!(psg.try_set_amplitude(2, 9), Ok(()));
+        assert_eq!(psg.try_set_amplitude(200, 9), Err(Error::InvalidChannel(200)));
+        assert_eq!(psg.try_set_register(10, 0x0c), Ok(()));
+        assert_eq!(psg.try_set_register(16, 0x0c), Err(Error::InvalidRegister(16)));
+
+        assert_eq!((psg.tone_period(ChannelIndex::B), psg.amplitude(ChannelIndex::C)), (0x123, 0x0c));
+
+        assert_eq!(
+            PSG::new(1_000_000_000.0, 44100).err(),
+            Some(Error::ClockRateTooHigh { clock_rate: 1e9, max: 44100.0 * 1024.0 })
+        );
+    }
+
+    #[test]
+    fn channel_iterators() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        for (index, channel) in psg.channels_mut() {
+            channel.set_amplitude(u8::from(index) + 1);
+        }
+
+        assert_eq!(
+            psg.channels().map(|(index, channel)| (index, channel.amplitude())).collect::<Vec<_>>(),
+            [(ChannelIndex::A, 1), (ChannelIndex::B, 2), (ChannelIndex::C, 3)]
+        );
+    }
+
+    #[test]
+    fn register_file() {
+        type Change = fn(&mut PSG);
+
+        // For every register, a change to the state and the value of the used bits it should
+        // result in
+        let changes: [(Change, u8); 16] = [
+            (|psg| psg.channel_mut(ChannelIndex::A).set_period_lsb(0x12), 0x12),
+            (|psg| psg.channel_mut(ChannelIndex::A).set_period_msb(0x03), 0x03),
+            (|psg| psg.channel_mut(ChannelIndex::B).set_period_lsb(0x34), 0x34),
+            (|psg| psg.set_tone_period(ChannelIndex::B, 0x0567), 0x05),
+            (|psg| psg.channel_mut(ChannelIndex::C).set_period_lsb(0x78), 0x78),
+            (|psg| psg.channel_mut(ChannelIndex::C).set_period_msb(0x09), 0x09),
+            (|psg| psg.noise_generator_mut().set_period(0x0a), 0x0a),
+            (|psg| psg.set_tone_disabled(ChannelIndex::A, false), 0xfe),
+            (|psg| psg.channel_mut(ChannelIndex::A).set_amplitude(3), 0x13),
+            (|psg| psg.set_envelope_enabled(ChannelIndex::B, false), 0x0f),
+            (|psg| psg.channel_mut(ChannelIndex::C).set_amplitude_and_envelope_enabled(0x05), 0x05),
+            (|psg| psg.envelope_generator_mut().set_period_lsb(0xbc), 0xbc),
+            (|psg| psg.set_envelope_period(0xde00), 0xde),
+            (|psg| psg.envelope_generator_mut().set_shape(0x09), 0x09),
+            (|psg| psg.set_register(14, 0x42), 0x42),
+            (|psg| psg.set_register(15, 0x24), 0x24)
+        ];
+
+        for (register, (change, used)) in changes.into_iter().enumerate() {
+            let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+            psg.set_register(register as u8, 0xff);
+            assert_eq!(psg.raw_register(register as u8), 0xff, "register {}", register);
+
+            // The used bits follow the state, while the unused bits are kept
+            change(&mut psg);
+            assert_eq!(psg.raw_register(register as u8), !REGISTER_MASKS[register] | used, "register {}", register);
+
+            // Writing back the raw values restores the registers exactly
+            let raw = psg.raw_registers();
+            let mut restored = PSG::new(1_000_000.0, 44100).unwrap();
+
+            for (register, value) in raw.into_iter().enumerate() {
+                restored.set_register(register as u8, value);
+            }
+
+            assert_eq!(restored.raw_registers(), raw);
+            assert_eq!(restored.registers(), psg.registers());
+        }
+
+        assert_eq!(PSG::new(1_000_000.0, 44100).unwrap().raw_register(16), 0);
+    }
+
+    #[test]
+    fn write_observer() {
+        use std::sync::{Arc, Mutex};
+
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        psg.set_register(0, 0x12);
+
+        let observed = writes.clone();
+        psg.set_write_observer(Some(Box::new(move |register, value, frame| {
+            observed.lock().unwrap().push((register, value, frame));
+        })));
+
+        psg.set_register(8, 0x0f);
+        psg.render();
+        psg.write_address(13);
+        psg.write_data(0x0a);
+        psg.set_register(16, 0xff);
+
+        psg.set_write_observer(None);
+        psg.set_register(1, 0x01);
+
+        assert_eq!(*writes.lock().unwrap(), [(8, 0x0f, 0), (13, 0x0a, 1)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut psg = test_tune(2_000_000.0, 8000);
+
+        psg.set_chip_type(ChipType::AY);
+        psg.set_interpolation_mode(InterpolationMode::BSpline);
+        psg.set_output_filter(OutputFilter::Machine(MachinePreset::AtariSt));
+        psg.set_stereo_width(0.5);
+        psg.enable_metering(true);
+        psg.channel_mut(ChannelIndex::B).set_panning(0.3, true);
+        psg.write_register_at(100_000, 8, 0x0f);
+
+        // Serialize in the middle of a note, with all filters filled
+        for _ in 0..1000 {
+            psg.render();
+        }
+
+        let json = serde_json::to_string(&psg).unwrap();
+        let mut restored: PSG = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.chip_type(), ChipType::AY);
+        assert_eq!(restored.raw_registers(), psg.raw_registers());
+
+        for _ in 0..10_000 {
+            let (left, right) = psg.render();
+            let (restored_left, restored_right) = restored.render();
+
+            assert_eq!(left.to_bits(), restored_left.to_bits());
+            assert_eq!(right.to_bits(), restored_right.to_bits());
+        }
+
+        assert_eq!(restored.meter(), psg.meter());
+    }
+
+    #[test]
+    fn channel_phase() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        psg.channel_mut(ChannelIndex::A).set_phase(1000, true);
+        assert_eq!(psg.channel(ChannelIndex::A).phase(), (0, true));
+
+        psg.set_tone_period(ChannelIndex::A, 100);
+        psg.channel_mut(ChannelIndex::A).set_phase(250, false);
+        assert_eq!(psg.channel(ChannelIndex::A).phase(), (50, false));
+
+        // Two channels with the same period but different phases stay locked after a reset
+        psg.set_tone_period(ChannelIndex::B, 100);
+
+        for _ in 0..100 {
+            psg.render();
+        }
+
+        assert_ne!(psg.channel(ChannelIndex::A).phase(), psg.channel(ChannelIndex::B).phase());
+
+        for (_, channel) in psg.channels_mut() {
+            channel.reset_phase();
+        }
+
+        for _ in 0..1000 {
+            psg.render();
+            assert_eq!(psg.channel(ChannelIndex::A).phase(), psg.channel(ChannelIndex::B).phase());
+        }
+    }
+
+    #[test]
+    fn reset() {
+        let mut psg = test_tune(1_000_000.0, 44100);
+
+        psg.set_chip_type(ChipType::AY);
+
+        // Make port A an output and write both port registers, which are cleared by the reset too
+        psg.set_register(7, 0x70);
+        psg.set_register(14, 0x55);
+        psg.set_register(15, 0xaa);
+        psg.channel_mut(ChannelIndex::B).set_panning(0.25, false);
+        psg.write_address(9);
+
+        for _ in 0..1000 {
+            psg.render();
+        }
+
+        psg.reset();
+
+        assert_eq!(psg.raw_registers(), [0; 16]);
+        assert_eq!(psg.registers(), [0; 16]);
+        assert_eq!(psg.address(), 0);
+
+        for (_, channel) in psg.channels() {
+            assert_eq!(channel.phase(), (0, false));
+        }
+
+        assert_eq!(psg.noise_generator().state(), 0x4001);
+        assert_eq!(psg.envelope_generator().value(), 0);
+
+        // The configuration is kept
+        assert_eq!(psg.chip_type(), ChipType::AY);
+        assert_eq!(psg.channel(ChannelIndex::B).panning(), (0.75, 0.25));
+        assert_eq!(psg.frames_rendered(), 1000);
+
+        // The filters have settled, so the output is silent right away
+        for _ in 0..100 {
+            let (left, right) = psg.render();
+            assert!(left.abs() < 1e-6 && right.abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn clone() {
+        let mut psg = test_tune(2_000_000.0, 8000);
+
+        psg.set_output_filter(OutputFilter::Machine(MachinePreset::Msx));
+        psg.write_register_at(100_000, 8, 0x0f);
+
+        for _ in 0..1000 {
+            psg.render();
+        }
+
+        let mut clone = psg.clone();
+
+        for _ in 0..1000 {
+            let (left, right) = psg.render();
+            let (clone_left, clone_right) = clone.render();
+
+            assert_eq!(left.to_bits(), clone_left.to_bits());
+            assert_eq!(right.to_bits(), clone_right.to_bits());
+        }
+
+        // Changing the clone leaves the original untouched
+        clone.set_amplitude(ChannelIndex::A, 0);
+        assert_eq!(psg.amplitude(ChannelIndex::A), 15);
+
+        let debug = format!("{:?}", psg);
+        assert!(debug.contains("registers: [1c, 01, fd, 00, 33, 02, 0b, 30, 0f, 10, 0a, 00, 08, 0e, 00, 00]"));
+        assert!(debug.contains("NoiseGenerator { period: 11"));
+    }
+
+    #[test]
+    fn save_state() {
+        let mut psg = test_tune(2_000_000.0, 8000);
+
+        psg.set_chip_type(ChipType::AY);
+        psg.set_interpolation_mode(InterpolationMode::BSpline);
+        psg.set_output_filter(OutputFilter::Machine(MachinePreset::ZxSpectrum));
+        psg.set_dc_filter_mode(DCFilterMode::OnePole { cutoff_hz: 20.0 });
+        psg.set_mixing_mode(MixingMode::Analog { compression: 0.25 });
+        psg.set_stereo_width(0.5);
+        psg.enable_metering(true);
+        psg.channel_mut(ChannelIndex::B).set_panning(0.3, true);
+        psg.write_register_at(100_000, 8, 0x0f);
+
+        // Save in the middle of a note, with all filters filled
+        for _ in 0..1000 {
+            psg.render();
+        }
+
+        let state = psg.save_state();
+        let mut restored = PSG::new(2_000_000.0, 8000).unwrap();
+        restored.load_state(&state).unwrap();
+
+        assert_eq!(restored.chip_type(), ChipType::AY);
+        assert_eq!(restored.raw_registers(), psg.raw_registers());
+        assert_eq!(restored.frames_rendered(), 1000);
+        assert_eq!(restored.save_state(), state);
+
+        for _ in 0..10_000 {
+            let (left, right) = psg.render();
+            let (restored_left, restored_right) = restored.render();
+
+            assert_eq!(left.to_bits(), restored_left.to_bits());
+            assert_eq!(right.to_bits(), restored_right.to_bits());
+        }
+
+        assert_eq!(restored.meter(), psg.meter());
+    }
+
+    #[test]
+    fn state_equality() {
+        let writes = [(0, 0x1c), (7, 0x30), (8, 0x0f), (6, 0x0b), (13, 0x0e), (8, 0x10)];
+
+        let mut first = PSG::new(1_000_000.0, 44100).unwrap();
+        let mut second = PSG::new(1_000_000.0, 44100).unwrap();
+
+        for (register, value) in writes {
+            first.set_register(register, value);
+            second.set_register(register, value);
+
+            for _ in 0..100 {
+                first.render();
+                second.render();
+            }
+        }
+
+        assert!(first == second);
+        assert_eq!(first.state_fingerprint(), second.state_fingerprint());
+
+        // A single differing write changes the state
+        second.set_register(6, 0x0c);
+
+        assert!(first != second);
+        assert_ne!(first.state_fingerprint(), second.state_fingerprint());
+    }
+
+    #[test]
+    fn load_state_errors() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        psg.set_amplitude(ChannelIndex::A, 15);
+
+        let state = PSG::new(1_000_000.0, 8000).unwrap().save_state();
+        assert_eq!(psg.load_state(&state), Err(Error::StateSampleRateMismatch { state: 8000, psg: 44100 }));
+
+        let state = PSG::new(2_000_000.0, 44100).unwrap().save_state();
+        assert_eq!(psg.load_state(&state), Err(Error::StateClockRateMismatch { state: 2_000_000.0, psg: 1_000_000.0 }));
+
+        let state = PSG::with_dc_filter_length(1_000_000.0, 44100, 256).unwrap().save_state();
+        assert_eq!(psg.load_state(&state), Err(Error::StateDCFilterLengthMismatch { state: 256, psg: 1024 }));
+
+        let bytes = PSG::new(1_000_000.0, 44100).unwrap().save_state().into_bytes();
+
+        let mut version = bytes.clone();
+        version[4] = 2;
+        assert_eq!(psg.load_state(&PsgState::from_bytes(version)), Err(Error::UnsupportedStateVersion(2)));
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert_eq!(psg.load_state(&PsgState::from_bytes(magic)), Err(Error::InvalidState));
+
+        let truncated = bytes[..bytes.len() - 1].to_vec();
+        assert_eq!(psg.load_state(&PsgState::from_bytes(truncated)), Err(Error::InvalidState));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(psg.load_state(&PsgState::from_bytes(trailing)), Err(Error::InvalidState));
+
+        // Failed loads leave the PSG untouched
+        assert_eq!(psg.amplitude(ChannelIndex::A), 15);
+
+        psg.load_state(&PsgState::from_bytes(bytes)).unwrap();
+        assert_eq!(psg.amplitude(ChannelIndex::A), 0);
+    }
+
+    #[test]
+    fn state_golden_bytes() {
+        let mut psg = test_tune(1_000_000.0, 44100);
+        psg.write_register_at(5000, 8, 0x0f);
+
+        // The amplitudes of the built-in tables are rounded in single precision builds, which
+        // would change the state's bytes
+        psg.set_dac_table(&std::array::from_fn(|level| level as f64 / 32.0)).unwrap();
+
+        let bytes = psg.save_state().into_bytes();
+
+        // The header: magic bytes, format version, clock rate, sample rate, and DC filter length
+        assert_eq!(&bytes[..22], &[
+            b'P', b'S', b'G', b'S', 0x01, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x80, 0x84, 0x2e, 0x41,
+            0x44, 0xac, 0x00, 0x00,
+            0x00, 0x04, 0x00, 0x00
+        ]);
+
+        // The rest of the layout is pinned by an FNV-1a hash, any change to it after a release
+        // requires a new format version
+        assert_eq!((bytes.len(), psg.state_fingerprint()), (23321, 0x782e99484b637c53));
+    }
+
+    #[test]
+    fn dc_filter_lengths() {
+        assert_eq!(PSG::new(1_000_000.0, 44100).unwrap().dc_filter_length(), 1024);
+
+        for length in [256, 4096] {
+            // With these rates a single period of the tone spans exactly 64 frames
+            let mut psg = PSG::with_dc_filter_length(1_102_500.0, 44100, length).unwrap();
+            assert_eq!(psg.dc_filter_length(), length);
+
+            // The DC offset of a constant level is removed once the window is filled
+            psg.set_amplitude(ChannelIndex::A, 15);
+            psg.prime();
+
+            let frames = psg.render_frames(length);
+            // The moving sum accumulates some rounding errors, especially in single precision
+            let residual = frames.iter().fold(0.0_f64, |peak, (left, right)| peak.max(left.abs()).max(right.abs()));
+            assert!(residual < 1e-4, "length {}, residual {}", length, residual);
+
+            // A tone that spans an integer amount of periods in the window stays centered
+            psg.set_tone_period(ChannelIndex::A, 100);
+            psg.set_tone_disabled(ChannelIndex::A, false);
+
+            let _ = psg.render_frames(length * 2);
+            let frames = psg.render_frames(length);
+            let mean = frames.iter().map(|(left, _)| left).sum::<f64>() / length as f64;
+            let peak = frames.iter().fold(0.0_f64, |peak, (left, _)| peak.max(left.abs()));
+
+            assert!(mean.abs() < 0.01 * peak, "length {}, mean {}, peak {}", length, mean, peak);
+        }
+    }
+
+    #[test]
+    fn output_filter() {
+        let rms = |filter: OutputFilter| {
+            let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+            psg.set_amplitude(ChannelIndex::A, 15);
+            psg.set_tone_period(ChannelIndex::A, 12);
+            psg.set_tone_disabled(ChannelIndex::A, false);
+            psg.set_output_filter(filter);
+
+            let frames = psg.render_frames(8820);
+            (frames[4410..].iter().map(|(left, _)| left * left).sum::<f64>() / 4410.0).sqrt()
+        };
+
+        // A 5.2 kHz square wave is attenuated by low-pass filters below that frequency
+        let unfiltered = rms(OutputFilter::None);
+
+        assert!(rms(OutputFilter::LowPass { cutoff_hz: 1000.0 }) < unfiltered * 0.2);
+        assert!(rms(OutputFilter::Machine(MachinePreset::ZxSpectrum)) < unfiltered * 0.8);
+        assert!(rms(OutputFilter::Machine(MachinePreset::Msx)) > rms(OutputFilter::Machine(MachinePreset::ZxSpectrum)));
+
+        // Cutoff frequencies beyond the Nyquist frequency are clamped
+        assert!(rms(OutputFilter::LowPass { cutoff_hz: 100000.0 }).is_finite());
+    }
+
+    #[test]
+    fn analog_mixing() {
+        let level = |channels: usize, mode: MixingMode| {
+            let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+            // Channels with both tone and noise disabled output a constant level
+            for channel in &ChannelIndex::ALL[..channels] {
+                psg.set_amplitude(*channel, 15);
+            }
+
+            psg.set_mixing_mode(mode);
+            psg.set_dc_filter_enabled(false);
+            psg.prime();
+            psg.render().0
+        };
+
+        let mode = MixingMode::Analog { compression: 0.25 };
+
+        #[cfg(not(feature = "f32"))]
+        let tolerance = 1e-9;
+
+        // Single precision processing rounds the levels
+        #[cfg(feature = "f32")]
+        let tolerance = 1e-6;
+
+        assert!((level(3, MixingMode::Linear) - 3.0 * level(1, MixingMode::Linear)).abs() < tolerance);
+        assert!((level(1, mode) - level(1, MixingMode::Linear)).abs() < tolerance);
+        assert!(level(3, mode) < 3.0 * level(1, mode));
+        assert!(level(3, mode) > level(2, mode));
+    }
+
+    #[test]
+    fn precomputed_gains() {
+        // Analog mixing without compression scales by exactly 1.0, and multiplies the DAC levels
+        // by the panning factors while rendering instead of using the precomputed gains
+        let mut psgs = [
+            PSG::new(1_000_000.0, 44100).unwrap(),
+            PSG::new(1_000_000.0, 44100).unwrap()
+        ];
+
+        psgs[1].set_mixing_mode(MixingMode::Analog { compression: 0.0 });
+
+        let mut seed: u32 = 54321;
+
+        for frame in 0..8192 {
+            if frame % 512 == 0 {
+                next_random(&mut seed);
+
+                for psg in &mut psgs {
+                    psg.set_chip_type(if seed & 0x10000 == 0 { ChipType::AY } else { ChipType::YM });
+                    psg.set_mixer(0);
+
+                    for (number, channel) in ChannelIndex::ALL.into_iter().enumerate() {
+                        psg.set_amplitude(channel, ((seed >> (number * 4)) & 15) as u8);
+                        psg.set_tone_period(channel, 50 + number as u16 * 37);
+                        psg.channel_mut(channel).set_panning((seed >> 20) as f64 / 4096.0, channel == ChannelIndex::B);
+                    }
+                }
+            }
+
+            let (first, second) = (psgs[0].render(), psgs[1].render());
+
+            assert_eq!(first.0.to_bits(), second.0.to_bits());
+            assert_eq!(first.1.to_bits(), second.1.to_bits());
+        }
+    }
+
+    #[test]
+    fn interpolation_mode() {
+        // Measure the energy of everything but the fundamental of a 12.5 kHz tone, relative to the
+        // energy of the fundamental. A second of audio contains an integer amount of periods, so
+        // the fundamental falls exactly on a single DFT bin.
+        let aliasing = |mode: InterpolationMode| {
+            let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+
+            psg.set_interpolation_mode(mode);
+            psg.set_amplitude(ChannelIndex::A, 15);
+            psg.set_tone_period(ChannelIndex::A, 10);
+            psg.set_tone_disabled(ChannelIndex::A, false);
+            psg.prime();
+
+            let samples: Vec<f64> = psg.render_frames(44100).into_iter().map(|(left, _)| left).collect();
+            let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+
+            let (mut real, mut imaginary, mut total) = (0.0, 0.0, 0.0);
+
+            for (index, sample) in samples.iter().enumerate() {
+                let phase = 2.0 * std::f64::consts::PI * 12500.0 * index as f64 / 44100.0;
+
+                real += (sample - mean) * phase.cos();
+                imaginary += (sample - mean) * phase.sin();
+                total += (sample - mean) * (sample - mean);
+            }
+
+            let fundamental = 2.0 * (real * real + imaginary * imaginary) / samples.len() as f64;
+
+            (total - fundamental) / fundamental
+        };
+
+        let parabolic = aliasing(InterpolationMode::Parabolic);
+        let b_spline = aliasing(InterpolationMode::BSpline);
+
+        // The B-spline interpolator reduces the aliasing energy by about 30 dB
+        assert!(b_spline < parabolic * 0.01, "parabolic {}, b-spline {}", parabolic, b_spline);
+    }
+
+    #[test]
+    fn stereo_width_and_swap() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+
+        psg.set_amplitude(ChannelIndex::A, 15);
+        psg.set_tone_period(ChannelIndex::A, 100);
+        psg.set_tone_disabled(ChannelIndex::A, false);
+        psg.channel_mut(ChannelIndex::A).set_panning(0.2, false);
+
+        psg.set_stereo_width(0.0);
+
+        for _ in 0..1000 {
+            let (left, right) = psg.render();
+            assert_eq!(left, right);
+        }
+
+        psg.set_stereo_width(0.5);
+        psg.set_channels_swapped(true);
+
+        let mut reference = PSG::new(1_000_000.0, 44100).unwrap();
+
+        reference.set_amplitude(ChannelIndex::A, 15);
+        reference.set_tone_period(ChannelIndex::A, 100);
+        reference.set_tone_disabled(ChannelIndex::A, false);
+        reference.channel_mut(ChannelIndex::A).set_panning(0.2, false);
+
+        for _ in 0..1000 {
+            reference.render();
+        }
+
+        for _ in 0..1000 {
+            let (left, right) = psg.render();
+            let (reference_left, reference_right) = reference.render();
+
+            // Half width results in a 3:1 mix, and the channels are swapped
+            assert!((left - (reference_right * 0.75 + reference_left * 0.25)).abs() < 1e-12);
+            assert!((right - (reference_left * 0.75 + reference_right * 0.25)).abs() < 1e-12);
+        }
+    }
+}
+
+/// Tests that are compiled with the crate in no_std mode.
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_tests {
+    use crate::{ChannelIndex, PSG, math};
+
+    #[test]
+    fn render() {
+        let clock_rate = 1_789_772.5;
+        let mut psg = PSG::new(clock_rate, 44100).unwrap();
+
+        let channel = psg.channel_mut(ChannelIndex::A);
+        channel.set_period(math::midi_pitch_to_tone_period(69.0, clock_rate));
+        channel.set_amplitude(15);
+        channel.set_tone_disabled(false);
+        channel.set_panning(0.25, true);
+
+        assert_eq!(math::midi_pitch_to_frequency(69.0), 440.0);
+        assert_eq!(psg.tone_period(ChannelIndex::A), 254);
+
+        let mut peak = 0.0f64;
+
+        for _ in 0..4410 {
+            let (left, right) = psg.render();
+
+            assert!(left.is_finite() && right.is_finite());
+            peak = peak.max(left.abs());
+        }
+
+        assert!(peak > 0.1);
     }
 }
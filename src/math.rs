@@ -1,18 +1,240 @@
-//! This module contains useful mathematical operations on frequencies, tone/envelope periods, and
-//! MIDI pitch numbers.
+//! This module contains useful mathematical operations on frequencies, tone/envelope/noise
+//! periods, MIDI pitch numbers, and amplitudes.
+//!
+//! All conversions take the clock rate at which the tone and envelope generators are driven. When
+//! a YM2149's clock divider is enabled, this is the divided clock rate, as returned by
+//! [`PSG::effective_clock_rate`](crate::PSG::effective_clock_rate) or
+//! [`ClockDivider::divide`](crate::ClockDivider::divide).
+//!
+//! The conversions into periods round to the closest period, which may lie outside of the range
+//! of the period register. The `_checked` variants instead return a [`RangeError`] for pitches
+//! that cannot be represented.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::DacTable;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// The error returned by the checked period conversions when a pitch lies outside of the range of
+/// the period register. Both variants contain the period that is closest to the requested pitch,
+/// and the frequency that this period produces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RangeError {
+    /// The pitch is too low, the period would exceed the largest possible period.
+    TooLow {
+        /// The largest possible period.
+        period: u16,
+
+        /// The frequency of the largest possible period in Hz.
+        frequency: f64
+    },
+
+    /// The pitch is too high, the period would be smaller than 1.
+    TooHigh {
+        /// The smallest possible period.
+        period: u16,
+
+        /// The frequency of the smallest possible period in Hz.
+        frequency: f64
+    }
+}
+
+impl RangeError {
+    /// The period that is closest to the requested pitch.
+    pub fn period(&self) -> u16 {
+        match *self {
+            RangeError::TooLow { period, .. } | RangeError::TooHigh { period, .. } => period
+        }
+    }
+
+    /// The frequency of the period that is closest to the requested pitch in Hz.
+    pub fn frequency(&self) -> f64 {
+        match *self {
+            RangeError::TooLow { frequency, .. } | RangeError::TooHigh { frequency, .. } => frequency
+        }
+    }
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::TooLow { frequency, .. } => write!(f, "the pitch is too low, the lowest possible frequency is {} Hz", frequency),
+            RangeError::TooHigh { frequency, .. } => write!(f, "the pitch is too high, the highest possible frequency is {} Hz", frequency)
+        }
+    }
+}
+
+impl core::error::Error for RangeError {}
+
+/// The accidental that is used when formatting the names of notes that are not natural notes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Accidental {
+    /// Use sharps, for example `C#4`.
+    Sharp,
+
+    /// Use flats, for example `Db4`.
+    Flat
+}
+
+/// The error returned by [`note_name_to_midi_pitch`] when a note name is malformed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NoteNameError {
+    /// The note name is empty.
+    Empty,
+
+    /// The note name does not start with one of the letters A through G.
+    InvalidLetter(char),
+
+    /// The octave is missing, or is not an integer. Contains the part of the name after the
+    /// letter and accidental.
+    InvalidOctave(String)
+}
+
+impl fmt::Display for NoteNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoteNameError::Empty => write!(f, "the note name is empty"),
+            NoteNameError::InvalidLetter(letter) => write!(f, "'{}' is not a note letter, it must be one of A through G", letter),
+            NoteNameError::InvalidOctave(octave) => write!(f, "'{}' is not a valid octave, it must be an integer", octave)
+        }
+    }
+}
+
+impl core::error::Error for NoteNameError {}
+
+/// The names of the notes within an octave using sharps and flats, as used by
+/// [`midi_pitch_to_note_name`]. Natural notes use a dash as the separator, like in trackers.
+const SHARP_NOTE_NAMES: [&str; 12] = ["C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-"];
+const FLAT_NOTE_NAMES: [&str; 12] = ["C-", "Db", "D-", "Eb", "E-", "F-", "Gb", "G-", "Ab", "A-", "Bb", "B-"];
+
+/// Round a period to an integer and check that it lies between 1 and `max` inclusive, where
+/// `to_frequency` converts a period back into its frequency for the error.
+fn check_period(period: f64, max: u16, to_frequency: impl Fn(u16) -> f64) -> Result<u16, RangeError> {
+    let period = period.round();
+
+    if period > max as f64 {
+        Err(RangeError::TooLow { period: max, frequency: to_frequency(max) })
+    } else if period >= 1.0 {
+        Ok(period as u16)
+    } else {
+        Err(RangeError::TooHigh { period: 1, frequency: to_frequency(1) })
+    }
+}
+
+/// The frequency of A4 (MIDI pitch number 69) in Hz that is used by the MIDI pitch conversions.
+/// The `_with_reference` variants of the conversions accept a different frequency, for example
+/// 415 Hz for baroque tuning.
+pub const CONCERT_PITCH: f64 = 440.0;
 
 /// Convert a MIDI pitch number into its corresponding frequency.
 ///
 /// The pitch number is not required to be an integer.
 pub fn midi_pitch_to_frequency(pitch: f64) -> f64 {
-    (2.0_f64).powf((pitch - 69.0) / 12.0) * 440.0
+    midi_pitch_to_frequency_with_reference(pitch, CONCERT_PITCH)
+}
+
+/// Convert a MIDI pitch number into its corresponding frequency, using the specified frequency of
+/// A4 in Hz.
+///
+/// The pitch number is not required to be an integer.
+pub fn midi_pitch_to_frequency_with_reference(pitch: f64, a4_frequency: f64) -> f64 {
+    (2.0_f64).powf((pitch - 69.0) / 12.0) * a4_frequency
 }
 
 /// Convert a frequency to its corresponding MIDI pitch number.
 ///
 /// The resulting pitch number is not guaranteed to be an integer.
 pub fn frequency_to_midi_pitch(frequency: f64) -> f64 {
-    (frequency / 440.0).log2() * 12.0 + 69.0
+    frequency_to_midi_pitch_with_reference(frequency, CONCERT_PITCH)
+}
+
+/// Convert a frequency to its corresponding MIDI pitch number, using the specified frequency of A4
+/// in Hz.
+///
+/// The resulting pitch number is not guaranteed to be an integer.
+pub fn frequency_to_midi_pitch_with_reference(frequency: f64, a4_frequency: f64) -> f64 {
+    (frequency / a4_frequency).log2() * 12.0 + 69.0
+}
+
+/// Compute the interval from the first to the second frequency in cents. The result is positive
+/// when the second frequency is higher than the first.
+pub fn cents_between(from: f64, to: f64) -> f64 {
+    (to / from).log2() * 1200.0
+}
+
+/// Convert a note name such as `A#3` into its MIDI pitch number, where `C4` is middle C (MIDI pitch
+/// number 60).
+///
+/// A note name consists of a letter from A through G (in either case), followed by an optional
+/// accidental, and the octave number. The accidental is either `#` for a sharp, `b` for a flat,
+/// or a dash or space for a natural note, so `C4`, `C-4`, and `C 4` are equivalent. Since the
+/// dash acts as a separator, a negative octave after a dash is written with a second dash, for
+/// example `C--1` for MIDI pitch number 0, while `C-1` means octave 1.
+///
+/// Returns an error describing the problem when the name is malformed.
+pub fn note_name_to_midi_pitch(name: &str) -> Result<f64, NoteNameError> {
+    let mut chars = name.chars();
+
+    let semitone = match chars.next().ok_or(NoteNameError::Empty)? {
+        'C' | 'c' => 0,
+        'D' | 'd' => 2,
+        'E' | 'e' => 4,
+        'F' | 'f' => 5,
+        'G' | 'g' => 7,
+        'A' | 'a' => 9,
+        'B' | 'b' => 11,
+        letter => return Err(NoteNameError::InvalidLetter(letter))
+    };
+
+    let rest = chars.as_str();
+
+    let (offset, octave) = match rest.chars().next() {
+        Some('#') => (1, &rest[1..]),
+        Some('b') => (-1, &rest[1..]),
+        Some('-' | ' ') if rest.len() > 1 => (0, &rest[1..]),
+        _ => (0, rest)
+    };
+
+    let octave: i32 = octave.parse().map_err(|_| NoteNameError::InvalidOctave(octave.to_string()))?;
+
+    Ok(((octave + 1) * 12 + semitone + offset) as f64)
+}
+
+/// Format a MIDI pitch number as the name of the nearest note, using the specified accidental for
+/// notes that are not natural notes. The name can be parsed again using
+/// [`note_name_to_midi_pitch`].
+///
+/// Natural notes are formatted with a dash as the separator like in trackers, for example `C-4`,
+/// and other notes as for example `C#4` or `Db4`. When the pitch number is not an integer, the
+/// remainder is appended in cents when it rounds to a non-zero value, for example `A-4+12c`.
+pub fn midi_pitch_to_note_name(pitch: f64, accidental: Accidental) -> String {
+    let semitone = pitch.round();
+    let cents = ((pitch - semitone) * 100.0).round();
+
+    let names = match accidental {
+        Accidental::Sharp => &SHARP_NOTE_NAMES,
+        Accidental::Flat => &FLAT_NOTE_NAMES
+    };
+
+    let semitone = semitone as i32;
+    let mut name = format!("{}{}", names[semitone.rem_euclid(12) as usize], semitone.div_euclid(12) - 1);
+
+    if cents != 0.0 {
+        name.push_str(&format!("{:+}c", cents));
+    }
+
+    name
+}
+
+/// Convert a note name into a suitable tone period value for the specified clock rate. See
+/// [`note_name_to_midi_pitch`] for the format of the name.
+pub fn note_name_to_tone_period(name: &str, clock_rate: f64) -> Result<u16, NoteNameError> {
+    Ok(midi_pitch_to_tone_period(note_name_to_midi_pitch(name)?, clock_rate))
 }
 
 /// Convert a MIDI pitch number into a suitable tone period value for the specified clock rate.
@@ -22,6 +244,84 @@ pub fn midi_pitch_to_tone_period(pitch: f64, clock_rate: f64) -> u16 {
     frequency_to_tone_period(midi_pitch_to_frequency(pitch), clock_rate)
 }
 
+/// Convert a MIDI pitch number into a suitable tone period value for the specified clock rate,
+/// using the specified frequency of A4 in Hz.
+///
+/// The pitch number is not required to be an integer.
+pub fn midi_pitch_to_tone_period_with_reference(pitch: f64, clock_rate: f64, a4_frequency: f64) -> u16 {
+    frequency_to_tone_period(midi_pitch_to_frequency_with_reference(pitch, a4_frequency), clock_rate)
+}
+
+/// Convert a MIDI note number and a 14-bit MIDI pitch bend value into the closest tone period for
+/// the specified clock rate.
+///
+/// A bend value of 8192 is the center position, which leaves the note unchanged, while 0 and
+/// 16383 bend the note down and up by `range_semitones` semitones respectively. Higher bend values
+/// are treated as 16383. The period saturates at the range of 1 to 4095 inclusive.
+pub fn midi_pitch_bend_to_tone_period(note: u8, bend: u16, range_semitones: f64, clock_rate: f64) -> u16 {
+    let offset = bend.min(0x3fff) as f64 - 8192.0;
+
+    // Scale both halves separately, so that the extreme values bend by exactly the full range
+    let bend = if offset < 0.0 { offset / 8192.0 } else { offset / 8191.0 };
+    let frequency = midi_pitch_to_frequency(note as f64 + bend * range_semitones);
+
+    frequency_to_tone_period_checked(frequency, clock_rate).unwrap_or_else(|error| error.period())
+}
+
+/// Detune a tone period by the specified amount of cents for the specified clock rate, where
+/// positive amounts raise the pitch.
+///
+/// The result is rounded to the closest period, so detunes that are smaller than the difference
+/// between adjacent periods may leave the period unchanged. The period saturates at the range of
+/// 1 to 4095 inclusive. A period of zero behaves like a period of 1.
+pub fn detune_period_cents(period: u16, cents: f64, clock_rate: f64) -> u16 {
+    let frequency = tone_period_to_frequency(period.max(1), clock_rate) * (cents / 1200.0).exp2();
+
+    frequency_to_tone_period_checked(frequency, clock_rate).unwrap_or_else(|error| error.period())
+}
+
+/// Compute the tone and envelope periods for a "buzzer" sound, in which the envelope runs at
+/// `ratio` times the frequency of the tone at the specified MIDI pitch number.
+///
+/// The tone generator divides the clock by 16 and the envelope generator by 256 per repetition of
+/// a sawtooth, so the envelope period is the tone period divided by 16 times the ratio. Since both
+/// periods are integers, the envelope is usually slightly detuned from the tone. Returns the tone
+/// period, the envelope period, and the detune of the envelope relative to the exact ratio in
+/// cents, which is positive when the envelope is too high.
+///
+/// Note that the triangle shapes take two repetitions for a full cycle, so their waveform has
+/// half of the frequency of the sawtooth shapes. Returns an error when either period lies outside
+/// of its range.
+pub fn buzzer_periods(pitch: f64, ratio: f64, clock_rate: f64) -> Result<(u16, u16, f64), RangeError> {
+    let tone_period = midi_pitch_to_tone_period_checked(pitch, clock_rate)?;
+    let frequency = tone_period_to_frequency(tone_period, clock_rate) * ratio;
+    let envelope_period = frequency_to_envelope_period_checked(frequency, clock_rate)?;
+    let detune = cents_between(frequency, envelope_period_to_frequency(envelope_period, clock_rate));
+
+    Ok((tone_period, envelope_period, detune))
+}
+
+/// Compute the tuning error in cents of the tone period that is closest to a MIDI pitch number for
+/// the specified clock rate. Since periods are integers, most pitches cannot be reproduced
+/// exactly. The error is positive when the produced pitch is higher than the requested pitch.
+///
+/// The pitch number is not required to be an integer.
+pub fn tone_period_cents_error(pitch: f64, clock_rate: f64) -> f64 {
+    midi_pitch_to_tone_period_with_error(pitch, clock_rate).1
+}
+
+/// Convert a MIDI pitch number into the closest tone period value for the specified clock rate,
+/// and return it together with its tuning error in cents. See
+/// [`tone_period_cents_error`].
+///
+/// The pitch number is not required to be an integer.
+pub fn midi_pitch_to_tone_period_with_error(pitch: f64, clock_rate: f64) -> (u16, f64) {
+    let frequency = midi_pitch_to_frequency(pitch);
+    let period = frequency_to_tone_period(frequency, clock_rate);
+
+    (period, cents_between(frequency, tone_period_to_frequency(period, clock_rate)))
+}
+
 /// Convert a MIDI pitch number into a suitable envelope period value for the specified clock rate.
 ///
 /// The pitch number is not required to be an integer.
@@ -29,6 +329,46 @@ pub fn midi_pitch_to_envelope_period(pitch: f64, clock_rate: f64) -> u16 {
     frequency_to_envelope_period(midi_pitch_to_frequency(pitch), clock_rate)
 }
 
+/// Convert a MIDI pitch number into a suitable envelope period value for the specified clock rate,
+/// using the specified frequency of A4 in Hz.
+///
+/// The pitch number is not required to be an integer.
+pub fn midi_pitch_to_envelope_period_with_reference(pitch: f64, clock_rate: f64, a4_frequency: f64) -> u16 {
+    frequency_to_envelope_period(midi_pitch_to_frequency_with_reference(pitch, a4_frequency), clock_rate)
+}
+
+/// Convert a MIDI pitch number into the closest noise period value for the specified clock rate.
+/// See [`frequency_to_noise_period`] for the limited range of the noise period.
+///
+/// The pitch number is not required to be an integer.
+pub fn midi_pitch_to_noise_period(pitch: f64, clock_rate: f64) -> u8 {
+    frequency_to_noise_period(midi_pitch_to_frequency(pitch), clock_rate)
+}
+
+/// Convert a MIDI pitch number into a tone period value for the specified clock rate, or return an
+/// error when the pitch cannot be represented using the 12-bit tone period.
+///
+/// The pitch number is not required to be an integer.
+pub fn midi_pitch_to_tone_period_checked(pitch: f64, clock_rate: f64) -> Result<u16, RangeError> {
+    frequency_to_tone_period_checked(midi_pitch_to_frequency(pitch), clock_rate)
+}
+
+/// Convert a MIDI pitch number into an envelope period value for the specified clock rate, or
+/// return an error when the pitch cannot be represented using the 16-bit envelope period.
+///
+/// The pitch number is not required to be an integer.
+pub fn midi_pitch_to_envelope_period_checked(pitch: f64, clock_rate: f64) -> Result<u16, RangeError> {
+    frequency_to_envelope_period_checked(midi_pitch_to_frequency(pitch), clock_rate)
+}
+
+/// Convert a MIDI pitch number into a noise period value for the specified clock rate, or return
+/// an error when the pitch cannot be represented using the 5-bit noise period.
+///
+/// The pitch number is not required to be an integer.
+pub fn midi_pitch_to_noise_period_checked(pitch: f64, clock_rate: f64) -> Result<u8, RangeError> {
+    frequency_to_noise_period_checked(midi_pitch_to_frequency(pitch), clock_rate)
+}
+
 /// Convert a tone period value into its corresponding MIDI pitch number for the specified clock
 /// rate.
 ///
@@ -55,6 +395,36 @@ pub fn frequency_to_envelope_period(frequency: f64, clock_rate: f64) -> u16 {
     (clock_rate / (256.0 * frequency)).round() as u16
 }
 
+/// Convert a frequency into the closest noise period value for the specified clock rate.
+///
+/// The frequency is the rate at which the noise generator's shift register is updated, which
+/// determines the "color" of the noise. Like the tone generator, the noise generator divides the
+/// clock by 16, but its period register is only 5 bits wide. The resulting period is therefore
+/// clamped to the range of 1 to 31 inclusive, which only spans around five octaves. At a clock
+/// rate of 2 MHz for example, this covers frequencies between roughly 4 kHz and 125 kHz.
+pub fn frequency_to_noise_period(frequency: f64, clock_rate: f64) -> u8 {
+    (clock_rate / (16.0 * frequency)).round().clamp(1.0, 31.0) as u8
+}
+
+/// Convert a frequency into its corresponding tone period value for the specified clock rate, or
+/// return an error when the period lies outside of the range of 1 to 4095 inclusive.
+pub fn frequency_to_tone_period_checked(frequency: f64, clock_rate: f64) -> Result<u16, RangeError> {
+    check_period(clock_rate / (16.0 * frequency), 0x0fff, |period| tone_period_to_frequency(period, clock_rate))
+}
+
+/// Convert a frequency into its corresponding envelope period value for the specified clock rate,
+/// or return an error when the period lies outside of the range of 1 to 65535 inclusive.
+pub fn frequency_to_envelope_period_checked(frequency: f64, clock_rate: f64) -> Result<u16, RangeError> {
+    check_period(clock_rate / (256.0 * frequency), 0xffff, |period| envelope_period_to_frequency(period, clock_rate))
+}
+
+/// Convert a frequency into its corresponding noise period value for the specified clock rate, or
+/// return an error when the period lies outside of the range of 1 to 31 inclusive.
+pub fn frequency_to_noise_period_checked(frequency: f64, clock_rate: f64) -> Result<u8, RangeError> {
+    check_period(clock_rate / (16.0 * frequency), 31, |period| noise_period_to_frequency(period as u8, clock_rate))
+        .map(|period| period as u8)
+}
+
 /// Convert a tone period value into its corresponding frequency for the specified clock rate.
 pub fn tone_period_to_frequency(period: u16, clock_rate: f64) -> f64 {
     clock_rate / (period as f64 * 16.0)
@@ -65,8 +435,512 @@ pub fn envelope_period_to_frequency(period: u16, clock_rate: f64) -> f64 {
     clock_rate / (period as f64 * 256.0)
 }
 
-#[cfg(test)]
+/// Convert a noise period value into the frequency at which the noise generator's shift register
+/// is updated for the specified clock rate. A period of zero behaves like a period of 1.
+pub fn noise_period_to_frequency(period: u8, clock_rate: f64) -> f64 {
+    clock_rate / (period.max(1) as f64 * 16.0)
+}
+
+/// Divides a stream of output samples into frames of a register stream player, for example at
+/// 50 Hz or 60 Hz.
+///
+/// When the sample rate is not a multiple of the frame rate, the frames cannot all have the same
+/// length. The clock distributes the fractional remainder over the frames, so that the total
+/// number of samples after any number of frames is the rounded exact value, and long runs never
+/// drift:
+///
+/// ```
+/// use psg::math::FrameClock;
+///
+/// let mut clock = FrameClock::new(44100, 59.94);
+///
+/// let lengths: Vec<usize> = (0..4).map(|_| clock.samples_for_next_frame()).collect();
+/// assert_eq!(lengths, [736, 735, 736, 736]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameClock {
+    sample_rate: u32,
+    frame_rate: f64,
+    frames: u64,
+    samples: u64
+}
+
+impl FrameClock {
+    /// Initialize a new frame clock for the specified sample rate and frame rate in Hz.
+    pub fn new(sample_rate: u32, frame_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            frame_rate,
+            frames: 0,
+            samples: 0
+        }
+    }
+
+    /// The number of samples to render for the next frame.
+    pub fn samples_for_next_frame(&mut self) -> usize {
+        self.frames += 1;
+
+        let samples = frames_to_samples(self.frames, self.sample_rate, self.frame_rate);
+        let length = samples - self.samples;
+
+        self.samples = samples;
+        length as usize
+    }
+
+    /// The number of frames that have elapsed.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The total number of samples of the elapsed frames.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Restart the clock at the first frame.
+    pub fn reset(&mut self) {
+        self.frames = 0;
+        self.samples = 0;
+    }
+}
+
+/// Convert a number of frames at the specified frame rate into the closest number of samples at
+/// the specified sample rate.
+pub fn frames_to_samples(frames: u64, sample_rate: u32, frame_rate: f64) -> u64 {
+    (frames as f64 * sample_rate as f64 / frame_rate).round() as u64
+}
+
+/// Convert a number of samples at the specified sample rate into the number of frames at the
+/// specified frame rate.
+///
+/// The resulting number of frames is not guaranteed to be an integer.
+pub fn samples_to_frames(samples: u64, sample_rate: u32, frame_rate: f64) -> f64 {
+    samples as f64 * frame_rate / sample_rate as f64
+}
+
+/// The MIDI pitch number of the first entry of [`standard_tone_period_table`], which is C1.
+pub const STANDARD_TABLE_LOWEST_PITCH: u8 = 24;
+
+/// Precompute the tone periods of `count` consecutive semitones for the specified clock rate,
+/// starting at the MIDI pitch number `lowest_pitch`. This avoids computing the periods while
+/// playing, like the period tables of classic music engines.
+///
+/// Periods that lie outside of the range of 1 to 4095 inclusive are clamped to that range. Use
+/// [`tone_period_table_checked`] to find out which entries were clamped.
+pub fn tone_period_table(clock_rate: f64, lowest_pitch: u8, count: usize) -> Vec<u16> {
+    clamp_table(tone_period_table_checked(clock_rate, lowest_pitch, count))
+}
+
+/// Precompute the tone periods of `count` consecutive semitones like [`tone_period_table`], but
+/// flag the entries that lie outside of the range of the tone period with a [`RangeError`], which
+/// contains the clamped period.
+pub fn tone_period_table_checked(clock_rate: f64, lowest_pitch: u8, count: usize) -> Vec<Result<u16, RangeError>> {
+    (0..count).map(|index| midi_pitch_to_tone_period_checked((lowest_pitch as usize + index) as f64, clock_rate)).collect()
+}
+
+/// Precompute the envelope periods of `count` consecutive semitones for the specified clock rate,
+/// starting at the MIDI pitch number `lowest_pitch`.
+///
+/// Periods that lie outside of the range of 1 to 65535 inclusive are clamped to that range. Use
+/// [`envelope_period_table_checked`] to find out which entries were clamped.
+pub fn envelope_period_table(clock_rate: f64, lowest_pitch: u8, count: usize) -> Vec<u16> {
+    clamp_table(envelope_period_table_checked(clock_rate, lowest_pitch, count))
+}
+
+/// Precompute the envelope periods of `count` consecutive semitones like
+/// [`envelope_period_table`], but flag the entries that lie outside of the range of the envelope
+/// period with a [`RangeError`], which contains the clamped period.
+pub fn envelope_period_table_checked(clock_rate: f64, lowest_pitch: u8, count: usize) -> Vec<Result<u16, RangeError>> {
+    (0..count).map(|index| midi_pitch_to_envelope_period_checked((lowest_pitch as usize + index) as f64, clock_rate)).collect()
+}
+
+/// Precompute the tone periods of the standard 8-octave range of 96 semitones for the specified
+/// clock rate, starting at C1 (see [`STANDARD_TABLE_LOWEST_PITCH`]). Periods that lie outside of
+/// the range of the tone period are clamped, like with [`tone_period_table`].
+pub fn standard_tone_period_table(clock_rate: f64) -> [u16; 96] {
+    core::array::from_fn(|index| {
+        let pitch = (STANDARD_TABLE_LOWEST_PITCH as usize + index) as f64;
+        midi_pitch_to_tone_period_checked(pitch, clock_rate).unwrap_or_else(|error| error.period())
+    })
+}
+
+/// Compute the tone periods of the steps of an arpeggio for the specified clock rate, where each
+/// step plays the base pitch raised by an offset in semitones.
+///
+/// Periods that lie outside of the range of 1 to 4095 inclusive are clamped to that range. Use
+/// [`arpeggio_periods_checked`] to find out which steps were clamped.
+pub fn arpeggio_periods(base_pitch: f64, offsets: &[i8], clock_rate: f64) -> Vec<u16> {
+    clamp_table(arpeggio_periods_checked(base_pitch, offsets, clock_rate))
+}
+
+/// Compute the tone periods of the steps of an arpeggio like [`arpeggio_periods`], but flag the
+/// steps that lie outside of the range of the tone period with a [`RangeError`], which contains
+/// the clamped period.
+pub fn arpeggio_periods_checked(base_pitch: f64, offsets: &[i8], clock_rate: f64) -> Vec<Result<u16, RangeError>> {
+    offsets.iter().map(|&offset| midi_pitch_to_tone_period_checked(base_pitch + offset as f64, clock_rate)).collect()
+}
+
+/// Cycles through the steps of an arpeggio, which fakes a chord on a single channel by quickly
+/// alternating between its notes.
+///
+/// Call [`next_period`](Self::next_period) once per frame, and write the result to the channel's
+/// tone period:
+///
+/// ```
+/// use psg::math::Arpeggiator;
+///
+/// // A major chord on A4, advancing every frame
+/// let mut arpeggiator = Arpeggiator::new(69.0, &[0, 4, 7], 1, 1_000_000.0);
+///
+/// let periods: Vec<u16> = (0..4).map(|_| arpeggiator.next_period()).collect();
+/// assert_eq!(periods, [142, 113, 95, 142]);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arpeggiator {
+    periods: Vec<u16>,
+    clamped: bool,
+    step_length: u32,
+    step: usize,
+    frame: u32
+}
+
+impl Arpeggiator {
+    /// Initialize a new arpeggiator for the specified base pitch, offsets in semitones, and clock
+    /// rate, which plays each step for `step_length` frames. A step length of zero is treated as
+    /// a single frame. The periods are clamped like with [`arpeggio_periods`].
+    ///
+    /// An arpeggiator without any offsets plays the base pitch.
+    pub fn new(base_pitch: f64, offsets: &[i8], step_length: u32, clock_rate: f64) -> Self {
+        let offsets = if offsets.is_empty() { &[0] } else { offsets };
+        let periods = arpeggio_periods_checked(base_pitch, offsets, clock_rate);
+
+        Self {
+            clamped: periods.iter().any(Result::is_err),
+            periods: clamp_table(periods),
+            step_length: step_length.max(1),
+            step: 0,
+            frame: 0
+        }
+    }
+
+    /// The tone period for the next frame.
+    pub fn next_period(&mut self) -> u16 {
+        let period = self.periods[self.step];
+
+        self.frame += 1;
+
+        if self.frame >= self.step_length {
+            self.frame = 0;
+            self.step = (self.step + 1) % self.periods.len();
+        }
+
+        period
+    }
+
+    /// The tone periods of the steps.
+    pub fn periods(&self) -> &[u16] {
+        &self.periods
+    }
+
+    /// Whether the period of any step lies outside of the range of the tone period, and was
+    /// clamped.
+    pub fn clamped(&self) -> bool {
+        self.clamped
+    }
+
+    /// The number of frames for which each step is played.
+    pub fn step_length(&self) -> u32 {
+        self.step_length
+    }
+
+    /// Set the number of frames for which each step is played. A step length of zero is treated
+    /// as a single frame.
+    pub fn set_step_length(&mut self, step_length: u32) {
+        self.step_length = step_length.max(1);
+        self.frame = self.frame.min(self.step_length - 1);
+    }
+
+    /// Restart the arpeggio at its first step.
+    pub fn reset(&mut self) {
+        self.step = 0;
+        self.frame = 0;
+    }
+}
+
+/// Interpolate between two tone periods for the specified clock rate, where `t` runs from 0 at the
+/// first period to 1 at the second period. Values outside of this range are clamped.
+///
+/// The interpolation is linear in pitch instead of in period, so a glide between two notes keeps
+/// a constant speed in semitones, regardless of the register of the notes. The result is rounded
+/// to the closest period, and is exactly equal to the respective period at both ends.
+pub fn glide(from_period: u16, to_period: u16, t: f64, clock_rate: f64) -> u16 {
+    match t {
+        t if t <= 0.0 => from_period,
+        t if t >= 1.0 => to_period,
+        t => {
+            let from = tone_period_to_midi_pitch(from_period.max(1), clock_rate);
+            let to = tone_period_to_midi_pitch(to_period.max(1), clock_rate);
+
+            pitch_to_clamped_tone_period(from + (to - from) * t, clock_rate)
+        }
+    }
+}
+
+/// Glides from one tone period to another at a constant rate in semitones per frame, for playing
+/// portamento effects.
+///
+/// Call [`step`](Self::step) once per frame, and write the result to the channel's tone period.
+/// Like [`glide`], the glide is linear in pitch, and it ends exactly on the target period.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glide {
+    pitch: f64,
+    target_pitch: f64,
+    target_period: u16,
+    rate: f64,
+    clock_rate: f64
+}
+
+impl Glide {
+    /// Initialize a new glide between two tone periods for the specified clock rate, which moves
+    /// `rate` semitones per frame towards the target. The sign of the rate is ignored.
+    pub fn new(from_period: u16, to_period: u16, rate: f64, clock_rate: f64) -> Self {
+        Self {
+            pitch: tone_period_to_midi_pitch(from_period.max(1), clock_rate),
+            target_pitch: tone_period_to_midi_pitch(to_period.max(1), clock_rate),
+            target_period: to_period,
+            rate: rate.abs(),
+            clock_rate
+        }
+    }
+
+    /// Advance the glide by a frame, and return the tone period for the frame. Once the target
+    /// has been reached, the target period is returned.
+    pub fn step(&mut self) -> u16 {
+        let distance = self.target_pitch - self.pitch;
+
+        if distance.abs() <= self.rate {
+            self.pitch = self.target_pitch;
+            return self.target_period;
+        }
+
+        self.pitch += self.rate.copysign(distance);
+        pitch_to_clamped_tone_period(self.pitch, self.clock_rate)
+    }
+
+    /// Whether the glide has reached its target.
+    pub fn is_finished(&self) -> bool {
+        self.pitch == self.target_pitch
+    }
+
+    /// The target tone period.
+    pub fn target_period(&self) -> u16 {
+        self.target_period
+    }
+}
+
+/// The waveform of a [`Vibrato`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VibratoWaveform {
+    /// A sine wave.
+    Sine,
+
+    /// A triangle wave, which changes the pitch at a constant speed.
+    Triangle,
+
+    /// A square wave, which alternates between the highest and lowest pitch.
+    Square
+}
+
+impl VibratoWaveform {
+    /// The value of the waveform at the specified phase between 0 and 1, which lies between -1
+    /// and 1. Every waveform starts at the center and rises first, except for the square wave,
+    /// which starts at its highest value.
+    fn value(self, phase: f64) -> f64 {
+        match self {
+            VibratoWaveform::Sine => (phase * core::f64::consts::TAU).sin(),
+            VibratoWaveform::Triangle => match phase {
+                phase if phase < 0.25 => phase * 4.0,
+                phase if phase < 0.75 => 2.0 - phase * 4.0,
+                phase => phase * 4.0 - 4.0
+            },
+            VibratoWaveform::Square => if phase < 0.5 { 1.0 } else { -1.0 }
+        }
+    }
+}
+
+/// A low frequency oscillator that modulates the pitch of a tone period once per frame, for
+/// playing vibrato effects.
+///
+/// Call [`next_period`](Self::next_period) once per frame with the period of the note, and write
+/// the result to the channel's tone period:
+///
+/// ```
+/// use psg::math::{Vibrato, VibratoWaveform};
+///
+/// // A vibrato of a quarter semitone at 5 Hz, for a player running at 50 Hz
+/// let mut vibrato = Vibrato::new(25.0, 5.0, VibratoWaveform::Sine, 50.0);
+///
+/// let period = vibrato.next_period(1000, 1_000_000.0);
+/// assert_eq!(period, 1000);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vibrato {
+    depth: f64,
+    rate: f64,
+    waveform: VibratoWaveform,
+    frame_rate: f64,
+    phase: f64
+}
+
+impl Vibrato {
+    /// Initialize a new vibrato that deviates at most `depth` cents from the note in both
+    /// directions, with the specified rate in Hz, waveform, and frame rate of the player in Hz.
+    pub fn new(depth: f64, rate: f64, waveform: VibratoWaveform, frame_rate: f64) -> Self {
+        Self {
+            depth,
+            rate,
+            waveform,
+            frame_rate,
+            phase: 0.0
+        }
+    }
+
+    /// The tone period for the next frame, given the period of the note and the clock rate.
+    ///
+    /// The modulated pitch is rounded to the closest period, like [`detune_period_cents`]. The
+    /// period of a low note changes more often than that of a high note, and a vibrato that is
+    /// smaller than half the difference between adjacent periods leaves the period unchanged.
+    pub fn next_period(&mut self, base_period: u16, clock_rate: f64) -> u16 {
+        let cents = self.depth * self.waveform.value(self.phase);
+
+        self.phase = (self.phase + self.rate / self.frame_rate).fract();
+        detune_period_cents(base_period, cents, clock_rate)
+    }
+
+    /// Restart the vibrato at the start of its waveform, for example when a new note starts.
+    pub fn retrigger(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// The maximum deviation from the note in cents.
+    pub fn depth(&self) -> f64 {
+        self.depth
+    }
+
+    /// Set the maximum deviation from the note in cents.
+    pub fn set_depth(&mut self, depth: f64) {
+        self.depth = depth;
+    }
+
+    /// The rate of the vibrato in Hz.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Set the rate of the vibrato in Hz.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+
+    /// The waveform of the vibrato.
+    pub fn waveform(&self) -> VibratoWaveform {
+        self.waveform
+    }
+
+    /// Set the waveform of the vibrato.
+    pub fn set_waveform(&mut self, waveform: VibratoWaveform) {
+        self.waveform = waveform;
+    }
+}
+
+/// The way in which a MIDI velocity or a volume fader position between 0 and 127 inclusive is
+/// mapped onto the 16 logarithmic amplitude levels, see [`velocity_to_amplitude`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VolumeCurve {
+    /// The velocity is scaled linearly onto the amplitudes. Since the amplitudes are already
+    /// logarithmic, most of the velocity range ends up in the loud part of the range.
+    Linear,
+
+    /// The velocity is scaled linearly onto a 40 dB range of levels, so every velocity step
+    /// changes the level by the same number of decibels.
+    Logarithmic,
+
+    /// The velocity is treated as a loudness, which is converted into a level using Stevens'
+    /// power law (loudness grows with the 0.6th power of the amplitude). Equal velocity steps
+    /// give roughly equal loudness steps on the YM2149's DAC.
+    Perceptual
+}
+
+/// The dynamic range of [`VolumeCurve::Logarithmic`] in decibels.
+const LOGARITHMIC_RANGE_DB: f64 = 40.0;
+
+/// Convert a MIDI velocity or a volume fader position between 0 and 127 inclusive into an
+/// amplitude between 0 and 15 inclusive, using the specified curve. Higher velocities are
+/// treated as 127.
+///
+/// A velocity of 0 always maps to amplitude 0, and 127 to amplitude 15. Higher velocities never
+/// map to lower amplitudes.
+pub fn velocity_to_amplitude(velocity: u8, curve: VolumeCurve) -> u8 {
+    let velocity = velocity.min(127);
+
+    if velocity == 0 {
+        return 0;
+    }
+
+    let fraction = velocity as f64 / 127.0;
+
+    match curve {
+        VolumeCurve::Linear => (fraction * 15.0).round() as u8,
+        VolumeCurve::Logarithmic => db_to_amplitude((fraction - 1.0) * LOGARITHMIC_RANGE_DB),
+        VolumeCurve::Perceptual => db_to_amplitude(20.0 * fraction.log10() / 0.6)
+    }
+}
+
+/// Convert an amplitude between 0 and 15 inclusive into the lowest velocity that produces at
+/// least that amplitude using the specified curve, which is the inverse of
+/// [`velocity_to_amplitude`]. Higher amplitudes are treated as 15.
+pub fn amplitude_to_velocity(amplitude: u8, curve: VolumeCurve) -> u8 {
+    let amplitude = amplitude.min(15);
+
+    (0..=127).find(|&velocity| velocity_to_amplitude(velocity, curve) >= amplitude).unwrap_or(127)
+}
+
+/// The level of an amplitude on the YM2149's DAC in decibels relative to amplitude 15. The
+/// 4-bit amplitudes select every second level of the 5-bit table.
+fn amplitude_db(amplitude: u8) -> f64 {
+    20.0 * DacTable::YM.levels()[amplitude as usize * 2 + 1].log10()
+}
+
+/// Convert a level in decibels relative to full scale into the amplitude between 0 and 15
+/// inclusive whose level on the YM2149's DAC is closest to it.
+///
+/// Levels above 0 dB map to amplitude 15. Levels that are more than half a step below the level of
+/// amplitude 1 map to amplitude 0, which is silent.
+pub fn db_to_amplitude(db: f64) -> u8 {
+    let silence = amplitude_db(1) - (amplitude_db(2) - amplitude_db(1)) / 2.0;
+
+    if db.is_nan() || db < silence {
+        return 0;
+    }
+
+    (1..=15).min_by(|&a, &b| (amplitude_db(a) - db).abs().total_cmp(&(amplitude_db(b) - db).abs())).unwrap_or(15)
+}
+
+/// Convert a MIDI pitch number into the closest tone period, saturating at the range of the tone
+/// period.
+fn pitch_to_clamped_tone_period(pitch: f64, clock_rate: f64) -> u16 {
+    midi_pitch_to_tone_period_checked(pitch, clock_rate).unwrap_or_else(|error| error.period())
+}
+
+/// Replace the flagged entries of a checked period table with their clamped periods.
+fn clamp_table(table: Vec<Result<u16, RangeError>>) -> Vec<u16> {
+    table.into_iter().map(|entry| entry.unwrap_or_else(|error| error.period())).collect()
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
+    use super::{Accidental, NoteNameError, RangeError, VolumeCurve};
+
     #[test]
     fn midi_pitch_to_frequency() {
         assert_eq!(super::midi_pitch_to_frequency(81.0), 880.0);
@@ -122,6 +996,343 @@ mod tests {
         assert_eq!(period, 100);
     }
 
+    #[test]
+    fn noise_period_conversion() {
+        let period = super::frequency_to_noise_period(12500.0, 1000000.0);
+        let frequency = super::noise_period_to_frequency(period, 1000000.0);
+
+        assert_eq!(period, 5);
+        assert_eq!(frequency, 12500.0);
+
+        for period in 1..=31 {
+            let frequency = super::noise_period_to_frequency(period, 1000000.0);
+            assert_eq!(super::frequency_to_noise_period(frequency, 1000000.0), period);
+        }
+
+        // Frequencies outside of the 5-bit range are clamped
+        assert_eq!(super::frequency_to_noise_period(10.0, 1000000.0), 31);
+        assert_eq!(super::frequency_to_noise_period(1e6, 1000000.0), 1);
+        assert_eq!(super::noise_period_to_frequency(0, 1000000.0), 62500.0);
+
+        let period = super::midi_pitch_to_noise_period(super::frequency_to_midi_pitch(12500.0), 1000000.0);
+        assert_eq!(period, 5);
+    }
+
+    #[test]
+    fn checked_period_conversion() {
+        assert_eq!(super::frequency_to_tone_period_checked(100.0, 1000000.0), Ok(625));
+        assert_eq!(super::frequency_to_envelope_period_checked(1.25, 1000000.0), Ok(3125));
+        assert_eq!(super::frequency_to_noise_period_checked(12500.0, 1000000.0), Ok(5));
+
+        // A very low note would overflow the 12-bit period
+        let error = super::frequency_to_tone_period_checked(10.0, 1000000.0).unwrap_err();
+        assert_eq!(error, RangeError::TooLow { period: 4095, frequency: super::tone_period_to_frequency(4095, 1000000.0) });
+        assert_eq!(error.period(), 4095);
+
+        // A very high note would round to a period of zero
+        let error = super::midi_pitch_to_tone_period_checked(170.0, 1000000.0).unwrap_err();
+        assert_eq!(error, RangeError::TooHigh { period: 1, frequency: 62500.0 });
+        assert_eq!(error.frequency(), 62500.0);
+
+        assert_eq!(super::frequency_to_tone_period_checked(0.0, 1000000.0).unwrap_err().period(), 4095);
+        assert_eq!(super::frequency_to_envelope_period_checked(0.001, 1000000.0).unwrap_err().period(), 65535);
+        assert_eq!(super::midi_pitch_to_envelope_period_checked(21.0, 1000000.0), Ok(142));
+        assert_eq!(super::midi_pitch_to_noise_period_checked(0.0, 1000000.0).unwrap_err().period(), 31);
+        assert_eq!(super::frequency_to_noise_period_checked(1e6, 1000000.0).unwrap_err().period(), 1);
+
+        assert_eq!(
+            RangeError::TooHigh { period: 1, frequency: 62500.0 }.to_string(),
+            "the pitch is too high, the highest possible frequency is 62500 Hz"
+        );
+    }
+
+    #[test]
+    fn concert_pitch_reference() {
+        assert_eq!(super::midi_pitch_to_frequency_with_reference(69.0, 432.0), 432.0);
+        assert_eq!(super::midi_pitch_to_frequency_with_reference(81.0, 415.0), 830.0);
+        assert_eq!(super::frequency_to_midi_pitch_with_reference(432.0, 432.0), 69.0);
+        assert_eq!(super::frequency_to_midi_pitch_with_reference(216.0, 432.0), 57.0);
+
+        // Lowering A4 to 432 Hz lengthens every period by 440 / 432, up to rounding
+        for pitch in 24..=108 {
+            let pitch = pitch as f64;
+            let period = super::midi_pitch_to_tone_period(pitch, 4000000.0) as f64;
+            let shifted = super::midi_pitch_to_tone_period_with_reference(pitch, 4000000.0, 432.0) as f64;
+
+            assert!((shifted - period * 440.0 / 432.0).abs() <= 1.0);
+
+            let period = super::midi_pitch_to_envelope_period(pitch - 48.0, 4000000.0) as f64;
+            let shifted = super::midi_pitch_to_envelope_period_with_reference(pitch - 48.0, 4000000.0, 432.0) as f64;
+
+            assert!((shifted - period * 440.0 / 432.0).abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn note_names() {
+        for pitch in 0..=127 {
+            for accidental in [Accidental::Sharp, Accidental::Flat] {
+                let name = super::midi_pitch_to_note_name(pitch as f64, accidental);
+                assert_eq!(super::note_name_to_midi_pitch(&name), Ok(pitch as f64), "{}", name);
+            }
+        }
+
+        assert_eq!(super::midi_pitch_to_note_name(60.0, Accidental::Sharp), "C-4");
+        assert_eq!(super::midi_pitch_to_note_name(70.0, Accidental::Sharp), "A#4");
+        assert_eq!(super::midi_pitch_to_note_name(70.0, Accidental::Flat), "Bb4");
+        assert_eq!(super::midi_pitch_to_note_name(1.0, Accidental::Sharp), "C#-1");
+        assert_eq!(super::midi_pitch_to_note_name(0.0, Accidental::Sharp), "C--1");
+        assert_eq!(super::midi_pitch_to_note_name(69.12, Accidental::Sharp), "A-4+12c");
+        assert_eq!(super::midi_pitch_to_note_name(68.7, Accidental::Flat), "A-4-30c");
+
+        for name in ["A#3", "a#3", "Bb3", "bb3"] {
+            assert_eq!(super::note_name_to_midi_pitch(name), Ok(58.0), "{}", name);
+        }
+
+        for name in ["C4", "C-4", "C 4", "B#3"] {
+            assert_eq!(super::note_name_to_midi_pitch(name), Ok(60.0), "{}", name);
+        }
+
+        assert_eq!(super::note_name_to_midi_pitch("C-1"), Ok(24.0));
+        assert_eq!(super::note_name_to_tone_period("A-4", 1000000.0), Ok(142));
+
+        assert_eq!(super::note_name_to_midi_pitch(""), Err(NoteNameError::Empty));
+        assert_eq!(super::note_name_to_midi_pitch("H4"), Err(NoteNameError::InvalidLetter('H')));
+        assert_eq!(super::note_name_to_midi_pitch("C#"), Err(NoteNameError::InvalidOctave(String::new())));
+        assert_eq!(super::note_name_to_midi_pitch("C-x"), Err(NoteNameError::InvalidOctave("x".to_string())));
+        assert_eq!(
+            super::note_name_to_midi_pitch("Cx4").unwrap_err().to_string(),
+            "'x4' is not a valid octave, it must be an integer"
+        );
+    }
+
+    #[test]
+    fn period_tables() {
+        let table = super::tone_period_table(1773400.0, 36, 48);
+
+        assert_eq!(table.len(), 48);
+
+        for (index, &period) in table.iter().enumerate() {
+            assert_eq!(period, super::midi_pitch_to_tone_period(36.0 + index as f64, 1773400.0));
+        }
+
+        let table = super::envelope_period_table(1773400.0, 12, 24);
+        assert_eq!(table[12], super::midi_pitch_to_envelope_period(24.0, 1773400.0));
+
+        // At 4 MHz the lowest notes of the standard range overflow the 12-bit tone period
+        let standard = super::standard_tone_period_table(4000000.0);
+        let checked = super::tone_period_table_checked(4000000.0, super::STANDARD_TABLE_LOWEST_PITCH, 96);
+
+        assert_eq!(checked[0], Err(RangeError::TooLow { period: 4095, frequency: super::tone_period_to_frequency(4095, 4000000.0) }));
+        assert_eq!(standard[0], 4095);
+        assert_eq!(standard[45], 568);
+        assert_eq!(checked[45], Ok(568));
+        assert_eq!(standard.to_vec(), super::tone_period_table(4000000.0, super::STANDARD_TABLE_LOWEST_PITCH, 96));
+        assert_eq!(checked.iter().filter(|entry| entry.is_err()).count(), 11);
+    }
+
+    #[test]
+    fn pitch_bend() {
+        let center = super::midi_pitch_to_tone_period(69.0, 1000000.0);
+
+        assert_eq!(super::midi_pitch_bend_to_tone_period(69, 8192, 2.0, 1000000.0), center);
+        assert_eq!(super::midi_pitch_bend_to_tone_period(69, 16383, 2.0, 1000000.0), super::midi_pitch_to_tone_period(71.0, 1000000.0));
+        assert_eq!(super::midi_pitch_bend_to_tone_period(69, 0, 2.0, 1000000.0), super::midi_pitch_to_tone_period(67.0, 1000000.0));
+        assert_eq!(super::midi_pitch_bend_to_tone_period(69, 0xffff, 2.0, 1000000.0), super::midi_pitch_to_tone_period(71.0, 1000000.0));
+
+        // The period saturates instead of wrapping
+        assert_eq!(super::midi_pitch_bend_to_tone_period(0, 0, 12.0, 1000000.0), 4095);
+        assert_eq!(super::midi_pitch_bend_to_tone_period(127, 16383, 48.0, 1000000.0), 1);
+    }
+
+    #[test]
+    fn detune() {
+        // A period of 100 lies 17 cents from its neighbours, so small detunes round to no change
+        assert_eq!(super::detune_period_cents(100, 5.0, 1000000.0), 100);
+        assert_eq!(super::detune_period_cents(100, -5.0, 1000000.0), 100);
+        assert_eq!(super::detune_period_cents(100, 0.0, 1000000.0), 100);
+
+        assert_eq!(super::detune_period_cents(100, 1200.0, 1000000.0), 50);
+        assert_eq!(super::detune_period_cents(100, -1200.0, 1000000.0), 200);
+        assert_eq!(super::detune_period_cents(100, 20.0, 1000000.0), 99);
+
+        assert_eq!(super::detune_period_cents(4000, -100.0, 1000000.0), 4095);
+        assert_eq!(super::detune_period_cents(2, 2400.0, 1000000.0), 1);
+    }
+
+    #[test]
+    fn frame_clock() {
+        // An hour of 50 Hz frames at 44100 Hz
+        let mut clock = super::FrameClock::new(44100, 50.0);
+
+        for _ in 0..50 * 3600 {
+            assert_eq!(clock.samples_for_next_frame(), 882);
+        }
+
+        assert_eq!(clock.samples(), 44100 * 3600);
+
+        // An hour of 59.94 Hz frames, which alternate between two lengths without drifting
+        let mut clock = super::FrameClock::new(44100, 59.94);
+        let mut total = 0;
+
+        for _ in 0..215784 {
+            let length = clock.samples_for_next_frame();
+            assert!(length == 735 || length == 736);
+            total += length as u64;
+        }
+
+        assert_eq!(clock.frames(), 215784);
+        assert_eq!(total, super::frames_to_samples(215784, 44100, 59.94));
+        assert_eq!(total, 44100 * 3600);
+
+        clock.reset();
+        assert_eq!((clock.frames(), clock.samples()), (0, 0));
+
+        assert_eq!(super::frames_to_samples(50, 44100, 50.0), 44100);
+        assert_eq!(super::samples_to_frames(44100, 44100, 50.0), 50.0);
+        assert_eq!(super::samples_to_frames(441, 44100, 50.0), 0.5);
+    }
+
+    #[test]
+    fn buzzer() {
+        // A tone period of 160 matches an envelope period of 10 at a ratio of 1 exactly
+        let pitch = super::tone_period_to_midi_pitch(160, 1000000.0);
+        assert_eq!(super::buzzer_periods(pitch, 1.0, 1000000.0), Ok((160, 10, 0.0)));
+
+        let (tone, envelope, detune) = super::buzzer_periods(pitch, 2.0, 1000000.0).unwrap();
+        assert_eq!((tone, envelope, detune), (160, 5, 0.0));
+
+        // A4 has a tone period of 142, which cannot be divided into an exact envelope period
+        let (tone, envelope, detune) = super::buzzer_periods(69.0, 1.0, 1000000.0).unwrap();
+        assert_eq!((tone, envelope), (142, 9));
+        assert!((detune - super::cents_between(144.0, 142.0)).abs() < 1e-9);
+
+        assert!(matches!(super::buzzer_periods(69.0, 100.0, 1000000.0), Err(RangeError::TooHigh { period: 1, .. })));
+        assert!(matches!(super::buzzer_periods(69.0, 0.0001, 1000000.0), Err(RangeError::TooLow { period: 65535, .. })));
+    }
+
+    #[test]
+    fn arpeggio() {
+        // A major chord on A4 consists of A4, C#5, and E5
+        let periods = super::arpeggio_periods(69.0, &[0, 4, 7], 1000000.0);
+
+        assert_eq!(periods, [
+            super::midi_pitch_to_tone_period(69.0, 1000000.0),
+            super::midi_pitch_to_tone_period(73.0, 1000000.0),
+            super::midi_pitch_to_tone_period(76.0, 1000000.0)
+        ]);
+
+        let mut arpeggiator = super::Arpeggiator::new(69.0, &[0, 4, 7], 2, 1000000.0);
+        assert!(!arpeggiator.clamped());
+
+        let frames: Vec<u16> = (0..8).map(|_| arpeggiator.next_period()).collect();
+        assert_eq!(frames, [periods[0], periods[0], periods[1], periods[1], periods[2], periods[2], periods[0], periods[0]]);
+
+        arpeggiator.set_step_length(1);
+        arpeggiator.reset();
+        assert_eq!(arpeggiator.next_period(), periods[0]);
+        assert_eq!(arpeggiator.next_period(), periods[1]);
+
+        // Offsets beyond the range of the tone period are clamped and flagged
+        let checked = super::arpeggio_periods_checked(24.0, &[0, -24], 1000000.0);
+        assert!(checked[0].is_ok());
+        assert_eq!(checked[1].unwrap_err().period(), 4095);
+
+        let arpeggiator = super::Arpeggiator::new(24.0, &[0, -24], 1, 1000000.0);
+        assert!(arpeggiator.clamped());
+        assert_eq!(arpeggiator.periods()[1], 4095);
+
+        assert_eq!(super::Arpeggiator::new(69.0, &[], 1, 1000000.0).next_period(), periods[0]);
+    }
+
+    #[test]
+    fn glide() {
+        assert_eq!(super::glide(1000, 100, 0.0, 1000000.0), 1000);
+        assert_eq!(super::glide(1000, 100, 1.0, 1000000.0), 100);
+        assert_eq!(super::glide(1000, 100, 2.0, 1000000.0), 100);
+
+        // Halfway between two periods that are a decade apart in pitch is their geometric mean
+        assert_eq!(super::glide(1000, 100, 0.5, 1000000.0), 316);
+
+        // A glide of 40 semitones upwards, in steps of a third of a semitone
+        let mut glide = super::Glide::new(1000, 100, 1.0 / 3.0, 1000000.0);
+        let mut previous = 1000;
+        let mut frames = 0;
+
+        while !glide.is_finished() {
+            let period = glide.step();
+
+            assert!(period <= previous);
+            previous = period;
+            frames += 1;
+        }
+
+        assert_eq!(previous, 100);
+        assert_eq!(frames, 120);
+        assert_eq!(glide.step(), glide.target_period());
+
+        // And back down
+        let mut glide = super::Glide::new(100, 1000, 0.5, 1000000.0);
+        let periods: Vec<u16> = (0..100).map(|_| glide.step()).collect();
+
+        assert!(periods.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(periods[99], 1000);
+    }
+
+    #[test]
+    fn vibrato() {
+        for waveform in [super::VibratoWaveform::Sine, super::VibratoWaveform::Triangle, super::VibratoWaveform::Square] {
+            // A vibrato of half a semitone at 6.25 Hz, which takes 8 frames at 50 Hz
+            let mut vibrato = super::Vibrato::new(50.0, 6.25, waveform, 50.0);
+            let periods: Vec<u16> = (0..16).map(|_| vibrato.next_period(1000, 1000000.0)).collect();
+
+            // The periods repeat every cycle, and deviate equally in both directions up to rounding
+            assert_eq!(periods[..8], periods[8..]);
+
+            let min = *periods.iter().min().unwrap();
+            let max = *periods.iter().max().unwrap();
+
+            assert_eq!(min, super::detune_period_cents(1000, 50.0, 1000000.0));
+            assert_eq!(max, super::detune_period_cents(1000, -50.0, 1000000.0));
+            assert!((1000 - min).abs_diff(max - 1000) <= 1);
+
+            vibrato.retrigger();
+            assert_eq!(vibrato.next_period(1000, 1000000.0), periods[0]);
+
+            // Without depth, or with a depth below the period quantization, the period is unchanged
+            vibrato.set_depth(0.0);
+            assert!((0..20).all(|_| vibrato.next_period(1000, 1000000.0) == 1000));
+
+            vibrato.set_depth(0.5);
+            assert!((0..20).all(|_| vibrato.next_period(1000, 1000000.0) == 1000));
+        }
+    }
+
+    #[test]
+    fn cents_error() {
+        assert_eq!(super::cents_between(440.0, 880.0), 1200.0);
+        assert_eq!(super::cents_between(880.0, 440.0), -1200.0);
+        assert_eq!(super::cents_between(440.0, 440.0), 0.0);
+
+        // 1 MHz / (16 * 142) = 440.14 Hz, which is 0.55 cents sharp
+        let (period, error) = super::midi_pitch_to_tone_period_with_error(69.0, 1000000.0);
+        assert_eq!(period, 142);
+        assert!((error - 0.5537).abs() < 1e-3);
+        assert_eq!(super::tone_period_cents_error(69.0, 1000000.0), error);
+
+        // The error never exceeds half of the interval between the period and the next longer one
+        for step in 0..1000 {
+            let pitch = 30.0 + step as f64 * 0.1;
+            let (period, error) = super::midi_pitch_to_tone_period_with_error(pitch, 1000000.0);
+            let interval = super::cents_between(
+                super::tone_period_to_frequency(period, 1000000.0),
+                super::tone_period_to_frequency(period - 1, 1000000.0)
+            );
+
+            assert!(error.abs() <= interval / 2.0);
+        }
+    }
+
     #[test]
     fn envelop_period_conversion() {
         let period = super::frequency_to_envelope_period(1.25, 1000000.0);
@@ -136,4 +1347,39 @@ mod tests {
 
         assert_eq!(period, 100);
     }
+
+    #[test]
+    fn volume_curves() {
+        for curve in [VolumeCurve::Linear, VolumeCurve::Logarithmic, VolumeCurve::Perceptual] {
+            let amplitudes: Vec<u8> = (0..=127).map(|velocity| super::velocity_to_amplitude(velocity, curve)).collect();
+
+            // The mapping is monotonic, and covers the full range of amplitudes
+            assert!(amplitudes.windows(2).all(|pair| pair[0] <= pair[1]), "{curve:?}");
+            assert_eq!((amplitudes[0], amplitudes[127]), (0, 15), "{curve:?}");
+            assert!((0..=15).all(|amplitude| amplitudes.contains(&amplitude)), "{curve:?}");
+
+            for amplitude in 0..=15 {
+                let velocity = super::amplitude_to_velocity(amplitude, curve);
+
+                assert_eq!(super::velocity_to_amplitude(velocity, curve), amplitude, "{curve:?}");
+                assert!(velocity == 0 || super::velocity_to_amplitude(velocity - 1, curve) < amplitude, "{curve:?}");
+            }
+        }
+
+        // The linear curve wastes most of the velocity range on the loud amplitudes
+        assert_eq!(super::amplitude_to_velocity(8, VolumeCurve::Linear), 64);
+        assert!(super::amplitude_to_velocity(8, VolumeCurve::Perceptual) < 64);
+    }
+
+    #[test]
+    fn db_to_amplitude() {
+        assert_eq!(super::db_to_amplitude(0.0), 15);
+        assert_eq!(super::db_to_amplitude(6.0), 15);
+        assert_eq!(super::db_to_amplitude(-3.0), 14);
+        assert_eq!(super::db_to_amplitude(-6.0), 13);
+        assert_eq!(super::db_to_amplitude(-42.2), 1);
+        assert_eq!(super::db_to_amplitude(-60.0), 0);
+        assert_eq!(super::db_to_amplitude(f64::NEG_INFINITY), 0);
+        assert_eq!(super::db_to_amplitude(f64::NAN), 0);
+    }
 }
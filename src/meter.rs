@@ -0,0 +1,115 @@
+use crate::Error;
+use crate::state::{StateReader, StateWriter};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// A snapshot of the PSG's output levels, as returned by [`PSG::meter`](crate::PSG::meter).
+///
+/// All levels use a linear scale between 0.0 and 1.0, where 1.0 corresponds to the highest value
+/// in the chip's digital-to-analog conversion table. The master levels are measured after panning
+/// and DC filtering, so they can exceed 1.0 when multiple channels are panned to the same side.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Meter {
+    /// The peak level of each individual channel, before panning is applied. This is the value
+    /// that is looked up from the digital-to-analog conversion table for the channel's amplitude
+    /// or envelope level.
+    pub channel_peaks: [f64; 3],
+
+    /// The peak level of the left output channel.
+    pub left_peak: f64,
+
+    /// The peak level of the right output channel.
+    pub right_peak: f64,
+
+    /// The root mean square level of the left output channel.
+    pub left_rms: f64,
+
+    /// The root mean square level of the right output channel.
+    pub right_rms: f64
+}
+
+/// Accumulates the levels that make up a [`Meter`] between reads.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct MeterAccumulator {
+    channel_peaks: [f64; 3],
+    left_peak: f64,
+    right_peak: f64,
+    left_sum: f64,
+    right_sum: f64,
+    frames: u64
+}
+
+impl MeterAccumulator {
+    /// Initialize a new meter accumulator.
+    pub(crate) fn new() -> Self {
+        Self {
+            channel_peaks: [0.0; 3],
+            left_peak: 0.0,
+            right_peak: 0.0,
+            left_sum: 0.0,
+            right_sum: 0.0,
+            frames: 0
+        }
+    }
+
+    /// Write the accumulated levels to a saved state.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        for &peak in &self.channel_peaks {
+            writer.f64(peak);
+        }
+
+        writer.f64(self.left_peak);
+        writer.f64(self.right_peak);
+        writer.f64(self.left_sum);
+        writer.f64(self.right_sum);
+        writer.u64(self.frames);
+    }
+
+    /// Restore the accumulated levels from a saved state.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        for peak in &mut self.channel_peaks {
+            *peak = reader.f64()?;
+        }
+
+        self.left_peak = reader.f64()?;
+        self.right_peak = reader.f64()?;
+        self.left_sum = reader.f64()?;
+        self.right_sum = reader.f64()?;
+        self.frames = reader.u64()?;
+        Ok(())
+    }
+
+    /// Feed a channel's level for the current tick.
+    pub(crate) fn feed_channel(&mut self, index: usize, level: f64) {
+        self.channel_peaks[index] = self.channel_peaks[index].max(level);
+    }
+
+    /// Feed a rendered output frame.
+    pub(crate) fn feed_frame(&mut self, left: f64, right: f64) {
+        self.left_peak = self.left_peak.max(left.abs());
+        self.right_peak = self.right_peak.max(right.abs());
+        self.left_sum += left * left;
+        self.right_sum += right * right;
+        self.frames += 1;
+    }
+
+    /// Produce a meter from the accumulated levels and reset the accumulator.
+    pub(crate) fn take(&mut self) -> Meter {
+        let frames = self.frames.max(1) as f64;
+
+        let meter = Meter {
+            channel_peaks: self.channel_peaks,
+            left_peak: self.left_peak,
+            right_peak: self.right_peak,
+            left_rms: (self.left_sum / frames).sqrt(),
+            right_rms: (self.right_sum / frames).sqrt()
+        };
+
+        *self = Self::new();
+
+        meter
+    }
+}
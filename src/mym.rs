@@ -0,0 +1,186 @@
+//! This module contains a decoder for MYM files, the compressed register dump format that was
+//! made for Oric and Atari ST music. It is available when the `formats-mym` feature is enabled.
+//!
+//! A MYM file starts with the number of frames as a little-endian 16-bit value, followed by a bit
+//! stream that is read from the most significant bit of each byte onwards. The frames are stored
+//! in fragments of 128 frames, and every fragment contains one stream per register:
+//!
+//! - A 0 bit means that the register keeps its value for the whole fragment. A 1 bit is followed
+//!   by one code for every frame, until the fragment is complete.
+//! - A 0 bit code repeats the register's current value.
+//! - A 1 bit followed by a 1 bit reads a new value, which has as many bits as the register.
+//! - A 1 bit followed by a 0 bit copies a run of values from the 128 frames before the current
+//!   one. It is followed by a 7-bit distance and a 7-bit length minus one, where a distance of 0
+//!   refers to the value 128 frames back.
+//!
+//! The last fragment is padded to 128 frames. Files are played at 50 Hz, and register 13 is 0xFF
+//! in frames that do not write the envelope shape, like in YM files.
+//!
+//! ```no_run
+//! use psg::{FramePlayer, PSG};
+//! use psg::mym;
+//!
+//! let data = std::fs::read("tune.mym").unwrap();
+//! let frames = mym::parse(&data).unwrap();
+//!
+//! let player = FramePlayer::new(PSG::new(2_000_000.0, 44100).unwrap(), mym::FRAME_RATE, frames);
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The frame rate of MYM files in Hz.
+pub const FRAME_RATE: f64 = 50.0;
+
+/// The number of frames in a fragment.
+const FRAGMENT_SIZE: usize = 128;
+
+/// The number of bits of the copy distance and length.
+const COPY_BITS: u32 = 7;
+
+/// The number of bits that are stored for each register.
+const REGISTER_BITS: [u32; 14] = [8, 4, 8, 4, 8, 4, 5, 8, 5, 5, 5, 8, 8, 8];
+
+/// The error returned by [`parse`] when a file cannot be decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MymError {
+    /// The file ends before all of its frames have been decoded.
+    Truncated,
+
+    /// A copy extends beyond the end of its fragment, which means that the file is corrupt.
+    InvalidCopy
+}
+
+impl fmt::Display for MymError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MymError::Truncated => write!(f, "the MYM file is truncated"),
+            MymError::InvalidCopy => write!(f, "the MYM file contains a copy beyond the end of a fragment")
+        }
+    }
+}
+
+impl core::error::Error for MymError {}
+
+/// Decode the contents of a MYM file into frames of register values, to be played at 50 Hz.
+pub fn parse(data: &[u8]) -> Result<Vec<[u8; 14]>, MymError> {
+    let (&[low, high], stream) = data.split_first_chunk().ok_or(MymError::Truncated)?;
+    let frame_count = u16::from_le_bytes([low, high]) as usize;
+
+    let mut reader = BitReader::new(stream);
+    let mut frames = Vec::with_capacity(frame_count.next_multiple_of(FRAGMENT_SIZE));
+    let mut current = [0; 14];
+
+    // The values of every register in the previous and the current fragment
+    let mut window = [[0; 2 * FRAGMENT_SIZE]; 14];
+
+    while frames.len() < frame_count {
+        for (register, values) in window.iter_mut().enumerate() {
+            values.copy_within(FRAGMENT_SIZE.., 0);
+
+            if !reader.bit()? {
+                values[FRAGMENT_SIZE..].fill(current[register]);
+                continue;
+            }
+
+            let mut index = FRAGMENT_SIZE;
+
+            while index < values.len() {
+                if !reader.bit()? {
+                    values[index] = current[register];
+                    index += 1;
+                } else if reader.bit()? {
+                    current[register] = reader.bits(REGISTER_BITS[register])? as u8;
+                    values[index] = current[register];
+                    index += 1;
+                } else {
+                    let source = index - FRAGMENT_SIZE + reader.bits(COPY_BITS)? as usize;
+                    let length = reader.bits(COPY_BITS)? as usize + 1;
+
+                    if index + length > values.len() {
+                        return Err(MymError::InvalidCopy);
+                    }
+
+                    // The source may overlap the values that are being copied
+                    for offset in 0..length {
+                        values[index + offset] = values[source + offset];
+                    }
+
+                    index += length;
+                    current[register] = values[index - 1];
+                }
+            }
+        }
+
+        frames.extend((FRAGMENT_SIZE..2 * FRAGMENT_SIZE).map(|index| core::array::from_fn(|register| window[register][index])));
+    }
+
+    frames.truncate(frame_count);
+    Ok(frames)
+}
+
+/// Reads bits from the most significant bit of each byte onwards.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn bit(&mut self) -> Result<bool, MymError> {
+        let byte = self.data.get(self.position / 8).ok_or(MymError::Truncated)?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+
+        self.position += 1;
+        Ok(bit)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, MymError> {
+        (0..count).try_fold(0, |value, _| Ok((value << 1) | self.bit()? as u32))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{FRAME_RATE, MymError, parse};
+    use crate::{FramePlayer, PSG, PlayerStatus};
+
+    /// A MYM file with 300 frames of an arpeggio, which uses all three kinds of codes and ends
+    /// with a partial fragment.
+    const ARPEGGIO_MYM: &[u8] = include_bytes!("../testdata/arpeggio.mym");
+
+    #[test]
+    fn decode() {
+        let frames = parse(ARPEGGIO_MYM).unwrap();
+
+        assert_eq!(frames.len(), u16::from_le_bytes([ARPEGGIO_MYM[0], ARPEGGIO_MYM[1]]) as usize);
+        assert_eq!(frames.len(), 300);
+        assert_eq!(frames[0], [0x1c, 0x01, 0x1c, 0x02, 0, 0, 0, 0x3c, 15, 10, 0, 0, 0, 0xff]);
+        assert_eq!(frames[131], [0x7c, 0x01, 0x1c, 0x02, 0, 0, 0, 0x3c, 14, 10, 0, 0, 0, 0xff]);
+        assert_eq!(frames[299][..2], [0x1c, 0x01]);
+        assert_eq!(frames[256][13], 0x0a);
+    }
+
+    #[test]
+    fn playback() {
+        let frames = parse(ARPEGGIO_MYM).unwrap();
+        let mut player = FramePlayer::new(PSG::new(2_000_000.0, 44100).unwrap(), FRAME_RATE, frames);
+        let mut buffer = vec![0.0; 2 * 44100];
+
+        assert_eq!(player.render_into(&mut buffer), PlayerStatus::Playing);
+        assert!(buffer.iter().any(|&sample| sample.abs() > 0.1));
+    }
+
+    #[test]
+    fn truncated() {
+        for length in 0..ARPEGGIO_MYM.len() {
+            assert_eq!(parse(&ARPEGGIO_MYM[..length]), Err(MymError::Truncated));
+        }
+
+        // A copy of 128 values after the first value of a fragment
+        assert_eq!(parse(&[1, 0, 0xa0, 0x1f, 0xc0]), Err(MymError::InvalidCopy));
+    }
+}
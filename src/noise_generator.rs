@@ -1,11 +1,32 @@
+use core::fmt;
+
+use crate::Error;
+use crate::state::{StateReader, StateWriter};
+
+/// The default state of the noise generator's shift register, which is equivalent to Ayumi's
+/// initial state.
+const DEFAULT_STATE: u32 = 0x4001;
+
 /// The PSG's noise generator consists of a 17-bit linear feedback shift register with taps at bits
 /// 13 and 16.
 ///
 /// The register is updated once every N samples (the period).
+///
+/// The register is implemented in Galois form, while Ayumi uses the equivalent Fibonacci form.
+/// Both produce the same output sequence, but the register contents differ. An Ayumi noise state
+/// `x` can be converted into the equivalent state for this generator as `x ^ ((x & 7) << 14)`,
+/// and the same expression converts a state of this generator back into Ayumi's form. For example,
+/// Ayumi's initial state of 1 corresponds to the initial state 0x4001 of this generator.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NoiseGenerator {
     period: u8,
     counter: u8,
-    value: u32
+    value: u32,
+
+    // The period that a period register of zero behaves like, which is 0 in Ayumi compatibility
+    // mode and 1 otherwise
+    minimum_period: u8
 }
 
 impl NoiseGenerator {
@@ -18,10 +39,20 @@ impl NoiseGenerator {
             // This initialization value is needed so that the generator's Galois LFSR will produce
             // identical results to Ayumi's Fibonacci LFSR, which starts at 1. Galois LFSRs are
             // faster to compute but introduce a delay, so compensation is needed.
-            value: 0x4001
+            value: DEFAULT_STATE,
+
+            minimum_period: 1
         }
     }
 
+    /// Reset the noise generator to its power-on state, with a period register of zero and the
+    /// shift register restored to its initial state.
+    pub fn reset(&mut self) {
+        self.period = 0;
+        self.counter = 0;
+        self.value = DEFAULT_STATE;
+    }
+
     /// Render the next tick for the noise generator.
     ///
     /// This returns a byte containing the next noise value. The actual noise value is a 1-bit
@@ -29,14 +60,97 @@ impl NoiseGenerator {
     pub(crate) fn render(&mut self) -> u8 {
         self.counter += 1;
 
-        if self.counter >= (self.period << 1) {
+        if self.counter >= (self.period.max(self.minimum_period) << 1) {
             self.counter = 0;
+            self.shift();
+        }
+
+        (self.value & 1) as u8
+    }
+
+    /// Render the next tick for the noise generator when it is clocked by the PSG's shared
+    /// prescaler, which only advances the period counter on the prescaler's rising edges. The
+    /// counter then counts edges instead of ticks, so it is updated when it reaches the period.
+    pub(crate) fn render_prescaled(&mut self, edge: bool) -> u8 {
+        if edge {
+            self.counter += 1;
+
+            if self.counter >= self.period.max(self.minimum_period) {
+                self.counter = 0;
+                self.shift();
+            }
+        }
+
+        (self.value & 1) as u8
+    }
+
+    /// Compute the next value of the LFSR in Galois form.
+    fn shift(&mut self) {
+        let lsb = self.value & 1;
+        self.value = ((self.value >> 1) as i32 ^ ((-(lsb as i32)) & 0x12000)) as u32;
+    }
+
+    /// Write the noise generator's state to a saved state.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        writer.u8(self.period);
+        writer.u8(self.counter);
+        writer.u32(self.value);
+    }
+
+    /// Restore the noise generator's state from a saved state.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        self.period = reader.u8()?;
+        self.counter = reader.u8()?;
+        self.value = reader.u32()?;
 
-            // Compute the next value of the LFSR in Galois form
-            let lsb = self.value & 1;
-            self.value = ((self.value >> 1) as i32 ^ ((-(lsb as i32)) & 0x12000)) as u32;
+        if self.period > 0x1f || self.counter >= 0x40 || self.value >= 0x20000 {
+            return Err(Error::InvalidState);
         }
 
+        Ok(())
+    }
+
+    /// Enable or disable Ayumi's behavior for a period register of zero, which updates the shift
+    /// register on every tick instead of behaving like a period of 1.
+    pub(crate) fn set_ayumi_compatible(&mut self, enabled: bool) {
+        self.minimum_period = if enabled { 0 } else { 1 };
+    }
+
+    /// The contents of the noise generator's 17-bit shift register, in Galois form. See the
+    /// struct documentation for converting to and from Ayumi's representation.
+    pub fn state(&self) -> u32 {
+        self.value
+    }
+
+    /// Set the contents of the noise generator's 17-bit shift register, in Galois form.
+    ///
+    /// Higher bits are ignored. Since a register that only contains zeros would never produce any
+    /// noise, a value of zero is replaced by the default initial state.
+    pub fn set_state(&mut self, state: u32) {
+        self.value = match state & 0x1ffff {
+            0 => DEFAULT_STATE,
+            state => state
+        };
+    }
+
+    /// The noise generator's period counter, which counts the ticks until the shift register is
+    /// updated. The register is updated when the counter reaches twice the period. When the PSG
+    /// uses its shared prescaler, the counter counts the prescaler's edges instead, and the
+    /// register is updated when the counter reaches the period. See
+    /// [`PSG::set_shared_prescaler`](crate::PSG::set_shared_prescaler).
+    pub fn counter(&self) -> u8 {
+        self.counter
+    }
+
+    /// Set the noise generator's period counter to a value between 0 and 63 inclusive.
+    ///
+    /// Higher values are wrapped.
+    pub fn set_counter(&mut self, counter: u8) {
+        self.counter = counter & 0x3f;
+    }
+
+    /// The current output of the noise generator, which is either 0 or 1.
+    pub fn output(&self) -> u8 {
         (self.value & 1) as u8
     }
 
@@ -44,13 +158,68 @@ impl NoiseGenerator {
     ///
     /// This returns a value between 1 and 31 inclusive.
     pub fn period(&self) -> u8 {
+        self.period.max(1)
+    }
+
+    /// The noise generator's period register, as it was written.
+    ///
+    /// This returns a value between 0 and 31 inclusive, where 0 behaves like a period of 1.
+    pub(crate) fn period_register(&self) -> u8 {
         self.period
     }
 
     /// Set the noise generator's period to a value between 1 and 31 inclusive.
     ///
-    /// Lower values are set to 1, higher values are wrapped.
+    /// Lower values behave like a period of 1, higher values are wrapped.
     pub fn set_period(&mut self, period: u8) {
-        self.period = (period & 0x1f).max(1);
+        self.period = period & 0x1f;
+    }
+}
+
+impl fmt::Debug for NoiseGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NoiseGenerator")
+            .field("period", &self.period)
+            .field("counter", &self.counter)
+            .field("state", &format_args!("{:#07x}", self.value))
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::NoiseGenerator;
+
+    #[test]
+    fn state() {
+        let mut generator = NoiseGenerator::new();
+        assert_eq!(generator.state(), 0x4001);
+
+        generator.set_state(0x3_0000);
+        assert_eq!(generator.state(), 0x1_0000);
+
+        generator.set_state(0x2_0000);
+        assert_eq!(generator.state(), 0x4001);
+
+        generator.set_counter(100);
+        assert_eq!(generator.counter(), 36);
+    }
+
+    #[test]
+    fn ayumi_state() {
+        let mut ayumi: u32 = 0x1abcd;
+
+        let mut generator = NoiseGenerator::new();
+        generator.set_state(ayumi ^ ((ayumi & 7) << 14));
+
+        for _ in 0..10_000 {
+            // Ayumi's Fibonacci LFSR, updated every other tick with a period of 1
+            for _ in 0..2 {
+                assert_eq!(generator.output(), (ayumi & 1) as u8);
+                generator.render();
+            }
+
+            ayumi = (ayumi >> 1) | (((ayumi ^ (ayumi >> 3)) & 1) << 16);
+        }
     }
 }
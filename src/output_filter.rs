@@ -0,0 +1,182 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+
+use crate::Error;
+use crate::state::{StateReader, StateWriter};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// The maximum cutoff frequency of the output filter, relative to the sample rate. Cutoff
+/// frequencies above this value are clamped to keep the filter stable.
+const MAX_RELATIVE_CUTOFF: f64 = 0.45;
+
+/// An analog output filter that is applied to the PSG's output signal, emulating the circuitry
+/// between the sound chip and the speaker.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OutputFilter {
+    /// No output filter is applied. This is the default.
+    None,
+
+    /// A second-order Butterworth low-pass filter with the specified cutoff frequency in Hz.
+    LowPass {
+        /// The cutoff frequency of the filter in Hz.
+        cutoff_hz: f64
+    },
+
+    /// A filter that approximates the output stage of a specific machine.
+    Machine(MachinePreset)
+}
+
+/// Approximations of the output stages of popular machines that contain a PSG.
+///
+/// These are based on the RC networks and amplifiers found in the machines' audio paths, and are
+/// intended to reproduce their general character rather than to be exact models.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MachinePreset {
+    /// The ZX Spectrum 128K and later models. The PSG output is mixed with the beeper and passes
+    /// through several RC stages on its way to the TV modulator and the edge connector, which
+    /// results in a fairly dull sound. Emulated as a fourth-order low-pass filter at 4 kHz.
+    ZxSpectrum,
+
+    /// The MSX family. Most machines have a simple mixing stage with a low-pass filter at around
+    /// 10 kHz.
+    Msx,
+
+    /// The Atari ST. The YM2149 output passes through a low-pass filter in the monitor output,
+    /// with a cutoff frequency of roughly 8 kHz.
+    AtariSt,
+
+    /// The Amstrad CPC. The stereo output on the headphone jack passes through an amplifier with
+    /// a low-pass filter at roughly 7 kHz.
+    AmstradCpc
+}
+
+impl MachinePreset {
+    /// Return the cutoff frequencies and quality factors of the low-pass stages that make up the
+    /// preset.
+    fn stages(&self) -> &'static [(f64, f64)] {
+        match self {
+            MachinePreset::ZxSpectrum => &[(4000.0, 0.54), (4000.0, 1.31)],
+            MachinePreset::Msx => &[(10000.0, 0.707)],
+            MachinePreset::AtariSt => &[(8000.0, 0.707)],
+            MachinePreset::AmstradCpc => &[(7000.0, 0.707)]
+        }
+    }
+}
+
+/// A two channel biquad filter in transposed direct form II.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    left_state: (f64, f64),
+    right_state: (f64, f64)
+}
+
+impl Biquad {
+    /// Initialize a new low-pass biquad filter for the specified cutoff frequency, quality factor,
+    /// and sample rate.
+    fn low_pass(cutoff_hz: f64, q: f64, sample_rate: u32) -> Self {
+        let cutoff_hz = cutoff_hz.clamp(1.0, sample_rate as f64 * MAX_RELATIVE_CUTOFF);
+        let omega = 2.0 * PI * cutoff_hz / sample_rate as f64;
+        let alpha = omega.sin() / (2.0 * q);
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha;
+
+        Self {
+            b0: (1.0 - cos) * 0.5 / a0,
+            b1: (1.0 - cos) / a0,
+            b2: (1.0 - cos) * 0.5 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            left_state: (0.0, 0.0),
+            right_state: (0.0, 0.0)
+        }
+    }
+
+    /// Apply the filter to a single sample using the provided channel state.
+    fn process(&self, input: f64, state: &mut (f64, f64)) -> f64 {
+        let output = self.b0 * input + state.0;
+
+        state.0 = self.b1 * input - self.a1 * output + state.1;
+        state.1 = self.b2 * input - self.a2 * output;
+
+        output
+    }
+
+    /// Render a new frame for the provided input samples.
+    fn render(&mut self, left: f64, right: f64) -> (f64, f64) {
+        let mut left_state = self.left_state;
+        let mut right_state = self.right_state;
+
+        let result = (self.process(left, &mut left_state), self.process(right, &mut right_state));
+
+        self.left_state = left_state;
+        self.right_state = right_state;
+
+        result
+    }
+}
+
+/// A chain of biquad filters implementing an [`OutputFilter`].
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct OutputFilterChain {
+    stages: Vec<Biquad>
+}
+
+impl OutputFilterChain {
+    /// Initialize a new filter chain for the specified output filter and sample rate.
+    pub(crate) fn new(filter: OutputFilter, sample_rate: u32) -> Self {
+        let stages = match filter {
+            OutputFilter::None => Vec::new(),
+            OutputFilter::LowPass { cutoff_hz } => vec![Biquad::low_pass(cutoff_hz, core::f64::consts::FRAC_1_SQRT_2, sample_rate)],
+            OutputFilter::Machine(preset) => preset.stages().iter()
+                .map(|&(cutoff_hz, q)| Biquad::low_pass(cutoff_hz, q, sample_rate))
+                .collect()
+        };
+
+        Self {
+            stages
+        }
+    }
+
+    /// Write the state of every filter in the chain to a saved state. The coefficients are not
+    /// included, as they are derived from the output filter and the sample rate.
+    pub(crate) fn save_state(&self, writer: &mut StateWriter) {
+        for stage in &self.stages {
+            for value in [stage.left_state.0, stage.left_state.1, stage.right_state.0, stage.right_state.1] {
+                writer.f64(value);
+            }
+        }
+    }
+
+    /// Restore the state of every filter in the chain from a saved state. The chain must have
+    /// been created for the same output filter as the chain that saved the state.
+    pub(crate) fn load_state(&mut self, reader: &mut StateReader) -> Result<(), Error> {
+        for stage in &mut self.stages {
+            stage.left_state = (reader.f64()?, reader.f64()?);
+            stage.right_state = (reader.f64()?, reader.f64()?);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the chain contains no filters, in which case rendering can be skipped entirely.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Render a new frame for the provided input samples.
+    pub(crate) fn render(&mut self, left: f64, right: f64) -> (f64, f64) {
+        self.stages.iter_mut().fold((left, right), |(left, right), stage| stage.render(left, right))
+    }
+}
@@ -0,0 +1,324 @@
+use alloc::vec::Vec;
+
+use crate::PSG;
+use crate::math::FrameClock;
+
+/// The outcome of a call to [`FramePlayer::render_into`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayerStatus {
+    /// The buffer was filled, and the song continues.
+    Playing,
+
+    /// The buffer was filled, and the song jumped back to its loop frame at least once.
+    Looped,
+
+    /// The song has ended. The part of the buffer after the end is filled with silence.
+    Finished
+}
+
+/// Plays a stream of register frames, which is what every register dump format boils down to.
+///
+/// Each frame contains the values of registers 0 through 13, which are written to the PSG at the
+/// frame rate, for example 50 Hz. Frames are timed using a [`FrameClock`], so their lengths in
+/// samples never drift from the exact frame rate. Like in the YM file format, register 13 is only
+/// written when its value differs from a sentinel, which is 0xff by default, because writing the
+/// envelope shape restarts the envelope.
+///
+/// ```
+/// use psg::{FramePlayer, PlayerStatus, PSG};
+///
+/// let psg = PSG::new(2_000_000.0, 44100).unwrap();
+/// let frames = vec![[0x1c, 0x01, 0, 0, 0, 0, 0, 0x3e, 15, 0, 0, 0, 0, 0xff]; 50];
+///
+/// let mut player = FramePlayer::new(psg, 50.0, frames);
+/// let mut buffer = vec![0.0; 2 * 4410];
+///
+/// while player.render_into(&mut buffer) != PlayerStatus::Finished {
+///     // Send the buffer to the audio device
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct FramePlayer {
+    psg: PSG,
+    frame_rate: f64,
+    frames: Vec<[u8; 14]>,
+    loop_frame: Option<usize>,
+    envelope_sentinel: Option<u8>,
+
+    // The index of the next frame to apply, and the samples that remain of the current frame
+    position: usize,
+    clock: FrameClock,
+    remaining_samples: usize,
+    finished: bool
+}
+
+impl FramePlayer {
+    /// Initialize a new player that plays the frames on the PSG at the specified frame rate in Hz.
+    ///
+    /// The song plays once without looping. The frame rate must be positive.
+    pub fn new(psg: PSG, frame_rate: f64, frames: Vec<[u8; 14]>) -> Self {
+        let clock = FrameClock::new(psg.sample_rate(), frame_rate);
+
+        Self {
+            psg,
+            frame_rate,
+            frames,
+            loop_frame: None,
+            envelope_sentinel: Some(0xff),
+            position: 0,
+            clock,
+            remaining_samples: 0,
+            finished: false
+        }
+    }
+
+    /// Render interleaved stereo samples into the buffer, applying the frames as their time comes.
+    ///
+    /// When the last frame has been played, the song continues at the loop frame if one is set.
+    /// Otherwise the rest of the buffer is filled with silence, and this and every further call
+    /// return [`PlayerStatus::Finished`] until the player is seeked.
+    pub fn render_into(&mut self, buffer: &mut [f32]) -> PlayerStatus {
+        let mut status = PlayerStatus::Playing;
+
+        for frame in buffer.chunks_exact_mut(2) {
+            while self.remaining_samples == 0 && !self.finished {
+                if self.position == self.frames.len() {
+                    match self.loop_frame {
+                        Some(loop_frame) => {
+                            self.position = loop_frame;
+                            status = PlayerStatus::Looped;
+                        }
+                        None => {
+                            self.finished = true;
+                            break;
+                        }
+                    }
+                }
+
+                self.apply_frame(self.position);
+                self.position += 1;
+                self.remaining_samples = self.clock.samples_for_next_frame();
+            }
+
+            if self.finished {
+                frame.fill(0.0);
+                continue;
+            }
+
+            let (left, right) = self.psg.render();
+
+            frame[0] = left as f32;
+            frame[1] = right as f32;
+            self.remaining_samples -= 1;
+        }
+
+        if self.finished { PlayerStatus::Finished } else { status }
+    }
+
+    /// Continue playback at the specified frame, without rendering the frames before it.
+    ///
+    /// The registers are set to the state they would have after playing all frames up to the
+    /// specified frame, so notes continue as if the song had been played from the start. The
+    /// envelope restarts if any of these frames writes the envelope shape. A frame beyond the end
+    /// of the song seeks to the end.
+    pub fn seek(&mut self, frame: usize) {
+        let frame = frame.min(self.frames.len());
+        let mut shape = None;
+
+        if let Some(registers) = self.frames[..frame].last() {
+            for (register, &value) in registers[..13].iter().enumerate() {
+                self.psg.set_register(register as u8, value);
+            }
+        }
+
+        for registers in &self.frames[..frame] {
+            if Some(registers[13]) != self.envelope_sentinel {
+                shape = Some(registers[13]);
+            }
+        }
+
+        if let Some(shape) = shape {
+            self.psg.set_register(13, shape);
+        }
+
+        self.position = frame;
+        self.remaining_samples = 0;
+        self.finished = false;
+    }
+
+    /// The index of the next frame that will be applied.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The number of frames in the song.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The frame rate in Hz.
+    pub fn frame_rate(&self) -> f64 {
+        self.frame_rate
+    }
+
+    /// The frame at which the song continues after its last frame, if it loops.
+    pub fn loop_frame(&self) -> Option<usize> {
+        self.loop_frame
+    }
+
+    /// Set the frame at which the song continues after its last frame, or disable looping by
+    /// passing `None`. A loop frame beyond the last frame disables looping as well.
+    pub fn set_loop_frame(&mut self, frame: Option<usize>) {
+        self.loop_frame = frame.filter(|&frame| frame < self.frames.len());
+    }
+
+    /// The value of register 13 that means that the envelope shape is not written.
+    pub fn envelope_sentinel(&self) -> Option<u8> {
+        self.envelope_sentinel
+    }
+
+    /// Set the value of register 13 that means that the envelope shape is not written, or pass
+    /// `None` to write it in every frame. The default is 0xff.
+    pub fn set_envelope_sentinel(&mut self, sentinel: Option<u8>) {
+        self.envelope_sentinel = sentinel;
+    }
+
+    /// Whether the song has ended.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The PSG that plays the song.
+    pub fn psg(&self) -> &PSG {
+        &self.psg
+    }
+
+    /// The PSG that plays the song, for changing its settings.
+    pub fn psg_mut(&mut self) -> &mut PSG {
+        &mut self.psg
+    }
+
+    /// Stop playing, and return the PSG.
+    pub fn into_psg(self) -> PSG {
+        self.psg
+    }
+
+    /// Write a frame's registers to the PSG.
+    fn apply_frame(&mut self, index: usize) {
+        let registers = self.frames[index];
+
+        for (register, &value) in registers[..13].iter().enumerate() {
+            self.psg.set_register(register as u8, value);
+        }
+
+        if Some(registers[13]) != self.envelope_sentinel {
+            self.psg.set_register(13, registers[13]);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{FramePlayer, PlayerStatus};
+    use crate::{ChannelIndex, PSG};
+
+    fn player() -> FramePlayer {
+        let psg = PSG::new(2_000_000.0, 44100).unwrap();
+
+        let frames = vec![
+            [100, 0, 0, 0, 0, 0, 0, 0x3e, 15, 0, 0, 0x10, 0, 0x0e],
+            [101, 0, 0, 0, 0, 0, 0, 0x3e, 14, 0, 0, 0x10, 0, 0xff],
+            [102, 0, 0, 0, 0, 0, 0, 0x3e, 13, 0, 0, 0x10, 0, 0x08]
+        ];
+
+        // At 50 Hz every frame lasts 882 samples
+        FramePlayer::new(psg, 50.0, frames)
+    }
+
+    fn shape_writes(player: &mut FramePlayer, samples: usize) -> (PlayerStatus, usize) {
+        let mut buffer = vec![0.0; samples * 2];
+
+        player.psg_mut().start_recording();
+        let status = player.render_into(&mut buffer);
+
+        (status, player.psg_mut().stop_recording().iter().filter(|event| event.register == 13).count())
+    }
+
+    #[test]
+    fn frame_timing() {
+        let mut player = player();
+        let mut buffer = vec![0.0; 2];
+
+        player.render_into(&mut buffer);
+        assert_eq!(player.psg().tone_period(ChannelIndex::A), 100);
+
+        let mut buffer = vec![0.0; 2 * 881];
+
+        player.render_into(&mut buffer);
+        assert_eq!(player.psg().tone_period(ChannelIndex::A), 100);
+
+        let mut buffer = vec![0.0; 2];
+
+        player.render_into(&mut buffer);
+        assert_eq!(player.psg().tone_period(ChannelIndex::A), 101);
+        assert_eq!(player.position(), 2);
+
+        // The second frame does not write the envelope shape, and the third one does
+        assert_eq!(shape_writes(&mut player, 882), (PlayerStatus::Playing, 1));
+        assert_eq!(player.psg().register(13), 0x08);
+    }
+
+    #[test]
+    fn end_of_song() {
+        let mut player = player();
+        let mut buffer = vec![1.0; 2 * 3000];
+
+        assert_eq!(player.render_into(&mut buffer), PlayerStatus::Finished);
+        assert!(buffer[2 * 2646..].iter().all(|&sample| sample == 0.0));
+        assert!(player.is_finished());
+        assert_eq!(player.render_into(&mut buffer), PlayerStatus::Finished);
+
+        // Seeking restarts playback
+        player.seek(2);
+        assert!(!player.is_finished());
+        assert_eq!(player.render_into(&mut buffer[..2 * 882]), PlayerStatus::Playing);
+    }
+
+    #[test]
+    fn looping() {
+        let mut player = player();
+        player.set_loop_frame(Some(1));
+
+        assert_eq!(shape_writes(&mut player, 2646), (PlayerStatus::Playing, 2));
+        assert_eq!(shape_writes(&mut player, 882), (PlayerStatus::Looped, 0));
+        assert_eq!(player.psg().tone_period(ChannelIndex::A), 101);
+        assert_eq!(shape_writes(&mut player, 882 * 4), (PlayerStatus::Looped, 2));
+
+        player.set_loop_frame(Some(3));
+        assert_eq!(player.loop_frame(), None);
+    }
+
+    #[test]
+    fn seek() {
+        let mut player = player();
+
+        player.psg_mut().start_recording();
+        player.seek(2);
+
+        // The registers are fast-forwarded without rendering, and the envelope shape is written
+        // once
+        let events = player.psg_mut().stop_recording();
+
+        assert_eq!(events.iter().filter(|event| event.register == 13).count(), 1);
+        assert_eq!(player.psg().frames_rendered(), 0);
+        assert_eq!(player.psg().tone_period(ChannelIndex::A), 101);
+        assert_eq!(player.psg().amplitude(ChannelIndex::A), 14);
+        assert_eq!(player.psg().register(13), 0x0e);
+        assert_eq!(player.position(), 2);
+
+        let mut buffer = vec![0.0; 2];
+
+        player.render_into(&mut buffer);
+        assert_eq!(player.psg().tone_period(ChannelIndex::A), 102);
+    }
+}
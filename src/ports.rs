@@ -0,0 +1,19 @@
+/// The devices that are connected to the PSG's two GPIO ports, such as the keyboard matrix and
+/// joystick ports of the MSX. See [`PSG::set_ports`](crate::PSG::set_ports).
+///
+/// Each port is configured as an input or an output using bits 6 and 7 of the mixer register.
+/// Reads of a port that is configured as an input are passed on to the `read` methods, while
+/// writes to a port that is configured as an output are passed on to the `write` methods.
+pub trait PsgPorts {
+    /// Read the value of port A when it is configured as an input.
+    fn read_a(&mut self) -> u8;
+
+    /// Write a value to port A when it is configured as an output.
+    fn write_a(&mut self, value: u8);
+
+    /// Read the value of port B when it is configured as an input.
+    fn read_b(&mut self) -> u8;
+
+    /// Write a value to port B when it is configured as an output.
+    fn write_b(&mut self, value: u8);
+}
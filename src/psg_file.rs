@@ -0,0 +1,265 @@
+//! This module contains a parser and writer for .psg files, the register stream format that is
+//! widely used for ZX Spectrum music. It is available when the `formats-psg` feature is enabled.
+//!
+//! A .psg file consists of a 16-byte header, followed by a stream of register and value pairs
+//! that are separated by markers: 0xFF ends a frame, 0xFE followed by a count skips four times
+//! that many frames, and 0xFD ends the song. Frames last 1/50th of a second.
+//!
+//! The parser converts the stream into the frames that are played by the [`FramePlayer`]. The
+//! envelope shape register of a frame is 0xFF when the shape was not written during that frame,
+//! which the frame player treats as no write.
+//!
+//! ```
+//! use psg::{FramePlayer, PSG};
+//! use psg::psg_file;
+//!
+//! let mut psg = PSG::new(1_773_400.0, 44100).unwrap();
+//! psg.start_recording();
+//!
+//! for frame in 0..50 {
+//!     psg.set_register(0, frame);
+//!     psg.set_register(8, 15);
+//!     let _ = psg.render_frames(882);
+//! }
+//!
+//! let data = psg_file::write(&psg.stop_recording(), 44100, psg.frames_rendered());
+//! let frames = psg_file::parse(&data).unwrap();
+//!
+//! assert_eq!(frames.len(), 50);
+//!
+//! let player = FramePlayer::new(PSG::new(1_773_400.0, 44100).unwrap(), psg_file::FRAME_RATE, frames);
+//! ```
+//!
+//! [`FramePlayer`]: crate::FramePlayer
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::RegisterEvent;
+
+/// The frame rate of .psg files in Hz.
+pub const FRAME_RATE: f64 = 50.0;
+
+/// The size of the header, which consists of the signature, a version and a frame rate, followed
+/// by reserved bytes.
+const HEADER_SIZE: usize = 16;
+
+/// The marker that ends a frame.
+const END_OF_FRAME: u8 = 0xff;
+
+/// The marker that skips four times the count in the next byte of frames.
+const SKIP_FRAMES: u8 = 0xfe;
+
+/// The marker that ends the song.
+const END_OF_SONG: u8 = 0xfd;
+
+/// The error returned by [`parse`] when a file cannot be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PsgFileError {
+    /// The file does not start with the "PSG" signature followed by 0x1A.
+    InvalidSignature,
+
+    /// The file ends in the middle of a register write or a skip marker. Contains the offset of
+    /// the incomplete command.
+    Truncated(usize),
+
+    /// The stream contains a register number above 15. Contains the offset of the register byte.
+    InvalidRegister(usize)
+}
+
+impl fmt::Display for PsgFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PsgFileError::InvalidSignature => write!(f, "the file is not a .psg file"),
+            PsgFileError::Truncated(offset) => write!(f, "the .psg file ends in the middle of the command at offset {offset}"),
+            PsgFileError::InvalidRegister(offset) => write!(f, "the .psg file contains an invalid register number at offset {offset}")
+        }
+    }
+}
+
+impl core::error::Error for PsgFileError {}
+
+/// Parse the contents of a .psg file into frames of register values, to be played at 50 Hz.
+///
+/// Every frame contains the values of the registers after all writes up to the end of that frame.
+/// Writes that follow the last end of frame marker form a final frame. Writes to the GPIO port
+/// registers 14 and 15 are ignored.
+pub fn parse(data: &[u8]) -> Result<Vec<[u8; 14]>, PsgFileError> {
+    if !data.starts_with(b"PSG\x1a") {
+        return Err(PsgFileError::InvalidSignature);
+    }
+
+    let mut frames = Vec::new();
+    let mut registers = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff];
+    let mut pending = false;
+    let mut offset = HEADER_SIZE;
+
+    // Emit frames with the current register values, of which only the first has the envelope
+    // shape written
+    let mut end_frames = |registers: &mut [u8; 14], count: usize| {
+        for _ in 0..count {
+            frames.push(*registers);
+            registers[13] = 0xff;
+        }
+    };
+
+    while let Some(&command) = data.get(offset) {
+        match command {
+            END_OF_FRAME => {
+                end_frames(&mut registers, 1);
+                pending = false;
+                offset += 1;
+            }
+            SKIP_FRAMES => {
+                let count = *data.get(offset + 1).ok_or(PsgFileError::Truncated(offset))?;
+
+                end_frames(&mut registers, 4 * count as usize);
+                pending = false;
+                offset += 2;
+            }
+            END_OF_SONG => break,
+            register @ 0..=15 => {
+                let value = *data.get(offset + 1).ok_or(PsgFileError::Truncated(offset))?;
+
+                // A written shape of 0xFF would read as no write, and only its low bits matter
+                if register < 14 {
+                    registers[register as usize] = if register == 13 && value == 0xff { 0x0f } else { value };
+                }
+
+                pending = true;
+                offset += 2;
+            }
+            _ => return Err(PsgFileError::InvalidRegister(offset))
+        }
+    }
+
+    if pending {
+        end_frames(&mut registers, 1);
+    }
+
+    Ok(frames)
+}
+
+/// Write recorded register events to a .psg file, and return its contents.
+///
+/// The events are recorded at the specified sample rate, and grouped into 50 Hz frames. The file
+/// lasts for the specified number of output frames, counted like the frames of the events, which
+/// is rounded up to a whole number of 50 Hz frames. Events from that frame onwards are left out.
+pub fn write(events: &[RegisterEvent], sample_rate: u32, frames: u64) -> Vec<u8> {
+    let frame_count = (frames as u128 * FRAME_RATE as u128).div_ceil(sample_rate as u128) as u64;
+    let mut data = Vec::with_capacity(HEADER_SIZE + 3 * events.len());
+
+    data.extend(b"PSG\x1a");
+    data.push(10);
+    data.push(FRAME_RATE as u8);
+    data.resize(HEADER_SIZE, 0);
+
+    let mut events = events.iter().peekable();
+    let mut empty_frames = 0;
+
+    for frame in 0..frame_count {
+        let mut written = false;
+
+        while let Some(event) = events.next_if(|event| (event.frame as u128 * FRAME_RATE as u128 / sample_rate as u128) as u64 <= frame) {
+            if !written {
+                write_frame_ends(&mut data, empty_frames);
+                empty_frames = 0;
+                written = true;
+            }
+
+            data.extend([event.register & 0x0f, event.value]);
+        }
+
+        empty_frames += 1;
+    }
+
+    write_frame_ends(&mut data, empty_frames);
+    data
+}
+
+/// Write the markers that end the specified number of frames, using skip markers for groups of
+/// four frames.
+fn write_frame_ends(data: &mut Vec<u8>, mut count: u64) {
+    while count >= 4 {
+        let skip = (count / 4).min(255);
+
+        data.extend([SKIP_FRAMES, skip as u8]);
+        count -= 4 * skip;
+    }
+
+    for _ in 0..count {
+        data.push(END_OF_FRAME);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{PsgFileError, parse, write};
+    use crate::{PSG, RegisterEvent};
+
+    #[test]
+    fn round_trip() {
+        let mut psg = PSG::new(1_773_400.0, 44100).unwrap();
+        psg.start_recording();
+
+        for frame in 0..20u8 {
+            psg.set_register(0, frame);
+            psg.set_register(8, 15 - frame % 16);
+
+            if frame % 5 == 0 {
+                psg.set_register(13, 0x0e);
+            }
+
+            let _ = psg.render_frames(882);
+        }
+
+        // A long silence follows, which is written with skip markers
+        let mut events = psg.stop_recording();
+
+        events.push(RegisterEvent { frame: 882 * 20, register: 8, value: 0 });
+        events.push(RegisterEvent { frame: 882 * 1050, register: 1, value: 1 });
+
+        let data = write(&events, 44100, 882 * 1050 + 441);
+
+        assert_eq!(&data[..6], b"PSG\x1a\x0a\x32");
+        assert!(data.windows(2).any(|pair| pair == [0xfe, 255]));
+
+        let frames = parse(&data).unwrap();
+
+        assert_eq!(frames.len(), 1051);
+        assert_eq!(frames[7][..2], [7, 0]);
+        assert_eq!(frames[7][8], 8);
+        assert_eq!([frames[4][13], frames[5][13], frames[6][13]], [0xff, 0x0e, 0xff]);
+        assert_eq!(frames[1049][8], 0);
+        assert_eq!(frames[1050][..2], [19, 1]);
+    }
+
+    #[test]
+    fn markers() {
+        let mut data = b"PSG\x1a\x0a\x32".to_vec();
+        data.resize(16, 0);
+        data.extend([0xff, 0x07, 0x3e, 0x08, 0x0f, 0xfe, 0x02, 0x08, 0x0c, 0x0d, 0x0a, 0xff, 0xfd, 0x08, 0x00, 0xff]);
+
+        let frames = parse(&data).unwrap();
+
+        // A frame before the first write, eight skipped frames, and one more before the end
+        assert_eq!(frames.len(), 10);
+        assert_eq!(frames[0], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff]);
+        assert!(frames[1..9].iter().all(|frame| frame[7] == 0x3e && frame[8] == 15 && frame[13] == 0xff));
+        assert_eq!((frames[9][8], frames[9][13]), (12, 0x0a));
+    }
+
+    #[test]
+    fn errors() {
+        let mut data = b"PSG\x1a".to_vec();
+        data.resize(16, 0);
+        data.extend([0x00, 0x10, 0xff, 0x20, 0x00]);
+
+        assert_eq!(parse(&data), Err(PsgFileError::InvalidRegister(19)));
+        assert_eq!(parse(&data[..17]), Err(PsgFileError::Truncated(16)));
+        assert_eq!(parse(b"YM5!"), Err(PsgFileError::InvalidSignature));
+
+        let events = [RegisterEvent { frame: 0, register: 7, value: 0x38 }];
+        assert_eq!(parse(&write(&events, 44100, 1)).unwrap(), [[0, 0, 0, 0, 0, 0, 0, 0x38, 0, 0, 0, 0, 0, 0xff]]);
+    }
+}
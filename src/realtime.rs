@@ -0,0 +1,158 @@
+//! This module contains a helper for playing a PSG in real time on the default output device
+//! using cpal. It is available when the `cpal` feature is enabled.
+//!
+//! The PSG is built at the native sample rate of the device and rendered in the audio callback as
+//! a [`ControlledPsg`], so it can only be changed through a [`PsgController`]. The callback
+//! renders a whole device buffer at a time, so the latency of a change is up to one buffer, unless
+//! it is a scheduled write.
+//!
+//! ```no_run
+//! use psg::{PsgBuilder, PsgController};
+//! use psg::realtime;
+//!
+//! let (controller, receiver) = PsgController::new(256);
+//! let stream = realtime::spawn(PsgBuilder::new(2_000_000.0, 44100), receiver).unwrap();
+//!
+//! controller.write_register(0, 0x1c).unwrap();
+//! controller.write_register(7, 0x3e).unwrap();
+//! controller.write_register(8, 15).unwrap();
+//!
+//! std::thread::sleep(std::time::Duration::from_secs(1));
+//! stream.stop();
+//! ```
+//!
+//! [`ControlledPsg`]: crate::ControlledPsg
+//! [`PsgController`]: crate::PsgController
+
+use std::fmt;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use crate::{CommandReceiver, ControlledPsg, PsgBuilder, Sample};
+
+/// The error returned by [`spawn`] when the stream cannot be started.
+#[derive(Debug)]
+pub enum RealtimeError {
+    /// There is no default output device.
+    NoOutputDevice,
+
+    /// The configuration of the output device cannot be queried.
+    DefaultConfig(cpal::DefaultStreamConfigError),
+
+    /// The output device uses a sample format that is not supported.
+    UnsupportedSampleFormat(cpal::SampleFormat),
+
+    /// The PSG cannot be built at the sample rate of the output device.
+    Psg(crate::Error),
+
+    /// The output stream cannot be built.
+    BuildStream(cpal::BuildStreamError),
+
+    /// The output stream cannot be started.
+    PlayStream(cpal::PlayStreamError)
+}
+
+impl fmt::Display for RealtimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RealtimeError::NoOutputDevice => write!(f, "there is no default output device"),
+            RealtimeError::DefaultConfig(error) => write!(f, "the output device configuration cannot be queried: {error}"),
+            RealtimeError::UnsupportedSampleFormat(format) => write!(f, "the output device uses the unsupported sample format {format}"),
+            RealtimeError::Psg(error) => write!(f, "the PSG cannot be built: {error}"),
+            RealtimeError::BuildStream(error) => write!(f, "the output stream cannot be built: {error}"),
+            RealtimeError::PlayStream(error) => write!(f, "the output stream cannot be started: {error}")
+        }
+    }
+}
+
+impl std::error::Error for RealtimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RealtimeError::NoOutputDevice | RealtimeError::UnsupportedSampleFormat(_) => None,
+            RealtimeError::DefaultConfig(error) => Some(error),
+            RealtimeError::Psg(error) => Some(error),
+            RealtimeError::BuildStream(error) => Some(error),
+            RealtimeError::PlayStream(error) => Some(error)
+        }
+    }
+}
+
+/// A playing output stream, which stops when it is dropped.
+pub struct StreamHandle {
+    stream: cpal::Stream,
+    sample_rate: u32
+}
+
+impl StreamHandle {
+    /// The sample rate of the output device, at which the PSG is rendered.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Stop playing and close the stream. This is the same as dropping the handle.
+    pub fn stop(self) {
+        drop(self.stream);
+    }
+}
+
+impl fmt::Debug for StreamHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamHandle").field("sample_rate", &self.sample_rate).finish_non_exhaustive()
+    }
+}
+
+/// Build a PSG using the configuration, and play it on the default output device.
+///
+/// The sample rate of the configuration is replaced by the native sample rate of the device. The
+/// commands that are sent to the receiver's controller are applied in the audio callback by a
+/// [`ControlledPsg`](crate::ControlledPsg), which does not allocate. The left and right outputs
+/// are written to the first two channels of the device, and the remaining channels are silent.
+/// Mono devices get the average of both outputs.
+pub fn spawn(config: PsgBuilder, control: CommandReceiver) -> Result<StreamHandle, RealtimeError> {
+    let device = cpal::default_host().default_output_device().ok_or(RealtimeError::NoOutputDevice)?;
+    let supported = device.default_output_config().map_err(RealtimeError::DefaultConfig)?;
+
+    let stream_config = supported.config();
+    let sample_rate = stream_config.sample_rate.0;
+    let psg = ControlledPsg::new(config.with_sample_rate(sample_rate).build().map_err(RealtimeError::Psg)?, control);
+
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, psg),
+        cpal::SampleFormat::F64 => build_stream::<f64>(&device, &stream_config, psg),
+        cpal::SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, psg),
+        cpal::SampleFormat::I32 => build_stream::<i32>(&device, &stream_config, psg),
+        format => return Err(RealtimeError::UnsupportedSampleFormat(format))
+    }.map_err(RealtimeError::BuildStream)?;
+
+    stream.play().map_err(RealtimeError::PlayStream)?;
+
+    Ok(StreamHandle { stream, sample_rate })
+}
+
+/// Build an output stream that renders the PSG in samples of the specified format.
+fn build_stream<S: cpal::SizedSample + Sample + Send + 'static>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut psg: ControlledPsg
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+
+    device.build_output_stream(config, move |data: &mut [S], _: &cpal::OutputCallbackInfo| {
+        for frame in data.chunks_exact_mut(channels) {
+            let (left, right) = psg.render();
+
+            match frame {
+                [mono] => *mono = S::from_f64(0.5 * (left + right)),
+                [first, second, rest @ ..] => {
+                    *first = S::from_f64(left);
+                    *second = S::from_f64(right);
+                    rest.fill(S::from_f64(0.0));
+                }
+                [] => {}
+            }
+        }
+    }, |_error| {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(error = %_error, "audio stream error");
+    }, None)
+}
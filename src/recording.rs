@@ -0,0 +1,14 @@
+/// A single register write, as captured by [`PSG::start_recording`](crate::PSG::start_recording).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegisterEvent {
+    /// The number of frames that had been rendered when the write occurred. Replaying the write
+    /// before rendering this frame number reproduces the original output.
+    pub frame: u64,
+
+    /// The register number, between 0 and 15 inclusive.
+    pub register: u8,
+
+    /// The value that was written to the register.
+    pub value: u8
+}
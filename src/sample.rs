@@ -0,0 +1,68 @@
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// A sample format that rendered audio can be converted into.
+///
+/// This trait is implemented for the most common sample formats used by audio backends: `f32`,
+/// `f64`, `i16` and `i32`. Floating point samples are passed through unchanged (apart from the
+/// conversion to single precision for `f32`), while integer samples are clamped to the range -1.0
+/// to 1.0 and scaled by the highest representable value. The range is symmetric, so -1.0 maps to
+/// the negated highest value (for example `-i16::MAX` rather than `i16::MIN`), and the lowest
+/// representable value is never produced.
+pub trait Sample: Copy {
+    /// Convert a double precision sample into this sample format.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Sample for f64 {
+    #[inline(always)]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+impl Sample for f32 {
+    #[inline(always)]
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl Sample for i16 {
+    #[inline(always)]
+    fn from_f64(value: f64) -> Self {
+        (value.clamp(-1.0, 1.0) * i16::MAX as f64).round() as i16
+    }
+}
+
+impl Sample for i32 {
+    #[inline(always)]
+    fn from_f64(value: f64) -> Self {
+        (value.clamp(-1.0, 1.0) * i32::MAX as f64).round() as i32
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::Sample;
+
+    #[test]
+    fn float_conversion() {
+        assert_eq!(f64::from_f64(0.25), 0.25);
+        assert_eq!(f32::from_f64(-0.5), -0.5);
+    }
+
+    #[test]
+    fn integer_conversion() {
+        assert_eq!(i16::from_f64(0.0), 0);
+        assert_eq!(i16::from_f64(1.0), i16::MAX);
+        assert_eq!(i16::from_f64(-1.0), -i16::MAX);
+        assert_eq!(i16::from_f64(2.0), i16::MAX);
+        assert_eq!(i16::from_f64(-2.0), -i16::MAX);
+
+        assert_eq!(i32::from_f64(0.5), i32::MAX / 2 + 1);
+        assert_eq!(i32::from_f64(1.5), i32::MAX);
+        assert_eq!(i32::from_f64(-1.5), -i32::MAX);
+    }
+}
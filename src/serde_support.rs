@@ -0,0 +1,24 @@
+//! Serialization helpers for field types that serde does not support out of the box.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::{Serialize, Serializer};
+
+/// Serializes arrays of any length as sequences. Serde only supports arrays of up to 32 elements,
+/// which is not enough for the filter buffers.
+pub(crate) mod array {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer, T: Serialize, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error> {
+        array.as_slice().serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>, const N: usize>(deserializer: D) -> Result<[T; N], D::Error> {
+        let values = Vec::<T>::deserialize(deserializer)?;
+        let length = values.len();
+
+        values.try_into().map_err(|_| D::Error::invalid_length(length, &format!("an array of length {}", N).as_str()))
+    }
+}
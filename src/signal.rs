@@ -0,0 +1,121 @@
+use dasp::signal::{Map, Signal};
+
+use crate::PSG;
+
+/// The conversion that is applied by [`PsgSignal::into_f32`].
+type ToF32 = fn([f64; 2]) -> [f32; 2];
+
+/// A [`dasp::Signal`] that plays a PSG, available when the `dasp` feature is enabled.
+///
+/// The signal yields stereo frames at the sample rate of the PSG, and never ends, so it works with
+/// dasp's combinators like any other signal. For example, mixing the PSG with a quiet sine:
+///
+/// ```
+/// use dasp::{Signal, signal};
+/// use psg::{PSG, PsgSignal};
+///
+/// let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+/// psg.set_register(7, 0x3e);
+/// psg.set_register(8, 15);
+///
+/// let sine = signal::rate(44100.0).const_hz(440.0).sine().map(|sample| [0.25 * sample; 2]);
+/// let mixed = PsgSignal::new(psg).add_amp(sine);
+///
+/// let frames: Vec<[f64; 2]> = mixed.take(1024).collect();
+/// ```
+///
+/// The registers can be changed between calls through [`psg_mut`](Self::psg_mut), or by borrowing
+/// the signal using [`by_ref`](Signal::by_ref) while building a chain.
+#[derive(Clone, Debug)]
+pub struct PsgSignal(PSG);
+
+impl PsgSignal {
+    /// Initialize a new signal that plays the PSG.
+    pub fn new(psg: PSG) -> Self {
+        Self(psg)
+    }
+
+    /// The inner PSG.
+    pub fn psg(&self) -> &PSG {
+        &self.0
+    }
+
+    /// The inner PSG, for changing its registers.
+    pub fn psg_mut(&mut self) -> &mut PSG {
+        &mut self.0
+    }
+
+    /// Consume the signal and return the inner PSG.
+    pub fn into_inner(self) -> PSG {
+        self.0
+    }
+
+    /// Convert the signal to one that yields single precision frames, for graphs that process
+    /// `f32` samples.
+    pub fn into_f32(self) -> Map<Self, ToF32, [f32; 2]> {
+        self.map((|[left, right]: [f64; 2]| [left as f32, right as f32]) as ToF32)
+    }
+}
+
+impl Signal for PsgSignal {
+    type Frame = [f64; 2];
+
+    fn next(&mut self) -> [f64; 2] {
+        let (left, right) = self.0.render();
+        [left, right]
+    }
+
+    fn is_exhausted(&self) -> bool {
+        false
+    }
+}
+
+impl From<PSG> for PsgSignal {
+    fn from(psg: PSG) -> Self {
+        Self::new(psg)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use dasp::{Signal, signal};
+
+    use super::PsgSignal;
+    use crate::PSG;
+
+    fn tone() -> PSG {
+        let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+        psg.set_register(0, 0x1c);
+        psg.set_register(1, 0x01);
+        psg.set_register(7, 0x3e);
+        psg.set_register(8, 15);
+        psg
+    }
+
+    #[test]
+    fn combinators() {
+        let mut reference = tone();
+        let expected: Vec<(f64, f64)> = (0..1000).map(|_| reference.render()).collect();
+
+        let signal = PsgSignal::new(tone());
+        assert!(!signal.is_exhausted());
+
+        let frames: Vec<_> = signal.zip_map(signal::equilibrium::<[f64; 2]>(), |[left, right], [silence, _]| [left + silence, right]).take(1000).collect();
+        assert!(frames.iter().zip(&expected).all(|(&[left, right], &frame)| (left, right) == frame));
+
+        let frames: Vec<[f32; 2]> = PsgSignal::new(tone()).into_f32().take(1000).collect();
+        assert!(frames.iter().zip(&expected).all(|(&frame, &(left, right))| frame == [left as f32, right as f32]));
+        assert!(frames.iter().any(|frame| frame[0] != 0.0));
+    }
+
+    #[test]
+    fn register_changes() {
+        let mut signal = PsgSignal::from(PSG::new(2_000_000.0, 44100).unwrap());
+        assert!(signal.by_ref().take(100).all(|frame| frame == [0.0; 2]));
+
+        signal.psg_mut().set_register(7, 0x3f);
+        signal.psg_mut().set_register(8, 15);
+
+        assert!(signal.take(100).any(|frame| frame != [0.0; 2]));
+    }
+}
@@ -0,0 +1,152 @@
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::PSG;
+
+/// The number of frames that are rendered each time the PSG is locked.
+const BLOCK_SIZE: usize = 64;
+
+/// A [`rodio::Source`] that plays a PSG, available when the `rodio` feature is enabled.
+///
+/// The source yields interleaved stereo samples at the sample rate of the PSG, and never ends. The
+/// PSG is kept behind a mutex, so other threads can change its registers while it plays using the
+/// handle returned by [`psg`](Self::psg):
+///
+/// ```no_run
+/// use psg::{PSG, PsgSource};
+///
+/// let source = PsgSource::new(PSG::new(2_000_000.0, 44100).unwrap());
+/// let psg = source.psg();
+///
+/// // Hand the source to rodio, for example using a mixer
+///
+/// psg.lock().unwrap().set_register(8, 15);
+/// ```
+///
+/// To keep the locking overhead low, the source renders blocks of 64 frames at a time while
+/// holding the lock, and register changes take effect at the start of the next block. This adds
+/// up to 64 frames (1.5 ms at 44100 Hz) to the latency of rodio's own buffering, which is usually
+/// much larger. Changes made in quick succession from another thread can end up in the same block,
+/// so music that needs exact timing should be played with a [`FramePlayer`](crate::FramePlayer)
+/// on a thread of its own, or rendered into a buffer instead.
+#[derive(Debug)]
+pub struct PsgSource {
+    psg: Arc<Mutex<PSG>>,
+    sample_rate: u32,
+    buffer: [f32; 2 * BLOCK_SIZE],
+    position: usize
+}
+
+impl PsgSource {
+    /// Initialize a new source that plays the PSG.
+    pub fn new(psg: PSG) -> Self {
+        Self::from_shared(Arc::new(Mutex::new(psg)))
+    }
+
+    /// Initialize a new source that plays a PSG that is already shared with other threads.
+    ///
+    /// The sample rate of the PSG is read once, so the PSG must not be replaced by one with a
+    /// different sample rate while it plays.
+    pub fn from_shared(psg: Arc<Mutex<PSG>>) -> Self {
+        let sample_rate = psg.lock().unwrap_or_else(PoisonError::into_inner).sample_rate();
+
+        Self {
+            psg,
+            sample_rate,
+            buffer: [0.0; 2 * BLOCK_SIZE],
+            position: 2 * BLOCK_SIZE
+        }
+    }
+
+    /// A handle to the PSG, for changing its registers while it plays.
+    pub fn psg(&self) -> Arc<Mutex<PSG>> {
+        Arc::clone(&self.psg)
+    }
+}
+
+impl Iterator for PsgSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position == self.buffer.len() {
+            // A panic on another thread leaves the PSG in a usable state, so keep playing
+            self.psg.lock().unwrap_or_else(PoisonError::into_inner).render_into_samples(&mut self.buffer);
+            self.position = 0;
+        }
+
+        let sample = self.buffer[self.position];
+        self.position += 1;
+
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl rodio::Source for PsgSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> rodio::ChannelCount {
+        2
+    }
+
+    fn sample_rate(&self) -> rodio::SampleRate {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use rodio::Source;
+
+    use super::PsgSource;
+    use crate::{ChannelIndex, PSG};
+
+    #[test]
+    fn interleaved_stereo() {
+        let mut psg = PSG::new(2_000_000.0, 22050).unwrap();
+        psg.set_register(0, 0x1c);
+        psg.set_register(1, 0x01);
+        psg.set_register(7, 0x3e);
+        psg.set_register(8, 15);
+        psg.channel_mut(ChannelIndex::A).set_panning(0.0, false);
+
+        let mut reference = psg.clone();
+        let source = PsgSource::new(psg);
+
+        assert_eq!((source.channels(), source.sample_rate(), source.total_duration()), (2, 22050, None));
+
+        let samples: Vec<f32> = source.take(2 * 1000).collect();
+        let mut expected = vec![0.0; 2 * 1000];
+        reference.render_into_samples(&mut expected);
+
+        assert_eq!(samples, expected);
+        assert!(samples.chunks_exact(2).any(|frame| frame[0] != 0.0));
+        assert!(samples.chunks_exact(2).all(|frame| frame[1] == 0.0));
+    }
+
+    #[test]
+    fn shared_changes() {
+        let source = PsgSource::new(PSG::new(2_000_000.0, 44100).unwrap());
+        let psg = source.psg();
+        let mut source = source.skip(2 * 64);
+
+        assert_eq!(source.next(), Some(0.0));
+
+        // The change takes effect at the next block
+        psg.lock().unwrap().set_register(7, 0x3f);
+        psg.lock().unwrap().set_register(8, 15);
+
+        let samples: Vec<f32> = source.by_ref().take(2 * 63 - 1).collect();
+        assert!(samples.iter().all(|&sample| sample == 0.0));
+        assert!(source.take(2 * 64).any(|sample| sample != 0.0));
+    }
+}
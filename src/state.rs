@@ -0,0 +1,210 @@
+use alloc::vec::Vec;
+
+use crate::{Error, Float, widen};
+
+/// The magic bytes at the start of every saved state.
+const MAGIC: [u8; 4] = *b"PSGS";
+
+/// The version of the state format that is written by [`PSG::save_state`](crate::PSG::save_state).
+pub const STATE_VERSION: u16 = 1;
+
+/// A snapshot of the complete dynamic state of a PSG, as produced by
+/// [`PSG::save_state`](crate::PSG::save_state).
+///
+/// The state is stored in a compact binary format that can be written to disk as is using
+/// [`as_bytes`](Self::as_bytes), and restored using [`from_bytes`](Self::from_bytes). All values
+/// are stored in little-endian byte order, and all floating point values are stored as 64-bit
+/// floats, so states can be exchanged between platforms and between builds with and without the
+/// `f32` feature.
+///
+/// The format starts with a header containing the magic bytes `PSGS`, the format version as a
+/// 16-bit integer (see [`STATE_VERSION`]), and the clock rate, sample rate, and DC filter length
+/// of the PSG that produced it. The header is followed by the chip's registers and counters, and
+/// finally the contents of the PSG's filters.
+///
+/// Besides the chip itself, the state contains the settings that shape the output, such as the
+/// mixing mode, the panning, and the filter settings. This includes the mute and solo flags of
+/// the channels, so loading a state that was saved while auditioning a channel restores those
+/// flags as well, and the progress of a fade out, which continues from where it was when the
+/// state was saved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PsgState {
+    bytes: Vec<u8>
+}
+
+impl PsgState {
+    /// Wrap previously saved bytes into a state. The bytes are validated when the state is loaded
+    /// using [`PSG::load_state`](crate::PSG::load_state).
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes
+        }
+    }
+
+    /// The state's binary representation.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Consume the state and return its binary representation.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Compute a 64-bit FNV-1a hash of the state's binary representation.
+    pub(crate) fn fingerprint(&self) -> u64 {
+        self.bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+}
+
+/// Serializes values into a [`PsgState`].
+pub(crate) struct StateWriter {
+    bytes: Vec<u8>
+}
+
+impl StateWriter {
+    /// Initialize a new writer and write the magic bytes and format version.
+    pub(crate) fn new() -> Self {
+        let mut writer = Self {
+            bytes: Vec::new()
+        };
+
+        writer.bytes.extend_from_slice(&MAGIC);
+        writer.u16(STATE_VERSION);
+        writer
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    /// Write a value of the internal sample type, which is always stored as a 64-bit float.
+    pub(crate) fn float(&mut self, value: Float) {
+        self.f64(widen(value));
+    }
+
+    pub(crate) fn floats(&mut self, values: &[Float]) {
+        for &value in values {
+            self.float(value);
+        }
+    }
+
+    /// Write a buffer index, which is always stored as a 32-bit integer.
+    pub(crate) fn index(&mut self, value: usize) {
+        self.u32(value as u32);
+    }
+
+    /// Finish writing and return the state.
+    pub(crate) fn finish(self) -> PsgState {
+        PsgState::from_bytes(self.bytes)
+    }
+}
+
+/// Deserializes values from a [`PsgState`]. All methods return [`Error::InvalidState`] when the
+/// state is truncated or contains an invalid value.
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> StateReader<'a> {
+    /// Initialize a new reader and validate the magic bytes and format version.
+    pub(crate) fn new(state: &'a PsgState) -> Result<Self, Error> {
+        let mut reader = Self {
+            bytes: state.as_bytes()
+        };
+
+        if reader.take::<4>()? != MAGIC {
+            return Err(Error::InvalidState);
+        }
+
+        match reader.u16()? {
+            STATE_VERSION => Ok(reader),
+            version => Err(Error::UnsupportedStateVersion(version))
+        }
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let (head, tail) = self.bytes.split_first_chunk::<N>().ok_or(Error::InvalidState)?;
+        self.bytes = tail;
+        Ok(*head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidState)
+        }
+    }
+
+    pub(crate) fn float(&mut self) -> Result<Float, Error> {
+        Ok(self.f64()? as Float)
+    }
+
+    pub(crate) fn floats(&mut self, values: &mut [Float]) -> Result<(), Error> {
+        for value in values {
+            *value = self.float()?;
+        }
+
+        Ok(())
+    }
+
+    /// Read a buffer index and verify that it is smaller than the buffer's length.
+    pub(crate) fn index(&mut self, length: usize) -> Result<usize, Error> {
+        match self.u32()? as usize {
+            index if index < length => Ok(index),
+            _ => Err(Error::InvalidState)
+        }
+    }
+
+    /// Finish reading, verifying that the entire state has been consumed.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidState)
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use crate::PSG;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// Produces the "sync buzzer" effect of Atari ST music, by retriggering the envelope at an audio
+/// rate through scheduled writes to the envelope shape register.
+///
+/// Every retrigger restarts the envelope, so a channel that uses the envelope plays a waveform
+/// whose pitch is the retrigger rate, and whose timbre depends on how much of the envelope shape
+/// fits in between two retriggers. The envelope period should therefore be chosen such that one
+/// cycle of the shape is somewhat longer than the retrigger interval. Sawtooth shapes such as
+/// 0x0c give the classic sound.
+///
+/// The writes are scheduled using [`PSG::write_register_at`] one block at a time, ahead of
+/// rendering the block. The time of the next retrigger is carried over from one block to the next,
+/// so the phase of the effect is independent of the block size. Changing the frequency keeps the
+/// phase as well, which makes it possible to slide the pitch by changing the frequency every frame.
+///
+/// ```
+/// use psg::{ChannelIndex, PSG, SyncBuzzer};
+///
+/// let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+/// psg.set_envelope_period(60);
+/// psg.set_envelope_enabled(ChannelIndex::A, true);
+///
+/// let mut buzzer = SyncBuzzer::new(110.0, 0x0c);
+///
+/// for _ in 0..50 {
+///     buzzer.schedule_frames(&mut psg, 882);
+///     let _ = psg.render_frames(882);
+/// }
+///
+/// buzzer.stop(&mut psg);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SyncBuzzer {
+    frequency: f64,
+    shape: u8,
+
+    // The chip clock cycle of the next retrigger, once scheduling has started
+    next_retrigger: Option<f64>
+}
+
+impl SyncBuzzer {
+    /// Initialize a new sync buzzer that retriggers the envelope with the specified shape at the
+    /// specified frequency in Hz.
+    ///
+    /// The frequency must be positive.
+    pub fn new(frequency: f64, shape: u8) -> Self {
+        Self {
+            frequency,
+            shape,
+            next_retrigger: None
+        }
+    }
+
+    /// The retrigger frequency in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    /// Set the retrigger frequency in Hz, which must be positive.
+    ///
+    /// The retrigger that is already scheduled next keeps its time, and the new frequency applies
+    /// from there on. This keeps the phase continuous, so the pitch changes without a glitch.
+    pub fn set_frequency(&mut self, frequency: f64) {
+        self.frequency = frequency;
+    }
+
+    /// The envelope shape that is written at every retrigger.
+    pub fn shape(&self) -> u8 {
+        self.shape
+    }
+
+    /// Set the envelope shape that is written at every retrigger. This applies to writes that are
+    /// scheduled after the change.
+    pub fn set_shape(&mut self, shape: u8) {
+        self.shape = shape;
+    }
+
+    /// Schedule the retriggers that occur before the specified chip clock cycle. See
+    /// [`PSG::write_register_at`] for how cycles are counted.
+    ///
+    /// The first call starts the effect at the current cycle of the PSG. Later calls continue
+    /// where the previous one left off.
+    pub fn schedule_until(&mut self, psg: &mut PSG, cycle: u64) {
+        let clock_rate = psg.effective_clock_rate();
+        let mut next_retrigger = self.next_retrigger.unwrap_or(psg.cycles_elapsed() as f64);
+
+        while next_retrigger < cycle as f64 {
+            psg.write_register_at(next_retrigger.round() as u64, 13, self.shape);
+            next_retrigger += clock_rate / self.frequency;
+        }
+
+        self.next_retrigger = Some(next_retrigger);
+    }
+
+    /// Schedule the retriggers for the specified number of frames that follow the frames which
+    /// have been rendered so far, for example right before rendering them.
+    pub fn schedule_frames(&mut self, psg: &mut PSG, frames: u64) {
+        let cycle = psg.frame_to_cycle(psg.frames_rendered() + frames);
+        self.schedule_until(psg, cycle);
+    }
+
+    /// Stop the effect, by discarding the writes to the envelope shape register that have been
+    /// scheduled after the current cycle of the PSG. The envelope keeps running from the last
+    /// retrigger. Scheduling again afterwards restarts the effect at the PSG's current cycle.
+    ///
+    /// Note that this also discards writes to the envelope shape register that were scheduled by
+    /// other means.
+    pub fn stop(&mut self, psg: &mut PSG) {
+        psg.cancel_scheduled_writes(13, psg.cycles_elapsed());
+        self.next_retrigger = None;
+    }
+
+    /// Whether the effect has been started and not stopped since.
+    pub fn is_running(&self) -> bool {
+        self.next_retrigger.is_some()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::SyncBuzzer;
+    use crate::{ChannelIndex, PSG, math};
+
+    /// Measure the frequency of a signal from the times of its first and last rising zero
+    /// crossings, which are interpolated between samples.
+    fn measure_frequency(samples: &[f64], sample_rate: f64) -> f64 {
+        let crossings: Vec<f64> = samples.windows(2).enumerate().filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0).map(|(index, pair)| {
+            index as f64 + pair[0] / (pair[0] - pair[1])
+        }).collect();
+
+        let duration = (crossings[crossings.len() - 1] - crossings[0]) / sample_rate;
+        (crossings.len() - 1) as f64 / duration
+    }
+
+    #[test]
+    fn retrigger_rate() {
+        let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+
+        psg.set_envelope_period(math::frequency_to_envelope_period(180.0, 2_000_000.0));
+        psg.set_envelope_enabled(ChannelIndex::A, true);
+
+        let mut buzzer = SyncBuzzer::new(220.0, 0x0c);
+        let mut samples = Vec::new();
+
+        // Blocks that do not line up with the retriggers must not disturb the phase
+        for block in 0..300 {
+            buzzer.schedule_frames(&mut psg, 441 + block % 7);
+            samples.extend(psg.render_frames(441 + block as usize % 7).into_iter().map(|(left, _)| left));
+        }
+
+        // Skip the first second, in which the DC filter settles
+        let frequency = measure_frequency(&samples[44100..], 44100.0);
+        assert!(math::cents_between(220.0, frequency).abs() < 1.0);
+
+        // A slide keeps the phase, and ends up at the new frequency
+        for step in 1..=50 {
+            buzzer.set_frequency(220.0 + step as f64 * 0.4);
+            buzzer.schedule_frames(&mut psg, 441);
+            let _ = psg.render_frames(441);
+        }
+
+        samples.clear();
+
+        for _ in 0..200 {
+            buzzer.schedule_frames(&mut psg, 441);
+            samples.extend(psg.render_frames(441).into_iter().map(|(left, _)| left));
+        }
+
+        let frequency = measure_frequency(&samples[44100..], 44100.0);
+        assert!(math::cents_between(240.0, frequency).abs() < 1.0);
+
+        // Stopping discards the retriggers that are scheduled ahead
+        buzzer.schedule_frames(&mut psg, 4410);
+        buzzer.stop(&mut psg);
+        assert!(!buzzer.is_running());
+
+        psg.start_recording();
+        let _ = psg.render_frames(4410);
+        assert!(psg.stop_recording().is_empty());
+    }
+}
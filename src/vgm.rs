@@ -0,0 +1,402 @@
+//! This module contains a writer for VGM files, the video game music format that is played by
+//! most chiptune players. It is available when the `formats-vgm` feature is enabled.
+//!
+//! Register writes that have been captured with [`PSG::start_recording`] are converted to VGM
+//! version 1.71 commands for the AY8910, which VGM uses for all chips of the family. The writes
+//! keep their timing, which is converted from output frames to the 44100 Hz sample rate of VGM.
+//!
+//! ```
+//! use psg::PSG;
+//! use psg::vgm::{Gd3Tag, VgmWriter};
+//!
+//! let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+//! psg.start_recording();
+//!
+//! for note in 0..50 {
+//!     psg.set_register(0, note * 4);
+//!     psg.set_register(8, 15);
+//!     let _ = psg.render_frames(882);
+//! }
+//!
+//! let events = psg.stop_recording();
+//!
+//! let mut writer = VgmWriter::from_psg(&psg);
+//! writer.set_gd3_tag(Some(Gd3Tag { title: "Scale".into(), ..Gd3Tag::default() }));
+//!
+//! let data = writer.write(&events, 0, psg.frames_rendered());
+//! assert_eq!(&data[..4], b"Vgm ");
+//! ```
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{ChipType, PSG, RegisterEvent};
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// The sample rate that VGM files are timed at, in Hz.
+pub const VGM_SAMPLE_RATE: u32 = 44100;
+
+/// The VGM version that is written, which is 1.71.
+const VERSION: u32 = 0x171;
+
+/// The size of the header of VGM version 1.71.
+const HEADER_SIZE: usize = 0x100;
+
+/// The longest wait that a single 0x61 command can encode, in samples.
+const MAX_WAIT: u64 = 0xffff;
+
+/// The metadata of a VGM file, which is stored in its GD3 tag. The tag has English and Japanese
+/// versions of most fields, of which only the English ones are written.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Gd3Tag {
+    /// The title of the track.
+    pub title: String,
+
+    /// The name of the game or album that the track is from.
+    pub game: String,
+
+    /// The name of the system that the track was made for, for example "ZX Spectrum".
+    pub system: String,
+
+    /// The composer of the track.
+    pub author: String,
+
+    /// The release date, preferably in the form yyyy/mm/dd.
+    pub release_date: String,
+
+    /// The name of the person or tool that made the VGM file.
+    pub converted_by: String,
+
+    /// Any other notes.
+    pub notes: String
+}
+
+/// Converts recorded register writes into a VGM file.
+///
+/// The time of every write is converted from its output frame to a VGM sample by rounding the
+/// exact sample position, rather than by accumulating rounded waits, so sample rates other than
+/// 44100 Hz do not make the writes drift.
+#[derive(Clone, Debug)]
+pub struct VgmWriter {
+    clock_rate: f64,
+    sample_rate: u32,
+    chip_type: ChipType,
+    loop_frame: Option<u64>,
+    gd3_tag: Option<Gd3Tag>
+}
+
+impl VgmWriter {
+    /// Initialize a new writer for writes that were recorded from a PSG running at the specified
+    /// clock rate and sample rate in Hz. The clock rate should be the effective clock rate, after
+    /// the clock divider, which is what VGM stores. The chip type defaults to the YM2149.
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            sample_rate,
+            chip_type: ChipType::YM,
+            loop_frame: None,
+            gd3_tag: None
+        }
+    }
+
+    /// Initialize a new writer for writes that were recorded from the specified PSG, using its
+    /// effective clock rate, sample rate and chip type.
+    pub fn from_psg(psg: &PSG) -> Self {
+        let mut writer = Self::new(psg.effective_clock_rate(), psg.sample_rate());
+        writer.set_chip_type(psg.chip_type());
+        writer
+    }
+
+    /// The chip type that is stored in the file.
+    pub fn chip_type(&self) -> ChipType {
+        self.chip_type
+    }
+
+    /// Set the chip type that is stored in the file. The Sunsoft 5B is stored as a YM2149, as VGM
+    /// has no separate type for it.
+    pub fn set_chip_type(&mut self, chip_type: ChipType) {
+        self.chip_type = chip_type;
+    }
+
+    /// The frame at which playback continues after the end of the file, if it loops.
+    pub fn loop_frame(&self) -> Option<u64> {
+        self.loop_frame
+    }
+
+    /// Set the frame at which playback continues after the end of the file, or disable looping by
+    /// passing `None`. Frames are counted like the frames of the recorded events. A loop frame
+    /// outside of the written range of frames disables looping as well.
+    pub fn set_loop_frame(&mut self, frame: Option<u64>) {
+        self.loop_frame = frame;
+    }
+
+    /// The GD3 tag that is written after the commands, if any.
+    pub fn gd3_tag(&self) -> Option<&Gd3Tag> {
+        self.gd3_tag.as_ref()
+    }
+
+    /// Set the GD3 tag that is written after the commands, or pass `None` to write no tag.
+    pub fn set_gd3_tag(&mut self, tag: Option<Gd3Tag>) {
+        self.gd3_tag = tag;
+    }
+
+    /// Write the events that occur from the start frame up to the end frame to a VGM file, and
+    /// return its contents. The events must be ordered by frame, like they are in a recording.
+    ///
+    /// The file starts at the start frame, and lasts until the end frame. Events before the start
+    /// frame are written at the start of the file, so the registers have the right values when
+    /// playback starts, and events from the end frame onwards are left out.
+    pub fn write(&self, events: &[RegisterEvent], start_frame: u64, end_frame: u64) -> Vec<u8> {
+        let end_frame = end_frame.max(start_frame);
+        let total_samples = self.frame_to_sample(end_frame - start_frame);
+
+        let loop_sample = self.loop_frame.filter(|frame| (start_frame..end_frame).contains(frame))
+            .map(|frame| self.frame_to_sample(frame - start_frame));
+
+        let mut data = vec![0; HEADER_SIZE];
+        let mut position = 0;
+        let mut loop_offset = None;
+
+        // Every command that happens at or after the loop point is written after the loop offset
+        let mut wait_until = |data: &mut Vec<u8>, sample: u64| {
+            if let Some(loop_sample) = loop_sample {
+                if loop_offset.is_none() && sample >= loop_sample {
+                    write_wait(data, loop_sample - position);
+                    loop_offset = Some(data.len());
+                    position = loop_sample;
+                }
+            }
+
+            write_wait(data, sample - position);
+            position = sample;
+        };
+
+        for event in events.iter().take_while(|event| event.frame < end_frame) {
+            let sample = self.frame_to_sample(event.frame.saturating_sub(start_frame));
+
+            wait_until(&mut data, sample);
+            data.extend([0xa0, event.register, event.value]);
+        }
+
+        wait_until(&mut data, total_samples);
+        data.push(0x66);
+
+        let gd3_offset = self.gd3_tag.as_ref().map(|tag| {
+            let offset = data.len();
+            write_gd3_tag(&mut data, tag);
+            offset
+        });
+
+        let chip_type = match self.chip_type {
+            ChipType::AY => 0x00,
+            ChipType::AY8930 => 0x03,
+            _ => 0x10
+        };
+
+        let length = data.len();
+        let mut put = |offset: usize, value: u32| data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+
+        put(0x00, u32::from_le_bytes(*b"Vgm "));
+        put(0x04, (length - 0x04) as u32);
+        put(0x08, VERSION);
+        put(0x14, gd3_offset.map_or(0, |offset| (offset - 0x14) as u32));
+        put(0x18, total_samples as u32);
+        put(0x1c, loop_offset.map_or(0, |offset| (offset - 0x1c) as u32));
+        put(0x20, loop_sample.map_or(0, |sample| (total_samples - sample) as u32));
+        put(0x34, (HEADER_SIZE - 0x34) as u32);
+        put(0x74, self.clock_rate.round() as u32);
+
+        // The chip type, followed by the flags, of which bit 0 selects the regular output mixing
+        data[0x78] = chip_type;
+        data[0x79] = 0x01;
+
+        data
+    }
+
+    /// Convert a number of output frames to the nearest number of VGM samples.
+    fn frame_to_sample(&self, frame: u64) -> u64 {
+        ((frame as u128 * VGM_SAMPLE_RATE as u128 + self.sample_rate as u128 / 2) / self.sample_rate as u128) as u64
+    }
+}
+
+/// Write the shortest sequence of wait commands for the specified number of samples.
+fn write_wait(data: &mut Vec<u8>, mut samples: u64) {
+    while samples > 0 {
+        match samples {
+            735 => data.push(0x62),
+            882 => data.push(0x63),
+            1..=16 => data.push(0x70 + samples as u8 - 1),
+            _ => {
+                let wait = samples.min(MAX_WAIT);
+
+                data.push(0x61);
+                data.extend((wait as u16).to_le_bytes());
+                samples -= wait;
+                continue;
+            }
+        }
+
+        break;
+    }
+}
+
+/// Write a GD3 tag, which consists of a header followed by null-terminated UTF-16 strings.
+fn write_gd3_tag(data: &mut Vec<u8>, tag: &Gd3Tag) {
+    let strings = [
+        &tag.title, "",
+        &tag.game, "",
+        &tag.system, "",
+        &tag.author, "",
+        &tag.release_date,
+        &tag.converted_by,
+        &tag.notes
+    ];
+
+    let text: Vec<u8> = strings.iter().flat_map(|string| string.encode_utf16().chain([0])).flat_map(u16::to_le_bytes).collect();
+
+    data.extend(b"Gd3 ");
+    data.extend(0x100u32.to_le_bytes());
+    data.extend((text.len() as u32).to_le_bytes());
+    data.extend(text);
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{Gd3Tag, VgmWriter, write_wait};
+    use crate::{PSG, RegisterEvent};
+
+    /// The register writes of a VGM file, as tuples of the sample, register and value, together
+    /// with the total number of samples and the sample of the loop offset.
+    struct ParsedVgm {
+        writes: Vec<(u64, u8, u8)>,
+        total_samples: u64,
+        loop_sample: Option<u64>
+    }
+
+    fn parse(data: &[u8]) -> ParsedVgm {
+        let u32_at = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+
+        assert_eq!(&data[..4], b"Vgm ");
+        assert_eq!(u32_at(0x04), data.len() - 4);
+        assert_eq!(u32_at(0x08), 0x171);
+
+        let loop_offset = (u32_at(0x1c) != 0).then(|| u32_at(0x1c) + 0x1c);
+        let mut offset = u32_at(0x34) + 0x34;
+        let mut sample = 0;
+        let mut parsed = ParsedVgm { writes: Vec::new(), total_samples: u32_at(0x18) as u64, loop_sample: None };
+
+        loop {
+            if Some(offset) == loop_offset {
+                parsed.loop_sample = Some(sample);
+            }
+
+            match data[offset] {
+                0xa0 => {
+                    parsed.writes.push((sample, data[offset + 1], data[offset + 2]));
+                    offset += 3;
+                }
+                0x61 => {
+                    sample += u16::from_le_bytes([data[offset + 1], data[offset + 2]]) as u64;
+                    offset += 3;
+                }
+                0x62 => { sample += 735; offset += 1; }
+                0x63 => { sample += 882; offset += 1; }
+                command @ 0x70..=0x7f => { sample += (command - 0x6f) as u64; offset += 1; }
+                0x66 => break,
+                command => panic!("unexpected command {command:#x}")
+            }
+        }
+
+        assert_eq!(sample, parsed.total_samples);
+        assert_eq!(parsed.loop_sample.map(|loop_sample| parsed.total_samples - loop_sample), (u32_at(0x20) != 0).then(|| u32_at(0x20) as u64));
+
+        parsed
+    }
+
+    fn play(psg: &mut PSG, events: &[RegisterEvent], frames: u64) -> Vec<(f64, f64)> {
+        let mut events = events.iter().peekable();
+
+        (0..frames).map(|frame| {
+            while let Some(event) = events.next_if(|event| event.frame == frame) {
+                psg.set_register(event.register, event.value);
+            }
+
+            psg.render()
+        }).collect()
+    }
+
+    #[test]
+    fn wait_encoding() {
+        let encode = |samples| {
+            let mut data = Vec::new();
+            write_wait(&mut data, samples);
+            data
+        };
+
+        assert!(encode(0).is_empty());
+        assert_eq!(encode(1), [0x70]);
+        assert_eq!(encode(16), [0x7f]);
+        assert_eq!(encode(17), [0x61, 17, 0]);
+        assert_eq!(encode(735), [0x62]);
+        assert_eq!(encode(882), [0x63]);
+        assert_eq!(encode(65535 + 735), [0x61, 0xff, 0xff, 0x62]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+        psg.start_recording();
+
+        for step in 0..40u64 {
+            psg.set_register(0, (step * 7) as u8);
+            psg.set_register(7, 0x3e);
+            psg.set_register(8, 15 - (step % 8) as u8);
+
+            let _ = psg.render_frames(100 + step as usize * 13);
+        }
+
+        let events = psg.stop_recording();
+        let frames = psg.frames_rendered();
+
+        let mut writer = VgmWriter::from_psg(&psg);
+        writer.set_loop_frame(Some(1000));
+        writer.set_gd3_tag(Some(Gd3Tag { title: "Round trip".into(), author: "psg-rs".into(), ..Gd3Tag::default() }));
+
+        let data = writer.write(&events, 0, frames);
+        let parsed = parse(&data);
+
+        assert_eq!(parsed.total_samples, frames);
+        assert_eq!(parsed.loop_sample, Some(1000));
+
+        // At 44100 Hz, playing back the parsed writes renders exactly the same audio
+        let replayed: Vec<RegisterEvent> = parsed.writes.iter().map(|&(frame, register, value)| RegisterEvent { frame, register, value }).collect();
+
+        let original = play(&mut PSG::new(2_000_000.0, 44100).unwrap(), &events, frames);
+        let round_trip = play(&mut PSG::new(2_000_000.0, 44100).unwrap(), &replayed, frames);
+
+        assert_eq!(original, round_trip);
+
+        // The GD3 tag follows the end of the commands
+        let gd3_offset = u32::from_le_bytes(data[0x14..0x18].try_into().unwrap()) as usize + 0x14;
+        let title: Vec<u8> = "Round trip".encode_utf16().flat_map(u16::to_le_bytes).collect();
+
+        assert_eq!(&data[gd3_offset..gd3_offset + 4], b"Gd3 ");
+        assert_eq!(&data[gd3_offset + 12..gd3_offset + 12 + title.len()], title);
+        assert_eq!(data[0x78], 0x10);
+    }
+
+    #[test]
+    fn no_drift() {
+        // An hour of writes at 48 kHz, one every 50 Hz frame
+        let events: Vec<RegisterEvent> = (0..180_000).map(|frame| RegisterEvent { frame: frame * 960, register: 8, value: 15 }).collect();
+
+        let writer = VgmWriter::new(1_773_400.0, 48000);
+        let parsed = parse(&writer.write(&events, 0, 180_000 * 960));
+
+        assert!(parsed.writes.iter().enumerate().all(|(index, write)| write.0 == index as u64 * 882));
+        assert_eq!(parsed.total_samples, 3600 * 44100);
+    }
+}
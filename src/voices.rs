@@ -0,0 +1,760 @@
+//! This module contains helpers that play notes on the channels of a PSG: a voice allocator,
+//! which maps MIDI notes to the three channels, software envelopes, which shape the amplitude
+//! of each channel at the frame rate, and the ornaments and sample macros of tracker engines.
+
+use alloc::vec::Vec;
+
+use crate::{ChannelIndex, ChannelSelector, PSG, math};
+use crate::math::VolumeCurve;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// A note that is playing on a channel.
+#[derive(Clone, Copy, Debug)]
+struct Voice {
+    pitch: u8,
+
+    // The value of the allocator's note counter when the note started, which orders the notes
+    age: u64
+}
+
+/// Plays MIDI notes on the channels of a PSG, stealing the channel of the oldest note when all
+/// channels are in use.
+///
+/// On a note on, the allocator sets the channel's tone period for the PSG's effective clock rate,
+/// sets its amplitude according to the volume curve (see [`math::velocity_to_amplitude`]), and
+/// enables its tone. On a note off, the
+/// channel's amplitude is set to zero. A channel mask restricts the channels that are used, for
+/// example to reserve a channel for drums:
+///
+/// ```
+/// use psg::{ChannelIndex, PSG};
+/// use psg::voices::VoiceAllocator;
+///
+/// let mut psg = PSG::new(1_773_400.0, 44100).unwrap();
+///
+/// // Use channels A and B, and reserve channel C
+/// let mut allocator = VoiceAllocator::new();
+/// allocator.set_channel_mask(0b011);
+///
+/// assert_eq!(allocator.note_on(&mut psg, 60, 100), Some(ChannelIndex::A));
+/// assert_eq!(allocator.note_on(&mut psg, 64, 100), Some(ChannelIndex::B));
+///
+/// // Channel A plays the oldest note, so it is stolen
+/// assert_eq!(allocator.note_on(&mut psg, 67, 100), Some(ChannelIndex::A));
+/// ```
+#[derive(Clone, Debug)]
+pub struct VoiceAllocator {
+    voices: [Option<Voice>; 3],
+    channel_mask: u8,
+    volume_curve: VolumeCurve,
+    notes_started: u64
+}
+
+impl VoiceAllocator {
+    /// Initialize a new allocator that uses all three channels and the linear volume curve.
+    pub fn new() -> Self {
+        Self {
+            voices: [None; 3],
+            channel_mask: 0b111,
+            volume_curve: VolumeCurve::Linear,
+            notes_started: 0
+        }
+    }
+
+    /// Start playing a note with the specified MIDI pitch number and velocity, and return the
+    /// channel that plays it.
+    ///
+    /// When the note is already playing, it is retriggered on the same channel. Otherwise the
+    /// lowest free channel is used, or the channel of the oldest note when all channels are in use.
+    /// Returns `None` when the channel mask is empty. Following the MIDI convention, a velocity of
+    /// zero stops the note instead, see [`note_off`](Self::note_off).
+    pub fn note_on(&mut self, psg: &mut PSG, pitch: u8, velocity: u8) -> Option<ChannelIndex> {
+        if velocity == 0 {
+            self.note_off(psg, pitch);
+            return None;
+        }
+
+        let channel = self.channel_of(pitch).or_else(|| self.free_channel()).or_else(|| self.oldest_channel())?;
+        let clock_rate = psg.effective_clock_rate();
+        let period = math::midi_pitch_to_tone_period_checked(pitch as f64, clock_rate).unwrap_or_else(|error| error.period());
+
+        psg.set_tone_period(channel, period);
+        psg.set_amplitude(channel, math::velocity_to_amplitude(velocity, self.volume_curve));
+        psg.set_envelope_enabled(channel, false);
+        psg.set_tone_disabled(channel, false);
+
+        self.voices[usize::from(channel)] = Some(Voice {
+            pitch,
+            age: self.notes_started
+        });
+
+        self.notes_started += 1;
+        Some(channel)
+    }
+
+    /// Stop playing a note with the specified MIDI pitch number, and return the channel that
+    /// played it. When the note is not playing, for example because its channel was stolen by
+    /// another note, nothing happens and `None` is returned.
+    pub fn note_off(&mut self, psg: &mut PSG, pitch: u8) -> Option<ChannelIndex> {
+        let channel = self.channel_of(pitch)?;
+
+        psg.set_amplitude(channel, 0);
+        self.voices[usize::from(channel)] = None;
+
+        Some(channel)
+    }
+
+    /// Stop playing all notes.
+    pub fn all_notes_off(&mut self, psg: &mut PSG) {
+        for channel in ChannelIndex::ALL {
+            if self.voices[usize::from(channel)].take().is_some() {
+                psg.set_amplitude(channel, 0);
+            }
+        }
+    }
+
+    /// The MIDI pitch number of the note that is playing on a channel, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn channel_pitch(&self, channel: impl ChannelSelector) -> Option<u8> {
+        self.voices[usize::from(channel.channel_index())].map(|voice| voice.pitch)
+    }
+
+    /// The mask of channels that are used to play notes, in which bits 0 through 2 correspond to
+    /// channels A through C.
+    pub fn channel_mask(&self) -> u8 {
+        self.channel_mask
+    }
+
+    /// Set the mask of channels that are used to play notes, in which bits 0 through 2 correspond
+    /// to channels A through C. The default mask of 0b111 uses all channels. Notes that are
+    /// playing on channels that are removed from the mask keep playing until their note off.
+    ///
+    /// Higher bits are ignored.
+    pub fn set_channel_mask(&mut self, mask: u8) {
+        self.channel_mask = mask & 0b111;
+    }
+
+    /// The curve that converts note velocities into channel amplitudes.
+    pub fn volume_curve(&self) -> VolumeCurve {
+        self.volume_curve
+    }
+
+    /// Set the curve that converts note velocities into channel amplitudes. The default is
+    /// [`VolumeCurve::Linear`].
+    pub fn set_volume_curve(&mut self, curve: VolumeCurve) {
+        self.volume_curve = curve;
+    }
+
+    /// The channel that is playing a note, if any.
+    fn channel_of(&self, pitch: u8) -> Option<ChannelIndex> {
+        ChannelIndex::ALL.into_iter().find(|&channel| {
+            matches!(self.voices[usize::from(channel)], Some(voice) if voice.pitch == pitch)
+        })
+    }
+
+    /// The lowest channel in the mask that is not playing a note, if any.
+    fn free_channel(&self) -> Option<ChannelIndex> {
+        self.masked_channels().find(|&channel| self.voices[usize::from(channel)].is_none())
+    }
+
+    /// The channel in the mask that plays the oldest note, if any.
+    fn oldest_channel(&self) -> Option<ChannelIndex> {
+        self.masked_channels().min_by_key(|&channel| self.voices[usize::from(channel)].map_or(0, |voice| voice.age))
+    }
+
+    /// The channels in the mask.
+    fn masked_channels(&self) -> impl Iterator<Item = ChannelIndex> + '_ {
+        ChannelIndex::ALL.into_iter().filter(|&channel| self.channel_mask & (1 << u8::from(channel)) != 0)
+    }
+}
+
+impl Default for VoiceAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The stages of a [`SoftEnvelope`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoftEnvelopeStage {
+    /// The level rises to 15.
+    Attack,
+
+    /// The level falls from 15 to the sustain level.
+    Decay,
+
+    /// The level stays at the sustain level until the note is released.
+    Sustain,
+
+    /// The level falls to zero after the note is released.
+    Release,
+
+    /// The level is zero, and stays there until the next note starts.
+    Idle
+}
+
+/// An attack/decay/sustain/release envelope, which is computed in software once per frame.
+///
+/// The hardware envelope generator is shared by all channels, so players usually shape the volume
+/// of each channel in software instead, by writing its amplitude once per frame. The lengths of the
+/// attack, decay and release stages are specified in frames, and the sustain level is an amplitude
+/// between 0 and 15 inclusive.
+///
+/// Every stage ramps linearly from the level at which it starts. This means that releasing a note
+/// during its attack fades out from the level that was reached, rather than from the sustain
+/// level, and that retriggering a note during its release rises from the current level to avoid
+/// clicks.
+///
+/// An envelope is driven by calling [`tick`](Self::tick) once per frame and writing the returned
+/// amplitude to a channel, or by passing the envelopes of all channels to
+/// [`PSG::apply_soft_envelopes`]:
+///
+/// ```
+/// use psg::{ChannelIndex, PSG};
+/// use psg::voices::SoftEnvelope;
+///
+/// let mut psg = PSG::new(1_773_400.0, 44100).unwrap();
+/// let mut envelopes = [SoftEnvelope::new(2, 2, 9, 3); 3];
+///
+/// envelopes[0].note_on();
+///
+/// let levels: Vec<u8> = (0..6).map(|_| {
+///     psg.apply_soft_envelopes(&mut envelopes);
+///     psg.amplitude(ChannelIndex::A)
+/// }).collect();
+///
+/// assert_eq!(levels, [8, 15, 12, 9, 9, 9]);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SoftEnvelope {
+    attack: u16,
+    decay: u16,
+    sustain: u8,
+    release: u16,
+    stage: SoftEnvelopeStage,
+
+    // The number of frames that have passed in the current stage
+    position: u16,
+
+    // The level at the start of the current stage, and the current level
+    start_level: f64,
+    level: f64
+}
+
+impl SoftEnvelope {
+    /// Initialize a new idle envelope with the specified attack, decay and release lengths in
+    /// frames, and sustain level between 0 and 15 inclusive.
+    ///
+    /// Larger sustain levels are set to 15.
+    pub fn new(attack: u16, decay: u16, sustain: u8, release: u16) -> Self {
+        Self {
+            attack,
+            decay,
+            sustain: sustain.min(15),
+            release,
+            stage: SoftEnvelopeStage::Idle,
+            position: 0,
+            start_level: 0.0,
+            level: 0.0
+        }
+    }
+
+    /// Start the attack stage from the current level, for example when a new note starts.
+    pub fn note_on(&mut self) {
+        self.enter(SoftEnvelopeStage::Attack);
+    }
+
+    /// Start the release stage from the current level, unless the envelope is already idle or
+    /// releasing.
+    pub fn note_off(&mut self) {
+        if !matches!(self.stage, SoftEnvelopeStage::Release | SoftEnvelopeStage::Idle) {
+            self.enter(SoftEnvelopeStage::Release);
+        }
+    }
+
+    /// Advance the envelope by one frame, and return the resulting amplitude between 0 and 15
+    /// inclusive.
+    ///
+    /// A stage with a length of zero frames is skipped, so the first frame of a note with a zero
+    /// length attack is already at the full level of 15.
+    pub fn tick(&mut self) -> u8 {
+        match self.stage {
+            SoftEnvelopeStage::Attack => {
+                if self.ramp(self.attack, 15.0) {
+                    self.enter(SoftEnvelopeStage::Decay);
+                }
+            }
+            SoftEnvelopeStage::Decay => {
+                if self.ramp(self.decay, self.sustain as f64) {
+                    self.enter(SoftEnvelopeStage::Sustain);
+                }
+            }
+            SoftEnvelopeStage::Sustain => self.level = self.sustain as f64,
+            SoftEnvelopeStage::Release => {
+                if self.ramp(self.release, 0.0) {
+                    self.enter(SoftEnvelopeStage::Idle);
+                }
+            }
+            SoftEnvelopeStage::Idle => ()
+        }
+
+        self.amplitude()
+    }
+
+    /// The current amplitude between 0 and 15 inclusive, as returned by the last call to
+    /// [`tick`](Self::tick).
+    pub fn amplitude(&self) -> u8 {
+        self.level.round() as u8
+    }
+
+    /// The current stage.
+    pub fn stage(&self) -> SoftEnvelopeStage {
+        self.stage
+    }
+
+    /// Whether the envelope is producing sound, which is the case in all stages except
+    /// [`Idle`](SoftEnvelopeStage::Idle).
+    pub fn is_active(&self) -> bool {
+        self.stage != SoftEnvelopeStage::Idle
+    }
+
+    /// The length of the attack stage in frames.
+    pub fn attack(&self) -> u16 {
+        self.attack
+    }
+
+    /// Set the length of the attack stage in frames.
+    pub fn set_attack(&mut self, attack: u16) {
+        self.attack = attack;
+    }
+
+    /// The length of the decay stage in frames.
+    pub fn decay(&self) -> u16 {
+        self.decay
+    }
+
+    /// Set the length of the decay stage in frames.
+    pub fn set_decay(&mut self, decay: u16) {
+        self.decay = decay;
+    }
+
+    /// The sustain level.
+    pub fn sustain(&self) -> u8 {
+        self.sustain
+    }
+
+    /// Set the sustain level to a value between 0 and 15 inclusive.
+    ///
+    /// Larger values are set to 15.
+    pub fn set_sustain(&mut self, sustain: u8) {
+        self.sustain = sustain.min(15);
+    }
+
+    /// The length of the release stage in frames.
+    pub fn release(&self) -> u16 {
+        self.release
+    }
+
+    /// Set the length of the release stage in frames.
+    pub fn set_release(&mut self, release: u16) {
+        self.release = release;
+    }
+
+    /// Start a stage at the current level.
+    fn enter(&mut self, stage: SoftEnvelopeStage) {
+        self.stage = stage;
+        self.position = 0;
+        self.start_level = self.level;
+    }
+
+    /// Advance the current stage by one frame towards the target level over the specified
+    /// length, and return whether the target level was reached.
+    fn ramp(&mut self, length: u16, target: f64) -> bool {
+        self.position = self.position.saturating_add(1);
+
+        if self.position >= length {
+            self.level = target;
+            true
+        } else {
+            let fraction = self.position as f64 / length as f64;
+            self.level = self.start_level + (target - self.start_level) * fraction;
+            false
+        }
+    }
+}
+
+/// Return the index of the next row of a table with the specified length and loop point, and
+/// advance the position. A table without a loop point holds its last row.
+fn next_row_index(position: &mut usize, length: usize, loop_point: Option<usize>) -> Option<usize> {
+    let index = (*position).min(length.checked_sub(1)?);
+
+    if index + 1 == length {
+        *position = loop_point.unwrap_or(length);
+    } else {
+        *position = index + 1;
+    }
+
+    Some(index)
+}
+
+/// Steps through an ornament once per frame, which is a table of pitch offsets in semitones that
+/// is applied to a note, as used by Pro Tracker 3 and Vortex Tracker. Ornaments are commonly
+/// used for arpeggios.
+///
+/// After the last row the ornament continues at its loop point. An ornament without a loop point
+/// is played once, and then holds its last offset.
+#[derive(Clone, Debug)]
+pub struct OrnamentRunner {
+    offsets: Vec<i8>,
+    loop_point: Option<usize>,
+    position: usize
+}
+
+impl OrnamentRunner {
+    /// Initialize a new runner for an ornament with the specified offsets and loop point. A loop
+    /// point beyond the last row is ignored.
+    pub fn new(offsets: &[i8], loop_point: Option<usize>) -> Self {
+        Self {
+            offsets: offsets.to_vec(),
+            loop_point: loop_point.filter(|&point| point < offsets.len()),
+            position: 0
+        }
+    }
+
+    /// The pitch offset for the next frame in semitones. An empty ornament always returns zero.
+    pub fn next_offset(&mut self) -> i8 {
+        next_row_index(&mut self.position, self.offsets.len(), self.loop_point).map_or(0, |index| self.offsets[index])
+    }
+
+    /// Restart the ornament at its first row, for example when a new note starts.
+    pub fn reset(&mut self) {
+        self.position = 0;
+    }
+
+    /// Whether an ornament without a loop point has played all of its rows.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.offsets.len()
+    }
+}
+
+/// A row of a sample macro, see [`SampleMacroRunner`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SampleMacroRow {
+    /// The offset that is added to the note's tone period.
+    pub tone_offset: i16,
+
+    /// The difference between the channel's amplitude and the note's amplitude.
+    pub volume_delta: i8,
+
+    /// Whether the channel's tone is enabled in the mixer.
+    pub tone_enabled: bool,
+
+    /// Whether the channel's noise is enabled in the mixer.
+    pub noise_enabled: bool
+}
+
+/// Steps through a sample macro once per frame, which is a table of rows that shape the tone
+/// period, amplitude and mixer settings of a note, as used by Pro Tracker 3 and Vortex Tracker,
+/// which call them "samples".
+///
+/// After the last row the macro continues at its loop point. A macro without a loop point is
+/// played once, and then holds its last row.
+#[derive(Clone, Debug)]
+pub struct SampleMacroRunner {
+    rows: Vec<SampleMacroRow>,
+    loop_point: Option<usize>,
+    position: usize
+}
+
+impl SampleMacroRunner {
+    /// Initialize a new runner for a macro with the specified rows and loop point. A loop point
+    /// beyond the last row is ignored.
+    pub fn new(rows: &[SampleMacroRow], loop_point: Option<usize>) -> Self {
+        Self {
+            rows: rows.to_vec(),
+            loop_point: loop_point.filter(|&point| point < rows.len()),
+            position: 0
+        }
+    }
+
+    /// The row for the next frame. An empty macro always returns the default row, in which the
+    /// tone and noise are disabled.
+    pub fn next_row(&mut self) -> SampleMacroRow {
+        next_row_index(&mut self.position, self.rows.len(), self.loop_point).map_or(SampleMacroRow::default(), |index| self.rows[index])
+    }
+
+    /// Restart the macro at its first row, for example when a new note starts.
+    pub fn reset(&mut self) {
+        self.position = 0;
+    }
+
+    /// Whether a macro without a loop point has played all of its rows.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.rows.len()
+    }
+}
+
+/// The values to write into a channel for a single frame, as composed from a note, an ornament
+/// and a sample macro.
+///
+/// ```
+/// use psg::{ChannelIndex, PSG};
+/// use psg::voices::{ChannelFrame, OrnamentRunner, SampleMacroRow, SampleMacroRunner};
+///
+/// let mut psg = PSG::new(1_773_400.0, 44100).unwrap();
+/// let mut ornament = OrnamentRunner::new(&[0, 4, 7], Some(0));
+///
+/// let row = SampleMacroRow { tone_offset: 0, volume_delta: 0, tone_enabled: true, noise_enabled: false };
+/// let mut sample = SampleMacroRunner::new(&[row], Some(0));
+///
+/// // Once per frame
+/// let frame = ChannelFrame::compose(60, 15, ornament.next_offset(), sample.next_row(), psg.effective_clock_rate());
+/// frame.apply(&mut psg, ChannelIndex::A);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelFrame {
+    /// The channel's tone period.
+    pub tone_period: u16,
+
+    /// The channel's amplitude.
+    pub amplitude: u8,
+
+    /// Whether the channel's tone is enabled in the mixer.
+    pub tone_enabled: bool,
+
+    /// Whether the channel's noise is enabled in the mixer.
+    pub noise_enabled: bool
+}
+
+impl ChannelFrame {
+    /// Compose the values for a frame of a note with the specified MIDI pitch number and amplitude,
+    /// given the offset of its ornament and the row of its sample macro for the frame.
+    ///
+    /// The ornament offset transposes the note before its tone period is computed for the clock
+    /// rate, and the macro's tone offset is then added to the period. The resulting period is
+    /// clamped to the range of 1 to 4095 inclusive, and the amplitude to the range of 0 to 15
+    /// inclusive.
+    pub fn compose(note: u8, amplitude: u8, ornament_offset: i8, row: SampleMacroRow, clock_rate: f64) -> Self {
+        let pitch = note as f64 + ornament_offset as f64;
+        let period = math::midi_pitch_to_tone_period_checked(pitch, clock_rate).unwrap_or_else(|error| error.period());
+
+        Self {
+            tone_period: (period as i32 + row.tone_offset as i32).clamp(1, 4095) as u16,
+            amplitude: (amplitude.min(15) as i16 + row.volume_delta as i16).clamp(0, 15) as u8,
+            tone_enabled: row.tone_enabled,
+            noise_enabled: row.noise_enabled
+        }
+    }
+
+    /// Write the values into a channel's tone period, amplitude, and mixer bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a channel number is not smaller than 3.
+    pub fn apply(&self, psg: &mut PSG, channel: impl ChannelSelector) {
+        let channel = channel.channel_index();
+
+        psg.set_tone_period(channel, self.tone_period);
+        psg.set_amplitude(channel, self.amplitude);
+        psg.set_tone_disabled(channel, !self.tone_enabled);
+        psg.set_noise_disabled(channel, !self.noise_enabled);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{ChannelFrame, OrnamentRunner, SampleMacroRow, SampleMacroRunner, SoftEnvelope, SoftEnvelopeStage, VoiceAllocator};
+    use crate::{ChannelIndex, PSG, math};
+    use crate::math::VolumeCurve;
+
+    #[test]
+    fn stealing_order() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let mut allocator = VoiceAllocator::new();
+
+        assert_eq!(allocator.note_on(&mut psg, 60, 127), Some(ChannelIndex::A));
+        assert_eq!(allocator.note_on(&mut psg, 64, 127), Some(ChannelIndex::B));
+        assert_eq!(allocator.note_on(&mut psg, 67, 127), Some(ChannelIndex::C));
+
+        assert_eq!(psg.tone_period(ChannelIndex::B), math::midi_pitch_to_tone_period(64.0, 1_000_000.0));
+        assert_eq!(psg.amplitude(ChannelIndex::B), 15);
+        assert!(!psg.tone_disabled(ChannelIndex::B));
+
+        // The oldest notes are stolen first
+        assert_eq!(allocator.note_on(&mut psg, 72, 127), Some(ChannelIndex::A));
+        assert_eq!(allocator.note_on(&mut psg, 76, 127), Some(ChannelIndex::B));
+
+        // Retriggering a playing note keeps its channel, and makes it the newest note
+        assert_eq!(allocator.note_on(&mut psg, 67, 64), Some(ChannelIndex::C));
+        assert_eq!(psg.amplitude(ChannelIndex::C), 8);
+        assert_eq!(allocator.note_on(&mut psg, 79, 127), Some(ChannelIndex::A));
+
+        // A freed channel is used before stealing
+        assert_eq!(allocator.note_off(&mut psg, 76), Some(ChannelIndex::B));
+        assert_eq!(allocator.note_on(&mut psg, 81, 127), Some(ChannelIndex::B));
+    }
+
+    #[test]
+    fn note_off_of_stolen_note() {
+        let mut psg = PSG::new(1_000_000.0, 44100).unwrap();
+        let mut allocator = VoiceAllocator::new();
+        allocator.set_channel_mask(0b110);
+
+        allocator.note_on(&mut psg, 60, 127);
+        allocator.note_on(&mut psg, 64, 127);
+        assert_eq!(allocator.note_on(&mut psg, 67, 127), Some(ChannelIndex::B));
+
+        // The note off of the stolen note does not stop the note that replaced it
+        assert_eq!(allocator.note_off(&mut psg, 60), None);
+        assert_eq!(allocator.channel_pitch(ChannelIndex::B), Some(67));
+        assert_eq!(psg.amplitude(ChannelIndex::B), 15);
+
+        assert_eq!(allocator.note_on(&mut psg, 67, 0), None);
+        assert_eq!(allocator.channel_pitch(ChannelIndex::B), None);
+        assert_eq!(psg.amplitude(ChannelIndex::B), 0);
+
+        // The reserved channel is never used
+        assert_eq!(allocator.channel_pitch(ChannelIndex::A), None);
+
+        allocator.set_volume_curve(VolumeCurve::Logarithmic);
+        assert_eq!(allocator.note_on(&mut psg, 48, 20), Some(ChannelIndex::B));
+        assert_eq!(psg.amplitude(ChannelIndex::B), math::velocity_to_amplitude(20, VolumeCurve::Logarithmic));
+        assert_ne!(psg.amplitude(ChannelIndex::B), math::velocity_to_amplitude(20, VolumeCurve::Linear));
+
+        allocator.all_notes_off(&mut psg);
+        assert_eq!(psg.amplitude(ChannelIndex::C), 0);
+        assert_eq!(allocator.channel_pitch(ChannelIndex::C), None);
+
+        allocator.set_channel_mask(0);
+        assert_eq!(allocator.note_on(&mut psg, 48, 100), None);
+    }
+
+    fn ticks(envelope: &mut SoftEnvelope, count: usize) -> Vec<u8> {
+        (0..count).map(|_| envelope.tick()).collect()
+    }
+
+    #[test]
+    fn zero_length_attack() {
+        let mut envelope = SoftEnvelope::new(0, 3, 6, 0);
+        assert_eq!(envelope.tick(), 0);
+
+        envelope.note_on();
+        assert_eq!(ticks(&mut envelope, 5), [15, 12, 9, 6, 6]);
+        assert_eq!(envelope.stage(), SoftEnvelopeStage::Sustain);
+
+        // A zero length release cuts the note in the next frame
+        envelope.note_off();
+        assert_eq!(ticks(&mut envelope, 2), [0, 0]);
+        assert!(!envelope.is_active());
+    }
+
+    #[test]
+    fn release_during_attack() {
+        let mut envelope = SoftEnvelope::new(5, 1, 10, 2);
+
+        envelope.note_on();
+        assert_eq!(ticks(&mut envelope, 2), [3, 6]);
+
+        // The release starts from the current level, not from the sustain level
+        envelope.note_off();
+        assert_eq!(ticks(&mut envelope, 3), [3, 0, 0]);
+    }
+
+    #[test]
+    fn retrigger_during_release() {
+        let mut envelope = SoftEnvelope::new(3, 0, 15, 4);
+
+        envelope.note_on();
+        assert_eq!(ticks(&mut envelope, 4), [5, 10, 15, 15]);
+
+        envelope.note_off();
+        assert_eq!(ticks(&mut envelope, 2), [11, 8]);
+        assert_eq!(envelope.stage(), SoftEnvelopeStage::Release);
+
+        // The attack restarts from the current level
+        envelope.note_on();
+        assert_eq!(ticks(&mut envelope, 4), [10, 13, 15, 15]);
+        assert_eq!(envelope.stage(), SoftEnvelopeStage::Sustain);
+    }
+
+    #[test]
+    fn ornament_loops() {
+        let offsets = |ornament: &mut OrnamentRunner| -> Vec<i8> { (0..7).map(|_| ornament.next_offset()).collect() };
+
+        assert_eq!(offsets(&mut OrnamentRunner::new(&[0, 4, 7], Some(0))), [0, 4, 7, 0, 4, 7, 0]);
+        assert_eq!(offsets(&mut OrnamentRunner::new(&[0, 4, 7], Some(1))), [0, 4, 7, 4, 7, 4, 7]);
+        assert_eq!(offsets(&mut OrnamentRunner::new(&[12], Some(0))), [12; 7]);
+        assert_eq!(offsets(&mut OrnamentRunner::new(&[], Some(0))), [0; 7]);
+
+        // A one-shot ornament holds its last offset
+        let mut ornament = OrnamentRunner::new(&[0, 4, 7], None);
+
+        assert_eq!(offsets(&mut ornament), [0, 4, 7, 7, 7, 7, 7]);
+        assert!(ornament.is_finished());
+
+        ornament.reset();
+        assert_eq!(ornament.next_offset(), 0);
+        assert!(!ornament.is_finished());
+    }
+
+    #[test]
+    fn ornament_and_sample_macro() {
+        let clock_rate = 1_773_400.0;
+
+        let row = |tone_offset, volume_delta, tone_enabled, noise_enabled| SampleMacroRow {
+            tone_offset,
+            volume_delta,
+            tone_enabled,
+            noise_enabled
+        };
+
+        let rows = [row(0, 0, true, true), row(2, -1, true, false), row(-2, -3, true, false), row(0, -20, false, false)];
+
+        let mut ornament = OrnamentRunner::new(&[0, 12], Some(0));
+        let mut sample = SampleMacroRunner::new(&rows, Some(1));
+
+        let frames: Vec<ChannelFrame> = (0..6).map(|_| {
+            ChannelFrame::compose(60, 12, ornament.next_offset(), sample.next_row(), clock_rate)
+        }).collect();
+
+        let low = math::midi_pitch_to_tone_period(60.0, clock_rate);
+        let high = math::midi_pitch_to_tone_period(72.0, clock_rate);
+
+        let frame = |tone_period, amplitude, tone_enabled, noise_enabled| ChannelFrame {
+            tone_period,
+            amplitude,
+            tone_enabled,
+            noise_enabled
+        };
+
+        assert_eq!(frames, [
+            frame(low, 12, true, true),
+            frame(high + 2, 11, true, false),
+            frame(low - 2, 9, true, false),
+            frame(high, 0, false, false),
+            frame(low + 2, 11, true, false),
+            frame(high - 2, 9, true, false)
+        ]);
+
+        let mut psg = PSG::new(clock_rate, 44100).unwrap();
+        frames[1].apply(&mut psg, ChannelIndex::B);
+
+        assert_eq!(psg.tone_period(ChannelIndex::B), high + 2);
+        assert_eq!(psg.amplitude(ChannelIndex::B), 11);
+        assert_eq!(psg.register(7) & 0x12, 0x10);
+
+        // A one-shot macro holds its last row, and the period is clamped
+        let mut sample = SampleMacroRunner::new(&rows[..2], None);
+
+        assert_eq!(sample.next_row(), rows[0]);
+        assert_eq!(sample.next_row(), rows[1]);
+        assert_eq!(sample.next_row(), rows[1]);
+        assert!(sample.is_finished());
+        assert_eq!(ChannelFrame::compose(120, 15, 0, row(-100, 0, true, false), clock_rate).tone_period, 1);
+    }
+}
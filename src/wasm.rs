@@ -0,0 +1,143 @@
+//! This module contains WebAssembly bindings for the PSG, for running it in a browser using
+//! wasm-bindgen. It is available when the `wasm` feature is enabled.
+//!
+//! The bindings are exported by any `cdylib` crate that depends on this one with the `wasm`
+//! feature, and built for the `wasm32-unknown-unknown` target, for example using wasm-pack. From
+//! JavaScript, the PSG can then feed an `AudioWorklet`:
+//!
+//! ```js
+//! const psg = new WasmPsg(2000000, sampleRate);
+//!
+//! psg.setRegister(7, 0x3e);
+//! psg.setRegister(8, 15);
+//!
+//! const samples = psg.render(128); // A Float32Array of 256 interleaved samples
+//! ```
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use js_sys::Float32Array;
+use wasm_bindgen::prelude::{JsError, wasm_bindgen};
+
+use crate::PSG;
+
+/// A PSG that can be used from JavaScript, where it is called `WasmPsg`.
+///
+/// The samples are rendered into an internal buffer, which is reused between calls, and copied to
+/// JavaScript at once, so no values cross the boundary while rendering.
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct WasmPsg {
+    psg: PSG,
+    buffer: Vec<f32>
+}
+
+#[wasm_bindgen]
+impl WasmPsg {
+    /// Create a new PSG with the specified clock and sample rates, or throw an error when the
+    /// clock rate is too high for the sample rate.
+    #[wasm_bindgen(constructor)]
+    pub fn new(clock_rate: f64, sample_rate: u32) -> Result<WasmPsg, JsError> {
+        let psg = PSG::new(clock_rate, sample_rate).map_err(|error| JsError::new(&error.to_string()))?;
+
+        Ok(Self {
+            psg,
+            buffer: Vec::new()
+        })
+    }
+
+    /// Write a value to a register, or throw an error when the register number is above 15.
+    #[wasm_bindgen(js_name = setRegister)]
+    pub fn set_register(&mut self, register: u8, value: u8) -> Result<(), JsError> {
+        self.psg.try_set_register(register, value).map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    /// Reset the registers to zero, like the reset line of the chip.
+    pub fn reset(&mut self) {
+        self.psg.reset();
+    }
+
+    /// The sample rate, in Hz.
+    #[wasm_bindgen(getter, js_name = sampleRate)]
+    pub fn sample_rate(&self) -> u32 {
+        self.psg.sample_rate()
+    }
+
+    /// Render the specified number of frames, and return them as interleaved stereo samples.
+    pub fn render(&mut self, frames: usize) -> Float32Array {
+        Float32Array::from(self.render_into_buffer(frames))
+    }
+}
+
+impl WasmPsg {
+    /// The inner PSG.
+    pub fn psg(&self) -> &PSG {
+        &self.psg
+    }
+
+    /// The inner PSG, for changing settings that are not exported to JavaScript.
+    pub fn psg_mut(&mut self) -> &mut PSG {
+        &mut self.psg
+    }
+
+    /// Render the specified number of frames into the internal buffer, which only grows.
+    fn render_into_buffer(&mut self, frames: usize) -> &[f32] {
+        if self.buffer.len() < 2 * frames {
+            self.buffer.resize(2 * frames, 0.0);
+        }
+
+        let samples = &mut self.buffer[..2 * frames];
+        self.psg.render_into_samples(samples);
+        samples
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::WasmPsg;
+    use crate::PSG;
+
+    #[test]
+    fn render_into_buffer() {
+        let mut psg = WasmPsg::new(2_000_000.0, 44100).unwrap();
+        let mut reference = PSG::new(2_000_000.0, 44100).unwrap();
+
+        for (register, value) in [(0, 0x1c), (1, 0x01), (7, 0x3e), (8, 15)] {
+            psg.set_register(register, value).unwrap();
+            reference.set_register(register, value);
+        }
+
+        let mut expected = vec![0.0f32; 2 * 1024];
+        reference.render_into_samples(&mut expected[..2 * 1000]);
+        reference.render_into_samples(&mut expected[2 * 1000..]);
+
+        assert_eq!(psg.render_into_buffer(1000), &expected[..2 * 1000]);
+        assert_eq!(psg.render_into_buffer(24), &expected[2 * 1000..]);
+        assert_eq!(psg.buffer.len(), 2 * 1000);
+        assert_eq!(psg.sample_rate(), 44100);
+    }
+}
+
+// Run using `wasm-pack test --node --features wasm`, or `--headless --firefox`
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::WasmPsg;
+
+    #[wasm_bindgen_test]
+    fn render() {
+        let mut psg = WasmPsg::new(2_000_000.0, 44100).unwrap();
+
+        for (register, value) in [(0, 0x1c), (1, 0x01), (7, 0x3e), (8, 15)] {
+            psg.set_register(register, value).unwrap();
+        }
+
+        let samples = psg.render(1024);
+
+        assert_eq!(samples.length(), 2 * 1024);
+        assert!(samples.to_vec().iter().any(|&sample| sample != 0.0));
+        assert!(psg.set_register(16, 0).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! This module contains a writer for stereo WAV files, for rendering audio offline. It is
+//! available when the `wav` feature is enabled.
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use psg::PSG;
+//! use psg::wav::{self, WavFormat};
+//!
+//! let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+//! psg.set_register(8, 15);
+//!
+//! wav::render_to_file(&mut psg, 44100, Path::new("output.wav"), WavFormat::Pcm16).unwrap();
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::{PSG, Sample};
+
+/// The number of frames that [`render_to_file`] renders at a time.
+const BLOCK_SIZE: usize = 1024;
+
+/// The sample format of a WAV file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WavFormat {
+    /// 16-bit signed integer PCM, which every program can open.
+    Pcm16,
+
+    /// 32-bit IEEE floating point, which keeps the full dynamic range of the rendered audio.
+    Float32
+}
+
+impl WavFormat {
+    /// The format tag that is stored in the fmt chunk.
+    fn tag(self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 1,
+            WavFormat::Float32 => 3
+        }
+    }
+
+    /// The number of bytes per sample.
+    fn sample_size(self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 2,
+            WavFormat::Float32 => 4
+        }
+    }
+
+    /// The number of bytes before the audio data.
+    fn header_size(self) -> u64 {
+        match self {
+            WavFormat::Pcm16 => 44,
+            WavFormat::Float32 => 58
+        }
+    }
+
+    /// The largest number of bytes of audio data, which is limited by the 32-bit size of the RIFF
+    /// chunk that contains the rest of the header as well.
+    fn max_data_size(self) -> u64 {
+        u32::MAX as u64 - (self.header_size() - 8)
+    }
+}
+
+/// The error that is returned when the audio does not fit in a WAV file.
+fn too_long() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "the audio is too long for a WAV file")
+}
+
+/// Writes stereo audio to a WAV file as it is rendered, so long renders do not need to be kept in
+/// memory.
+///
+/// The header is written when the writer is created, and the chunk sizes in it are filled in by
+/// [`finish`](Self::finish), which must be called once all audio has been written. A WAV file holds
+/// up to 4 GiB of audio, which is about 6.7 hours of 16-bit audio at 44100 Hz. Writing more frames
+/// returns an error, and leaves the file as it was. The frames of
+/// a [`FramePlayer`](crate::FramePlayer) can be written using
+/// [`write_interleaved`](Self::write_interleaved).
+#[derive(Debug)]
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    format: WavFormat,
+    frames_written: u64,
+    data_size: u64
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Initialize a new writer for audio at the specified sample rate, and write the header.
+    pub fn new(mut writer: W, sample_rate: u32, format: WavFormat) -> io::Result<Self> {
+        let block_align = 2 * format.sample_size();
+
+        writer.write_all(b"RIFF\0\0\0\0WAVE")?;
+
+        // Formats other than PCM have an extension size in the fmt chunk, and a fact chunk
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&if format == WavFormat::Pcm16 { 16u32 } else { 18 }.to_le_bytes())?;
+        writer.write_all(&format.tag().to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        writer.write_all(&block_align.to_le_bytes())?;
+        writer.write_all(&(8 * format.sample_size()).to_le_bytes())?;
+
+        if format != WavFormat::Pcm16 {
+            writer.write_all(&0u16.to_le_bytes())?;
+            writer.write_all(b"fact\x04\0\0\0\0\0\0\0")?;
+        }
+
+        writer.write_all(b"data\0\0\0\0")?;
+
+        Ok(Self {
+            writer,
+            format,
+            frames_written: 0,
+            data_size: 0
+        })
+    }
+
+    /// Write a frame, or return an error when the file is full.
+    pub fn write_frame(&mut self, left: f64, right: f64) -> io::Result<()> {
+        let data_size = self.data_size.checked_add(2 * self.format.sample_size() as u64)
+            .filter(|&size| size <= self.format.max_data_size())
+            .ok_or_else(too_long)?;
+
+        match self.format {
+            WavFormat::Pcm16 => {
+                self.writer.write_all(&i16::from_f64(left).to_le_bytes())?;
+                self.writer.write_all(&i16::from_f64(right).to_le_bytes())?;
+            }
+            WavFormat::Float32 => {
+                self.writer.write_all(&f32::from_f64(left).to_le_bytes())?;
+                self.writer.write_all(&f32::from_f64(right).to_le_bytes())?;
+            }
+        }
+
+        self.frames_written += 1;
+        self.data_size = data_size;
+        Ok(())
+    }
+
+    /// Write frames as returned by [`PSG::render_frames`].
+    pub fn write_frames(&mut self, frames: &[(f64, f64)]) -> io::Result<()> {
+        frames.iter().try_for_each(|&(left, right)| self.write_frame(left, right))
+    }
+
+    /// Write interleaved stereo samples, as rendered by
+    /// [`FramePlayer::render_into`](crate::FramePlayer::render_into). A trailing odd sample is
+    /// ignored.
+    pub fn write_interleaved(&mut self, samples: &[f32]) -> io::Result<()> {
+        samples.chunks_exact(2).try_for_each(|frame| self.write_frame(frame[0] as f64, frame[1] as f64))
+    }
+
+    /// The number of frames that have been written.
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+
+    /// Fill in the chunk sizes in the header, and return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let header_size = self.format.header_size();
+
+        // The writes are limited to the maximum data size, so the sizes always fit
+        let riff_size = header_size.checked_add(self.data_size).and_then(|size| u32::try_from(size - 8).ok()).ok_or_else(too_long)?;
+        let data_size = u32::try_from(self.data_size).map_err(|_| too_long())?;
+        let frames = u32::try_from(self.frames_written).map_err(|_| too_long())?;
+
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&riff_size.to_le_bytes())?;
+
+        if self.format != WavFormat::Pcm16 {
+            self.writer.seek(SeekFrom::Start(46))?;
+            self.writer.write_all(&frames.to_le_bytes())?;
+        }
+
+        self.writer.seek(SeekFrom::Start(header_size - 4))?;
+        self.writer.write_all(&data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+/// Render the specified number of frames to a WAV file at the specified path, which is created or
+/// overwritten. The audio is rendered and written in small blocks.
+pub fn render_to_file(psg: &mut PSG, frames: usize, path: &Path, format: WavFormat) -> io::Result<()> {
+    let mut writer = WavWriter::new(BufWriter::new(File::create(path)?), psg.sample_rate(), format)?;
+    let mut block = Vec::with_capacity(BLOCK_SIZE);
+
+    for start in (0..frames).step_by(BLOCK_SIZE) {
+        block.clear();
+        block.extend((start..frames.min(start + BLOCK_SIZE)).map(|_| psg.render()));
+        writer.write_frames(&block)?;
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{WavFormat, WavWriter, render_to_file};
+    use crate::{FramePlayer, PSG};
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn pcm_header() {
+        let path = std::env::temp_dir().join(format!("psg-wav-test-{}.wav", std::process::id()));
+        let mut psg = PSG::new(2_000_000.0, 22050).unwrap();
+        psg.set_register(8, 15);
+
+        render_to_file(&mut psg, 2500, &path, WavFormat::Pcm16).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((&data[..4], &data[8..16]), (&b"RIFF"[..], &b"WAVEfmt "[..]));
+        assert_eq!(u32_at(&data, 4) as usize, data.len() - 8);
+        assert_eq!((u32_at(&data, 16), u16_at(&data, 20), u16_at(&data, 22)), (16, 1, 2));
+        assert_eq!((u32_at(&data, 24), u32_at(&data, 28)), (22050, 22050 * 4));
+        assert_eq!((u16_at(&data, 32), u16_at(&data, 34)), (4, 16));
+        assert_eq!(&data[36..40], b"data");
+        assert_eq!(u32_at(&data, 40), 2500 * 4);
+        assert_eq!(data.len(), 44 + 2500 * 4);
+        assert!(data[44..].iter().any(|&byte| byte != 0));
+    }
+
+    #[test]
+    fn float_header() {
+        let mut player = FramePlayer::new(PSG::new(2_000_000.0, 44100).unwrap(), 50.0, vec![[0x1c, 0x01, 0, 0, 0, 0, 0, 0x3e, 15, 0, 0, 0, 0, 0xff]; 2]);
+        let mut buffer = vec![0.0; 2 * 1000];
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), 44100, WavFormat::Float32).unwrap();
+
+        player.render_into(&mut buffer);
+        writer.write_interleaved(&buffer).unwrap();
+        writer.write_interleaved(&buffer[..2 * 764]).unwrap();
+        assert_eq!(writer.frames_written(), 1764);
+
+        let data = writer.finish().unwrap().into_inner();
+
+        assert_eq!(u32_at(&data, 4) as usize, data.len() - 8);
+        assert_eq!((u32_at(&data, 16), u16_at(&data, 20), u16_at(&data, 34), u16_at(&data, 36)), (18, 3, 32, 0));
+        assert_eq!((&data[38..42], u32_at(&data, 42), u32_at(&data, 46)), (&b"fact"[..], 4, 1764));
+        assert_eq!((&data[50..54], u32_at(&data, 54)), (&b"data"[..], 1764 * 8));
+        assert_eq!(f32::from_le_bytes(data[58..62].try_into().unwrap()), buffer[0]);
+    }
+
+    #[test]
+    fn size_limit() {
+        for format in [WavFormat::Pcm16, WavFormat::Float32] {
+            let mut writer = WavWriter::new(Cursor::new(Vec::new()), 44100, format).unwrap();
+            let frame_size = 2 * format.sample_size() as u64;
+
+            // Pretend that the file is one frame away from the limit of the RIFF chunk size
+            writer.data_size = format.max_data_size() / frame_size * frame_size - frame_size;
+            writer.frames_written = writer.data_size / frame_size;
+
+            writer.write_frame(0.5, -0.5).unwrap();
+            assert_eq!(writer.write_frame(0.5, -0.5).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+            assert_eq!(writer.write_frames(&[(0.0, 0.0)]).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+            let frames = writer.frames_written();
+            let data = writer.finish().unwrap().into_inner();
+            let header_size = format.header_size() as usize;
+
+            // The header and the last frame are all that was written
+            assert_eq!(data.len(), header_size + frame_size as usize);
+            assert_eq!(u32_at(&data, 4) as u64, frames * frame_size + header_size as u64 - 8);
+            assert_eq!(u32_at(&data, header_size - 4) as u64, frames * frame_size);
+            assert!(u32_at(&data, 4) > u32::MAX - frame_size as u32);
+        }
+    }
+}
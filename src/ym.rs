@@ -0,0 +1,645 @@
+//! This module contains a loader and player for YM files, the register dump format that is most
+//! commonly used for Atari ST music. It is available when the `formats-ym` feature is enabled.
+//!
+//! The YM2!, YM3!, YM3b, YM5! and YM6! variants of the format are supported, in both their
+//! interleaved and non-interleaved register layouts. Files that are compressed with LHA, which
+//! most YM files are, are decompressed automatically. The special effects of YM5! and YM6! files,
+//! such as digidrums and SID voices, are parsed but not played.
+//!
+//! ```no_run
+//! use psg::PlayerStatus;
+//! use psg::ym::{YmFile, YmPlayer};
+//!
+//! let data = std::fs::read("tune.ym").unwrap();
+//! let file = YmFile::parse(&data).unwrap();
+//!
+//! println!("{} by {}", file.title(), file.author());
+//!
+//! let mut player = YmPlayer::new(&file, 44100).unwrap();
+//! let mut buffer = vec![0.0; 2 * 4410];
+//!
+//! while player.render_into(&mut buffer) != PlayerStatus::Finished {
+//!     // Send the buffer to the audio device
+//! }
+//! ```
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{ChipType, FramePlayer, PSG, PlayerStatus};
+use crate::lha;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use crate::float::FloatExt;
+
+/// The clock rate of the Atari ST's YM2149 in Hz, which is used by the formats that do not store
+/// a clock rate.
+const ATARI_ST_CLOCK_RATE: f64 = 2_000_000.0;
+
+/// The frame rate of the formats that do not store a frame rate in Hz.
+const DEFAULT_FRAME_RATE: f64 = 50.0;
+
+/// The song attribute of YM5! and YM6! files that selects the interleaved register layout.
+const ATTRIBUTE_INTERLEAVED: u32 = 0x01;
+
+/// The error returned by [`YmFile::parse`] when a file cannot be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum YmError {
+    /// The file ends before all of the data that its header announces.
+    Truncated,
+
+    /// The file does not start with the signature of one of the supported formats. Contains the
+    /// first four bytes of the file.
+    UnsupportedFormat([u8; 4]),
+
+    /// The file is an LHA archive that uses a compression method other than -lh0- and -lh4-
+    /// through -lh7-. Contains the method, for example "-lh1-".
+    UnsupportedCompression(String),
+
+    /// The file is an LHA archive that is damaged. Contains a description of the problem.
+    CorruptArchive(&'static str),
+
+    /// The "LeOnArD!" check string of a YM5! or YM6! file is missing.
+    InvalidCheckString
+}
+
+impl fmt::Display for YmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YmError::Truncated => write!(f, "the YM file is truncated"),
+            YmError::UnsupportedFormat(signature) => {
+                write!(f, "the file is not a supported YM file, its signature is {:?}", String::from_utf8_lossy(signature))
+            }
+            YmError::UnsupportedCompression(method) => write!(f, "the YM file is compressed with the unsupported LHA method {method}"),
+            YmError::CorruptArchive(problem) => write!(f, "the YM file's LHA archive is corrupt: {problem}"),
+            YmError::InvalidCheckString => write!(f, "the YM file's check string is missing")
+        }
+    }
+}
+
+impl core::error::Error for YmError {}
+
+/// The variant of the YM format that a file uses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum YmFormat {
+    /// YM2!, the format of the Mad Max music rips, which is otherwise identical to YM3!.
+    Ym2,
+
+    /// YM3!, the original format, which consists of the interleaved register data only.
+    Ym3,
+
+    /// YM3b, which adds a loop frame to YM3!.
+    Ym3b,
+
+    /// YM5!, which adds a header with metadata, the clock and frame rates, and digidrums.
+    Ym5,
+
+    /// YM6!, which extends YM5! with more special effects.
+    Ym6
+}
+
+/// A parsed YM file, consisting of its metadata and the register values of every frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct YmFile {
+    format: YmFormat,
+    title: String,
+    author: String,
+    comment: String,
+    clock_rate: f64,
+    frame_rate: f64,
+    loop_frame: usize,
+    frames: Vec<[u8; 16]>,
+    digidrums: Vec<Vec<u8>>
+}
+
+impl YmFile {
+    /// Parse the contents of a YM file, decompressing it first if it is an LHA archive.
+    pub fn parse(data: &[u8]) -> Result<Self, YmError> {
+        if lha::is_lha(data) {
+            Self::parse_uncompressed(&lha::decompress(data)?)
+        } else {
+            Self::parse_uncompressed(data)
+        }
+    }
+
+    /// Parse the contents of a YM file that is not compressed.
+    fn parse_uncompressed(data: &[u8]) -> Result<Self, YmError> {
+        let mut reader = Reader::new(data);
+        let signature: [u8; 4] = reader.array()?;
+
+        let format = match &signature {
+            b"YM2!" => YmFormat::Ym2,
+            b"YM3!" => YmFormat::Ym3,
+            b"YM3b" => YmFormat::Ym3b,
+            b"YM5!" => YmFormat::Ym5,
+            b"YM6!" => YmFormat::Ym6,
+            _ => return Err(YmError::UnsupportedFormat(signature))
+        };
+
+        match format {
+            YmFormat::Ym2 | YmFormat::Ym3 | YmFormat::Ym3b => Self::parse_register_dump(format, data),
+            YmFormat::Ym5 | YmFormat::Ym6 => Self::parse_with_header(format, reader)
+        }
+    }
+
+    /// Parse a YM2!, YM3! or YM3b file, which consist of interleaved 14-register frames, and in
+    /// case of YM3b a little-endian loop frame at the end.
+    fn parse_register_dump(format: YmFormat, data: &[u8]) -> Result<Self, YmError> {
+        let (registers, loop_frame) = match format {
+            YmFormat::Ym3b => {
+                let (registers, loop_frame) = data[4..].split_at_checked(data.len().saturating_sub(8)).ok_or(YmError::Truncated)?;
+                (registers, u32::from_le_bytes(loop_frame.try_into().map_err(|_| YmError::Truncated)?) as usize)
+            }
+            _ => (&data[4..], 0)
+        };
+
+        Ok(Self {
+            format,
+            title: String::new(),
+            author: String::new(),
+            comment: String::new(),
+            clock_rate: ATARI_ST_CLOCK_RATE,
+            frame_rate: DEFAULT_FRAME_RATE,
+            loop_frame,
+            frames: deinterleave(registers, 14, registers.len() / 14),
+            digidrums: Vec::new()
+        })
+    }
+
+    /// Parse the remainder of a YM5! or YM6! file after its signature.
+    fn parse_with_header(format: YmFormat, mut reader: Reader) -> Result<Self, YmError> {
+        if &reader.array::<8>()? != b"LeOnArD!" {
+            return Err(YmError::InvalidCheckString);
+        }
+
+        let frame_count = reader.u32()? as usize;
+        let attributes = reader.u32()?;
+        let digidrum_count = reader.u16()?;
+        let clock_rate = reader.u32()? as f64;
+        let frame_rate = reader.u16()? as f64;
+        let loop_frame = reader.u32()? as usize;
+        let extra_size = reader.u16()? as usize;
+
+        reader.bytes(extra_size)?;
+
+        let digidrums = (0..digidrum_count).map(|_| {
+            let size = reader.u32()? as usize;
+            Ok(reader.bytes(size)?.to_vec())
+        }).collect::<Result<Vec<_>, YmError>>()?;
+
+        let title = reader.string()?;
+        let author = reader.string()?;
+        let comment = reader.string()?;
+
+        let registers = reader.bytes(frame_count.checked_mul(16).ok_or(YmError::Truncated)?)?;
+
+        let frames = if attributes & ATTRIBUTE_INTERLEAVED != 0 {
+            deinterleave(registers, 16, frame_count)
+        } else {
+            registers.chunks_exact(16).map(|frame| frame.try_into().unwrap_or([0; 16])).collect()
+        };
+
+        Ok(Self {
+            format,
+            title,
+            author,
+            comment,
+            clock_rate,
+            frame_rate,
+            loop_frame,
+            frames,
+            digidrums
+        })
+    }
+
+    /// The variant of the YM format that the file uses.
+    pub fn format(&self) -> YmFormat {
+        self.format
+    }
+
+    /// The title of the song, which is empty for the formats that do not store it.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// The author of the song, which is empty for the formats that do not store it.
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    /// The comment of the song, which is empty for the formats that do not store it.
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    /// The clock rate of the chip in Hz. Formats that do not store the clock rate use the Atari
+    /// ST's clock rate of 2 MHz.
+    pub fn clock_rate(&self) -> f64 {
+        self.clock_rate
+    }
+
+    /// The frame rate in Hz. Formats that do not store the frame rate use 50 Hz.
+    pub fn frame_rate(&self) -> f64 {
+        self.frame_rate
+    }
+
+    /// The frame at which the song continues after its last frame.
+    pub fn loop_frame(&self) -> usize {
+        self.loop_frame
+    }
+
+    /// The number of frames in the song.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The register values of every frame. The formats that store 14 registers per frame have
+    /// registers 14 and 15 set to zero. In YM5! and YM6! files these registers, and the unused
+    /// bits of the other registers, control the special effects.
+    pub fn frames(&self) -> &[[u8; 16]] {
+        &self.frames
+    }
+
+    /// The samples of the digidrums that are stored in the file.
+    pub fn digidrums(&self) -> &[Vec<u8>] {
+        &self.digidrums
+    }
+}
+
+/// Plays a [`YmFile`] using a [`FramePlayer`], looping at the file's loop frame.
+#[derive(Clone, Debug)]
+pub struct YmPlayer {
+    player: FramePlayer
+}
+
+impl YmPlayer {
+    /// Initialize a new player for the file, which renders at the specified sample rate using a
+    /// PSG that emulates the YM2149 at the file's clock rate. Returns an error when the clock rate
+    /// is too high for the sample rate.
+    pub fn new(file: &YmFile, sample_rate: u32) -> Result<Self, crate::Error> {
+        let mut psg = PSG::new(file.clock_rate, sample_rate)?;
+        psg.set_chip_type(ChipType::YM);
+
+        let frames = file.frames.iter().map(|frame| {
+            let mut registers = [0; 14];
+            registers.copy_from_slice(&frame[..14]);
+            registers
+        }).collect();
+
+        let mut player = FramePlayer::new(psg, file.frame_rate, frames);
+        player.set_loop_frame(Some(file.loop_frame));
+
+        Ok(Self { player })
+    }
+
+    /// Render interleaved stereo samples into the buffer. See [`FramePlayer::render_into`].
+    pub fn render_into(&mut self, buffer: &mut [f32]) -> PlayerStatus {
+        self.player.render_into(buffer)
+    }
+
+    /// The underlying frame player, for example for seeking.
+    pub fn frame_player(&self) -> &FramePlayer {
+        &self.player
+    }
+
+    /// The underlying frame player, for example for seeking or for disabling the loop.
+    pub fn frame_player_mut(&mut self) -> &mut FramePlayer {
+        &mut self.player
+    }
+
+    /// Stop playing, and return the underlying frame player.
+    pub fn into_frame_player(self) -> FramePlayer {
+        self.player
+    }
+}
+
+/// Writes uncompressed YM5! or YM6! files with the interleaved register layout.
+///
+/// The frames use the representation of the [`FramePlayer`], in which register 13 is 0xFF in
+/// frames that do not write the envelope shape. Register writes that have been captured with
+/// [`PSG::start_recording`] can be converted to such frames using
+/// [`debugdump::to_frames`](crate::debugdump::to_frames).
+///
+/// ```
+/// use psg::ym::{YmFile, YmWriter};
+///
+/// let frames = vec![[0x1c, 0x01, 0, 0, 0, 0, 0, 0x3e, 15, 0, 0, 0, 0, 0xff]; 50];
+///
+/// let mut writer = YmWriter::new();
+/// writer.set_title("Beep");
+/// writer.set_author("Someone");
+///
+/// let data = writer.write(&frames);
+/// assert_eq!(YmFile::parse(&data).unwrap().title(), "Beep");
+/// ```
+#[derive(Clone, Debug)]
+pub struct YmWriter {
+    format: YmFormat,
+    title: String,
+    author: String,
+    comment: String,
+    clock_rate: f64,
+    frame_rate: f64,
+    loop_frame: usize
+}
+
+impl YmWriter {
+    /// Initialize a new writer for YM6! files without metadata, at the Atari ST's clock rate of
+    /// 2 MHz and a frame rate of 50 Hz, which loop from the start.
+    pub fn new() -> Self {
+        Self {
+            format: YmFormat::Ym6,
+            title: String::new(),
+            author: String::new(),
+            comment: String::new(),
+            clock_rate: ATARI_ST_CLOCK_RATE,
+            frame_rate: DEFAULT_FRAME_RATE,
+            loop_frame: 0
+        }
+    }
+
+    /// The variant of the YM format that is written.
+    pub fn format(&self) -> YmFormat {
+        self.format
+    }
+
+    /// Set the variant of the YM format that is written, which is YM6! by default. Only YM5! and
+    /// YM6! can be written, and the other variants write YM6! instead.
+    pub fn set_format(&mut self, format: YmFormat) {
+        self.format = if format == YmFormat::Ym5 { YmFormat::Ym5 } else { YmFormat::Ym6 };
+    }
+
+    /// The title of the song.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Set the title of the song. Characters outside of Latin-1 are written as question marks.
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_owned();
+    }
+
+    /// The author of the song.
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    /// Set the author of the song. Characters outside of Latin-1 are written as question marks.
+    pub fn set_author(&mut self, author: &str) {
+        self.author = author.to_owned();
+    }
+
+    /// The comment of the song.
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    /// Set the comment of the song. Characters outside of Latin-1 are written as question marks.
+    pub fn set_comment(&mut self, comment: &str) {
+        self.comment = comment.to_owned();
+    }
+
+    /// The clock rate of the chip in Hz.
+    pub fn clock_rate(&self) -> f64 {
+        self.clock_rate
+    }
+
+    /// Set the clock rate of the chip in Hz, which is rounded to a whole number.
+    pub fn set_clock_rate(&mut self, clock_rate: f64) {
+        self.clock_rate = clock_rate;
+    }
+
+    /// The frame rate in Hz.
+    pub fn frame_rate(&self) -> f64 {
+        self.frame_rate
+    }
+
+    /// Set the frame rate in Hz, which is rounded to a whole number.
+    pub fn set_frame_rate(&mut self, frame_rate: f64) {
+        self.frame_rate = frame_rate;
+    }
+
+    /// The frame at which the song continues after its last frame.
+    pub fn loop_frame(&self) -> usize {
+        self.loop_frame
+    }
+
+    /// Set the frame at which the song continues after its last frame.
+    pub fn set_loop_frame(&mut self, frame: usize) {
+        self.loop_frame = frame;
+    }
+
+    /// Write the frames to a YM file, and return its contents. Registers 14 and 15, which control
+    /// the special effects, are written as zero.
+    pub fn write(&self, frames: &[[u8; 14]]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(64 + 16 * frames.len());
+
+        data.extend(if self.format == YmFormat::Ym5 { b"YM5!" } else { b"YM6!" });
+        data.extend(b"LeOnArD!");
+        data.extend((frames.len() as u32).to_be_bytes());
+        data.extend(ATTRIBUTE_INTERLEAVED.to_be_bytes());
+        data.extend(0u16.to_be_bytes());
+        data.extend((self.clock_rate.round() as u32).to_be_bytes());
+        data.extend((self.frame_rate.round() as u16).to_be_bytes());
+        data.extend((self.loop_frame as u32).to_be_bytes());
+        data.extend(0u16.to_be_bytes());
+
+        for string in [&self.title, &self.author, &self.comment] {
+            data.extend(string.chars().map(|char| u8::try_from(char).ok().filter(|&byte| byte != 0).unwrap_or(b'?')));
+            data.push(0);
+        }
+
+        for register in 0..16 {
+            data.extend(frames.iter().map(|frame| frame.get(register).copied().unwrap_or(0)));
+        }
+
+        data.extend(b"End!");
+        data
+    }
+}
+
+impl Default for YmWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert interleaved register data, which stores the values of the first register for all
+/// frames first, into frames.
+fn deinterleave(data: &[u8], registers: usize, frame_count: usize) -> Vec<[u8; 16]> {
+    (0..frame_count).map(|frame| {
+        core::array::from_fn(|register| if register < registers { data[register * frame_count + frame] } else { 0 })
+    }).collect()
+}
+
+/// Reads the big-endian fields of a YM file.
+struct Reader<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], YmError> {
+        let (bytes, rest) = self.data.split_at_checked(length).ok_or(YmError::Truncated)?;
+
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], YmError> {
+        self.bytes(N)?.try_into().map_err(|_| YmError::Truncated)
+    }
+
+    fn u16(&mut self) -> Result<u16, YmError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, YmError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    /// Read a null-terminated string, which is decoded as Latin-1.
+    fn string(&mut self) -> Result<String, YmError> {
+        let length = self.data.iter().position(|&byte| byte == 0).ok_or(YmError::Truncated)?;
+        let string = self.bytes(length)?.iter().map(|&byte| byte as char).collect();
+
+        self.bytes(1)?;
+        Ok(string)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{YmError, YmFile, YmFormat, YmPlayer, YmWriter};
+    use crate::{PSG, PlayerStatus, debugdump};
+
+    /// A YM5! file with four non-interleaved frames that play a tone on channel A, which was
+    /// written by hand.
+    const TINY_YM5: &[u8] = include_bytes!("../testdata/tiny.ym");
+
+    /// An LHA archive that contains an interleaved YM6! file, which is compressed with -lh5-.
+    const COMPRESSED_YM6: &[u8] = include_bytes!("../testdata/compressed.ym");
+
+    #[test]
+    fn parse_ym5() {
+        let file = YmFile::parse(TINY_YM5).unwrap();
+
+        assert_eq!(file.format(), YmFormat::Ym5);
+        assert_eq!(file.title(), "Tiny");
+        assert_eq!(file.author(), "psg-rs");
+        assert_eq!(file.comment(), "Test fixture");
+        assert_eq!(file.clock_rate(), 2_000_000.0);
+        assert_eq!(file.frame_rate(), 50.0);
+        assert_eq!(file.loop_frame(), 2);
+        assert_eq!(file.frame_count(), 4);
+        assert_eq!(file.digidrums(), [vec![0x80, 0xc0]]);
+        assert_eq!(file.frames()[1], [0x1c, 0x01, 0, 0, 0, 0, 0, 0x3e, 14, 0, 0, 0, 0, 0xff, 0, 0]);
+    }
+
+    #[test]
+    fn parse_compressed() {
+        let file = YmFile::parse(COMPRESSED_YM6).unwrap();
+
+        assert_eq!(file.format(), YmFormat::Ym6);
+        assert_eq!(file.title(), "Compressed");
+        assert_eq!(file.author(), "psg-rs");
+        assert_eq!(file.comment(), "lh5 test fixture");
+        assert_eq!(file.clock_rate(), 1_773_400.0);
+        assert_eq!(file.frame_rate(), 50.0);
+        assert_eq!(file.loop_frame(), 10);
+        assert_eq!(file.frame_count(), 100);
+        assert_eq!(file.frames()[9], [0x7c, 0x01, 0x82, 0x03, 0, 0, 0, 0x3c, 14, 12, 0, 0x40, 0, 0xff, 0, 0]);
+        assert_eq!(file.frames()[16][13], 0x0e);
+    }
+
+    #[test]
+    fn parse_ym3() {
+        // Two interleaved frames, followed by a loop frame for YM3b
+        let mut data = b"YM3b".to_vec();
+
+        for register in 0..14u8 {
+            data.extend([register, register + 0x10]);
+        }
+
+        data.extend(1u32.to_le_bytes());
+
+        let file = YmFile::parse(&data).unwrap();
+
+        assert_eq!(file.format(), YmFormat::Ym3b);
+        assert_eq!((file.clock_rate(), file.frame_rate(), file.loop_frame()), (2_000_000.0, 50.0, 1));
+        assert_eq!(file.frames()[1], [0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0, 0]);
+
+        data.truncate(4 + 28);
+        data[3] = b'!';
+
+        let file = YmFile::parse(&data).unwrap();
+        assert_eq!((file.format(), file.frame_count(), file.loop_frame()), (YmFormat::Ym3, 2, 0));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(YmFile::parse(b"YM4!LeOnArD!"), Err(YmError::UnsupportedFormat(*b"YM4!")));
+        assert_eq!(YmFile::parse(b"\x21\x3c-lh5-\x00\x00"), Err(YmError::CorruptArchive("the header is truncated")));
+        assert_eq!(YmFile::parse(b"YM5!LeOnArD"), Err(YmError::Truncated));
+        assert_eq!(YmFile::parse(b"YM6!LeOnArd!"), Err(YmError::InvalidCheckString));
+        assert_eq!(YmFile::parse(&TINY_YM5[..TINY_YM5.len() - 20]), Err(YmError::Truncated));
+        assert_eq!(YmFile::parse(b"YM3b"), Err(YmError::Truncated));
+    }
+
+    #[test]
+    fn playback() {
+        let file = YmFile::parse(TINY_YM5).unwrap();
+        let mut player = YmPlayer::new(&file, 44100).unwrap();
+        let mut buffer = vec![0.0; 2 * 882 * 4];
+
+        assert_eq!(player.render_into(&mut buffer), PlayerStatus::Playing);
+        assert!(buffer.iter().any(|&sample| sample.abs() > 0.1));
+
+        // The song continues at its loop frame
+        assert_eq!(player.render_into(&mut buffer[..2]), PlayerStatus::Looped);
+        assert_eq!(player.frame_player().position(), 3);
+    }
+
+    #[test]
+    fn write() {
+        let mut psg = PSG::new(2_000_000.0, 44100).unwrap();
+        psg.start_recording();
+
+        for frame in 0..10u8 {
+            psg.set_register(0, frame * 16);
+            psg.set_register(8, 15 - frame);
+
+            if frame == 4 {
+                psg.set_register(13, 0x0c);
+            }
+
+            let _ = psg.render_frames(882);
+        }
+
+        let frames = debugdump::to_frames(&psg.stop_recording(), 44100, 50.0);
+
+        let mut writer = YmWriter::new();
+        writer.set_format(YmFormat::Ym5);
+        writer.set_title("Round trip");
+        writer.set_author("psg-rs");
+        writer.set_comment("Caf\u{e9} \u{2603}");
+        writer.set_clock_rate(1_773_400.0);
+        writer.set_loop_frame(3);
+
+        let file = YmFile::parse(&writer.write(&frames)).unwrap();
+
+        assert_eq!(file.format(), YmFormat::Ym5);
+        assert_eq!((file.title(), file.author(), file.comment()), ("Round trip", "psg-rs", "Caf\u{e9} ?"));
+        assert_eq!((file.clock_rate(), file.frame_rate(), file.loop_frame(), file.frame_count()), (1_773_400.0, 50.0, 3, 10));
+        assert_eq!(file.frames()[3], [0x30, 0, 0, 0, 0, 0, 0, 0, 12, 0, 0, 0, 0, 0xff, 0, 0]);
+        assert_eq!((file.frames()[4][13], file.frames()[5][13]), (0x0c, 0xff));
+
+        let written: Vec<[u8; 14]> = file.frames().iter().map(|frame| std::array::from_fn(|register| frame[register])).collect();
+        assert_eq!(written, frames);
+    }
+}
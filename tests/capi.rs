@@ -0,0 +1,57 @@
+//! This test builds the C smoke test in `tests/capi/smoke.c` against the C bindings, and runs it.
+//! The bindings are built as a shared library by a wrapper crate, like the one that is described
+//! in the documentation of the `capi` module, and the smoke test is compiled using the C compiler
+//! in the `CC` environment variable, or `cc` by default.
+
+#![cfg(unix)]
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Run a command, and panic with its output when it fails.
+fn run(command: &mut Command) {
+    let output = command.output().unwrap_or_else(|error| panic!("could not run {command:?}: {error}"));
+
+    assert!(output.status.success(), "{command:?} failed with {}:\n{}", output.status, String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn c_smoke_test() {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let wrapper = Path::new(env!("CARGO_TARGET_TMPDIR")).join("capi");
+
+    fs::create_dir_all(wrapper.join("src")).unwrap();
+    fs::write(wrapper.join("src/lib.rs"), "pub use psg::capi::*;\n").unwrap();
+    fs::write(wrapper.join("Cargo.toml"), format!(r#"[package]
+name = "psg-c"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+psg = {{ path = {source:?}, features = ["capi"] }}
+
+[workspace]
+"#)).unwrap();
+
+    // The wrapper has its own target directory, so it does not wait for the lock of this build
+    run(Command::new(env!("CARGO")).current_dir(&wrapper).args(["build", "--quiet", "--target-dir", "target"]));
+
+    let library = wrapper.join("target/debug");
+    let executable = wrapper.join("smoke");
+
+    run(Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_owned()))
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-o"])
+        .arg(&executable)
+        .arg(source.join("tests/capi/smoke.c"))
+        .arg("-I").arg(source.join("include"))
+        .arg("-L").arg(&library)
+        .arg(format!("-Wl,-rpath,{}", library.display()))
+        .args(["-lpsg_c", "-lm"]));
+
+    let status = Command::new(&executable).status().unwrap();
+    assert!(status.success(), "the smoke test failed at step {:?}", status.code());
+}
@@ -0,0 +1,56 @@
+/*
+ * Renders a tone through the C bindings, to check that they can be called from C. Exits with zero
+ * on success, and a positive step number on failure. It is built and run by tests/capi.rs.
+ */
+
+#include <math.h>
+
+#include "psg.h"
+
+#define FRAMES 4410
+
+int main(void) {
+    static float buffer[2 * FRAMES];
+    float peak = 0.0f;
+    Psg *psg;
+    size_t index;
+
+    if (psg_set_register(NULL, 8, 15) != PSG_ERROR_NULL_POINTER) {
+        return 1;
+    }
+
+    psg = psg_new(2000000.0, 44100);
+
+    if (psg == NULL) {
+        return 2;
+    }
+
+    /* A 440 Hz tone on channel A at full volume */
+    if (psg_set_chip_type(psg, PSG_CHIP_YM) != PSG_OK
+            || psg_set_register(psg, 0, 0x1c) != PSG_OK
+            || psg_set_register(psg, 1, 0x01) != PSG_OK
+            || psg_set_register(psg, 7, 0x3e) != PSG_OK
+            || psg_set_register(psg, 8, 15) != PSG_OK
+            || psg_render(psg, buffer, FRAMES) != PSG_OK) {
+        psg_free(psg);
+        return 3;
+    }
+
+    for (index = 0; index < 2 * FRAMES; index++) {
+        peak = fmaxf(peak, fabsf(buffer[index]));
+    }
+
+    if (peak < 0.1f) {
+        psg_free(psg);
+        return 4;
+    }
+
+    /* After a reset the output decays to silence */
+    if (psg_reset(psg) != PSG_OK || psg_render(psg, buffer, FRAMES) != PSG_OK || fabsf(buffer[2 * FRAMES - 2]) > 0.01f) {
+        psg_free(psg);
+        return 5;
+    }
+
+    psg_free(psg);
+    return 0;
+}